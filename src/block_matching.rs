@@ -0,0 +1,326 @@
+//! Diffs pair the lines of the snapshots in the order they're printed, so a pass that reorders
+//! the blocks of a function, e.g. SimplifyCFG, looks like it rewrote them. The blocks of the
+//! functions after the pass are matched with the ones before it, by label, then by what they hold
+//! and where they branch to, and put in the order of their match before diffing. Blocks new to
+//! the pass stay after the block they followed.
+
+use crate::optpipeline::LABEL;
+use regex::Regex;
+use similar::TextDiff;
+use std::collections::HashMap;
+use std::ops::Range;
+
+struct Block<'a> {
+    label: Option<&'a str>,
+    lines: Vec<&'a str>,
+    successors: Vec<&'a str>,
+}
+
+impl Block<'_> {
+    fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+}
+
+/// Splits the body of a function into its blocks, the entry block first.
+fn blocks<'a>(body: &[&'a str], label: &Regex, successor: &Regex) -> Vec<Block<'a>> {
+    let mut blocks = vec![Block {
+        label: None,
+        lines: Vec::new(),
+        successors: Vec::new(),
+    }];
+    for &line in body {
+        if let Some(caps) = label.captures(line) {
+            blocks.push(Block {
+                label: Some(caps.get(1).unwrap().as_str()),
+                lines: Vec::new(),
+                successors: Vec::new(),
+            });
+        }
+        let block = blocks.last_mut().unwrap();
+        block.lines.push(line);
+        block.successors.extend(
+            successor
+                .captures_iter(line)
+                .map(|caps| caps.get(1).unwrap().as_str()),
+        );
+    }
+    blocks
+}
+
+/// Matches the blocks after the pass (by index) with the ones before it.
+fn match_blocks(before: &[Block<'_>], after: &[Block<'_>]) -> HashMap<usize, usize> {
+    let mut matched = HashMap::from([(0, 0)]);
+    let by_label = before
+        .iter()
+        .enumerate()
+        .filter_map(|(i, block)| Some((block.label?, i)))
+        .collect::<HashMap<_, _>>();
+    for (i, block) in after.iter().enumerate().skip(1) {
+        if let Some(&j) = block.label.and_then(|label| by_label.get(label)) {
+            matched.insert(i, j);
+        }
+    }
+    // The blocks left are matched by what they hold, the ones branching to the same blocks first.
+    // Functions with too many of them to compare with each other keep their order
+    let mut taken = matched.values().copied().collect::<Vec<_>>();
+    if (after.len() - matched.len()) * (before.len() - taken.len()) > 10_000 {
+        return matched;
+    }
+    let mut candidates = Vec::new();
+    for (i, after_block) in after
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !matched.contains_key(i))
+    {
+        for (j, before_block) in before
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| !taken.contains(j))
+        {
+            let ratio = TextDiff::from_lines(&before_block.text(), &after_block.text()).ratio();
+            let same_successors = after_block.successors == before_block.successors;
+            let score = ratio + if same_successors { 0.5 } else { 0.0 };
+            if score >= 0.75 {
+                candidates.push((score, i, j));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (_, i, j) in candidates {
+        if !matched.contains_key(&i) && !taken.contains(&j) {
+            matched.insert(i, j);
+            taken.push(j);
+        }
+    }
+    matched
+}
+
+/// The blocks after the pass in the order of the blocks they match, by index, each with its
+/// match.
+fn matched_order(before: &[Block<'_>], after: &[Block<'_>]) -> Vec<(usize, Option<usize>)> {
+    let matched = match_blocks(before, after);
+    // Blocks without a match go along with the closest matched block printed before them
+    let mut anchored = Vec::new();
+    let mut anchor = 0;
+    for i in 0..after.len() {
+        if let Some(&j) = matched.get(&i) {
+            anchor = j;
+        }
+        anchored.push((anchor, !matched.contains_key(&i), i));
+    }
+    anchored.sort();
+    anchored
+        .into_iter()
+        .map(|(_, _, i)| (i, matched.get(&i).copied()))
+        .collect()
+}
+
+/// The functions defined in a snapshot: the name their definition starts with, and the range of
+/// the lines of their body.
+fn functions<'a>(lines: &[&'a str]) -> Vec<(&'a str, Range<usize>)> {
+    let mut functions = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].starts_with("define ") {
+            let name = lines[i].split('(').next().unwrap();
+            let end = (i..lines.len())
+                .find(|&end| lines[end] == "}")
+                .unwrap_or(lines.len());
+            functions.push((name, i + 1..end));
+            i = end;
+        }
+        i += 1;
+    }
+    functions
+}
+
+/// The snapshot after the pass with the blocks of its functions in the order of the blocks they
+/// match before the pass.
+pub fn reorder(before: &str, after: &str) -> String {
+    let label = Regex::new(LABEL).unwrap();
+    let successor = Regex::new(r#"label (%(?:"[^"]*"|[\w.$-]+))"#).unwrap();
+    let before_lines = before.lines().collect::<Vec<_>>();
+    let after_lines = after.lines().collect::<Vec<_>>();
+    let bodies = functions(&before_lines)
+        .into_iter()
+        .map(|(name, body)| (name, &before_lines[body]))
+        .collect::<HashMap<_, _>>();
+
+    let mut reordered = Vec::<&str>::with_capacity(after_lines.len());
+    let mut printed = 0;
+    for (name, body) in functions(&after_lines) {
+        let Some(before_body) = bodies.get(name) else {
+            continue;
+        };
+        reordered.extend(&after_lines[printed..body.start]);
+        let before = blocks(before_body, &label, &successor);
+        let after = blocks(&after_lines[body.clone()], &label, &successor);
+        // The blank lines separating the blocks stay between them, wherever the blocks go
+        let separated = after
+            .iter()
+            .rev()
+            .skip(1)
+            .any(|block| block.lines.last() == Some(&""));
+        let reordered_blocks = matched_order(&before, &after)
+            .into_iter()
+            .map(|(i, _)| {
+                let lines = &after[i].lines;
+                let end = lines.iter().rposition(|line| !line.is_empty());
+                &lines[..end.map_or(0, |end| end + 1)]
+            })
+            .filter(|lines| !lines.is_empty())
+            .collect::<Vec<_>>();
+        for (n, lines) in reordered_blocks.into_iter().enumerate() {
+            if separated && n > 0 {
+                reordered.push("");
+            }
+            reordered.extend(lines);
+        }
+        printed = body.end;
+    }
+    reordered.extend(&after_lines[printed..]);
+    reordered.join("\n") + "\n"
+}
+
+/// A part of the snapshots compared on its own: a block, or the lines outside of the blocks.
+pub struct Segment {
+    pub name: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// The lines of the body of function `name`, none if it isn't defined.
+fn body<'b, 'a>(
+    lines: &'b [&'a str],
+    functions: &[(&str, Range<usize>)],
+    name: &str,
+) -> &'b [&'a str] {
+    functions
+        .iter()
+        .find(|(function, _)| *function == name)
+        .map_or(&[], |(_, body)| &lines[body.clone()])
+}
+
+fn block_name(block: &Block<'_>) -> String {
+    match block.label {
+        Some(label) => format!("%{}", label),
+        None => "entry".to_string(),
+    }
+}
+
+fn block_text(block: Option<&Block<'_>>) -> String {
+    match block {
+        Some(block) => block.text().trim_end().to_string() + "\n",
+        None => String::new(),
+    }
+}
+
+/// The parts of the snapshots that changed, block by block. Blocks removed by the pass come
+/// after the blocks of their function that are left.
+pub fn changed_segments(before: &str, after: &str) -> Vec<Segment> {
+    let label = Regex::new(LABEL).unwrap();
+    let successor = Regex::new(r#"label (%(?:"[^"]*"|[\w.$-]+))"#).unwrap();
+    let before_lines = before.lines().collect::<Vec<_>>();
+    let after_lines = after.lines().collect::<Vec<_>>();
+    let before_functions = functions(&before_lines);
+    let after_functions = functions(&after_lines);
+
+    // The lines outside the bodies, e.g. the definitions of the functions, go first
+    let outside = |lines: &[&str], functions: &[(&str, Range<usize>)]| {
+        let mut outside = String::new();
+        for (i, line) in lines.iter().enumerate() {
+            if !functions.iter().any(|(_, body)| body.contains(&i)) {
+                outside += line;
+                outside.push('\n');
+            }
+        }
+        outside
+    };
+    let mut segments = vec![Segment {
+        name: "outside the blocks".to_string(),
+        before: outside(&before_lines, &before_functions),
+        after: outside(&after_lines, &after_functions),
+    }];
+
+    let mut names = after_functions
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+    names.extend(
+        before_functions
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| !names.contains(name))
+            .collect::<Vec<_>>(),
+    );
+    for name in names {
+        let before = blocks(
+            body(&before_lines, &before_functions, name),
+            &label,
+            &successor,
+        );
+        let after = blocks(
+            body(&after_lines, &after_functions, name),
+            &label,
+            &successor,
+        );
+        let order = matched_order(&before, &after);
+        for &(i, j) in &order {
+            let added = match j {
+                Some(_) => "",
+                None => " (added)",
+            };
+            segments.push(Segment {
+                name: block_name(&after[i]) + added,
+                before: block_text(j.map(|j| &before[j])),
+                after: block_text(Some(&after[i])),
+            });
+        }
+        let removed = (0..before.len()).filter(|j| !order.iter().any(|&(_, k)| k == Some(*j)));
+        for j in removed {
+            segments.push(Segment {
+                name: block_name(&before[j]) + " (removed)",
+                before: block_text(Some(&before[j])),
+                after: String::new(),
+            });
+        }
+    }
+    segments.retain(|segment| segment.before != segment.after);
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changed_segments, reorder};
+
+    #[test]
+    fn swapped_blocks_make_no_diff() {
+        let before = "\
+define i32 @f(i1 %c) {
+entry:
+  br i1 %c, label %then, label %else
+
+then:
+  ret i32 1
+
+else:
+  ret i32 2
+}
+";
+        let after = "\
+define i32 @f(i1 %c) {
+entry:
+  br i1 %c, label %then, label %else
+
+else:
+  ret i32 2
+
+then:
+  ret i32 1
+}
+";
+        assert_eq!(reorder(before, after), before);
+        assert!(changed_segments(before, after).is_empty());
+    }
+}
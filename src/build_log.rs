@@ -0,0 +1,64 @@
+//! A build log holds the dumps of every translation unit compiled, each one after the line the
+//! build system printed when starting its compile command: `[3/10] Building CXX object ...` for
+//! ninja, or the command itself for make.
+
+use regex::Regex;
+
+/// Whether `line` starts a compile command, as opposed to being output of one.
+fn is_compile_command(progress: &Regex, compiler: &Regex, line: &str) -> bool {
+    if progress.is_match(line) {
+        return true;
+    }
+    // Environment variables, launchers and `cd build &&` come before the compiler
+    line.split("&&").any(|command| {
+        let program = command
+            .split_whitespace()
+            .find(|word| !word.contains('=') && *word != "ccache" && *word != "sccache");
+        program.is_some_and(|program| {
+            let program = program.rsplit(['/', '\\']).next().unwrap_or(program);
+            compiler.is_match(program)
+        })
+    })
+}
+
+/// Whether the compile command mentions the source file `tu`, or its object file. Either of them
+/// may be relative to the other.
+fn compiles(command: &str, tu: &str) -> bool {
+    command.split_whitespace().any(|word| {
+        let path = word.trim_matches(['"', '\'']);
+        let path = path
+            .strip_suffix(".o")
+            .or_else(|| path.strip_suffix(".obj"))
+            .unwrap_or(path);
+        path == tu
+            || path.ends_with(&format!("/{}", tu))
+            || (path.contains('.') && tu.ends_with(&format!("/{}", path)))
+    })
+}
+
+/// Keeps the output of the compile commands of `tu`, if any is found in the log.
+pub fn select_translation_unit(log: &str, tu: &str) -> Option<String> {
+    // ninja's `[3/10]`, and the `[ 30%]` of CMake generated makefiles
+    let progress = Regex::new(r"^\[\s*\d+(?:/\d+|%)\] ").unwrap();
+    let compiler = Regex::new(
+        r"^(?:clang(?:\+\+)?|clang-cl|gcc|g\+\+|cc|c\+\+|rustc|swiftc|flang(?:-new)?|opt|llc)(?:-[\d.]+)?(?:\.exe)?$",
+    )
+    .unwrap();
+
+    let mut selected: Option<String> = None;
+    let mut in_tu = false;
+    for line in log.lines() {
+        if is_compile_command(&progress, &compiler, line) {
+            in_tu = compiles(line, tu);
+            if in_tu {
+                selected.get_or_insert_with(String::new);
+            }
+            continue;
+        }
+        if let (true, Some(selected)) = (in_tu, selected.as_mut()) {
+            *selected += line;
+            selected.push('\n');
+        }
+    }
+    selected
+}
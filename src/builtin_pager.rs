@@ -0,0 +1,430 @@
+//! A pager of optdiff's own, for Windows where no pager can be piped to, and anywhere with
+//! `--pager builtin`. optdiff runs again with its output piped to the pager, which reads its
+//! commands from the terminal a key at a time, in raw mode, so none needs Enter:
+//!
+//! - Space, Enter, PgDn or `f` shows the next page, `d` half a page and `j` or Down the next line
+//! - `b` or PgUp goes back a page, `u` half a page and `k` or Up a line
+//! - `g` or Home and `G` or End go to the top and to the bottom
+//! - `/regex` searches forward, `?regex` backward, `n` and `N` repeat the search
+//! - `]` and `[` go to the next and the previous pass that changed the function, skipping over
+//!   the passes that didn't
+//! - `q`, Esc or Ctrl-C quits
+
+use crate::{cli_write, cli_writeln};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use is_terminal::IsTerminal;
+use regex::Regex;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// The pager to give `--pager` for this one.
+pub const NAME: &str = "builtin";
+
+/// Set for the run of optdiff whose output is paged.
+const PAGED: &str = "OPTDIFF_BUILTIN_PAGER_CHILD";
+
+/// Whether this is the run whose output the built-in pager shows.
+pub fn is_paged() -> bool {
+    env::var_os(PAGED).is_some()
+}
+
+/// Whether stdout is a terminal, or the built-in pager showing it on one.
+pub fn to_terminal() -> bool {
+    io::stdout().is_terminal() || is_paged()
+}
+
+/// Whether the pager selected is this one. External pagers are only piped to on unix, other
+/// systems get this one whichever was asked for.
+pub fn is_selected(pager: Option<&str>) -> bool {
+    pager.is_some_and(|pager| pager.trim() == NAME || cfg!(not(unix)))
+}
+
+/// The headers of the passes that changed the function, in the diffs as text, as plain text and
+/// as patches.
+const CHANGE_HEADER: &str = r"^(\[\d+/\d+\] .* — .*· (changed|deleted the function|invalidated the IR)|PASS: \d+ of \d+, .*, (changed|deleted the function|invalidated the IR)|diff --git )";
+
+/// The rows of the terminal as it's sized now, or `LINES` if the shell exports it and the
+/// terminal can't be asked.
+fn terminal_height() -> usize {
+    match terminal::size() {
+        Ok((_, rows)) if rows > 1 => rows as usize,
+        _ => env::var("LINES")
+            .ok()
+            .and_then(|lines| lines.parse().ok())
+            .filter(|&lines| lines > 1)
+            .unwrap_or(24),
+    }
+}
+
+/// The terminal in raw mode, the keys are read as they're pressed, until dropped.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// The next key pressed, or `None` if the terminal was resized and the page is to be drawn
+/// again.
+fn next_key() -> io::Result<Option<KeyEvent>> {
+    loop {
+        match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => return Ok(Some(key)),
+            Event::Resize(..) => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// Reads the regex of a search on the status line, after its `/` or `?`. `None` if the search
+/// is given up with Esc, or by deleting the `/`.
+fn read_pattern(stdout: &mut io::Stdout, prompt: char) -> io::Result<Option<String>> {
+    let mut pattern = String::new();
+    loop {
+        cli_write!(stdout, "\r\x1b[2K{}{}", prompt, pattern)?;
+        stdout.flush()?;
+        let Some(key) = next_key()? else {
+            continue;
+        };
+        match key.code {
+            KeyCode::Enter => return Ok(Some(pattern)),
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            KeyCode::Backspace if pattern.pop().is_none() => return Ok(None),
+            KeyCode::Char(c) => pattern.push(c),
+            _ => {}
+        }
+    }
+}
+
+/// The terminal to read the commands from, stdin may well be the dump.
+pub fn keyboard() -> io::Result<BufReader<File>> {
+    let path = match cfg!(windows) {
+        true => "CONIN$",
+        false => "/dev/tty",
+    };
+    File::open(path).map(BufReader::new)
+}
+
+/// The lines read so far, and whether there are no more to come.
+#[derive(Default)]
+struct Lines {
+    lines: Vec<String>,
+    done: bool,
+}
+
+/// `line` without its colours and links, for the searches.
+fn plain(line: &str) -> String {
+    let mut plain = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find('\x1b') {
+        plain += &rest[..start];
+        let escape = &rest[start + 1..];
+        let end = if let Some(parameters) = escape.strip_prefix('[') {
+            parameters
+                .find(|c: char| c.is_ascii_alphabetic())
+                .map_or(escape.len(), |end| end + 2)
+        } else if let Some(link) = escape.strip_prefix(']') {
+            link.find("\x1b\\").map_or(escape.len(), |end| end + 3)
+        } else {
+            0
+        };
+        rest = &escape[end..];
+    }
+    plain + rest
+}
+
+/// Prints the lines from `from` on as they're read, for output that fits on the screen or with
+/// no terminal to read commands from.
+fn print_rest(read: &Mutex<Lines>, mut from: usize) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    loop {
+        let (lines, done) = {
+            let read = read.lock().unwrap();
+            (read.lines[from..].to_vec(), read.done)
+        };
+        from += lines.len();
+        for line in lines {
+            cli_writeln!(stdout, "{}", line)?;
+        }
+        if done {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// The next line at or after `from` matching `regex`, or at or before it going `backward`.
+fn find(lines: &[String], from: usize, regex: &Regex, backward: bool) -> Option<usize> {
+    let is_match = |&i: &usize| regex.is_match(&plain(&lines[i]));
+    match backward {
+        false => (from..lines.len()).find(is_match),
+        true => (0..=from.min(lines.len().saturating_sub(1)))
+            .rev()
+            .find(is_match),
+    }
+}
+
+/// The next line matching `regex` after the one at the top of the page, or the previous one
+/// before it going `backward`.
+fn jump(lines: &[String], top: usize, regex: &Regex, backward: bool) -> Option<usize> {
+    let from = match backward {
+        false => top + 1,
+        true => top.checked_sub(1)?,
+    };
+    find(lines, from, regex, backward)
+}
+
+/// The line at the top of the page after a command scrolling it, `None` for the other
+/// commands. The last page is kept full.
+fn scrolled(top: usize, command: char, page: usize, count: usize) -> Option<usize> {
+    let last = count.saturating_sub(page);
+    Some(match command {
+        ' ' | 'f' => (top + page).min(last),
+        'd' => (top + page / 2).min(last),
+        'j' => (top + 1).min(last),
+        'b' => top.saturating_sub(page),
+        'u' => top.saturating_sub(page / 2),
+        'k' => top.saturating_sub(1),
+        'g' => 0,
+        'G' => last,
+        _ => return None,
+    })
+}
+
+/// Whether the lines read so far tell if they fit on the screen, and whether they do.
+fn fits(read: &Lines, height: usize) -> Option<bool> {
+    match read.lines.len() < height {
+        true => read.done.then_some(true),
+        false => Some(false),
+    }
+}
+
+/// The terminal in raw mode to page the lines in, none if they fit on the screen, as `less -F`
+/// does, or if there's no terminal to read the keys from: the lines are printed as they are.
+fn paging<R>(fits: bool, raw_mode: impl FnOnce() -> io::Result<R>) -> Option<R> {
+    match fits {
+        true => None,
+        false => raw_mode().ok(),
+    }
+}
+
+/// Shows the lines a page at a time until quit, returns whether it was quit before all the
+/// lines were read.
+fn show(read: &Mutex<Lines>) -> io::Result<bool> {
+    let fits = loop {
+        if let Some(fits) = fits(&read.lock().unwrap(), terminal_height()) {
+            break fits;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    let Some(_raw_mode) = paging(fits, RawMode::enable) else {
+        print_rest(read, 0)?;
+        return Ok(false);
+    };
+    let mut stdout = io::stdout();
+    let mut top = 0;
+    let mut search: Option<(Regex, bool)> = None;
+    let change_header = Regex::new(CHANGE_HEADER).unwrap();
+    let mut message = None;
+    loop {
+        // The page follows the size of the terminal
+        let page = terminal_height() - 1;
+        let (count, done) = {
+            let read = read.lock().unwrap();
+            let end = (top + page).min(read.lines.len());
+            // The screen is cleared and drawn again after each command, raw mode doesn't return
+            // the cursor at the end of the lines
+            cli_write!(stdout, "\x1b[H\x1b[2J")?;
+            for line in &read.lines[top.min(end)..end] {
+                cli_write!(stdout, "{}\x1b[0m\r\n", line)?;
+            }
+            (read.lines.len(), read.done)
+        };
+        let status = message.take().unwrap_or_else(|| {
+            format!(
+                "lines {}-{} of {}{}",
+                (top + 1).min(count),
+                (top + page).min(count),
+                count,
+                if done { "" } else { " so far" }
+            )
+        });
+        cli_write!(
+            stdout,
+            "\x1b[7m{} · Space: next page, b: back, ]: next change, /: search, q: quit\x1b[0m ",
+            status
+        )?;
+        stdout.flush()?;
+        let Some(key) = next_key()? else {
+            continue;
+        };
+        let command = match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => 'q',
+            KeyCode::Char(c) => c,
+            KeyCode::Enter | KeyCode::PageDown => 'f',
+            KeyCode::PageUp => 'b',
+            KeyCode::Down => 'j',
+            KeyCode::Up => 'k',
+            KeyCode::Home => 'g',
+            KeyCode::End => 'G',
+            KeyCode::Esc => 'q',
+            _ => continue,
+        };
+        if let Some(scrolled) = scrolled(top, command, page, count) {
+            top = scrolled;
+            continue;
+        }
+        let repeat = match command {
+            'q' | 'Q' => {
+                cli_write!(stdout, "\r\n")?;
+                return Ok(!done);
+            }
+            ']' | '[' => {
+                let backward = command == '[';
+                match jump(&read.lock().unwrap().lines, top, &change_header, backward) {
+                    Some(found) => top = found,
+                    None => message = Some("No more passes that changed a function".to_string()),
+                }
+                None
+            }
+            'n' => Some(false),
+            'N' => Some(true),
+            '/' | '?' => match read_pattern(&mut stdout, command)? {
+                None => None,
+                Some(pattern) => match Regex::new(&pattern) {
+                    Ok(regex) => {
+                        search = Some((regex, command == '?'));
+                        Some(false)
+                    }
+                    Err(err) => {
+                        message = Some(format!("Invalid regex: {}", err));
+                        None
+                    }
+                },
+            },
+            _ => {
+                message = Some(format!("Unknown command `{}`", command));
+                None
+            }
+        };
+        // `N` searches the other way
+        if let Some(reverse) = repeat {
+            let Some((regex, backward)) = &search else {
+                message = Some("No search to repeat".to_string());
+                continue;
+            };
+            let backward = *backward != reverse;
+            match jump(&read.lock().unwrap().lines, top, regex, backward) {
+                Some(found) => top = found,
+                None => message = Some(format!("Pattern not found: {}", regex)),
+            }
+        }
+    }
+}
+
+/// Runs optdiff again with the same arguments and shows its output, returns the code to exit
+/// with: the one of the run, unless the pager was quit before the run ended.
+pub fn run() -> io::Result<i32> {
+    let mut child = Command::new(env::current_exe()?)
+        .args(env::args_os().skip(1))
+        .env(PAGED, "1")
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let read = Arc::new(Mutex::new(Lines::default()));
+    let reader = {
+        let read = read.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).split(b'\n') {
+                let Ok(line) = line else {
+                    break;
+                };
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim_end_matches('\r').to_string();
+                read.lock().unwrap().lines.push(line);
+            }
+            read.lock().unwrap().done = true;
+        })
+    };
+    let quit = show(&read)?;
+    if quit {
+        let _ = child.kill();
+    }
+    let status = child.wait()?;
+    let _ = reader.join();
+    Ok(match quit {
+        true => 0,
+        false => status.code().unwrap_or(1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fits, jump, paging, scrolled, Lines, CHANGE_HEADER};
+    use regex::Regex;
+    use std::io;
+
+    #[test]
+    fn pages_scroll_within_the_lines() {
+        // 100 lines on pages of 23
+        assert_eq!(scrolled(0, ' ', 23, 100), Some(23));
+        assert_eq!(scrolled(70, 'f', 23, 100), Some(77));
+        assert_eq!(scrolled(70, 'd', 23, 100), Some(77));
+        assert_eq!(scrolled(10, 'd', 23, 100), Some(21));
+        assert_eq!(scrolled(77, 'j', 23, 100), Some(77));
+        assert_eq!(scrolled(10, 'b', 23, 100), Some(0));
+        assert_eq!(scrolled(30, 'u', 23, 100), Some(19));
+        assert_eq!(scrolled(0, 'k', 23, 100), Some(0));
+        assert_eq!(scrolled(50, 'g', 23, 100), Some(0));
+        assert_eq!(scrolled(0, 'G', 23, 100), Some(77));
+        assert_eq!(scrolled(0, 'G', 23, 10), Some(0));
+        assert_eq!(scrolled(0, 'n', 23, 100), None);
+    }
+
+    #[test]
+    fn brackets_jump_to_the_passes_that_changed_the_function() {
+        let lines = [
+            "PASS: 1 of 3, SROAPass on f, function, f, changed [+1 -2, 4 instructions]",
+            "-  %a = alloca i32",
+            "PASS: 2 of 3, GVNPass on f, function, f, unchanged",
+            "\x1b[1mPASS: 3 of 3, DCEPass on f, function, f, deleted the function\x1b[0m",
+            "diff --git a/f b/f",
+        ]
+        .map(str::to_string);
+        let change = Regex::new(CHANGE_HEADER).unwrap();
+        assert_eq!(jump(&lines, 0, &change, false), Some(3));
+        assert_eq!(jump(&lines, 3, &change, false), Some(4));
+        assert_eq!(jump(&lines, 4, &change, false), None);
+        assert_eq!(jump(&lines, 3, &change, true), Some(0));
+        assert_eq!(jump(&lines, 0, &change, true), None);
+    }
+
+    #[test]
+    fn output_that_fits_on_the_screen_is_printed_as_it_is() {
+        let read = |lines: usize, done| Lines {
+            lines: vec![String::new(); lines],
+            done,
+        };
+        assert_eq!(fits(&read(10, true), 24), Some(true));
+        assert_eq!(fits(&read(10, false), 24), None);
+        assert_eq!(fits(&read(24, false), 24), Some(false));
+        assert!(paging(true, || -> io::Result<()> { panic!("raw mode enabled") }).is_none());
+        assert!(paging(false, || Err::<(), _>(io::Error::other("no terminal"))).is_none());
+        assert!(paging(false, || Ok(())).is_some());
+    }
+}
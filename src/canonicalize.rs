@@ -0,0 +1,234 @@
+//! Differences that don't change what the snapshots mean, left out of both of them before
+//! they're compared.
+
+use regex::{Captures, Regex};
+
+/// Words after the parameters of a function that take an argument rather than being attributes.
+const KEYWORDS: &[&str] = &[
+    "align",
+    "comdat",
+    "gc",
+    "partition",
+    "personality",
+    "prefix",
+    "prologue",
+    "section",
+    "to",
+];
+
+/// The line up to its comment: `;` in LLVM IR, MIR and GIMPLE dumps, `//` in MLIR and SIL.
+fn code(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &line[..i],
+            '/' if !quoted && line[i..].starts_with("//") => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// The snapshot without comments, e.g. `; preds = %4` or the block frequencies some printers add.
+/// Lines holding a comment only are left out.
+pub fn strip_comments(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    for line in text.lines() {
+        let code = code(line).trim_end();
+        if code.is_empty() && !line.trim().is_empty() {
+            continue;
+        }
+        stripped += code;
+        stripped.push('\n');
+    }
+    stripped
+}
+
+/// The snapshot without indentation, its runs of spaces and tabs made single spaces.
+pub fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    for line in text.lines() {
+        collapsed += &line.split_whitespace().collect::<Vec<_>>().join(" ");
+        collapsed.push('\n');
+    }
+    collapsed
+}
+
+/// The snapshot with its floating-point constants in the hexadecimal form of their bits, whether
+/// they were printed in decimal (`1.000000e+00`) or not (`0x3FF0000000000000`).
+pub fn canonical_constants(text: &str) -> String {
+    let decimal = Regex::new(r"(^|[\s,(\[<{])(-?\d+\.\d+(?:e[+-]?\d+)?)([\s,)\]>}]|$)").unwrap();
+    text.lines()
+        .map(|line| {
+            decimal.replace_all(line, |caps: &Captures<'_>| match caps[2].parse::<f64>() {
+                Ok(value) => format!("{}0x{:016X}{}", &caps[1], value.to_bits(), &caps[3]),
+                Err(_) => caps[0].to_string(),
+            })
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n"
+}
+
+/// The snapshot with the numbers the linker or the IR mover gives to struct types of the same
+/// name left out, `%struct.foo.123` being `%struct.foo`.
+pub fn canonical_types(text: &str) -> String {
+    let numbered = Regex::new(r"(%(?:struct|class|union)\.[\w$.:]+?)(?:\.\d+)+\b").unwrap();
+    let quoted = Regex::new(r#"(%"(?:struct|class|union)\.[^"]+?)(?:\.\d+)+""#).unwrap();
+    let text = numbered.replace_all(text, "$1");
+    quoted.replace_all(&text, "$1\"").into_owned()
+}
+
+/// Splits at spaces that aren't quoted or within parentheses.
+fn words(text: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let (mut start, mut depth, mut quoted) = (0, 0, false);
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            ' ' if !quoted && depth == 0 => {
+                words.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    words.push(&text[start..]);
+    words.into_iter().filter(|word| !word.is_empty()).collect()
+}
+
+fn is_attribute(word: &str) -> bool {
+    let name = word.split('(').next().unwrap_or(word);
+    (word.starts_with('"') || name.chars().all(|c| c.is_ascii_lowercase() || c == '_'))
+        && !name.is_empty()
+        && !KEYWORDS.contains(&name)
+}
+
+/// Sorts the runs of attributes among `words`.
+fn sort_attributes(words: &[&str]) -> String {
+    let mut sorted = Vec::with_capacity(words.len());
+    let mut run = Vec::new();
+    for &word in words {
+        if is_attribute(word) {
+            run.push(word);
+            continue;
+        }
+        run.sort_unstable();
+        sorted.append(&mut run);
+        sorted.push(word);
+    }
+    run.sort_unstable();
+    sorted.append(&mut run);
+    sorted.join(" ")
+}
+
+/// The end of the parameters of the function defined or called on `line`.
+fn parameters_end(line: &str) -> Option<usize> {
+    let name = line.find(" @")?;
+    let open = name + line[name..].find('(')?;
+    let mut depth = 0;
+    for (i, c) in line[open..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The snapshot with the attributes of functions, calls and attribute groups sorted, so that
+/// printers listing them in another order don't make a change.
+pub fn canonical_attributes(text: &str) -> String {
+    let mut canonical = String::with_capacity(text.len());
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let group = line
+            .strip_prefix("attributes #")
+            .and_then(|rest| rest.split_once(" = { "))
+            .and_then(|(number, rest)| Some((number, rest.strip_suffix(" }")?)));
+        if let Some((number, attributes)) = group {
+            canonical += &format!(
+                "attributes #{} = {{ {} }}",
+                number,
+                sort_attributes(&words(attributes))
+            );
+        } else if let Some(attributes) = line.strip_prefix("; Function Attrs: ") {
+            canonical += "; Function Attrs: ";
+            canonical += &sort_attributes(&words(attributes));
+        } else if let Some(end) = (trimmed.starts_with("define ") || line.contains("call "))
+            .then(|| parameters_end(line))
+            .flatten()
+        {
+            canonical += &line[..end];
+            let attributes = sort_attributes(&words(&line[end..]));
+            if !attributes.is_empty() {
+                canonical.push(' ');
+                canonical += &attributes;
+            }
+        } else {
+            canonical += line;
+        }
+        canonical.push('\n');
+    }
+    canonical
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{canonical_attributes, canonical_constants, canonical_types};
+
+    #[test]
+    fn constants_are_the_same_however_they_were_printed() {
+        let decimal = canonical_constants("  %a = fadd double %x, 1.000000e+00\n");
+        let hexadecimal = canonical_constants("  %a = fadd double %x, 0x3FF0000000000000\n");
+        assert_eq!(decimal, "  %a = fadd double %x, 0x3FF0000000000000\n");
+        assert_eq!(decimal, hexadecimal);
+        // Numbers within names aren't constants
+        assert_eq!(
+            canonical_constants("  call void @llvm.foo.1.5(i32 0)\n"),
+            "  call void @llvm.foo.1.5(i32 0)\n"
+        );
+    }
+
+    #[test]
+    fn types_of_the_same_name_are_the_same_type() {
+        assert_eq!(
+            canonical_types("  %p = alloca %struct.foo.123\n  %q = alloca %\"class.a b.7\"\n"),
+            "  %p = alloca %struct.foo\n  %q = alloca %\"class.a b\"\n"
+        );
+        assert_eq!(
+            canonical_types("  %p = alloca %struct.foo\n"),
+            "  %p = alloca %struct.foo\n"
+        );
+    }
+
+    #[test]
+    fn attributes_are_sorted() {
+        let before = "\
+define void @f(ptr %p) nounwind readonly align 16 {
+  call void @g(i32 1) nounwind cold
+  ret void
+}
+attributes #0 = { nounwind \"frame-pointer\"=\"all\" mustprogress }
+";
+        let after = "\
+define void @f(ptr %p) readonly nounwind align 16 {
+  call void @g(i32 1) cold nounwind
+  ret void
+}
+attributes #0 = { mustprogress nounwind \"frame-pointer\"=\"all\" }
+";
+        assert_eq!(canonical_attributes(before), canonical_attributes(after));
+        assert!(canonical_attributes(before)
+            .starts_with("define void @f(ptr %p) nounwind readonly align 16 {\n"));
+    }
+}
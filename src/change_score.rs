@@ -0,0 +1,117 @@
+//! How much a pass changed a function, from the diff of its snapshots: the lines added and
+//! removed, and the instructions among them. A pass rewriting an instruction touches it once,
+//! even though the diff shows it removed and added.
+
+use similar::{DiffOp, TextDiff};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChangeScore {
+    pub added: usize,
+    pub removed: usize,
+    /// The instructions the pass added, removed or rewrote
+    pub instructions: usize,
+    /// The lines of the snapshot before the pass
+    pub lines: usize,
+}
+
+impl ChangeScore {
+    /// What passes are ranked by, the instructions first.
+    pub fn key(self) -> (usize, usize) {
+        (self.instructions, self.added + self.removed)
+    }
+}
+
+impl fmt::Display for ChangeScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let noun = match self.instructions {
+            1 => "instruction",
+            _ => "instructions",
+        };
+        write!(
+            f,
+            "+{} -{}, {} {}",
+            self.added, self.removed, self.instructions, noun
+        )
+    }
+}
+
+/// Instructions are the indented lines of the bodies, the labels, declarations and comments
+/// aren't.
+pub fn is_instruction(line: &str) -> bool {
+    let trimmed = line.trim();
+    line.starts_with([' ', '\t'])
+        && !trimmed.is_empty()
+        && !trimmed.starts_with(';')
+        && !trimmed.starts_with("//")
+        && !trimmed.starts_with('#')
+        && trimmed != "}"
+}
+
+/// Scores the change from `before` to `after`.
+pub fn score(before: &str, after: &str) -> ChangeScore {
+    let diff = TextDiff::from_lines(before, after);
+    let (old, new) = (diff.old_slices(), diff.new_slices());
+    let mut score = ChangeScore {
+        lines: old.len(),
+        ..ChangeScore::default()
+    };
+    for op in diff.ops() {
+        if let DiffOp::Equal { .. } = op {
+            continue;
+        }
+        let removed = op.old_range().filter(|&i| is_instruction(old[i])).count();
+        let added = op.new_range().filter(|&i| is_instruction(new[i])).count();
+        score.removed += op.old_range().len();
+        score.added += op.new_range().len();
+        score.instructions += removed.max(added);
+    }
+    score
+}
+
+/// The least a pass has to change for its diff to be shown, with `--min-change`.
+#[derive(Debug, Clone, Copy)]
+pub enum MinChange {
+    /// Lines added or removed
+    Lines(usize),
+    /// Lines added or removed, per hundred lines of the snapshot before the pass
+    Percent(f64),
+}
+
+impl FromStr for MinChange {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "`{}` isn't a number of lines (`5`) or a percentage (`2%`)",
+                text
+            )
+        };
+        match text.strip_suffix('%') {
+            Some(percent) => percent
+                .trim()
+                .parse()
+                .map(MinChange::Percent)
+                .map_err(|_| invalid()),
+            None => text
+                .trim()
+                .parse()
+                .map(MinChange::Lines)
+                .map_err(|_| invalid()),
+        }
+    }
+}
+
+impl MinChange {
+    pub fn is_met(self, score: ChangeScore) -> bool {
+        let changed = score.added + score.removed;
+        match self {
+            MinChange::Lines(lines) => changed >= lines,
+            MinChange::Percent(percent) => {
+                changed as f64 * 100.0 >= percent * score.lines.max(1) as f64
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! A chart of the instructions of a function over the passes of its pipeline, an SVG drawn above
+//! the passes of each function in the HTML report, to spot the pass where the function ballooned
+//! or collapsed before reading any diff.
+
+use crate::html_report::escape;
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 160.0;
+/// Room for the counts on the left and the axis below
+const LEFT: f64 = 48.0;
+const BOTTOM: f64 = 20.0;
+const MARGIN: f64 = 8.0;
+
+/// The instructions of the function at a point of the pipeline.
+pub struct Point {
+    /// What the function is the snapshot of, e.g. the pass that made it
+    pub label: String,
+    pub instructions: usize,
+}
+
+/// The chart of the points in order, nothing if there are too few of them to draw a line.
+pub fn svg(points: &[Point]) -> String {
+    if points.len() < 2 {
+        return String::new();
+    }
+    let most = points
+        .iter()
+        .map(|point| point.instructions)
+        .max()
+        .unwrap_or_default()
+        .max(1);
+    let x = |k: usize| LEFT + k as f64 * (WIDTH - LEFT - MARGIN) / (points.len() - 1) as f64;
+    let y =
+        |count: usize| MARGIN + (most - count) as f64 * (HEIGHT - MARGIN - BOTTOM) / most as f64;
+    let mut svg = format!(
+        "<svg class=\"chart\" viewBox=\"0 0 {} {}\" role=\"img\" aria-label=\"Instructions after each pass\">\n",
+        WIDTH, HEIGHT
+    );
+    // The axes, from no instructions to the most the function had
+    svg += &format!(
+        "<polyline class=\"axis\" points=\"{left},{top} {left},{bottom} {right},{bottom}\"/>\n",
+        left = LEFT,
+        top = MARGIN,
+        bottom = HEIGHT - BOTTOM,
+        right = WIDTH - MARGIN
+    );
+    svg += &format!(
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>\n<text x=\"{}\" y=\"{}\" text-anchor=\"end\">0</text>\n",
+        LEFT - 4.0,
+        MARGIN + 8.0,
+        most,
+        LEFT - 4.0,
+        HEIGHT - BOTTOM
+    );
+    svg += &format!(
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">passes →</text>\n",
+        WIDTH - MARGIN,
+        HEIGHT - 4.0
+    );
+    let line = points
+        .iter()
+        .enumerate()
+        .map(|(k, point)| format!("{:.1},{:.1}", x(k), y(point.instructions)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    svg += &format!("<polyline class=\"line\" points=\"{}\"/>\n", line);
+    // A dot per snapshot, coloured by whether the pass grew or shrank the function
+    for (k, point) in points.iter().enumerate() {
+        let previous = k.checked_sub(1).map(|k| points[k].instructions);
+        let (class, change) = match previous {
+            Some(previous) if point.instructions > previous => {
+                ("grew", format!(" (+{})", point.instructions - previous))
+            }
+            Some(previous) if point.instructions < previous => {
+                ("shrank", format!(" (-{})", previous - point.instructions))
+            }
+            _ => ("same", String::new()),
+        };
+        let instructions = match point.instructions {
+            1 => "1 instruction".to_string(),
+            count => format!("{} instructions", count),
+        };
+        svg += &format!(
+            "<circle class=\"{}\" cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\"><title>{}: {}{}</title></circle>\n",
+            class,
+            x(k),
+            y(point.instructions),
+            escape(&point.label),
+            instructions,
+            change
+        );
+    }
+    svg += "</svg>\n";
+    svg
+}
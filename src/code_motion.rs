@@ -0,0 +1,143 @@
+//! Instructions or blocks hoisted or sunk by a pass, e.g. by LICM, show up in a diff as lines
+//! removed in one place and the same lines added in another. Such lines are told apart as moves,
+//! so that the built-in engines can link where they were to where they went.
+
+use similar::{DiffOp, TextDiff};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Lines moved together, by index: from `from` before the pass to `to` after it.
+pub struct Move {
+    pub from: Range<usize>,
+    pub to: Range<usize>,
+}
+
+/// Lines too common to be told apart when they're found elsewhere, such as `}` or `ret void`.
+fn is_distinctive(line: &str) -> bool {
+    line.trim().len() >= 12
+}
+
+/// The lines removed and added again elsewhere. Only lines found once on each side are matched,
+/// and moves are grouped by the lines that moved together.
+pub fn moves(diff: &TextDiff<'_, '_, '_, str>) -> Vec<Move> {
+    let (old, new) = (diff.old_slices(), diff.new_slices());
+    let mut removed = HashMap::<&str, Vec<usize>>::new();
+    let mut added = HashMap::<&str, Vec<usize>>::new();
+    for op in diff.ops() {
+        let (old_range, new_range) = match *op {
+            DiffOp::Equal { .. } => continue,
+            _ => (op.old_range(), op.new_range()),
+        };
+        for index in old_range {
+            removed.entry(old[index].trim()).or_default().push(index);
+        }
+        for index in new_range {
+            added.entry(new[index].trim()).or_default().push(index);
+        }
+    }
+    let mut pairs = removed
+        .iter()
+        .filter(|(line, _)| is_distinctive(line))
+        .filter_map(
+            |(line, from)| match (from.as_slice(), added.get(line)?.as_slice()) {
+                ([from], [to]) => Some((*from, *to)),
+                _ => None,
+            },
+        )
+        .collect::<Vec<_>>();
+    pairs.sort_unstable();
+
+    let mut moves = Vec::<Move>::new();
+    for (from, to) in pairs {
+        match moves.last_mut() {
+            Some(last) if last.from.end == from && last.to.end == to => {
+                last.from.end += 1;
+                last.to.end += 1;
+            }
+            _ => moves.push(Move {
+                from: from..from + 1,
+                to: to..to + 1,
+            }),
+        }
+    }
+    moves
+}
+
+/// Line numbers as printed, `12` or `12-14`.
+pub fn lines(range: &Range<usize>) -> String {
+    match range.len() {
+        1 => (range.start + 1).to_string(),
+        _ => format!("{}-{}", range.start + 1, range.end),
+    }
+}
+
+/// Notes telling where the lines moved, printed under unified diffs.
+pub fn notes(moves: &[Move]) -> String {
+    moves
+        .iter()
+        .map(|line_move| {
+            let noun = match line_move.from.len() {
+                1 => "line",
+                _ => "lines",
+            };
+            format!(
+                "# Moved {} {} to {}\n",
+                noun,
+                lines(&line_move.from),
+                lines(&line_move.to)
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{moves, notes};
+    use similar::TextDiff;
+
+    fn moved(before: &str, after: &str) -> String {
+        notes(&moves(&TextDiff::from_lines(before, after)))
+    }
+
+    #[test]
+    fn hoisted_instructions_are_noted() {
+        let before = "\
+entry:
+  br label %loop
+loop:
+  %i = phi i32 [ 0, %entry ], [ %inc, %loop ]
+  %x = shl i32 %n, 1
+  store i32 %x, ptr %p
+  %inc = add i32 %i, 1
+  br label %loop
+";
+        let after = "\
+entry:
+  %x = shl i32 %n, 1
+  br label %loop
+loop:
+  %i = phi i32 [ 0, %entry ], [ %inc, %loop ]
+  store i32 %x, ptr %p
+  %inc = add i32 %i, 1
+  br label %loop
+";
+        assert_eq!(moved(before, after), "# Moved line 5 to 2\n");
+    }
+
+    #[test]
+    fn lines_swapped_within_a_hunk_are_noted() {
+        let before = "\
+entry:
+  %a = load i32, ptr %p
+  %b = load i32, ptr %q
+  ret void
+";
+        let after = "\
+entry:
+  %b = load i32, ptr %q
+  %a = load i32, ptr %p
+  ret void
+";
+        assert_eq!(moved(before, after), "# Moved line 3 to 2\n");
+    }
+}
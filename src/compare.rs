@@ -0,0 +1,504 @@
+//! Two dumps of the same source, e.g. made by two versions of the compiler or with and without a
+//! patch, are compared pass by pass. The passes of each function are lined up by name and run,
+//! and the IR of the two sessions after each pass is diffed wherever it diverges: the old session
+//! is shown as the "before" of the diff, the new one as the "after".
+
+use crate::highlight::Profile;
+use crate::hyperlinks::Links;
+use crate::optpipeline::{self, Dialect, DumpStream, Pass, ProcessOptions};
+use crate::progress::Progress;
+use crate::snapshot::{demangle_text, run_label, snapshots, Normalize};
+use crate::theme::{self, Theme};
+use crate::{
+    cli_writeln, enter_pager, input_kind, matches_pattern, pager_arg, print_func, rank_passes,
+    select_passes,
+};
+use crate::{context_lines, print_title, read_input, select_renderer, side_by_side};
+use crate::{side_by_side_columns, sort_functions, Args, Engine, Layout, Output, Renderer};
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    Result,
+};
+use regex::Regex;
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+mod pipeline_changes;
+
+/// The pipelines of the functions of a dump, by name. Functions defined in several modules are
+/// named after their module too, `foo [a.ll]`.
+fn read_pipelines(
+    path: &Path,
+    args: &Args,
+    options: &ProcessOptions,
+) -> Result<HashMap<String, Vec<Pass>>> {
+    let mut stream = DumpStream::new(true, options);
+    read_input(
+        Some(path),
+        args,
+        &mut stream,
+        &mut Progress::new(!args.no_progress),
+    )?;
+    let dump = stream.finish();
+    if dump.dialect == Dialect::PassDump && !dump.has_pass_dumps {
+        return Err(eyre!("{}", input_kind::explain(&dump.prefix, Some(path))));
+    }
+    for diagnostic in &dump.diagnostics {
+        cli_writeln!(
+            io::stderr(),
+            "warning: {}: {}, the snapshot is left out",
+            path.display(),
+            diagnostic
+        )?;
+    }
+
+    let mut modules = Vec::new();
+    for module in dump.modules {
+        let selected = match &args.module {
+            Some(pattern) => matches_pattern(&module.name, pattern, args.extended_regex)?,
+            None => true,
+        };
+        if selected {
+            modules.push(module);
+        }
+    }
+    let grouped = modules.len() > 1;
+    let mut pipelines = HashMap::new();
+    for module in modules {
+        let module_name = module.name.clone();
+        let result = module
+            .pipelines()
+            .wrap_err_with(|| format!("Parsing error in {}", path.display()))?;
+        for (name, pipeline) in result {
+            let pipeline = match args.depth {
+                Some(depth) => optpipeline::collapse_nested_passes(&pipeline, depth),
+                None => pipeline,
+            };
+            let name = match grouped {
+                true => format!("{} [{}]", name, module_name),
+                false => name,
+            };
+            pipelines.insert(name, pipeline);
+        }
+    }
+    Ok(pipelines)
+}
+
+/// A pass of one session or both, lined up.
+enum Step<'a> {
+    Both(&'a Pass, &'a Pass),
+    Old(&'a Pass),
+    New(&'a Pass),
+}
+
+/// Lines up the passes of the two sessions by name and run.
+fn align<'a>(old: &'a [Pass], new: &'a [Pass]) -> Vec<Step<'a>> {
+    let key = |pass: &Pass| pass.name.clone() + &run_label(pass);
+    let old_keys = old.iter().map(key).collect::<Vec<_>>();
+    let new_keys = new.iter().map(key).collect::<Vec<_>>();
+    let mut steps = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, &old_keys, &new_keys) {
+        match op {
+            DiffOp::Equal {
+                old_index,
+                new_index,
+                len,
+            } => {
+                steps.extend((0..len).map(|i| Step::Both(&old[old_index + i], &new[new_index + i])))
+            }
+            _ => {
+                steps.extend(op.old_range().map(|i| Step::Old(&old[i])));
+                steps.extend(op.new_range().map(|i| Step::New(&new[i])));
+            }
+        }
+    }
+    steps
+}
+
+/// The IR of a session after the pass, the IR it ran on if the pass left no snapshot.
+fn after_pass(pass: &Pass, ir: &str) -> String {
+    match pass.invalidated {
+        true => ir.to_string(),
+        false => pass.after.clone(),
+    }
+}
+
+/// A pass standing for the IR of two sessions after the pass, `old_ir` before and `new_ir` after.
+fn comparison(template: &Pass, name: String, old_ir: &str, new_ir: &str) -> Pass {
+    Pass {
+        name,
+        before: old_ir.to_string(),
+        after: new_ir.to_string(),
+        ir_changed: old_ir != new_ir,
+        invalidated: false,
+        deleted: false,
+        before_unknown: false,
+        after_unknown: false,
+        renamed_to: None,
+        run: 1,
+        runs: 1,
+        time: None,
+        remarks: Vec::new(),
+        skipped_before: Vec::new(),
+        skipped_after: Vec::new(),
+        ..template.clone()
+    }
+}
+
+/// The passes after which the IR of the two sessions differs, or stops differing, each standing
+/// for the IR of both: the old session before, the new one after.
+fn divergences(old: &[Pass], new: &[Pass], old_file: &str, new_file: &str) -> Vec<Pass> {
+    let (Some(first), Some(new_first)) = (old.first(), new.first()) else {
+        return Vec::new();
+    };
+    let divergence = |name, old_ir: &str, new_ir: &str| comparison(first, name, old_ir, new_ir);
+
+    let mut passes = Vec::new();
+    let (mut old_ir, mut new_ir) = (first.before.clone(), new_first.before.clone());
+    if old_ir != new_ir {
+        passes.push(divergence(
+            "The IR the pipelines start from".to_string(),
+            &old_ir,
+            &new_ir,
+        ));
+    }
+    for step in align(old, new) {
+        let (name, old_after, new_after) = match step {
+            Step::Both(old_pass, new_pass) => (
+                old_pass.name.clone() + &run_label(old_pass),
+                after_pass(old_pass, &old_ir),
+                after_pass(new_pass, &new_ir),
+            ),
+            Step::Old(pass) => (
+                format!("{}{} (only in {})", pass.name, run_label(pass), old_file),
+                after_pass(pass, &old_ir),
+                new_ir.clone(),
+            ),
+            Step::New(pass) => (
+                format!("{}{} (only in {})", pass.name, run_label(pass), new_file),
+                old_ir.clone(),
+                after_pass(pass, &new_ir),
+            ),
+        };
+        // Passes doing the same to both sessions aren't shown, nor passes after which both are
+        // the same as before
+        let diverged = old_ir != new_ir;
+        let unchanged = old_after == old_ir && new_after == new_ir;
+        if !unchanged && (diverged || old_after != new_after) {
+            let name = match old_after == new_after {
+                true => name + " (the same IR again)",
+                false => name,
+            };
+            passes.push(divergence(name, &old_after, &new_after));
+        }
+        (old_ir, new_ir) = (old_after, new_after);
+    }
+    passes
+}
+
+/// Prints how the pipelines of the functions changed, before the diffs.
+fn print_pipeline_changes(
+    functions: &[(&str, &[Pass], &[Pass])],
+    old_file: &str,
+    new_file: &str,
+) -> Result<()> {
+    let mut stdout = io::stdout();
+    let changes = pipeline_changes::report(functions, old_file, new_file);
+    if changes.is_empty() {
+        cli_writeln!(
+            stdout,
+            "# The pipelines run the same passes in both dumps\n"
+        )?;
+        return Ok(());
+    }
+    cli_writeln!(
+        stdout,
+        "# The pipelines changed from {} to {}:",
+        old_file,
+        new_file
+    )?;
+    for (change, functions) in changes {
+        match functions.as_slice() {
+            [function] => cli_writeln!(stdout, "#   {}, in {}", change, function)?,
+            _ => cli_writeln!(
+                stdout,
+                "#   {}, in {} functions, e.g. {}",
+                change,
+                functions.len(),
+                functions[0]
+            )?,
+        }
+    }
+    cli_writeln!(stdout, "")?;
+    Ok(())
+}
+
+/// The functions found in every dump and selected by `-f`, by name and demangled name. The
+/// functions missing from a dump are warned about.
+fn select_functions<'a>(
+    sessions: &'a [HashMap<String, Vec<Pass>>],
+    files: &[String],
+    args: &Args,
+) -> Result<Vec<(&'a str, String)>> {
+    let function = match (&args.function, args.extended_regex) {
+        (Some(pattern), true) => Some(
+            Regex::new(pattern).wrap_err_with(|| format!("Invalid regex pattern: {}", pattern))?,
+        ),
+        _ => None,
+    };
+    let is_selected = |name: &str| match (&args.function, &function) {
+        (_, Some(regex)) => regex.is_match(name),
+        (Some(expected), None) => name == expected,
+        (None, None) => true,
+    };
+    let all = sessions
+        .iter()
+        .flat_map(|functions| functions.keys().map(String::as_str))
+        .collect::<HashSet<_>>();
+    for (file, functions) in files.iter().zip(sessions) {
+        let missing = sort_functions(
+            all.iter()
+                .copied()
+                .filter(|name| !functions.contains_key(*name)),
+        );
+        let missing = missing
+            .into_iter()
+            .map(|(name, _)| demangle_text(name, args.demangle))
+            .filter(|name| is_selected(name))
+            .collect::<Vec<_>>();
+        if let Some(example) = missing.first() {
+            cli_writeln!(
+                io::stderr(),
+                "warning: {} function(s) are missing from {}, e.g. {}",
+                missing.len(),
+                file,
+                example
+            )?;
+        }
+    }
+    let mut selected = Vec::new();
+    for (name, _) in sort_functions(all.into_iter().filter(|name| {
+        sessions
+            .iter()
+            .all(|functions| functions.contains_key(*name))
+    })) {
+        let demangled = demangle_text(name, args.demangle);
+        if is_selected(&demangled) {
+            selected.push((name, demangled));
+        }
+    }
+    if selected.is_empty() {
+        return Err(eyre!(
+            "No function was found in all of {}",
+            files.join(", ")
+        ));
+    }
+    Ok(selected)
+}
+
+/// The IR of a variant after each pass of the base. The passes the base doesn't run count
+/// towards its next pass, or its last one at the end.
+fn variant_after(base: &[Pass], variant: &[Pass]) -> Vec<String> {
+    let mut ir = variant
+        .first()
+        .map_or(String::new(), |pass| pass.before.clone());
+    let mut after = Vec::with_capacity(base.len());
+    for step in align(base, variant) {
+        match step {
+            Step::Both(_, pass) => {
+                ir = after_pass(pass, &ir);
+                after.push(ir.clone());
+            }
+            Step::Old(_) => after.push(ir.clone()),
+            Step::New(pass) => ir = after_pass(pass, &ir),
+        }
+    }
+    if let Some(last) = after.last_mut() {
+        *last = ir;
+    }
+    after
+}
+
+/// Prints the IR of the base and the two variants side by side in three columns, after the
+/// passes where a variant diverges from the base.
+fn print_three_way(
+    func_name: &str,
+    base: &[Pass],
+    variants: [&[Pass]; 2],
+    args: &Args,
+    theme: &Theme,
+) -> Result<bool> {
+    let Some(first) = base.first() else {
+        return Ok(false);
+    };
+    let after = variants.map(|variant| variant_after(base, variant));
+    let normalize = Normalize::from_args(args);
+    let initial = variants.map(|variant| {
+        variant
+            .first()
+            .map_or(String::new(), |pass| pass.before.clone())
+    });
+    let mut last = (first.before.clone(), initial.clone());
+    let mut rows = Vec::new();
+    if initial.iter().any(|ir| *ir != first.before) {
+        rows.push((
+            format!("({}) The IR the pipelines start from", func_name),
+            last.clone(),
+            first.machine,
+        ));
+    }
+    let mut base_ir = first.before.clone();
+    for (k, pass) in base.iter().enumerate() {
+        base_ir = after_pass(pass, &base_ir);
+        let state = (base_ir.clone(), [after[0][k].clone(), after[1][k].clone()]);
+        let name = format!("({}·{}) {}{}", k + 1, func_name, pass.name, run_label(pass));
+        let diverged = last.1.iter().any(|ir| *ir != last.0);
+        let diverges = state.1.iter().any(|ir| *ir != state.0);
+        if state != last && (diverged || diverges) {
+            rows.push((name, state.clone(), pass.machine));
+        }
+        last = state;
+    }
+
+    let mut printed = false;
+    for (title, (base_ir, variant_irs), machine) in rows {
+        if let Some(filter) = &args.pass {
+            if !matches_pattern(&title, filter, args.extended_regex)? {
+                continue;
+            }
+        }
+        let diverges = variant_irs.iter().any(|ir| *ir != base_ir);
+        if args.skip_unchanged && !diverges {
+            continue;
+        }
+        let [(base_text, first_text), (_, second_text)] = variant_irs.map(|ir| {
+            let pass = comparison(first, String::new(), &base_ir, &ir);
+            snapshots(&pass, args.demangle, normalize)
+        });
+        let (columns, context) = (side_by_side_columns(args), context_lines(args));
+        let mut stdout = io::stdout();
+        print_title(
+            &mut stdout,
+            &title,
+            &title,
+            &Renderer::Builtin(Layout {
+                engine: Engine::SideBySide,
+                by_block: false,
+                context,
+                columns,
+                theme: theme.clone(),
+                links: Links::default(),
+            }),
+        )?;
+        let rendered = side_by_side::render_three_way(
+            &base_text,
+            &first_text,
+            &second_text,
+            columns.width,
+            Profile::of(machine),
+            context,
+            theme,
+        );
+        cli_writeln!(stdout, "{}", rendered)?;
+        printed = true;
+    }
+    Ok(printed)
+}
+
+/// Compares the dumps function by function, printing where their IR diverges: the first one
+/// against the second, or against the second and the third at once.
+pub fn compare(paths: &[PathBuf], args: &Args, options: &ProcessOptions) -> Result<()> {
+    if paths.len() == 3 && (args.diff_tool.is_some() || args.difft_args.is_some() || args.by_block)
+    {
+        return Err(eyre!(
+            "Three dumps are compared in three columns, options `--diff-tool`, `--difft-args` and `--by-block` only compare two"
+        ));
+    }
+    let mut sessions = Vec::new();
+    for path in paths {
+        sessions.push(read_pipelines(path, args, options)?);
+    }
+    let files = paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>();
+    let selected = select_functions(&sessions, &files, args)?;
+
+    let renderer = select_renderer(args, pager_arg(args))?;
+    enter_pager(pager_arg(args));
+    for variant in 1..sessions.len() {
+        let functions = selected
+            .iter()
+            .map(|(name, demangled)| {
+                (
+                    demangled.as_str(),
+                    &sessions[0][*name][..],
+                    &sessions[variant][*name][..],
+                )
+            })
+            .collect::<Vec<_>>();
+        print_pipeline_changes(&functions, &files[0], &files[variant])?;
+    }
+    let theme = theme::load(args.theme)?;
+    let mut diverged = 0;
+    for (name, demangled) in selected {
+        if let [base, first, second] = sessions.as_slice() {
+            if print_three_way(
+                &demangled,
+                &base[name],
+                [&first[name], &second[name]],
+                args,
+                &theme,
+            )? {
+                diverged += 1;
+            }
+            continue;
+        }
+        let (old, new) = (&sessions[0][name], &sessions[1][name]);
+        let passes = divergences(old, new, &files[0], &files[1]);
+        if passes.is_empty() {
+            continue;
+        }
+        diverged += 1;
+        let normalize = Normalize::from_args(args);
+        let shown = select_passes(
+            &passes,
+            0,
+            args.skip_unchanged,
+            args.pass.as_deref(),
+            args.extended_regex,
+            args.demangle,
+            normalize,
+        )?;
+        let ranked = rank_passes(
+            &[(demangled.as_str(), passes.as_slice(), shown)],
+            args.demangle,
+            normalize,
+            args.min_change,
+            args.sort_by_change,
+            &renderer,
+        );
+        print_func(
+            &demangled,
+            &passes,
+            0,
+            ranked.into_iter().next().unwrap(),
+            None,
+            args.demangle,
+            normalize,
+            args.describe,
+            &renderer,
+            &Output::Stdout,
+        )?;
+    }
+    if diverged == 0 {
+        cli_writeln!(
+            io::stderr(),
+            "The IR of {} is the same after every pass",
+            files.join(", ")
+        )?;
+    }
+    Ok(())
+}
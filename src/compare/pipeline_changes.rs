@@ -0,0 +1,106 @@
+//! A compiler upgrade changes the pipeline as well as what the passes do. The passes the two
+//! dumps run on each function are compared regardless of the IR: passes found in one of them
+//! only, passes that moved to another place of the pipeline, and passes that run another number
+//! of times. The same change usually shows up in many functions, it's reported once for all of
+//! them.
+
+use crate::optpipeline::Pass;
+use itertools::Itertools;
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+use std::collections::{HashMap, HashSet};
+
+/// The pass without the IR unit it ran on, `InstCombinePass` of `InstCombinePass on foo`.
+fn class(pass: &Pass) -> &str {
+    pass.name
+        .split_once(" on ")
+        .map_or(pass.name.as_str(), |(class, _)| class)
+}
+
+fn times(count: usize) -> String {
+    match count {
+        1 => "once".to_string(),
+        count => format!("{} times", count),
+    }
+}
+
+/// The passes in the order they first ran, each with the number of times it ran.
+fn runs(pipeline: &[Pass]) -> Vec<(&str, usize)> {
+    let counts = pipeline.iter().map(class).counts();
+    pipeline
+        .iter()
+        .map(class)
+        .unique()
+        .map(|class| (class, counts[class]))
+        .collect()
+}
+
+/// The differences of the pipelines of each function, from `old` to `new`, each followed by the
+/// functions it was found in.
+pub fn report<'a>(
+    functions: &[(&'a str, &[Pass], &[Pass])],
+    old_file: &str,
+    new_file: &str,
+) -> Vec<(String, Vec<&'a str>)> {
+    let mut changes = HashMap::<String, Vec<&str>>::new();
+    for &(function, old, new) in functions {
+        let (old, new) = (runs(old), runs(new));
+        let old_counts = old.iter().copied().collect::<HashMap<_, _>>();
+        let new_counts = new.iter().copied().collect::<HashMap<_, _>>();
+        let mut found = HashSet::new();
+        for (runs, others, file) in [(&old, &new_counts, old_file), (&new, &old_counts, new_file)] {
+            for &(class, _) in runs.iter().filter(|(class, _)| !others.contains_key(class)) {
+                found.insert(format!("{} only runs in {}", class, file));
+            }
+        }
+        for &(class, count) in &old {
+            match new_counts.get(class) {
+                Some(&new_count) if new_count != count => found.insert(format!(
+                    "{} runs {} in {}, {} in {}",
+                    class,
+                    times(new_count),
+                    new_file,
+                    times(count),
+                    old_file
+                )),
+                _ => false,
+            };
+        }
+        // The passes of both are lined up by where they first ran, the ones left out of the
+        // alignment moved
+        let old_order = old
+            .iter()
+            .map(|(class, _)| *class)
+            .filter(|class| new_counts.contains_key(class))
+            .collect::<Vec<_>>();
+        let new_order = new
+            .iter()
+            .map(|(class, _)| *class)
+            .filter(|class| old_counts.contains_key(class))
+            .collect::<Vec<_>>();
+        for op in capture_diff_slices(Algorithm::Myers, &old_order, &new_order) {
+            if let DiffOp::Equal { .. } = op {
+                continue;
+            }
+            for i in op.new_range() {
+                found.insert(match i {
+                    0 => format!("{} moved to the start in {}", new_order[i], new_file),
+                    _ => format!(
+                        "{} moved after {} in {}",
+                        new_order[i],
+                        new_order[i - 1],
+                        new_file
+                    ),
+                });
+            }
+        }
+        for change in found {
+            changes.entry(change).or_default().push(function);
+        }
+    }
+    changes
+        .into_iter()
+        .sorted_by(|(a, a_functions), (b, b_functions)| {
+            b_functions.len().cmp(&a_functions.len()).then(a.cmp(b))
+        })
+        .collect()
+}
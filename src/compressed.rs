@@ -0,0 +1,94 @@
+//! Dumps kept by CI are usually compressed. gzip, xz and zstd streams are told apart by their
+//! magic bytes rather than the file name, so compressed stdin works too, and are decoded as
+//! they're read.
+
+use flate2::bufread::MultiGzDecoder;
+use ruzstd::decoding::errors::{FrameDecoderError, ReadFrameHeaderError};
+use ruzstd::decoding::{BlockDecodingStrategy, FrameDecoder};
+use std::io::{self, BufRead, BufReader, Read};
+use xz2::bufread::XzDecoder;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Decodes the frames of a zstd stream one after the other, `zstd` writes one per file but the
+/// files may be concatenated.
+struct ZstdFrames<R: BufRead> {
+    source: R,
+    decoder: FrameDecoder,
+    /// The last frame was decoded
+    done: bool,
+}
+
+impl<R: BufRead> ZstdFrames<R> {
+    fn new(source: R) -> io::Result<Self> {
+        let mut frames = ZstdFrames {
+            source,
+            decoder: FrameDecoder::new(),
+            done: false,
+        };
+        frames.next_frame()?;
+        Ok(frames)
+    }
+
+    /// Starts decoding the next frame, skipping the frames that hold no data.
+    fn next_frame(&mut self) -> io::Result<()> {
+        loop {
+            if self.source.fill_buf()?.is_empty() {
+                self.done = true;
+                return Ok(());
+            }
+            match self.decoder.reset(&mut self.source) {
+                Ok(()) => return Ok(()),
+                Err(FrameDecoderError::ReadFrameHeaderError(ReadFrameHeaderError::SkipFrame {
+                    length,
+                    ..
+                })) => {
+                    io::copy(&mut (&mut self.source).take(length.into()), &mut io::sink())?;
+                }
+                Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Read for ZstdFrames<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !self.done && !buf.is_empty() {
+            if self.decoder.can_collect() > 0 {
+                return self.decoder.read(buf);
+            }
+            if self.decoder.is_finished() {
+                self.next_frame()?;
+                continue;
+            }
+            self.decoder
+                .decode_blocks(
+                    &mut self.source,
+                    BlockDecodingStrategy::UptoBytes(buf.len()),
+                )
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        }
+        Ok(0)
+    }
+}
+
+/// Wraps `input` in the decoder of its compression format, if it's compressed at all.
+pub fn decode<'a>(mut input: impl Read + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    // The magic bytes are read ahead, and put back in front of the rest
+    let mut magic = Vec::with_capacity(XZ_MAGIC.len());
+    (&mut input)
+        .take(XZ_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    let input = BufReader::new(io::Cursor::new(magic.clone()).chain(input));
+    Ok(if magic.starts_with(GZIP_MAGIC) {
+        Box::new(BufReader::new(MultiGzDecoder::new(input)))
+    } else if magic.starts_with(XZ_MAGIC) {
+        Box::new(BufReader::new(XzDecoder::new_multi_decoder(input)))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        Box::new(BufReader::new(ZstdFrames::new(input)?))
+    } else {
+        Box::new(input)
+    })
+}
@@ -0,0 +1,114 @@
+//! The config file, `~/.config/optdiff/config.toml` or wherever `OPTDIFF_CONFIG` points. Its
+//! sections are read by the features they configure, each a list of `key = "value"` lines:
+//!
+//! ```toml
+//! [theme]
+//! name = "light"
+//!
+//! [links]
+//! MyVectorizerPass = "https://docs.example.com/passes/my-vectorizer.html"
+//! ```
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A `key = "value"` line of a section.
+pub struct Entry {
+    /// The line of the file, for the errors
+    pub line: usize,
+    pub key: String,
+    pub value: String,
+}
+
+/// The config file, if there's a home to find it in.
+fn path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("OPTDIFF_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("optdiff").join("config.toml"))
+}
+
+pub fn invalid(path: &Path, line: usize, message: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}:{}: {}", path.display(), line, message),
+    )
+}
+
+/// `text` between double quotes, which a comment may follow.
+fn unquote(text: &str) -> Option<&str> {
+    let (value, rest) = text.strip_prefix('"')?.split_once('"')?;
+    let rest = rest.trim_start();
+    (rest.is_empty() || rest.starts_with('#')).then_some(value)
+}
+
+/// The entries of the section `name` of a config file. Other sections are left to whatever
+/// else reads them.
+fn parse_section(text: &str, path: &Path, name: &str) -> io::Result<Vec<Entry>> {
+    let mut in_section = false;
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[') {
+            in_section = section.trim_end_matches(']').trim() == name;
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        // Keys may be quoted, for the pass names with spaces
+        let split = match line.starts_with('"') {
+            true => line[1..]
+                .split_once('"')
+                .and_then(|(key, rest)| Some((key, rest.trim_start().strip_prefix('=')?))),
+            false => line.split_once('=').map(|(key, value)| (key.trim(), value)),
+        };
+        let Some((key, value)) = split else {
+            return Err(invalid(
+                path,
+                i + 1,
+                format!("Expected `key = \"value\"`: {}", line),
+            ));
+        };
+        let Some(value) = unquote(value.trim()) else {
+            return Err(invalid(
+                path,
+                i + 1,
+                format!("Expected a quoted string: {}", value.trim()),
+            ));
+        };
+        entries.push(Entry {
+            line: i + 1,
+            key: key.to_string(),
+            value: value.to_string(),
+        });
+    }
+    Ok(entries)
+}
+
+/// The path of the config file and the entries of its section `name`, none if there's no
+/// config file.
+pub fn section(name: &str) -> io::Result<Option<(PathBuf, Vec<Entry>)>> {
+    let Some(path) = path() else {
+        return Ok(None);
+    };
+    match fs::read_to_string(&path) {
+        Ok(text) => {
+            let entries = parse_section(&text, &path, name)?;
+            Ok(Some((path, entries)))
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(io::Error::new(
+            err.kind(),
+            format!("Failed to read {}: {}", path.display(), err),
+        )),
+    }
+}
@@ -0,0 +1,70 @@
+//! Metrics of the passes as CSV, with `--format csv`, a row per pass of each function to pivot
+//! in a spreadsheet: how many lines each pass added and removed, and how many instructions the
+//! function had before and after it.
+
+use crate::change_score::{self, is_instruction};
+use crate::optpipeline::Pass;
+
+const HEADER: &str = "function,number,pass,run,runs,changed,deleted,invalidated,lines_added,lines_removed,instructions_before,instructions_after";
+
+/// `field` quoted if it holds a separator, a quote or a line break.
+fn field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+fn instructions(snapshot: &str) -> usize {
+    snapshot.lines().filter(|line| is_instruction(line)).count()
+}
+
+/// A pass as a row. `snapshots` are the snapshots as compared, none for passes that left
+/// nothing to compare, whose counts are left empty.
+pub fn row(function: &str, number: usize, pass: &Pass, snapshots: Option<(&str, &str)>) -> String {
+    let counts = match snapshots {
+        Some((before, after)) => {
+            let score = change_score::score(before, after);
+            format!(
+                "{},{},{},{}",
+                score.added,
+                score.removed,
+                instructions(before),
+                instructions(after)
+            )
+        }
+        None => ",,,".to_string(),
+    };
+    format!(
+        "{},{},{},{},{},{},{},{},{}",
+        field(function),
+        number,
+        field(&pass.name),
+        pass.run,
+        pass.runs,
+        pass.ir_changed,
+        pass.deleted,
+        pass.invalidated,
+        counts
+    )
+}
+
+#[derive(Default)]
+pub struct Table {
+    rows: Vec<String>,
+}
+
+impl Table {
+    pub fn add_rows(&mut self, rows: Vec<String>) {
+        self.rows.extend(rows);
+    }
+
+    pub fn render(&self) -> String {
+        let mut table = format!("{}\n", HEADER);
+        for row in &self.rows {
+            table += row;
+            table += "\n";
+        }
+        table
+    }
+}
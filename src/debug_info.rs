@@ -0,0 +1,26 @@
+//! The debug info of LLVM IR and MIR is filtered out of the snapshots along with the metadata.
+//! The other dialects keep theirs, which changes along with the code and drowns out the changes
+//! of the code itself: `# DEBUG` binds of GIMPLE, `debug_value` of SIL and the `loc(...)`
+//! locations of MLIR.
+
+use regex::Regex;
+
+/// The lines that only carry debug info.
+fn is_debug_line(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("# DEBUG ")
+        || line.starts_with("debug_value ")
+        || line.starts_with("debug_step")
+        || (line.starts_with("#loc") && line.contains(" = loc("))
+}
+
+/// The snapshot without its debug info.
+pub fn strip(text: &str) -> String {
+    // Locations nest once at most in practice, e.g. `loc(callsite(#loc1 at #loc2))`
+    let location = Regex::new(r#" loc\((?:[^()"]|"[^"]*"|\((?:[^()"]|"[^"]*")*\))*\)"#).unwrap();
+    let lines = text
+        .split_inclusive('\n')
+        .filter(|line| !is_debug_line(line))
+        .collect::<String>();
+    location.replace_all(&lines, "").into_owned()
+}
@@ -60,9 +60,7 @@ where
         };
 
         if let Ok((sym, tail)) = BorrowedSymbol::with_tail(&line[idx..]) {
-            let demangled = sym
-                .demangle(&options)
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            let demangled = sym.demangle(&options).map_err(io::Error::other)?;
             write!(out, "{}", demangled)?;
             line = tail;
         } else {
@@ -0,0 +1,101 @@
+//! An overview of where in the pipeline a function changed, with `--stat`: a line per pass with
+//! the lines it added and removed, as a bar of `+` and `-` like `git diff --stat` prints.
+//!
+//! ```text
+//!  (3·foo) SROAPass on foo        | 14 ++++------
+//!  (7·foo) InstCombinePass on foo |  3 ++-
+//!  2 passes changed foo, 6 insertions(+), 11 deletions(-)
+//! ```
+
+use crate::change_score::ChangeScore;
+use crate::theme::Theme;
+
+/// The widest the bars get, longer ones are scaled down.
+const BAR_WIDTH: usize = 50;
+
+/// A pass as listed.
+pub struct Row {
+    pub name: String,
+    /// None for passes that didn't change the function or left nothing to compare
+    pub score: Option<ChangeScore>,
+    /// What the pass did instead if it left nothing to compare: deleted the function or
+    /// invalidated its IR
+    pub placeholder: Option<&'static str>,
+}
+
+/// `count` out of `total` scaled to `width`, at least one if there was any.
+fn scale(count: usize, total: usize, width: usize) -> usize {
+    match count {
+        0 => 0,
+        count if total <= width => count,
+        count => (count * width / total).max(1),
+    }
+}
+
+fn count(count: usize, one: &str, many: &str) -> String {
+    match count {
+        1 => format!("1 {}", one),
+        count => format!("{} {}", count, many),
+    }
+}
+
+/// The passes that changed the function, nothing if none did. Without a theme, uncoloured.
+pub fn render(func_name: &str, rows: &[Row], theme: Option<&Theme>) -> String {
+    let plain = Theme::default();
+    let theme = theme.unwrap_or(&plain);
+    let rows = rows
+        .iter()
+        .filter(|row| match row.score {
+            Some(score) => score.added + score.removed > 0,
+            None => row.placeholder.is_some(),
+        })
+        .collect::<Vec<_>>();
+    if rows.is_empty() {
+        return String::new();
+    }
+    let name_width = rows
+        .iter()
+        .map(|row| row.name.chars().count())
+        .max()
+        .unwrap_or_default();
+    let changed = |row: &&Row| row.score.map_or(0, |score| score.added + score.removed);
+    let most = rows.iter().map(changed).max().unwrap_or_default();
+    let count_width = most.to_string().len();
+
+    let mut rendered = String::new();
+    let (mut insertions, mut deletions) = (0, 0);
+    for row in &rows {
+        let Some(score) = row.score else {
+            rendered += &format!(
+                " {:name_width$} | {}\n",
+                row.name,
+                theme.comment.paint(row.placeholder.unwrap_or_default())
+            );
+            continue;
+        };
+        insertions += score.added;
+        deletions += score.removed;
+        let total = score.added + score.removed;
+        let width = scale(total, most, BAR_WIDTH);
+        let mut plus = scale(score.added, total, width);
+        if score.removed > 0 && plus == width && width > 1 {
+            plus -= 1;
+        }
+        let minus = width - plus;
+        rendered += &format!(
+            " {:name_width$} | {:>count_width$} {}{}\n",
+            row.name,
+            total,
+            theme.added.paint(&"+".repeat(plus)),
+            theme.removed.paint(&"-".repeat(minus))
+        );
+    }
+    rendered += &format!(
+        " {} changed {}, {}(+), {}(-)\n",
+        count(rows.len(), "pass", "passes"),
+        func_name,
+        count(insertions, "insertion", "insertions"),
+        count(deletions, "deletion", "deletions")
+    );
+    rendered
+}
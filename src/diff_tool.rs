@@ -0,0 +1,148 @@
+//! Diffs can be left to any program that compares two files, e.g. `--diff-tool 'diff -u'` or
+//! `--diff-tool 'difft --display inline {before} {after}'`. The snapshots before and after a pass
+//! are written to files, and the command is run once per pass.
+//!
+//! The same two files are rewritten for every pass, on tmpfs when there is one, and only when the
+//! snapshot differs from the one they hold. The built-in engines diff the snapshots in memory.
+//!
+//! The command is split at whitespace, no shell is involved. `{before}` and `{after}` stand for
+//! the paths of the snapshots, `{title}` for the pass as titled in diffs. Without `{before}` or
+//! `{after}`, both paths follow the command.
+//!
+//! Starting a process per pass adds up over big pipelines. In batches, the snapshots of all the
+//! passes of a function are written to a `before` and an `after` directory, one file per pass,
+//! and the command compares the directories once, e.g. `diff -ru` or `difft`.
+//!
+//! The snapshots are written as `.ll` files, and as `.mir` files for machine passes, so that
+//! tools can tell Machine IR apart, e.g. with difftastic's `--override='*.mir:...'`. Difftastic
+//! doesn't know either language and diffs them as text: it only parses the languages it was
+//! built with, so there's no grammar to pass it.
+
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Where the snapshots are written, `/dev/shm` keeps them off the disk.
+fn arena() -> PathBuf {
+    let shm = Path::new("/dev/shm");
+    let base = match shm.is_dir() {
+        true => shm.to_path_buf(),
+        false => std::env::temp_dir(),
+    };
+    base.join(format!("optdiff-{}", std::process::id()))
+}
+
+fn hash(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+pub struct DiffTool {
+    program: String,
+    args: Vec<String>,
+    /// The command takes the paths of the snapshots where it says, not at its end
+    placed_paths: bool,
+    pub titled: bool,
+    /// The command compares the passes of a function at once
+    pub batch: bool,
+    dir: PathBuf,
+    /// What the snapshot files hold
+    written: [Cell<Option<u64>>; 2],
+}
+
+impl DiffTool {
+    /// Returns `None` for a command without a program.
+    pub fn new(command: &str, batch: bool) -> Option<Self> {
+        let mut words = command.split_whitespace().map(str::to_string);
+        let program = words.next()?;
+        let args = words.collect::<Vec<_>>();
+        let uses = |placeholder: &str| args.iter().any(|arg| arg.contains(placeholder));
+        Some(DiffTool {
+            placed_paths: uses("{before}") || uses("{after}"),
+            titled: uses("{title}"),
+            batch,
+            program,
+            args,
+            dir: arena(),
+            written: Default::default(),
+        })
+    }
+
+    /// Writes a snapshot to its file, unless the file holds it already.
+    fn write(&self, side: usize, path: &Path, snapshot: &str) -> io::Result<()> {
+        let hash = hash((path, snapshot));
+        if self.written[side].get() != Some(hash) {
+            std::fs::write(path, snapshot)?;
+            self.written[side].set(Some(hash));
+        }
+        Ok(())
+    }
+
+    /// Runs the command on the snapshots of a pass, written as files with the `extension`. Its
+    /// output goes along with ours.
+    pub fn run(&self, title: &str, before: &str, after: &str, extension: &str) -> io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let before_path = self.dir.join(format!("before.{}", extension));
+        let after_path = self.dir.join(format!("after.{}", extension));
+        self.write(0, &before_path, before)?;
+        self.write(1, &after_path, after)?;
+        self.spawn(title, &before_path, &after_path)
+    }
+
+    /// Runs the command once on the snapshots of several passes, given by their file name.
+    pub fn run_batch(&self, title: &str, passes: &[(String, String, String)]) -> io::Result<()> {
+        let before_dir = self.dir.join("before");
+        let after_dir = self.dir.join("after");
+        for dir in [&before_dir, &after_dir] {
+            if dir.exists() {
+                std::fs::remove_dir_all(dir)?;
+            }
+            std::fs::create_dir_all(dir)?;
+        }
+        for (name, before, after) in passes {
+            std::fs::write(before_dir.join(name), before)?;
+            std::fs::write(after_dir.join(name), after)?;
+        }
+        self.spawn(title, &before_dir, &after_dir)
+    }
+
+    fn spawn(&self, title: &str, before_path: &Path, after_path: &Path) -> io::Result<()> {
+        let (before_path, after_path) = (before_path.display(), after_path.display());
+        let mut args = self
+            .args
+            .iter()
+            .map(|arg| {
+                arg.replace("{before}", &before_path.to_string())
+                    .replace("{after}", &after_path.to_string())
+                    .replace("{title}", title)
+            })
+            .collect::<Vec<_>>();
+        if !self.placed_paths {
+            args.push(before_path.to_string());
+            args.push(after_path.to_string());
+        }
+        // What was printed so far goes first
+        io::stdout().flush()?;
+        // Diff programs exit with an error when the files differ
+        Command::new(&self.program)
+            .args(args)
+            .status()
+            .map_err(|err| {
+                io::Error::new(
+                    err.kind(),
+                    format!("Failed to run the diff tool `{}`: {}", self.program, err),
+                )
+            })?;
+        Ok(())
+    }
+}
+
+impl Drop for DiffTool {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,103 @@
+//! The pass pipeline of the functions as a Graphviz graph, with `optdiff export --dot`, for
+//! documentation and to see how the pipeline is put together: a node per pass, in the order
+//! they ran, filled in if the pass changed the function, and boxed in with the passes that ran
+//! under the same pass manager adaptor.
+//!
+//! ```sh
+//! optdiff export --dot dump.txt -f foo | dot -Tsvg > pipeline.svg
+//! ```
+
+use crate::optpipeline::Pass;
+
+/// The fill of the passes that changed the function, and of those that deleted it or
+/// invalidated its IR.
+const CHANGED: &str = "#b7e4b0";
+const DROPPED: &str = "#f4b6b6";
+
+/// `text` as a quoted DOT string.
+fn quote(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// The graph of the functions, a cluster each.
+#[derive(Default)]
+pub struct Graph {
+    clusters: Vec<String>,
+}
+
+impl Graph {
+    /// Adds the passes of a function, by their index in the pipeline, labelled as given.
+    pub fn add_function(&mut self, name: &str, passes: &[(usize, &Pass, String)]) {
+        let f = self.clusters.len();
+        let mut cluster = format!(
+            "  subgraph cluster_{} {{\n    label = {};\n    style = bold;\n",
+            f,
+            quote(name)
+        );
+        // The adaptors enclosing the previous pass, a cluster each
+        let mut adaptors: &[&str] = &[];
+        let mut nested = 0;
+        // The passes are linked once their clusters are closed
+        let mut edges = String::new();
+        for (k, &(i, pass, ref label)) in passes.iter().enumerate() {
+            let common = adaptors
+                .iter()
+                .zip(&pass.adaptors)
+                .take_while(|(previous, adaptor)| previous == adaptor)
+                .count();
+            for depth in (common..adaptors.len()).rev() {
+                cluster += &format!("{}}}\n", "  ".repeat(depth + 2));
+            }
+            for (depth, adaptor) in pass.adaptors.iter().enumerate().skip(common) {
+                let indent = "  ".repeat(depth + 2);
+                cluster += &format!(
+                    "{indent}subgraph cluster_{}_{} {{\n{indent}  label = {};\n{indent}  style = dashed;\n",
+                    f,
+                    nested,
+                    quote(adaptor)
+                );
+                nested += 1;
+            }
+            adaptors = &pass.adaptors;
+            let fill = if pass.deleted || pass.invalidated {
+                DROPPED
+            } else if pass.ir_changed {
+                CHANGED
+            } else {
+                "white"
+            };
+            cluster += &format!(
+                "{}f{}_{} [label = {}, fillcolor = \"{}\"];\n",
+                "  ".repeat(adaptors.len() + 2),
+                f,
+                i,
+                quote(label),
+                fill
+            );
+            if k > 0 {
+                edges += &format!("    f{}_{} -> f{}_{};\n", f, passes[k - 1].0, f, i);
+            }
+        }
+        for depth in (0..adaptors.len()).rev() {
+            cluster += &format!("{}}}\n", "  ".repeat(depth + 2));
+        }
+        cluster += &edges;
+        cluster += "  }\n";
+        self.clusters.push(cluster);
+    }
+
+    pub fn render(&self) -> String {
+        let mut graph = String::from(
+            "digraph pipeline {\n  rankdir = TB;\n  node [shape = box, style = filled, fontname = \"monospace\"];\n",
+        );
+        for cluster in &self.clusters {
+            graph += cluster;
+        }
+        graph += "}\n";
+        graph
+    }
+}
@@ -0,0 +1,385 @@
+//! The snapshot of a function as a module of its own, for `optdiff extract`: the function as it
+//! was dumped, with the declarations, types, globals, attribute groups and metadata it refers to,
+//! so that `opt`, `llc` or a reducer read it without the rest of the dump.
+//!
+//! `-print-after-all` only prints the function a function pass ran on, what it refers to is
+//! taken from the snapshots of the whole module, as last printed before the snapshot extracted
+//! (or first printed after it). The functions defined in the module are declared.
+
+use regex::Regex;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// What a line outside of the functions defines, in the order LLVM prints a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Target,
+    Type,
+    Comdat,
+    Global,
+    Declaration,
+    /// A declaration made from the definition of a function
+    Defined,
+    AttributeGroup,
+    NamedMetadata,
+    Metadata,
+}
+
+/// The words of a function definition that can't be part of a declaration.
+const LINKAGES: [&str; 10] = [
+    "private",
+    "internal",
+    "available_externally",
+    "linkonce",
+    "linkonce_odr",
+    "weak",
+    "weak_odr",
+    "common",
+    "appending",
+    "external",
+];
+
+/// A definition as printed from a snapshot on, the snapshots are numbered in the order they
+/// were dumped.
+struct Version {
+    snapshot: usize,
+    kind: Kind,
+    text: String,
+}
+
+/// The module-level definitions of the snapshots of a module, by the name they're referred to by.
+pub struct ModuleScope {
+    /// The definitions as they changed over the snapshots
+    definitions: HashMap<String, Vec<Version>>,
+    /// The names in the order they were first found
+    order: Vec<String>,
+    /// The first snapshot each snapshot of a function was found in, by its hash
+    functions: HashMap<u64, usize>,
+    symbol: Regex,
+}
+
+fn hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The name a line defines, the symbols have their sigil.
+fn defined(line: &str, symbol: &Regex) -> Option<(Kind, String)> {
+    for target in ["source_filename", "target datalayout", "target triple"] {
+        if line.starts_with(target) {
+            return Some((Kind::Target, target.to_string()));
+        }
+    }
+    if let Some(rest) = line.strip_prefix("attributes ") {
+        let name = symbol.find(rest).filter(|name| name.start() == 0)?;
+        return Some((Kind::AttributeGroup, name.as_str().to_string()));
+    }
+    let declaration = line.starts_with("declare ");
+    let definition = line.starts_with("define ");
+    if declaration || definition {
+        let name = symbol
+            .find_iter(line)
+            .find(|name| name.as_str().starts_with('@'))?;
+        let kind = match declaration {
+            true => Kind::Declaration,
+            false => Kind::Defined,
+        };
+        return Some((kind, name.as_str().to_string()));
+    }
+    let name = symbol.find(line).filter(|name| name.start() == 0)?;
+    let rest = line[name.end()..].strip_prefix(" = ")?;
+    let name = name.as_str();
+    let kind = match name.as_bytes()[0] {
+        b'%' if rest.starts_with("type ") => Kind::Type,
+        b'$' if rest.starts_with("comdat") => Kind::Comdat,
+        b'@' => Kind::Global,
+        b'!' if name[1..].bytes().all(|b| b.is_ascii_digit()) => Kind::Metadata,
+        b'!' => Kind::NamedMetadata,
+        _ => return None,
+    };
+    Some((kind, name.to_string()))
+}
+
+/// The declaration of a function, from its definition: its signature up to the attribute
+/// groups, without a linkage a declaration can't have.
+fn declaration(define: &str) -> String {
+    let Some(name) = define.find('@') else {
+        return define.to_string();
+    };
+    let prefix = define["define ".len()..name]
+        .split(' ')
+        .filter(|word| !LINKAGES.contains(word))
+        .collect::<Vec<_>>()
+        .join(" ");
+    // The arguments end at the parenthesis matching the first one after the name
+    let mut depth = 0;
+    let mut end = define.len();
+    for (i, c) in define[name..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 1 => {
+                end = name + i + 1;
+                break;
+            }
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    let mut declaration = format!("declare {}{}", prefix, &define[name..end]);
+    for word in define[end..].split(' ') {
+        if word == "unnamed_addr" || word == "local_unnamed_addr" || word.starts_with('#') {
+            declaration.push(' ');
+            declaration += word;
+        }
+    }
+    declaration
+}
+
+impl ModuleScope {
+    /// The definitions of the snapshots of a module, what's outside of their functions and the
+    /// lines of their functions, in the order they were dumped.
+    pub fn new<'a>(snapshots: impl IntoIterator<Item = (&'a str, Vec<&'a [String]>)>) -> Self {
+        let mut scope = ModuleScope {
+            definitions: HashMap::new(),
+            order: Vec::new(),
+            functions: HashMap::new(),
+            symbol: Regex::new(r#"[@%$!#](?:"[^"]*"|[-\w.$]+)"#).unwrap(),
+        };
+        for (snapshot, (outside, functions)) in snapshots.into_iter().enumerate() {
+            let definitions = functions
+                .iter()
+                .filter_map(|lines| lines.first().map(String::as_str));
+            for line in outside.lines().chain(definitions) {
+                scope.add(snapshot, line);
+            }
+            for lines in functions {
+                let first = hash(&lines.join("\n"));
+                scope.functions.entry(first).or_insert(snapshot);
+            }
+        }
+        scope
+    }
+
+    /// Adds what `line` defines, if it changed since the last snapshot it was printed in.
+    fn add(&mut self, snapshot: usize, line: &str) {
+        let Some((kind, name)) = defined(line, &self.symbol) else {
+            return;
+        };
+        let text = match kind {
+            Kind::Defined => declaration(line),
+            _ => line.to_string(),
+        };
+        let versions = match self.definitions.get_mut(&name) {
+            Some(versions) => versions,
+            None => {
+                self.order.push(name.clone());
+                self.definitions.entry(name).or_default()
+            }
+        };
+        if versions.last().is_none_or(|last| last.text != text) {
+            versions.push(Version {
+                snapshot,
+                kind,
+                text,
+            });
+        }
+    }
+
+    /// The definition of `name` as last printed at `snapshot`, or first printed after it.
+    fn definition(&self, name: &str, snapshot: usize) -> Option<&Version> {
+        let versions = self.definitions.get(name)?;
+        versions
+            .iter()
+            .rev()
+            .find(|version| version.snapshot <= snapshot)
+            .or(versions.first())
+    }
+
+    /// The names `text` refers to, with the comdat of the symbols in a comdat of their own name.
+    fn references<'a>(&'a self, text: &'a str) -> impl Iterator<Item = String> + 'a {
+        let comdats = text.lines().filter_map(|line| {
+            let (_, name) = defined(line, &self.symbol)?;
+            let own = line.contains(" comdat") && !line.contains(" comdat(");
+            own.then(|| format!("${}", &name[1..]))
+        });
+        self.symbol
+            .find_iter(text)
+            .map(|name| name.as_str().to_string())
+            .chain(comdats)
+    }
+
+    /// `function` as a module of its own, and the symbols, attribute groups and metadata nodes it
+    /// refers to that no snapshot of the module defines. Snapshots that aren't of a function
+    /// definition, such as the blocks of a loop, are left as they are.
+    pub fn standalone(&self, function: &str) -> (String, Vec<String>) {
+        let Some(define) = function
+            .lines()
+            .next()
+            .filter(|line| line.starts_with("define "))
+        else {
+            return (function.to_string(), Vec::new());
+        };
+        let own = defined(define, &self.symbol).map(|(_, name)| name);
+        // Snapshots not found are of the whole dump, as last printed
+        let snapshot = self
+            .functions
+            .get(&hash(function))
+            .copied()
+            .unwrap_or(usize::MAX);
+        let mut included = HashSet::new();
+        let mut missing = Vec::new();
+        // Named metadata such as `!llvm.dbg.cu` is needed for the debug info to verify
+        let named = self
+            .order
+            .iter()
+            .filter(|name| self.definitions[*name][0].kind == Kind::NamedMetadata)
+            .cloned();
+        let target = self
+            .order
+            .iter()
+            .filter(|name| self.definitions[*name][0].kind == Kind::Target)
+            .cloned();
+        let mut pending = self
+            .references(function)
+            .chain(named)
+            .chain(target)
+            .collect::<Vec<_>>();
+        while let Some(name) = pending.pop() {
+            if Some(&name) == own.as_ref() || included.contains(&name) {
+                continue;
+            }
+            match self.definition(&name, snapshot) {
+                Some(version) => {
+                    pending.extend(self.references(&version.text));
+                    included.insert(name);
+                }
+                None => {
+                    // Local values, metadata kinds and strings share the syntax of the symbols
+                    let numbered =
+                        name.starts_with('!') && name[1..].bytes().all(|b| b.is_ascii_digit());
+                    let symbol = name.starts_with('@')
+                        || (name.starts_with('#') && !name.starts_with("#dbg_"));
+                    if (numbered || symbol) && !missing.contains(&name) {
+                        missing.push(name);
+                    }
+                }
+            }
+        }
+
+        let mut sections: Vec<(Kind, Vec<&str>)> = Vec::new();
+        let mut definitions = self
+            .order
+            .iter()
+            .filter(|name| included.contains(*name))
+            .filter_map(|name| self.definition(name, snapshot))
+            .collect::<Vec<_>>();
+        definitions.sort_by_key(|version| version.kind);
+        for version in definitions {
+            // The function goes after the globals, and declarations of either kind together
+            let kind = match version.kind {
+                Kind::Defined => Kind::Declaration,
+                kind => kind,
+            };
+            match sections.last_mut() {
+                Some((last, lines)) if *last == kind => lines.push(&version.text),
+                _ => sections.push((kind, vec![&version.text])),
+            }
+        }
+        let mut module = Vec::new();
+        let mut function_written = false;
+        for (kind, lines) in sections {
+            if kind > Kind::Global && !function_written {
+                module.push(function.trim_end().to_string());
+                function_written = true;
+            }
+            module.push(lines.join("\n"));
+        }
+        if !function_written {
+            module.push(function.trim_end().to_string());
+        }
+        missing.sort();
+        (module.join("\n\n") + "\n", missing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn snapshots_come_with_what_they_refer_to() {
+        let outside = "; ModuleID = 'a.c'\n\
+                       source_filename = \"a.c\"\n\
+                       target triple = \"x86_64-unknown-linux-gnu\"\n\
+                       %struct.S = type { i32 }\n\
+                       %struct.T = type { i64 }\n\
+                       @g = dso_local global %struct.S zeroinitializer, align 4\n\
+                       declare i32 @puts(ptr) #1\n\
+                       attributes #0 = { noinline }\n\
+                       attributes #1 = { nounwind }\n\
+                       attributes #2 = { cold }\n\
+                       !llvm.module.flags = !{!0}\n\
+                       !0 = !{i32 7, !\"Dwarf Version\", i32 5}\n\
+                       !1 = !{!2}\n\
+                       !2 = !{!\"unused\"}";
+        let callee = lines(
+            "define internal fastcc i32 @callee(i32 noundef %x) unnamed_addr #2 !dbg !1 {\n  ret i32 %x\n}",
+        );
+        let function = "define dso_local i32 @f() #0 {\n\
+                        entry:\n  \
+                        %0 = load i32, ptr @g, align 4\n  \
+                        %1 = call i32 @puts(ptr @g)\n  \
+                        %2 = call fastcc i32 @callee(i32 %0)\n  \
+                        %3 = call i32 @llvm.abs.i32(i32 %2, i1 false)\n  \
+                        ret i32 %3\n\
+                        }";
+        let f = lines(function);
+        let scope = ModuleScope::new([(outside, vec![callee.as_slice(), f.as_slice()])]);
+        let (module, missing) = scope.standalone(function);
+        assert_eq!(
+            module,
+            format!(
+                "source_filename = \"a.c\"\ntarget triple = \"x86_64-unknown-linux-gnu\"\n\n\
+                 %struct.S = type {{ i32 }}\n\n\
+                 @g = dso_local global %struct.S zeroinitializer, align 4\n\n\
+                 {}\n\n\
+                 declare i32 @puts(ptr) #1\n\
+                 declare fastcc i32 @callee(i32 noundef %x) unnamed_addr #2\n\n\
+                 attributes #0 = {{ noinline }}\nattributes #1 = {{ nounwind }}\nattributes #2 = {{ cold }}\n\n\
+                 !llvm.module.flags = !{{!0}}\n\n\
+                 !0 = !{{i32 7, !\"Dwarf Version\", i32 5}}\n",
+                function
+            )
+        );
+        assert_eq!(missing, ["@llvm.abs.i32"]);
+    }
+
+    #[test]
+    fn attribute_groups_are_taken_from_the_snapshots_around() {
+        let early = lines("define void @f() #0 {\n  ret void\n}");
+        let late = lines("define void @f() #0 {\n  unreachable\n}");
+        let scope = ModuleScope::new([
+            ("attributes #0 = { noinline }", vec![early.as_slice()]),
+            ("attributes #0 = { cold }", vec![late.as_slice()]),
+        ]);
+        let attributes = |function: &[String]| {
+            let (module, _) = scope.standalone(&function.join("\n"));
+            module.lines().last().unwrap().to_string()
+        };
+        assert_eq!(attributes(&early), "attributes #0 = { noinline }");
+        assert_eq!(attributes(&late), "attributes #0 = { cold }");
+    }
+
+    #[test]
+    fn loop_snapshots_are_left_as_they_are() {
+        let scope = ModuleScope::new([("declare void @g()", Vec::new())]);
+        let blocks = "; Preheader:\nentry:\n  br label %loop\n";
+        assert_eq!(scope.standalone(blocks), (blocks.to_string(), Vec::new()));
+    }
+}
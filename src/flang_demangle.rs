@@ -0,0 +1,39 @@
+//! Flang mangles Fortran names as `_Q` followed by tagged components, e.g. `_QMmodPsub` for the
+//! procedure `sub` of module `mod`. Fortran names are case insensitive and lowered, so every
+//! uppercase letter starts a new component.
+
+use regex::{Captures, Regex};
+
+/// Tags of the components that are scopes or named entities, others (constants, type
+/// descriptors, ...) are left mangled.
+const NAMED_COMPONENTS: &[char] = &['B', 'C', 'E', 'F', 'M', 'N', 'P', 'Q', 'S'];
+
+/// Demangles a single Flang name, `_QMmodFsubPinner` becomes `mod::sub::inner`.
+pub fn demangle(name: &str) -> Option<String> {
+    let mut rest = name.strip_prefix("_Q")?;
+    let mut components = Vec::new();
+    while let Some(tag) = rest.chars().next() {
+        if !NAMED_COMPONENTS.contains(&tag) {
+            return None;
+        }
+        let component = &rest[1..];
+        let end = component
+            .find(|c: char| c.is_ascii_uppercase())
+            .unwrap_or(component.len());
+        if end == 0 {
+            return None;
+        }
+        components.push(&component[..end]);
+        rest = &component[end..];
+    }
+    (!components.is_empty()).then(|| components.join("::"))
+}
+
+/// Demangles every Flang name found in `text`.
+pub fn demangle_line(text: &str) -> String {
+    let name = Regex::new(r"\b_Q[A-Za-z0-9_$]+").unwrap();
+    name.replace_all(text, |caps: &Captures| {
+        demangle(&caps[0]).unwrap_or_else(|| caps[0].to_string())
+    })
+    .to_string()
+}
@@ -0,0 +1,114 @@
+//! GCC writes a dump file per pass, `-fdump-tree-all -fdump-rtl-all` gives a set of
+//! `<source>.<pass number><t|r>.<pass>` files next to the output. IPA dumps (`i`) are about the
+//! call graph rather than the IR of the functions, so they are left out.
+
+use crate::optpipeline::gcc;
+use itertools::Itertools;
+use std::io;
+use std::path::Path;
+
+/// Splits a dump file name into the source it was compiled from, the pass number and the pass,
+/// e.g. `sq.c.034t.ccp1` into `sq.c`, `34` and `034t.ccp1`.
+fn parse_file_name(file_name: &str) -> Option<(&str, u32, &str)> {
+    let mut parts = file_name.rsplitn(3, '.');
+    let name = parts.next()?;
+    let number = parts.next()?;
+    let source = parts.next()?;
+    let (digits, kind) = number.split_at_checked(number.len().checked_sub(1)?)?;
+    if !matches!(kind, "t" | "r") || digits.len() != 3 {
+        return None;
+    }
+    let number = digits.parse().ok()?;
+    (!name.is_empty()).then(|| (source, number, &file_name[source.len() + 1..]))
+}
+
+/// Whether `path` is one of the dump files, or a directory containing some.
+pub fn is_session(path: &Path) -> bool {
+    if path.is_dir() {
+        std::fs::read_dir(path).is_ok_and(|entries| {
+            entries.flatten().any(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| parse_file_name(name).is_some())
+            })
+        })
+    } else {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| parse_file_name(name).is_some())
+    }
+}
+
+/// Puts the dump files found in the directory `path`, or next to the dump file `path` and from
+/// the same source, back together in pass order.
+pub fn read(path: &Path) -> io::Result<String> {
+    let (dir, source) = if path.is_dir() {
+        (path, None)
+    } else {
+        let file_name = path.file_name().and_then(|name| name.to_str());
+        let source = file_name
+            .and_then(parse_file_name)
+            .map(|(source, _, _)| source.to_string());
+        (path.parent().unwrap_or(Path::new(".")), source)
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+
+    let mut dump_files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some((file_source, number, pass)) = parse_file_name(file_name) else {
+            continue;
+        };
+        if source
+            .as_deref()
+            .is_some_and(|source| source != file_source)
+        {
+            continue;
+        }
+        dump_files.push((
+            file_source.to_string(),
+            number,
+            pass.to_string(),
+            path.clone(),
+        ));
+    }
+
+    let mut dump = String::new();
+    for (_, _, pass, path) in dump_files.into_iter().sorted() {
+        dump += &gcc::banner(&pass);
+        dump.push('\n');
+        dump += &String::from_utf8_lossy(&std::fs::read(&path)?);
+        if !dump.ends_with('\n') {
+            dump.push('\n');
+        }
+    }
+    Ok(dump)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_file_name;
+
+    #[test]
+    fn file_names_are_split_into_source_number_and_pass() {
+        assert_eq!(
+            parse_file_name("sq.c.034t.ccp1"),
+            Some(("sq.c", 34, "034t.ccp1"))
+        );
+        assert_eq!(
+            parse_file_name("a.b.c.310r.final"),
+            Some(("a.b.c", 310, "310r.final"))
+        );
+        assert_eq!(parse_file_name("sq.c.000i.cgraph"), None);
+        assert_eq!(parse_file_name("sq.c"), None);
+        assert_eq!(parse_file_name("sq.c.34t.ccp1"), None);
+    }
+}
@@ -0,0 +1,211 @@
+//! The diffs as a GitHub Actions job summary, with `--format gh-summary`, for CI jobs comparing
+//! IR pipelines: totals, a table of the passes that changed each function and their diffs
+//! collapsed below it, as in `--format markdown`. The summary is appended to the file of
+//! `GITHUB_STEP_SUMMARY`, and `::notice` and `::warning` commands on stdout annotate the run
+//! with the totals and with the passes that deleted a function or invalidated the IR.
+
+use crate::change_score::ChangeScore;
+use crate::html_report::{escape, PassSection};
+use crate::markdown;
+use crate::optpipeline::Pass;
+
+/// A pass that did something to a function, for the table.
+pub struct Row {
+    number: usize,
+    name: String,
+    run: usize,
+    runs: usize,
+    /// What the pass did, `changed`, `deleted the function` or `invalidated the IR`
+    status: &'static str,
+    score: Option<ChangeScore>,
+}
+
+/// The row of pass number `number`, none if it left the function as it was.
+pub fn row(number: usize, pass: &Pass, score: Option<ChangeScore>) -> Option<Row> {
+    let status = if pass.deleted {
+        "deleted the function"
+    } else if pass.invalidated {
+        "invalidated the IR"
+    } else if pass.ir_changed || pass.before != pass.after {
+        "changed"
+    } else {
+        return None;
+    };
+    Some(Row {
+        number,
+        name: pass.name.clone(),
+        run: pass.run,
+        runs: pass.runs,
+        status,
+        score,
+    })
+}
+
+struct Function {
+    name: String,
+    rows: Vec<Row>,
+    sections: Vec<PassSection>,
+}
+
+impl Function {
+    /// The lines added and removed and the instructions touched by the passes.
+    fn totals(&self) -> (usize, usize, usize) {
+        self.rows.iter().filter_map(|row| row.score).fold(
+            (0, 0, 0),
+            |(added, removed, instructions), score| {
+                (
+                    added + score.added,
+                    removed + score.removed,
+                    instructions + score.instructions,
+                )
+            },
+        )
+    }
+}
+
+/// `count` of `one`, or of `many` unless there's one.
+fn count(count: usize, one: &str, many: &str) -> String {
+    match count {
+        1 => format!("1 {}", one),
+        count => format!("{} {}", count, many),
+    }
+}
+
+/// Text in a cell of a table, where a `|` would end it.
+fn cell(text: &str) -> String {
+    escape(text).replace('|', "\\|")
+}
+
+/// The message of a workflow command, which ends at the line break.
+fn command_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// A property of a workflow command, which also ends at a `,` or a `:`.
+fn command_property(text: &str) -> String {
+    command_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[derive(Default)]
+pub struct Summary {
+    functions: Vec<Function>,
+}
+
+impl Summary {
+    /// Adds a function with the rows of its passes and their sections, left out if no pass
+    /// did anything to it.
+    pub fn add_function(&mut self, name: &str, rows: Vec<Row>, sections: Vec<PassSection>) {
+        if rows.is_empty() {
+            return;
+        }
+        self.functions.push(Function {
+            name: name.to_string(),
+            rows,
+            sections,
+        });
+    }
+
+    /// The passes that did something to a function, counted in each function.
+    fn passes(&self) -> usize {
+        self.functions
+            .iter()
+            .map(|function| function.rows.len())
+            .sum()
+    }
+
+    /// The job summary of the dumps `inputs`.
+    pub fn render(&self, inputs: &str) -> String {
+        let mut text = format!("## optdiff: {}\n\n", cell(inputs));
+        if self.functions.is_empty() {
+            text += "No pass changed any function.\n";
+            return text;
+        }
+        let (added, removed, instructions) = self.functions.iter().map(Function::totals).fold(
+            (0, 0, 0),
+            |(added, removed, instructions), totals| {
+                (
+                    added + totals.0,
+                    removed + totals.1,
+                    instructions + totals.2,
+                )
+            },
+        );
+        text += &format!(
+            "**{}** changed by **{}**: +{} -{} lines, {} touched.\n\n",
+            count(self.functions.len(), "function", "functions"),
+            count(self.passes(), "pass", "passes"),
+            added,
+            removed,
+            count(instructions, "instruction", "instructions")
+        );
+        text += "| Function | Passes | Lines added | Lines removed | Instructions |\n";
+        text += "|---|--:|--:|--:|--:|\n";
+        for function in &self.functions {
+            let (added, removed, instructions) = function.totals();
+            text += &format!(
+                "| `{}` | {} | {} | {} | {} |\n",
+                cell(&function.name.replace('`', "'")),
+                function.rows.len(),
+                added,
+                removed,
+                instructions
+            );
+        }
+        text += "\n";
+        for function in &self.functions {
+            text += &format!("### `{}`\n\n", function.name.replace('`', "'"));
+            text += "| # | Pass | Run | Status | Lines added | Lines removed | Instructions |\n";
+            text += "|--:|---|---|---|--:|--:|--:|\n";
+            for row in &function.rows {
+                let counts = match row.score {
+                    Some(score) => format!(
+                        "{} | {} | {}",
+                        score.added, score.removed, score.instructions
+                    ),
+                    None => " | | ".to_string(),
+                };
+                text += &format!(
+                    "| {} | {} | {} of {} | {} | {} |\n",
+                    row.number,
+                    cell(&row.name),
+                    row.run,
+                    row.runs,
+                    row.status,
+                    counts
+                );
+            }
+            text += "\n";
+            text += &markdown::sections(&function.sections);
+        }
+        text
+    }
+
+    /// The workflow commands annotating the run: a notice of the totals and a warning for each
+    /// pass that deleted a function or invalidated the IR.
+    pub fn annotations(&self) -> String {
+        let totals = match self.functions.len() {
+            0 => "No pass changed any function".to_string(),
+            functions => format!(
+                "{} changed by {}",
+                count(functions, "function", "functions"),
+                count(self.passes(), "pass", "passes")
+            ),
+        };
+        let mut text = format!("::notice title=optdiff::{}\n", command_data(&totals));
+        for function in &self.functions {
+            for row in function.rows.iter().filter(|row| row.status != "changed") {
+                text += &format!(
+                    "::warning title={}::{}\n",
+                    command_property(&format!("optdiff: {}", function.name)),
+                    command_data(&format!(
+                        "Pass {}, {} (run {} of {}), {}",
+                        row.number, row.name, row.run, row.runs, row.status
+                    ))
+                );
+            }
+        }
+        text
+    }
+}
@@ -0,0 +1,218 @@
+//! Syntax highlighting of the lines the built-in engines print uncoloured, the context around
+//! the changes: keywords and opcodes, types, value names, and metadata and attributes, which
+//! are dimmed along with comments. Changed lines keep the colour of the change.
+//!
+//! The tokens are told apart by their shape, which LLVM IR and MLIR mostly share, so no
+//! dialect is parsed. Machine IR gets a profile of its own: physical and virtual registers, their
+//! classes, and the flags of the operands (`killed`, `renamable`, `implicit-def`...). The colours
+//! are those of the theme, see [`crate::theme`].
+
+use crate::theme::Theme;
+
+const KEYWORDS: &[&str] = &[
+    "define",
+    "declare",
+    "global",
+    "constant",
+    "private",
+    "internal",
+    "external",
+    "linkonce_odr",
+    "weak_odr",
+    "dso_local",
+    "unnamed_addr",
+    "local_unnamed_addr",
+    "align",
+    "to",
+    "label",
+    "unwind",
+    "nuw",
+    "nsw",
+    "exact",
+    "inbounds",
+    "tail",
+    "musttail",
+    "call",
+    "volatile",
+    "atomic",
+    "true",
+    "false",
+    "null",
+    "undef",
+    "poison",
+    "zeroinitializer",
+    "func.func",
+    "return",
+];
+
+const TYPES: &[&str] = &[
+    "ptr", "void", "half", "bfloat", "float", "double", "fp128", "x86_fp80", "metadata", "token",
+    "index",
+];
+
+/// Flags of MIR instructions and operands, dimmed so the opcodes and registers stand out.
+const MIR_FLAGS: &[&str] = &[
+    "killed",
+    "renamable",
+    "implicit",
+    "implicit-def",
+    "dead",
+    "undef",
+    "def",
+    "internal",
+    "early-clobber",
+    "debug-use",
+    "debug-location",
+    "frame-setup",
+    "frame-destroy",
+    "nofpexcept",
+    "nsw",
+    "nuw",
+    "exact",
+    "nnan",
+    "ninf",
+    "nsz",
+    "arcp",
+    "contract",
+    "afn",
+    "reassoc",
+];
+
+const MIR_KEYWORDS: &[&str] = &["liveins", "successors", "livein", "liveout"];
+
+/// How the tokens of a snapshot are coloured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// LLVM IR, and MLIR which looks much the same
+    Ir,
+    /// Machine IR, for the snapshots of machine passes
+    Mir,
+}
+
+impl Profile {
+    pub fn of(machine: bool) -> Self {
+        match machine {
+            true => Profile::Mir,
+            false => Profile::Ir,
+        }
+    }
+}
+
+fn is_type(word: &str) -> bool {
+    TYPES.contains(&word)
+        || word
+            .strip_prefix(['i', 'f', 's'])
+            .is_some_and(|bits| !bits.is_empty() && bits.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "._$-".contains(c)
+}
+
+/// `line` with its tokens coloured.
+pub fn line(line: &str, profile: Profile, theme: &Theme) -> String {
+    if profile == Profile::Mir {
+        return mir_line(line, theme);
+    }
+    let mut highlighted = String::with_capacity(line.len() * 2);
+    // The first word after `=`, or the first of an instruction defining nothing, is its opcode
+    let mut expect_opcode = line.starts_with([' ', '\t']) && !line.contains(" = ");
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        if c == ';' || rest.starts_with("//") {
+            highlighted += &theme.comment.paint(rest);
+            break;
+        }
+        let token_len = match c {
+            '"' => rest[1..].find('"').map_or(rest.len(), |end| end + 2),
+            '%' | '@' | '!' | '#' | '$' if rest.len() > 1 => {
+                let name = &rest[1..];
+                let len = match name.starts_with('"') {
+                    true => name[1..].find('"').map_or(name.len(), |end| end + 2),
+                    false => name.find(|c| !is_name_char(c)).unwrap_or(name.len()),
+                };
+                1 + len
+            }
+            c if is_name_char(c) => rest.find(|c| !is_name_char(c)).unwrap_or(rest.len()),
+            c => c.len_utf8(),
+        };
+        let token = &rest[..token_len];
+        highlighted += &match c {
+            '%' | '@' | '$' if token_len > 1 => theme.value.paint(token),
+            '!' | '#' if token_len > 1 => theme.comment.paint(token),
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                if std::mem::take(&mut expect_opcode) || KEYWORDS.contains(&token) {
+                    theme.keyword.paint(token)
+                } else if is_type(token) {
+                    theme.r#type.paint(token)
+                } else {
+                    token.to_string()
+                }
+            }
+            '=' => {
+                expect_opcode = true;
+                token.to_string()
+            }
+            _ => token.to_string(),
+        };
+        rest = &rest[token_len..];
+    }
+    highlighted
+}
+
+/// A line of Machine IR with its tokens coloured: physical registers (`$rax`) apart from
+/// virtual ones (`%0`), and the register classes (`:gr32`) and flags dimmed.
+fn mir_line(line: &str, theme: &Theme) -> String {
+    if line.trim_start().starts_with('#') {
+        return theme.comment.paint(line);
+    }
+    let mut highlighted = String::with_capacity(line.len() * 2);
+    let mut expect_opcode = line.starts_with([' ', '\t']) && !line.contains(" = ");
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        if c == ';' {
+            highlighted += &theme.comment.paint(rest);
+            break;
+        }
+        let word_len = |text: &str| text.find(|c| !is_name_char(c)).unwrap_or(text.len());
+        let token_len = match c {
+            '%' | '$' | '@' | '!' => 1 + word_len(&rest[1..]),
+            c if is_name_char(c) => word_len(rest),
+            c => c.len_utf8(),
+        };
+        let token = &rest[..token_len];
+        rest = &rest[token_len..];
+        highlighted += &match c {
+            '$' if token_len > 1 => theme.register.paint(token),
+            '%' | '@' if token_len > 1 => {
+                // The class or bank of a virtual register, `%0:gr32`
+                let class_len = match rest.strip_prefix(':') {
+                    Some(class) if !class.starts_with(':') => 1 + word_len(class),
+                    _ => 0,
+                };
+                let class = &rest[..class_len];
+                rest = &rest[class_len..];
+                theme.value.paint(token) + &theme.comment.paint(class)
+            }
+            '!' if token_len > 1 => theme.comment.paint(token),
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                if MIR_FLAGS.contains(&token) {
+                    theme.comment.paint(token)
+                } else if std::mem::take(&mut expect_opcode)
+                    || MIR_KEYWORDS.contains(&token)
+                    || (token.starts_with("bb.") && !line.starts_with([' ', '\t']))
+                {
+                    theme.keyword.paint(token)
+                } else {
+                    token.to_string()
+                }
+            }
+            '=' => {
+                expect_opcode = true;
+                token.to_string()
+            }
+            _ => token.to_string(),
+        };
+    }
+    highlighted
+}
@@ -0,0 +1,220 @@
+//! The diffs of a whole session as an HTML page, with `--format html`: the functions are listed
+//! in a sidebar, and the passes of each function can be expanded one by one, their snapshots
+//! side by side.
+//!
+//! Each function comes with a chart of its instructions over the passes.
+//!
+//! The page holds its style and script, nothing is fetched from elsewhere, so it can be attached
+//! to a bug report or a review as a single file and opened in any browser.
+
+use similar::{DiffOp, TextDiff};
+
+/// A pass of a function as shown in the report.
+pub struct PassSection {
+    pub title: String,
+    pub changed: bool,
+    /// Notes and remarks, shown above the diff
+    pub notes: Vec<String>,
+    /// The diff as rendered for the page, none for passes without snapshots to compare
+    pub diff: Option<String>,
+}
+
+struct Function {
+    name: String,
+    passes: Vec<PassSection>,
+    /// The instructions over the passes, see [`crate::chart`]
+    chart: String,
+}
+
+#[derive(Default)]
+pub struct Report {
+    functions: Vec<Function>,
+}
+
+const STYLE: &str = "
+body { margin: 0; display: flex; font-family: sans-serif; font-size: 14px; }
+nav { position: sticky; top: 0; height: 100vh; overflow-y: auto; width: 280px; flex-shrink: 0;
+      padding: 8px 12px; box-sizing: border-box; background: #f6f8fa; border-right: 1px solid #d0d7de; }
+nav ul { list-style: none; padding: 0; margin: 0; }
+nav li { padding: 2px 0; white-space: nowrap; overflow: hidden; text-overflow: ellipsis; }
+nav a { color: #0969da; text-decoration: none; }
+nav .count { color: #57606a; font-size: 12px; }
+main { flex-grow: 1; padding: 8px 16px; min-width: 0; }
+h1 { font-size: 16px; }
+h2 { font-size: 15px; font-family: monospace; border-bottom: 1px solid #d0d7de; padding-bottom: 4px; }
+details { margin: 4px 0; }
+summary { cursor: pointer; font-family: monospace; padding: 2px 0; }
+details.unchanged summary { color: #8c959f; }
+.note { font-family: monospace; color: #57606a; margin: 4px 0 4px 16px; white-space: pre-wrap; }
+table.diff { border-collapse: collapse; width: 100%; table-layout: fixed; font-family: monospace;
+             font-size: 12px; margin: 4px 0 12px; }
+table.diff td { padding: 0 6px; white-space: pre; overflow: hidden; text-overflow: ellipsis; vertical-align: top; }
+table.diff td.number { width: 40px; text-align: right; color: #8c959f; user-select: none; }
+table.diff td.removed { background: #ffebe9; }
+table.diff td.added { background: #dafbe1; }
+table.diff tr.skipped td { background: #f6f8fa; color: #8c959f; text-align: center; }
+nav input { width: 100%; box-sizing: border-box; margin-bottom: 8px; }
+.toolbar button { margin-right: 4px; }
+svg.chart { display: block; width: 100%; max-width: 800px; height: auto; margin: 4px 0 8px; }
+svg.chart text { font-size: 11px; fill: #57606a; }
+svg.chart .axis { fill: none; stroke: #d0d7de; }
+svg.chart .line { fill: none; stroke: #0969da; stroke-width: 1.5; }
+svg.chart circle.same { fill: #8c959f; }
+svg.chart circle.grew { fill: #bf8700; }
+svg.chart circle.shrank { fill: #1a7f37; }
+";
+
+/// Filters the functions of the sidebar, and expands or collapses the passes all at once.
+const SCRIPT: &str = "
+document.getElementById('filter').addEventListener('input', (event) => {
+  const filter = event.target.value.toLowerCase();
+  for (const item of document.querySelectorAll('nav li')) {
+    item.style.display = item.textContent.toLowerCase().includes(filter) ? '' : 'none';
+  }
+});
+function expand(open, changedOnly) {
+  for (const details of document.querySelectorAll('details')) {
+    details.open = open && (!changedOnly || details.classList.contains('changed'));
+  }
+}
+";
+
+/// `text` with the characters HTML gives a meaning to escaped.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A cell of a side of the diff, its line number and its line.
+fn cells(number: Option<usize>, line: &str, class: &str) -> String {
+    match number {
+        Some(number) => format!(
+            "<td class=\"number\">{}</td><td class=\"{}\">{}</td>",
+            number + 1,
+            class,
+            escape(line.trim_end_matches(['\n', '\r']))
+        ),
+        None => "<td class=\"number\"></td><td></td>".to_string(),
+    }
+}
+
+/// The lines that changed between `before` and `after` side by side, with 10 lines of context.
+pub fn diff_table(before: &str, after: &str) -> String {
+    let diff = TextDiff::from_lines(before, after);
+    let (old, new) = (diff.old_slices(), diff.new_slices());
+    let mut table = String::from("<table class=\"diff\">\n");
+    for (i, group) in diff.grouped_ops(10).iter().enumerate() {
+        if i > 0 {
+            table += "<tr class=\"skipped\"><td colspan=\"4\">⋯</td></tr>\n";
+        }
+        for op in group {
+            if let DiffOp::Equal {
+                old_index,
+                new_index,
+                len,
+            } = *op
+            {
+                for offset in 0..len {
+                    table += &format!(
+                        "<tr>{}{}</tr>\n",
+                        cells(Some(old_index + offset), old[old_index + offset], ""),
+                        cells(Some(new_index + offset), new[new_index + offset], "")
+                    );
+                }
+                continue;
+            }
+            // Removed and added lines are paired up, the longer side goes on alone
+            let (removed, added) = (op.old_range(), op.new_range());
+            let rows = removed.len().max(added.len());
+            let (mut removed, mut added) = (removed.into_iter(), added.into_iter());
+            for _ in 0..rows {
+                let left = match removed.next() {
+                    Some(index) => cells(Some(index), old[index], "removed"),
+                    None => cells(None, "", ""),
+                };
+                let right = match added.next() {
+                    Some(index) => cells(Some(index), new[index], "added"),
+                    None => cells(None, "", ""),
+                };
+                table += &format!("<tr>{}{}</tr>\n", left, right);
+            }
+        }
+    }
+    table += "</table>\n";
+    table
+}
+
+impl Report {
+    pub fn add_function(&mut self, name: &str, passes: Vec<PassSection>, chart: String) {
+        self.functions.push(Function {
+            name: name.to_string(),
+            passes,
+            chart,
+        });
+    }
+
+    /// The page, titled after the dump it was made of.
+    pub fn render(&self, title: &str) -> String {
+        let mut page = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>optdiff: {}</title>\n<style>{}</style>\n</head>\n<body>\n",
+            escape(title),
+            STYLE
+        );
+        page += &format!(
+            "<nav>\n<h1>{}</h1>\n<input id=\"filter\" type=\"search\" placeholder=\"Filter functions\">\n<ul>\n",
+            escape(title)
+        );
+        for (i, function) in self.functions.iter().enumerate() {
+            let changed = function.passes.iter().filter(|pass| pass.changed).count();
+            page += &format!(
+                "<li><a href=\"#function-{}\">{}</a> <span class=\"count\">{}/{}</span></li>\n",
+                i,
+                escape(&function.name),
+                changed,
+                function.passes.len()
+            );
+        }
+        page += "</ul>\n</nav>\n<main>\n<div class=\"toolbar\">\n";
+        page += "<button onclick=\"expand(true, true)\">Expand changed</button>\n";
+        page += "<button onclick=\"expand(true, false)\">Expand all</button>\n";
+        page += "<button onclick=\"expand(false, false)\">Collapse all</button>\n</div>\n";
+        for (i, function) in self.functions.iter().enumerate() {
+            page += &format!(
+                "<section id=\"function-{}\">\n<h2>{}</h2>\n",
+                i,
+                escape(&function.name)
+            );
+            page += &function.chart;
+            for pass in &function.passes {
+                let class = match pass.changed {
+                    true => "changed",
+                    false => "unchanged",
+                };
+                page += &format!(
+                    "<details class=\"{}\">\n<summary>{}</summary>\n",
+                    class,
+                    escape(&pass.title)
+                );
+                for note in &pass.notes {
+                    page += &format!("<div class=\"note\"># {}</div>\n", escape(note));
+                }
+                if let Some(diff) = &pass.diff {
+                    page += diff;
+                }
+                page += "</details>\n";
+            }
+            page += "</section>\n";
+        }
+        page += &format!("</main>\n<script>{}</script>\n</body>\n</html>\n", SCRIPT);
+        page
+    }
+}
@@ -0,0 +1,112 @@
+//! Links from the pass names in the headers of the built-in engines to the documentation of the
+//! passes, as OSC 8 hyperlinks that the terminals supporting them make clickable. The passes of
+//! LLVM link to their class in its Doxygen documentation, which links to the source. Downstream
+//! compilers can point their passes elsewhere in the `[links]` section of the config file, see
+//! [`crate::config`]:
+//!
+//! ```toml
+//! [links]
+//! # `{}` is replaced by the pass, for the passes not named below
+//! default = "https://docs.example.com/passes/{}.html"
+//! MyVectorizerPass = "https://docs.example.com/my-vectorizer.html"
+//! "My Machine Pass" = "https://docs.example.com/my-machine-pass.html"
+//! ```
+
+use crate::config;
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::env;
+use std::io;
+
+/// Where LLVM documents its pass classes, `{}` replaced by the class.
+const LLVM_DOCS: &str = "https://llvm.org/doxygen/classllvm_1_1{}.html";
+
+/// When to make the pass names links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum When {
+    /// When the terminal is known to support them
+    Auto,
+    Always,
+    Never,
+}
+
+/// Whether the terminal is one known to support OSC 8 hyperlinks, as the variables it sets tell.
+fn terminal_supports() -> bool {
+    let var = |name: &str| env::var(name).unwrap_or_default();
+    if let Ok(force) = env::var("FORCE_HYPERLINK") {
+        return force != "0";
+    }
+    let vte_version = var("VTE_VERSION").parse::<u32>().unwrap_or_default();
+    let term = var("TERM");
+    env::var_os("WT_SESSION").is_some()
+        || env::var_os("KONSOLE_VERSION").is_some()
+        || env::var_os("DOMTERM").is_some()
+        || vte_version >= 5000
+        || ["iTerm.app", "WezTerm", "vscode", "Hyper", "ghostty"]
+            .contains(&var("TERM_PROGRAM").as_str())
+        || ["xterm-kitty", "alacritty", "xterm-ghostty"].contains(&term.as_str())
+        || term.starts_with("foot")
+}
+
+/// The documentation of the passes.
+#[derive(Debug, Clone, Default)]
+pub struct Links {
+    enabled: bool,
+    /// The pages of the passes named in the config file
+    passes: HashMap<String, String>,
+    /// The page of the other passes, with `{}` for the pass
+    default: Option<String>,
+}
+
+impl Links {
+    /// The links of `--hyperlinks`, to the pages of the config file if there is one.
+    /// `to_terminal` tells whether the output goes to a terminal.
+    pub fn load(when: When, to_terminal: bool) -> io::Result<Self> {
+        let enabled = match when {
+            When::Auto => to_terminal && terminal_supports(),
+            When::Always => true,
+            When::Never => false,
+        };
+        let mut links = Links {
+            enabled,
+            ..Default::default()
+        };
+        if !enabled {
+            return Ok(links);
+        }
+        for entry in config::section("links")?.map_or_else(Vec::new, |(_, entries)| entries) {
+            match entry.key.as_str() {
+                "default" => links.default = Some(entry.value),
+                _ => {
+                    links.passes.insert(entry.key, entry.value);
+                }
+            }
+        }
+        Ok(links)
+    }
+
+    /// The page of the pass, by the name it's dumped with, `InstCombinePass on foo`.
+    fn url(&self, name: &str) -> Option<String> {
+        let pass = name.split_once(" on ").map_or(name, |(pass, _)| pass);
+        if let Some(url) = self.passes.get(pass) {
+            return Some(url.clone());
+        }
+        // Only the classes of the new pass manager are known to have a page
+        let is_class =
+            pass.ends_with("Pass") && pass.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        let template = match &self.default {
+            Some(template) => template,
+            None if is_class => LLVM_DOCS,
+            None => return None,
+        };
+        Some(template.replace("{}", &pass.replace(' ', "%20")))
+    }
+
+    /// The pass name `name` linked to its page, as it is if it has none or links are off.
+    pub fn link(&self, name: &str) -> String {
+        match self.url(name).filter(|_| self.enabled) {
+            Some(url) => format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, name),
+            None => name.to_string(),
+        }
+    }
+}
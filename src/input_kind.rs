@@ -0,0 +1,102 @@
+//! Input without a single snapshot is usually the wrong file or stream: the source itself, the
+//! assembly written to stdout while the dumps went to stderr, or the remarks of the compile. It's
+//! told apart by what its lines look like, so the error can give the flags that make a dump.
+
+use std::path::Path;
+
+const DUMP_FLAGS: &str = "-mllvm -print-before-all -mllvm -print-after-all";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputKind {
+    Empty,
+    /// `-fsave-optimization-record` YAML
+    Remarks,
+    /// LLVM IR that went through no pass, e.g. `-emit-llvm` output
+    LlvmIr,
+    Assembly,
+    /// C, C++ or Objective-C source
+    Source,
+    Unknown,
+}
+
+fn is_directive(line: &str) -> bool {
+    [
+        ".text", ".globl", ".section", ".type", ".file", ".p2align", ".cfi_",
+    ]
+    .iter()
+    .any(|directive| line.starts_with(directive))
+}
+
+fn is_preprocessor(line: &str) -> bool {
+    [
+        "#include ",
+        "#include<",
+        "#define ",
+        "#ifndef ",
+        "#pragma ",
+        "#import ",
+    ]
+    .iter()
+    .any(|directive| line.starts_with(directive))
+}
+
+fn detect(text: &str) -> InputKind {
+    let lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        return InputKind::Empty;
+    }
+    let remarks = ["--- !Passed", "--- !Missed", "--- !Analysis"];
+    if lines
+        .iter()
+        .any(|line| remarks.iter().any(|remark| line.starts_with(remark)))
+    {
+        return InputKind::Remarks;
+    }
+    if lines
+        .iter()
+        .any(|line| line.starts_with("define ") || line.starts_with("; ModuleID = "))
+    {
+        return InputKind::LlvmIr;
+    }
+    let directives = lines.iter().filter(|line| is_directive(line)).count();
+    if directives * 10 >= lines.len() {
+        return InputKind::Assembly;
+    }
+    if lines.iter().any(|line| is_preprocessor(line)) || text.contains("int main(") {
+        return InputKind::Source;
+    }
+    InputKind::Unknown
+}
+
+/// Tells what the input without snapshots seems to be instead, and how to make a dump out of it.
+pub fn explain(text: &str, path: Option<&Path>) -> String {
+    let file = |default: &str| path.map_or(default.to_string(), |path| path.display().to_string());
+    match detect(text) {
+        InputKind::Empty => format!(
+            "The input is empty. The dumps are printed to stderr, e.g. `clang input.c -O2 {} -c -o /dev/null 2>&1 | optdiff`",
+            DUMP_FLAGS
+        ),
+        InputKind::Remarks => format!(
+            "The input looks like optimization remarks. Pass them along with the dump of the same compile: `optdiff dump.txt --remarks {}`",
+            file("input.opt.yaml")
+        ),
+        InputKind::LlvmIr => format!(
+            "The input is LLVM IR without pass dumps. Run the passes over it with `opt -passes='default<O2>' -print-before-all -print-after-all -disable-output {} 2>&1 | optdiff`",
+            file("input.ll")
+        ),
+        InputKind::Assembly => format!(
+            "The input looks like the assembly of the compile rather than its dumps, which are printed to stderr: `clang input.c -O2 {} -c -o /dev/null 2>&1 | optdiff`",
+            DUMP_FLAGS
+        ),
+        InputKind::Source => format!(
+            "The input looks like source code rather than a dump. Compile it with the passes dumped: `clang {} -O2 {} -c -o /dev/null 2>&1 | optdiff`",
+            file("input.c"),
+            DUMP_FLAGS
+        ),
+        InputKind::Unknown => format!("Did you forget to add `{}`?", DUMP_FLAGS),
+    }
+}
@@ -0,0 +1,97 @@
+//! `-ir-dump-directory` writes every snapshot to its own file instead of stderr, named
+//! `<pass number>-<IR unit>-<pass>-{before,after,invalidated}.ll`. Each file still starts with
+//! its banner, so putting them back in pass order gives the same dump as a streamed one.
+
+use itertools::Itertools;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where a snapshot goes between the others of the same pass.
+fn suffix_order(file_name: &str) -> Option<u8> {
+    if file_name.ends_with("-before.ll") {
+        Some(0)
+    } else if file_name.ends_with("-after.ll") {
+        Some(1)
+    } else if file_name.ends_with("-invalidated.ll") {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+fn collect_snapshots(
+    dir: &Path,
+    snapshots: &mut Vec<(PathBuf, u64, u8, PathBuf)>,
+) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_snapshots(&path, snapshots)?;
+            continue;
+        }
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some(order) = suffix_order(file_name) else {
+            continue;
+        };
+        let Some(pass_number) = file_name
+            .split_once('-')
+            .and_then(|(number, _)| number.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        snapshots.push((dir.to_path_buf(), pass_number, order, path));
+    }
+    Ok(())
+}
+
+/// Reassembles the dump written to `dir`. Directories below it are taken as separate runs and
+/// follow each other.
+pub fn read(dir: &Path) -> io::Result<String> {
+    let mut snapshots = Vec::new();
+    collect_snapshots(dir, &mut snapshots)?;
+    if snapshots.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no `-ir-dump-directory` snapshots found in the directory",
+        ));
+    }
+
+    let mut dump = String::new();
+    for (_, _, _, path) in snapshots.into_iter().sorted() {
+        dump += &String::from_utf8_lossy(&std::fs::read(&path)?);
+        if !dump.ends_with('\n') {
+            dump.push('\n');
+        }
+    }
+    Ok(dump)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read;
+
+    #[test]
+    fn snapshots_are_put_back_in_pass_order() {
+        let dir = std::env::temp_dir().join(format!("optdiff-ir-dump-{}", std::process::id()));
+        let run = dir.join("run");
+        std::fs::create_dir_all(&run).unwrap();
+        for (name, text) in [
+            ("10-f-GVNPass-after.ll", "after 10"),
+            ("10-f-GVNPass-before.ll", "before 10\n"),
+            ("2-f-SROAPass-after.ll", "after 2\n"),
+            ("2-f-SROAPass-before.ll", "before 2\n"),
+            ("notes.txt", "ignored\n"),
+            ("run/1-f-LICMPass-invalidated.ll", "invalidated 1\n"),
+        ] {
+            std::fs::write(dir.join(name), text).unwrap();
+        }
+        let dump = read(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            dump.unwrap(),
+            "before 2\nafter 2\nbefore 10\nafter 10\ninvalidated 1\n"
+        );
+    }
+}
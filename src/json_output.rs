@@ -0,0 +1,130 @@
+//! The session as a JSON document, with `--format json`, for other tools to read what `optdiff`
+//! parsed: the functions, their passes as titled in diffs, the snapshots compared and the hunks
+//! of their diff.
+//!
+//! ```json
+//! {"inputs": ["dump.txt"], "functions": [{"name": "foo", "passes": [{"number": 1,
+//!   "name": "SROAPass on foo", "run": 1, "runs": 1, "changed": true, "deleted": false,
+//!   "invalidated": false, "renamed_to": null, "score": {"added": 2, "removed": 5,
+//!   "instructions": 5}, "remarks": [], "before": "...", "after": "...",
+//!   "hunks": [{"header": "@@ -1,7 +1,4 @@", "lines": [" define i32 @foo() {", "-  ..."]}]}]}]}
+//! ```
+
+use crate::change_score::ChangeScore;
+use crate::optpipeline::Pass;
+use similar::{ChangeTag, TextDiff};
+
+/// `text` as a JSON string.
+pub fn string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => escaped += "\\\"",
+            '\\' => escaped += "\\\\",
+            '\n' => escaped += "\\n",
+            '\r' => escaped += "\\r",
+            '\t' => escaped += "\\t",
+            c if (c as u32) < 0x20 => escaped += &format!("\\u{:04x}", c as u32),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn strings<'a>(texts: impl IntoIterator<Item = &'a str>) -> String {
+    let texts = texts.into_iter().map(string).collect::<Vec<_>>();
+    format!("[{}]", texts.join(", "))
+}
+
+/// The hunks of the diff of the snapshots, with 3 lines of context as `diff -u` has.
+fn hunks(before: &str, after: &str) -> String {
+    let diff = TextDiff::from_lines(before, after);
+    let hunks = diff
+        .unified_diff()
+        .context_radius(3)
+        .iter_hunks()
+        .map(|hunk| {
+            let lines = hunk
+                .iter_changes()
+                .map(|change| {
+                    let sign = match change.tag() {
+                        ChangeTag::Equal => ' ',
+                        ChangeTag::Delete => '-',
+                        ChangeTag::Insert => '+',
+                    };
+                    format!("{}{}", sign, change.value().trim_end_matches('\n'))
+                })
+                .collect::<Vec<_>>();
+            format!(
+                "{{\"header\": {}, \"lines\": {}}}",
+                string(&hunk.header().to_string()),
+                strings(lines.iter().map(String::as_str))
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", hunks.join(", "))
+}
+
+/// A pass as a JSON object. `snapshots` are the snapshots as compared, none for passes that left
+/// nothing to compare.
+pub fn pass(
+    number: usize,
+    name: &str,
+    pass: &Pass,
+    snapshots: Option<(&str, &str)>,
+    score: Option<ChangeScore>,
+) -> String {
+    let score = match score {
+        Some(score) => format!(
+            "{{\"added\": {}, \"removed\": {}, \"instructions\": {}}}",
+            score.added, score.removed, score.instructions
+        ),
+        None => "null".to_string(),
+    };
+    let (before, after, hunks) = match snapshots {
+        Some((before, after)) => (string(before), string(after), hunks(before, after)),
+        None => ("null".to_string(), "null".to_string(), "[]".to_string()),
+    };
+    format!(
+        "{{\"number\": {}, \"name\": {}, \"run\": {}, \"runs\": {}, \"changed\": {}, \"deleted\": {}, \"invalidated\": {}, \"renamed_to\": {}, \"score\": {}, \"remarks\": {}, \"before\": {}, \"after\": {}, \"hunks\": {}}}",
+        number,
+        string(name),
+        pass.run,
+        pass.runs,
+        pass.ir_changed,
+        pass.deleted,
+        pass.invalidated,
+        pass.renamed_to.as_deref().map_or("null".to_string(), string),
+        score,
+        strings(pass.remarks.iter().map(String::as_str)),
+        before,
+        after,
+        hunks
+    )
+}
+
+#[derive(Default)]
+pub struct Document {
+    functions: Vec<String>,
+}
+
+impl Document {
+    pub fn add_function(&mut self, name: &str, passes: Vec<String>) {
+        self.functions.push(format!(
+            "{{\"name\": {}, \"passes\": [{}]}}",
+            string(name),
+            passes.join(", ")
+        ));
+    }
+
+    /// The document, one function per line.
+    pub fn render(&self, inputs: &[String]) -> String {
+        format!(
+            "{{\"inputs\": {}, \"functions\": [\n{}\n]}}\n",
+            strings(inputs.iter().map(String::as_str)),
+            self.functions.join(",\n")
+        )
+    }
+}
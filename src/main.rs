@@ -1,33 +1,231 @@
-use clap::Parser;
+use change_score::{ChangeScore, MinChange};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
 use color_eyre::{
     eyre::{eyre, WrapErr},
     Result,
 };
 use color_print::cformat;
+use diff_tool::DiffTool;
+use highlight::Profile;
+use hyperlinks::{Links, When};
 use is_terminal::IsTerminal;
 use itertools::Itertools;
-use memchr::memmem;
-use optpipeline::Pass;
+use optpipeline::{Dialect, DumpStream, Pass, PassTime, ProcessOptions};
+use output_dir::{Output, Split};
+use progress::Progress;
 use regex::Regex;
-use similar::TextDiff;
-use std::path::PathBuf;
+use similar::{ChangeTag, TextDiff};
+use snapshot::{
+    demangle_text, file_name_part, run_label, snapshot_extension, snapshots, Normalize,
+};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
 use std::{
-    collections::HashSet,
-    io::{self, Read, Write},
+    collections::{HashMap, HashSet},
+    env,
+    io::{self, BufRead, BufReader, Read, Write},
 };
+use theme::Theme;
 
 #[cfg(unix)]
 use pager::Pager;
 
+mod block_matching;
+mod build_log;
+mod builtin_pager;
+mod canonicalize;
+mod change_score;
+mod chart;
 mod cli_write;
+mod code_motion;
+mod compare;
+mod compressed;
+mod config;
+mod csv_output;
+mod debug_info;
 mod demangle;
+mod diff_stat;
+mod diff_tool;
+mod dot_graph;
+mod extract;
+mod flang_demangle;
+mod gcc_dump_files;
+mod gh_summary;
+mod highlight;
+mod html_report;
+mod hyperlinks;
+mod input_kind;
+mod ir_dump_directory;
+mod json_output;
+mod markdown;
+mod opcodes;
 mod optpipeline;
+mod output_dir;
+mod pass_descriptions;
+mod pipeline_reference;
+mod progress;
+mod remarks;
+mod side_by_side;
+mod snapshot;
+mod sqlite_export;
+mod step;
+mod theme;
+mod trace_export;
+mod tui;
+mod value_numbering;
+
+/// How the diff of a pass is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Engine {
+    /// Side by side when printing to a terminal without delta or riff to highlight unified diffs,
+    /// inline if the terminal is too narrow for that, unified otherwise
+    Auto,
+    /// Unified diffs in the format of `git diff`, for delta, riff or other tools to read
+    Unified,
+    /// The snapshots before and after the pass next to each other, coloured
+    SideBySide,
+    /// The lines removed and added one after the other with their line numbers, coloured, for
+    /// terminals too narrow for two columns
+    Inline,
+}
+
+/// Formatting differences left out of the snapshots with `--canonicalize`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Canonical {
+    /// Floating-point constants in hexadecimal, however they were printed
+    Constants,
+    /// Struct types without the numbers appended to tell types of the same name apart
+    Types,
+    /// Attributes of functions, calls and attribute groups in sorted order
+    Attributes,
+}
+
+/// How the output is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Diffs as text, for terminals and pagers
+    #[default]
+    Text,
+    /// A page with a sidebar of the functions and their passes collapsed, diffed side by side
+    Html,
+    /// A document of the functions and their passes, with the snapshots and the hunks of their
+    /// diffs, for other tools to read
+    Json,
+    /// Plain unified diffs with `---`, `+++` and `@@` headers, a patch per pass, for `patch`,
+    /// review tools or to keep as artifacts
+    Unified,
+    /// The passes that changed a function as collapsed sections with their diffs, to paste into
+    /// a pull request or an issue
+    Markdown,
+    /// Diffs without colours or box drawing, each line behind a marker that stays the same
+    /// whatever the terminal: `PASS:` headers, `HUNK:` positions, `NOTE:` notes and `+`, `-` or
+    /// blanks before the lines, for screen readers, grep and logs
+    Plain,
+    /// A row per pass of each function with the lines it added and removed and the instructions
+    /// before and after it, for spreadsheets
+    Csv,
+    /// A GitHub Actions job summary: the totals, a table of the passes that changed each
+    /// function and their diffs collapsed, appended to `GITHUB_STEP_SUMMARY`, with annotations
+    /// on stdout
+    GhSummary,
+}
+
+/// Summaries shown instead of the diffs with `--stat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Stat {
+    /// The lines added and removed by each pass, with a bar as `git diff --stat` prints
+    Lines,
+    /// The instructions added and removed by each pass, counted by opcode
+    Ops,
+}
+
+/// What the diffs are grouped by, with `--group-by`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum GroupBy {
+    /// The passes of each function in turn
+    #[default]
+    Function,
+    /// Each pass with its diffs in all the functions it ran on
+    Pass,
+}
+
+impl Normalize {
+    fn from_args(args: &Args) -> Self {
+        Normalize {
+            values: args.normalize_values,
+            blocks: args.normalize_blocks,
+            metadata_ids: args.ignore_metadata_ids,
+            debug_info: args.ignore_debug,
+            whitespace: args.ignore_whitespace,
+            comments: args.ignore_comments,
+            match_blocks: args.match_blocks,
+            constants: args.canonicalize.contains(&Canonical::Constants),
+            types: args.canonicalize.contains(&Canonical::Types),
+            attributes: args.canonicalize.contains(&Canonical::Attributes),
+            only_added: args.only_added,
+            only_removed: args.only_removed,
+        }
+    }
+}
+
+/// How the built-in engines render the diffs, shared with the threads diffing the passes.
+#[derive(Debug, Clone)]
+struct Layout {
+    /// Unified, side by side or inline, never auto
+    engine: Engine,
+    by_block: bool,
+    /// The unchanged lines shown around the changes
+    context: usize,
+    columns: side_by_side::Columns,
+    theme: Theme,
+    /// The documentation of the passes, their names in the headers link to
+    links: Links,
+}
+
+/// How the diffs are printed, as decided from `--engine`, `--by-block`, `--diff-tool`, `--stat`
+/// and `--format`.
+enum Renderer {
+    /// The built-in engines
+    Builtin(Layout),
+    Tool(DiffTool),
+    /// The instructions added and removed by opcode, instead of the diffs
+    Ops,
+    /// A line per pass with a bar of the lines it added and removed, instead of the diffs, in
+    /// the colours of the theme if any
+    Stat(Option<Theme>),
+    /// The functions are added to an HTML report, written once they're all diffed
+    Html(RefCell<html_report::Report>),
+    /// Likewise for a JSON document
+    Json(RefCell<json_output::Document>),
+    /// A patch per pass, without titles, notes or colours
+    Patch,
+    /// Markdown sections, printed once the passes of a function are all diffed
+    Markdown,
+    /// Diffs with markers instead of colours, with this many lines of context
+    Plain(usize),
+    /// Likewise for a CSV table
+    Csv(RefCell<csv_output::Table>),
+    /// Likewise for a GitHub Actions job summary
+    GhSummary(RefCell<gh_summary::Summary>),
+    /// The functions are written to an SQLite database as they're diffed
+    Sqlite(RefCell<sqlite_export::Database>),
+    /// The pipelines are added to a Graphviz graph, written once they're all read
+    Dot(RefCell<dot_graph::Graph>),
+    /// Likewise for the timings of the passes as a Chrome trace
+    Trace(RefCell<trace_export::Trace>),
+}
 
 #[derive(Parser)]
 #[command(
     author,
     version,
-    about = "Display diffs of LLVM IR changes between optimization passes"
+    about = "Display diffs of LLVM IR changes between optimization passes",
+    args_conflicts_with_subcommands = true
 )]
 #[command(after_help = cformat!("<s><u>Note:</u></s>
    For syntax highlighting of diffs, install delta: https://github.com/dandavison/delta
@@ -43,19 +241,297 @@ mod optpipeline;
    clang input.c -O2 -mllvm -print-before-all -mllvm -print-after-all -c -o /dev/null &> dump.txt
    optdiff dump.txt
 
+   <i># From a directory written by `-ir-dump-directory`:</i>
+   clang input.c -O2 -mllvm -print-before-all -mllvm -print-after-all -mllvm -ir-dump-directory=dumps -c -o /dev/null
+   optdiff dumps
+
+   <i># From the dumps of several translation units:</i>
+   optdiff a.txt b.txt dumps --per-file
+
+   <i># From GCC dump files:</i>
+   gcc input.c -O2 -fdump-tree-all -fdump-rtl-all -c -o /dev/null
+   optdiff input.c.005t.original
+
+   <i># To compare the dumps of two compiler versions:</i>
+   optdiff compare old.txt new.txt -s
+
    <i># To filter functions/passes (and optionally with regex `-E`):</i>
    optdiff dump.txt -E -f 'foo.*'              # match functions starting with 'foo'
    optdiff dump.txt -E -P 'Combine|Simplify'   # match passes containing 'Combine' or 'Simplify'
    optdiff dump.txt -E -f '^main$' -P '.*Opt$' # match exactly 'main' function and passes ending in 'Opt'"))]
-struct Args {
-    /// Path to LLVM pass dump file. If not provided, reads from stdin
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path to LLVM pass dump file, `-ir-dump-directory` directory, or GCC dump file (or directory
+    /// of them). If not provided, reads from stdin. gzip, xz and zstd compressed dumps are
+    /// decoded as they're read. Several dumps, e.g. of the translation units of a build, are
+    /// read as one, their functions attributed to the file they were found in
+    #[arg(value_name = "FILE")]
+    input: Vec<PathBuf>,
+
+    #[command(flatten)]
+    args: Args,
+
+    #[command(flatten)]
+    view: View,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compare two dumps of the same source pass by pass, e.g. made by two versions of the
+    /// compiler, and show where the IR of the new one diverges from the old one after a pass
+    Compare(CompareArgs),
+    /// Export the session instead of showing the diffs: to an SQLite database, as a Graphviz
+    /// graph of the pass pipelines or as a Chrome trace of the timings of the passes
+    Export(ExportArgs),
+    /// Write the snapshot of a function before or after a pass as a module of its own, to feed
+    /// to `opt`, `llc` or a reducer
+    #[command(mut_arg("function", |arg| arg.required(true)))]
+    Extract(ExtractArgs),
+}
+
+/// The options of `optdiff compare`.
+#[derive(clap::Args)]
+struct CompareArgs {
+    /// The dump of the old compiler and the one of the new compiler. With a third dump, e.g. of
+    /// two candidate patches, both new dumps are shown next to the old one in three columns
+    #[arg(required = true, num_args = 2..=3, value_names = ["OLD", "NEW"])]
+    dumps: Vec<PathBuf>,
+
+    #[command(flatten)]
+    args: Args,
+}
+
+/// The options of `optdiff export`.
+#[derive(clap::Args)]
+struct ExportArgs {
+    /// The dumps exported, read as by `optdiff`
+    #[arg(value_name = "FILE")]
+    input: Vec<PathBuf>,
+
+    #[command(flatten)]
+    to: Export,
+
+    /// Keep the functions of each input file apart, instead of merging the functions of all
+    /// files by name
+    #[arg(long = "per-file")]
+    per_file: bool,
+
+    /// Write the graph of `--dot` to this file instead of stdout
+    #[arg(short = 'o', long = "output", value_name = "FILE", conflicts_with_all = ["sqlite", "trace"])]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    args: Args,
+}
+
+/// What `optdiff export` exports the session to.
+#[derive(clap::Args, Default)]
+#[command(group(ArgGroup::new("to").args(["sqlite", "dot", "trace"]).required(true)))]
+struct Export {
+    /// Export the session to this SQLite database: the functions, their passes with their
+    /// snapshots and how much they changed them. Each export adds a session to the database
+    #[arg(long = "sqlite", value_name = "FILE", conflicts_with_all = ["tool", "stat"])]
+    sqlite: Option<PathBuf>,
+
+    /// Export the pass pipelines of the functions as a Graphviz graph, a node per pass, filled in
+    /// if it changed the function and grouped by the adaptors it ran under
+    #[arg(long = "dot", conflicts_with_all = ["tool", "stat"])]
+    dot: bool,
+
+    /// Export the timings of the passes as a Chrome trace to this file, for `chrome://tracing` or
+    /// Perfetto: a track per function with its passes. The dump needs a `-time-passes` report
+    #[arg(long = "trace", value_name = "FILE", conflicts_with_all = ["tool", "stat"])]
+    trace: Option<PathBuf>,
+}
+
+/// The options of `optdiff extract`.
+#[derive(clap::Args)]
+struct ExtractArgs {
+    /// The dumps the snapshot is taken from, read as by `optdiff`
     #[arg(value_name = "FILE")]
-    input: Option<PathBuf>,
+    input: Vec<PathBuf>,
+
+    #[command(flatten)]
+    pass: Extract,
+
+    /// Write the snapshot to this file instead of stdout
+    #[arg(short = 'o', long = "output", value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    args: Args,
+}
+
+/// The snapshot `optdiff extract` writes.
+#[derive(clap::Args, Default)]
+#[command(group(ArgGroup::new("snapshot").args(["before", "after"]).required(true)))]
+struct Extract {
+    /// Write the snapshot of the function dumped before this pass. The pass is given by its
+    /// number or its name, matched like `-P`
+    #[arg(long = "before", value_name = "PASS")]
+    before: Option<String>,
+
+    /// Write the snapshot of the function dumped after this pass
+    #[arg(long = "after", value_name = "PASS")]
+    after: Option<String>,
+}
+
+/// How a dump is shown, the options of `optdiff` alone.
+#[derive(clap::Args, Default)]
+struct View {
+    /// Keep the functions of each input file together, listed under the file, instead of sorting
+    /// the functions of all files by name
+    #[arg(long = "per-file")]
+    per_file: bool,
+
+    /// List available functions
+    #[arg(short = 'l', long = "list")]
+    list: bool,
+
+    /// Optimization remarks of the same compile (`-fsave-optimization-record` YAML file), shown
+    /// under the diff of the pass that emitted them
+    #[arg(long = "remarks", value_name = "FILE")]
+    remarks: Option<PathBuf>,
+
+    /// The pipeline the dump was made with, as printed by `opt -print-pipeline-passes` (or a file
+    /// holding it). Passes of it that weren't dumped are marked, and passes that run elsewhere
+    /// are warned about
+    #[arg(long = "reference", value_name = "PIPELINE")]
+    reference: Option<String>,
+
+    /// Diff the snapshot before this pass against the one after the pass of `--to`, for the
+    /// cumulative effect of a range of the pipeline. The pass is given by its number or its name,
+    /// matched like `-P`. Without `--to`, the range goes to the end of the pipeline
+    #[arg(long = "from", value_name = "PASS", conflicts_with_all = ["tree", "follow"])]
+    from: Option<String>,
+
+    /// The last pass of the range diffed, the first one matching at or after the pass of `--from`.
+    /// Without `--from`, the range starts at the beginning of the pipeline
+    #[arg(long = "to", value_name = "PASS", conflicts_with_all = ["tree", "follow"])]
+    to: Option<String>,
+
+    /// Show a single diff per function, of its first snapshot against its last one, for what the
+    /// whole pipeline did to it
+    #[arg(long = "net", conflicts_with_all = ["from", "to", "tree", "follow"])]
+    net: bool,
+
+    /// List the passes of the functions with how much they changed them: the lines added and
+    /// removed, and the instructions touched, instead of the diffs
+    #[arg(long = "list-passes", conflicts_with_all = ["list", "tree", "follow", "stat"])]
+    list_passes: bool,
+
+    /// Print only the passes that changed the functions, a line per pass with the function, the
+    /// number of the pass and its name separated by tabs, for scripts
+    #[arg(
+        long = "names-only",
+        conflicts_with_all = ["list", "tree", "follow", "list_passes"]
+    )]
+    names_only: bool,
+
+    /// Show the passes one at a time, each on a screen of its own under the function and the
+    /// number of the pass, the next one when Enter is pressed. For teaching, or walking a team
+    /// through a regression
+    #[arg(
+        long = "step",
+        conflicts_with_all = ["output", "stat", "tree", "list_passes", "names_only", "follow"]
+    )]
+    step: bool,
+
+    /// Browse the functions and their passes in an interactive viewer instead of printing the
+    /// diffs: the functions, the passes of the function selected marked if they changed it, and
+    /// the diff of the pass selected, in panes moved between with the arrow keys
+    #[arg(
+        long = "tui",
+        conflicts_with_all = ["format", "output", "tool", "stat", "tree", "list_passes", "names_only", "follow", "step"]
+    )]
+    tui: bool,
+
+    /// Group the diffs by `function`, the passes of each function in turn, or by `pass`: each
+    /// pass, in the order they first ran, with its diffs in every function of the dump. With
+    /// `--pass`, to weigh a single pass over a whole translation unit
+    #[arg(
+        long = "group-by",
+        value_enum,
+        default_value_t = GroupBy::Function,
+        conflicts_with_all = ["output", "tree", "list_passes", "names_only", "follow"]
+    )]
+    group_by: GroupBy,
+
+    /// How to write the output: `html` makes a page of the whole session, with the functions
+    /// in a sidebar and the passes collapsed, `json` a document of the parsed session for other
+    /// tools to read, `unified` a patch per pass, `markdown` collapsed sections to paste into a
+    /// comment, `csv` metrics of the passes, `plain` the diffs with markers instead of colours,
+    /// and `gh-summary` a GitHub Actions job summary appended to `GITHUB_STEP_SUMMARY`, with a
+    /// `::notice` of the totals and a `::warning` for each pass that deleted a function or
+    /// invalidated the IR on stdout
+    #[arg(
+        long = "format",
+        value_enum,
+        default_value_t = Format::Text,
+        conflicts_with_all = ["tool", "stat", "tree", "list_passes", "follow"]
+    )]
+    format: Format,
+
+    /// Write the output of `--format html`, `json` or `csv` to this file instead of stdout. The
+    /// diffs of the other formats can be written to a directory instead, ending with `/`, a
+    /// file per function
+    #[arg(short = 'o', long = "output", value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// What each file of the directory given to `--output` holds: the diffs of a function, or of
+    /// a pass in a directory per function
+    #[arg(
+        long = "split-by",
+        value_enum,
+        default_value_t = Split::Function,
+        requires = "output",
+        conflicts_with = "stat"
+    )]
+    split_by: Split,
+
+    /// Print the pass pipeline of the functions as a tree of the pass manager adaptors the passes
+    /// ran under, instead of the diffs
+    #[arg(long = "tree")]
+    tree: bool,
+
+    /// Keep reading the dump file as the compiler writes it, and print the diffs of the passes as
+    /// their snapshots are complete, like `tail -f`
+    #[arg(long = "follow", conflicts_with_all = ["list", "tree", "tu", "remarks", "reference"])]
+    follow: bool,
+}
+
+/// The options of all the commands.
+#[derive(clap::Args)]
+#[command(group(ArgGroup::new("tool").args(["diff_tool", "difft_args"])))]
+struct Args {
+    /// The dumps read, from the command line
+    #[arg(skip)]
+    input: Vec<PathBuf>,
+
+    /// The options of `optdiff` alone, at their defaults in the other commands
+    #[arg(skip)]
+    view: View,
+
+    /// What `optdiff export` exports to, nothing in the other commands
+    #[arg(skip)]
+    export: Export,
+
+    /// The snapshot `optdiff extract` writes, none in the other commands
+    #[arg(skip)]
+    extract: Extract,
 
     /// Hide optimization passes that don't modify the IR
     #[arg(short = 's', long = "skip-unchanged")]
     skip_unchanged: bool,
 
+    /// Hide the passes that changed fewer lines than this, a number of lines (`5`) or a
+    /// percentage of the lines of the function (`2%`). Passes that didn't change the IR are
+    /// hidden too
+    #[arg(long = "min-change", value_name = "N")]
+    min_change: Option<MinChange>,
+
     /// Only show passes for specified function
     #[arg(short = 'f', long = "function")]
     function: Option<String>,
@@ -68,32 +544,335 @@ struct Args {
     #[arg(short = 'E', long = "extended-regex")]
     extended_regex: bool,
 
-    /// List available functions
-    #[arg(short = 'l', long = "list")]
-    list: bool,
-
-    /// Demangle C++ symbols
+    /// Demangle C++ and Fortran symbols
     #[arg(short = 'd', long = "demangle")]
     demangle: bool,
 
-    /// Which pager to use
+    /// Which pager to use, instead of `PAGER` or the first of delta, riff and less found.
+    /// `builtin` is optdiff's own, the one used on Windows
     #[arg(short = 'p', long = "pager", env = "OPTDIFF_PAGER")]
     pager: Option<String>,
 
+    /// Write the diffs straight to the terminal, without a pager
+    #[arg(long = "no-pager")]
+    no_pager: bool,
+
+    /// Don't show the progress of parsing and diffing big dumps on stderr
+    #[arg(long = "no-progress")]
+    no_progress: bool,
+
+    /// How to render the diffs
+    #[arg(
+        long = "engine",
+        visible_alias = "display",
+        value_enum,
+        default_value_t = Engine::Auto
+    )]
+    engine: Engine,
+
+    /// Compare the snapshots of each pass with this program instead, e.g. `diff -u`. `{before}`
+    /// and `{after}` stand for the paths of the snapshots, which otherwise follow the command,
+    /// and `{title}` for the pass
+    #[arg(long = "diff-tool", value_name = "COMMAND", conflicts_with = "engine")]
+    diff_tool: Option<String>,
+
+    /// Compare the snapshots of each pass with difftastic, run with these arguments, e.g.
+    /// `--display inline --width 120`. The `DFT_*` environment variables are passed on to it
+    #[arg(
+        long = "difft-args",
+        value_name = "ARGS",
+        allow_hyphen_values = true,
+        conflicts_with_all = ["engine", "diff_tool"]
+    )]
+    difft_args: Option<String>,
+
+    /// Run the diff tool once per function on a `before` and an `after` directory, holding a file
+    /// per pass, e.g. with `diff -ru` or `difft`. Notes and remarks of the passes aren't shown
+    #[arg(long = "diff-tool-batch", requires = "tool")]
+    diff_tool_batch: bool,
+
+    /// Diff the blocks of the functions one by one, each under its label (`bb: %for.body`), and
+    /// only the ones that changed. Blocks are matched as with `--match-blocks`
+    #[arg(long = "by-block", conflicts_with = "tool")]
+    by_block: bool,
+
+    /// How many unchanged lines the built-in engines show around the changes
+    #[arg(
+        short = 'U',
+        long = "context",
+        value_name = "N",
+        default_value_t = 10,
+        conflicts_with = "tool"
+    )]
+    context: usize,
+
+    /// Show the whole snapshots around the changes, not only the lines near them
+    #[arg(long = "full-context", conflicts_with_all = ["context", "tool"])]
+    full_context: bool,
+
+    /// The width to fit the side-by-side diffs in, instead of `COLUMNS`
+    #[arg(long = "width", value_name = "COLUMNS", conflicts_with = "tool")]
+    width: Option<usize>,
+
+    /// Wrap the lines too wide for their column in side-by-side diffs onto more rows, rather
+    /// than cutting them off
+    #[arg(long = "wrap", conflicts_with = "tool")]
+    wrap: bool,
+
+    /// When to make the pass names in the headers of the built-in engines links to their
+    /// documentation: `auto` if the terminal is known to support OSC 8 hyperlinks. The
+    /// `[links]` section of the config file points the passes of downstream compilers elsewhere
+    #[arg(
+        long = "hyperlinks",
+        value_name = "WHEN",
+        value_enum,
+        default_value_t = When::Auto,
+        env = "OPTDIFF_HYPERLINKS"
+    )]
+    hyperlinks: When,
+
+    /// The colours of the built-in engines, overridden by the `[theme]` section of the config
+    /// file
+    #[arg(
+        long = "theme",
+        value_enum,
+        env = "OPTDIFF_THEME",
+        conflicts_with = "tool"
+    )]
+    theme: Option<theme::ThemeName>,
+
+    /// When to colour the bars of `--stat`: `auto` when writing to a terminal, unless `NO_COLOR`
+    /// is set
+    #[arg(
+        long = "color",
+        value_name = "WHEN",
+        value_enum,
+        default_value_t = When::Auto,
+        env = "OPTDIFF_COLOR"
+    )]
+    color: When,
+
+    /// Renumber the unnamed values (`%5`) after each pass to match the values they stand for
+    /// before it, so that values renumbered by the pass don't show up as changes
+    #[arg(long = "normalize-values")]
+    normalize_values: bool,
+
+    /// Renumber the numbered blocks (`bb.5`, `^bb5`, `bb5`, `<bb 5>`) after each pass to match
+    /// the blocks they stand for before it. The numbered blocks of LLVM IR are values, see
+    /// `--normalize-values`
+    #[arg(long = "normalize-blocks")]
+    normalize_blocks: bool,
+
+    /// Match the blocks of each function after a pass with the blocks before it, by label, branch
+    /// targets and contents, and show them in the order of their match, so that reordered blocks
+    /// don't show up as changes. New blocks stay after the block they followed
+    #[arg(long = "match-blocks")]
+    match_blocks: bool,
+
+    /// Renumber the metadata nodes (`!5`) and attribute groups (`#5`) after each pass to match
+    /// the ones they stand for before it, so that passes renumbering them don't show up as changes
+    #[arg(long = "ignore-metadata-ids")]
+    ignore_metadata_ids: bool,
+
+    /// Leave the debug info of GIMPLE, SIL and MLIR out of the snapshots (`# DEBUG`,
+    /// `debug_value`, `loc(...)`), as it is of LLVM IR. With `-s`, passes that only change debug
+    /// info are hidden too
+    #[arg(long = "ignore-debug")]
+    ignore_debug: bool,
+
+    /// Ignore differences in indentation and whitespace. With `-s`, passes that only change
+    /// whitespace are hidden too
+    #[arg(short = 'w', long = "ignore-whitespace")]
+    ignore_whitespace: bool,
+
+    /// Only show the lines each pass added, e.g. to look for the calls or branches it introduced
+    #[arg(long = "only-added", conflicts_with = "only_removed")]
+    only_added: bool,
+
+    /// Only show the lines each pass removed
+    #[arg(long = "only-removed")]
+    only_removed: bool,
+
+    /// Leave comments out of the snapshots (`;` in LLVM IR, MIR and GIMPLE, `//` in MLIR and SIL),
+    /// e.g. `; preds = %4` or block frequencies. With `-s`, passes that only change comments are
+    /// hidden too
+    #[arg(long = "ignore-comments")]
+    ignore_comments: bool,
+
+    /// Show the attributes of the attribute groups (`#0`) of LLVM IR where they're used, so that
+    /// a function or call moved to another group shows which attributes changed
+    #[arg(long = "expand-attributes")]
+    expand_attributes: bool,
+
+    /// Leave formatting differences out of the snapshots, a comma-separated list. With `-s`,
+    /// passes that only change the formatting are hidden too
+    #[arg(
+        long = "canonicalize",
+        value_name = "WHAT",
+        value_enum,
+        value_delimiter = ','
+    )]
+    canonicalize: Vec<Canonical>,
+
+    /// Show whole-module snapshots of every pass instead of the function's definition, for
+    /// dumps made with `-print-module-scope`
+    #[arg(long = "full-module")]
+    full_module: bool,
+
+    /// Only show the dumps of this translation unit, for build logs of several compile commands
+    #[arg(long = "tu", value_name = "SOURCE")]
+    tu: Option<String>,
+
+    /// Only show modules with names containing this string, for dumps of several modules such as
+    /// CUDA or ThinLTO compiles. Modules are named by their ModuleID, and target triple if shared
+    #[arg(long = "module")]
+    module: Option<String>,
+
+    /// Keep the text printed between the dumps (`-debug` output, remarks, cargo and rustc
+    /// messages) instead of leaving it out of the snapshots
+    #[arg(long = "keep-noise")]
+    keep_noise: bool,
+
+    /// Show how the global variables, aliases and ifuncs change instead of the functions, from
+    /// the snapshots of module passes
+    #[arg(long = "globals")]
+    globals: bool,
+
+    /// Collapse the passes nested under more than this many pass manager adaptors into a single
+    /// pass of the adaptor at that depth, e.g. 0 for the module passes only
+    #[arg(long = "depth", value_name = "N")]
+    depth: Option<usize>,
+
+    /// Describe what the common passes of LLVM do under their headers, e.g. `SROA: splits
+    /// aggregates into scalar SSA values`
+    #[arg(long = "describe")]
+    describe: bool,
+
+    /// Summarize the passes instead of showing their diffs: `lines` (the default) lists the passes
+    /// that changed each function with a bar of the lines they added and removed, `ops` counts
+    /// the instructions each pass added and removed by opcode (`+3 load, -5 store`)
+    #[arg(
+        long = "stat",
+        value_name = "WHAT",
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "lines",
+        conflicts_with_all = ["tool", "by_block"]
+    )]
+    stat: Option<Stat>,
+
+    /// Show the passes of each function by how much they changed it, the pass touching the most
+    /// instructions first, instead of in the order they ran
+    #[arg(long = "sort-by-change")]
+    sort_by_change: bool,
+
     /// Pass through prefix
     #[arg(long = "passthrough")]
     passthrough: bool,
 }
 
-fn read_input(args: &Args) -> Result<String, io::Error> {
-    match &args.input {
-        Some(path) => std::fs::read_to_string(path),
+/// Decodes a chunk of the dump starting at byte `offset`, invalid UTF-8 is replaced rather than
+/// given up on. The offset of the first invalid byte is kept in `invalid_at`.
+fn decode_lossy<'a>(bytes: &'a [u8], offset: u64, invalid_at: &mut Option<u64>) -> Cow<'a, str> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Cow::Borrowed(text),
+        Err(err) => {
+            invalid_at.get_or_insert(offset + err.valid_up_to() as u64);
+            String::from_utf8_lossy(bytes)
+        }
+    }
+}
+
+/// Feeds the dump to the stream as it's read, a line at a time.
+fn stream_lines(
+    mut reader: impl BufRead,
+    stream: &mut DumpStream,
+    invalid_at: &mut Option<u64>,
+    progress: &mut Progress,
+) -> Result<(), io::Error> {
+    let mut line = Vec::new();
+    let mut offset = 0;
+    let mut lines = 0usize;
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        stream.push_line(&decode_lossy(&line, offset, invalid_at));
+        offset += line.len() as u64;
+        line.clear();
+        lines += 1;
+        if lines.is_multiple_of(1024) {
+            progress.parsed(stream.functions_found());
+        }
+    }
+    Ok(())
+}
+
+fn read_input(
+    input: Option<&Path>,
+    args: &Args,
+    stream: &mut DumpStream,
+    progress: &mut Progress,
+) -> Result<()> {
+    let read_error = || match input {
+        None => "Failed to read from stdin".to_string(),
+        Some(path) => format!("Failed to read from file: {}", path.display()),
+    };
+    // Dumps written to several files are put back together first
+    let reader = match input {
+        Some(path) if gcc_dump_files::is_session(path) => {
+            let dump = gcc_dump_files::read(path).wrap_err_with(read_error)?;
+            return stream_text(&dump, args, stream);
+        }
+        Some(path) if path.is_dir() => {
+            let dump = ir_dump_directory::read(path).wrap_err_with(read_error)?;
+            return stream_text(&dump, args, stream);
+        }
+        Some(path) => File::open(path).and_then(|file| {
+            let size = file.metadata()?.len();
+            let read = progress.reading(path.display().to_string(), Some(size));
+            compressed::decode(progress::Counted::new(file, &read))
+        }),
         None => {
-            let mut buffer = String::new();
-            io::stdin().read_to_string(&mut buffer)?;
-            Ok(buffer)
+            let read = progress.reading("stdin".to_string(), None);
+            compressed::decode(progress::Counted::new(io::stdin().lock(), &read))
+        }
+    };
+    let mut reader = reader.wrap_err_with(read_error)?;
+    let mut invalid_at = None;
+    if args.tu.is_none() {
+        stream_lines(reader, stream, &mut invalid_at, progress).wrap_err_with(read_error)?;
+    } else {
+        let mut dump = Vec::new();
+        reader.read_to_end(&mut dump).wrap_err_with(read_error)?;
+        stream_text(&decode_lossy(&dump, 0, &mut invalid_at), args, stream)?;
+    }
+    progress.clear();
+    // Symbols with raw bytes or a dump cut off within a character shouldn't cost the rest of it
+    if let Some(offset) = invalid_at {
+        cli_writeln!(
+            io::stderr(),
+            "warning: {} isn't valid UTF-8 at byte {}, invalid bytes are shown as U+FFFD",
+            input.map_or("the input".to_string(), |path| path.display().to_string()),
+            offset
+        )?;
+    }
+    Ok(())
+}
+
+/// Feeds a dump read as a whole, build logs are only searched for the translation unit then.
+fn stream_text(dump: &str, args: &Args, stream: &mut DumpStream) -> Result<()> {
+    let selected;
+    let dump = match &args.tu {
+        Some(tu) => {
+            selected = build_log::select_translation_unit(dump, tu).ok_or_else(|| {
+                eyre!("No compile command for `{}` was found in the build log", tu)
+            })?;
+            &selected
         }
+        None => dump,
+    };
+    for line in dump.split_inclusive('\n') {
+        stream.push_line(line);
     }
+    Ok(())
 }
 
 fn matches_pattern(text: &str, pattern: &str, use_regex: bool) -> Result<bool> {
@@ -106,191 +885,2378 @@ fn matches_pattern(text: &str, pattern: &str, use_regex: bool) -> Result<bool> {
     }
 }
 
-fn demangle_text(text: &str, should_demangle: bool) -> String {
-    if !should_demangle {
-        return text.to_string();
+/// The instructions of the function before the passes and after each of them, charted.
+fn instruction_chart(pipeline: &[Pass], first: usize, should_demangle: bool) -> String {
+    let count = |snapshot: &str| {
+        snapshot
+            .lines()
+            .filter(|line| change_score::is_instruction(line))
+            .count()
+    };
+    let mut points = Vec::new();
+    if let Some(pass) = pipeline.get(first).filter(|pass| !pass.before.is_empty()) {
+        points.push(chart::Point {
+            label: "Before the passes".to_string(),
+            instructions: count(&pass.before),
+        });
+    }
+    for (i, pass) in pipeline.iter().enumerate().skip(first) {
+        // An invalidated IR unit leaves no snapshot to count
+        if pass.invalidated {
+            continue;
+        }
+        points.push(chart::Point {
+            label: format!(
+                "{}. {}{}",
+                i + 1,
+                demangle_text(&pass.name, should_demangle),
+                run_label(pass)
+            ),
+            instructions: match pass.deleted {
+                true => 0,
+                false => count(&pass.after),
+            },
+        });
+    }
+    chart::svg(&points)
+}
+
+/// The wall time of the pass from the `-time-passes` report, if there is one.
+fn time_label(pass: &Pass) -> String {
+    match pass.time {
+        Some(PassTime::Run(time)) => format!(" [{:.1} ms]", time * 1000.0),
+        Some(PassTime::AllRuns(time)) => format!(" [{:.1} ms in all runs]", time * 1000.0),
+        None => String::new(),
+    }
+}
+
+/// How much the pass changed the function, none for passes that didn't change it or left no
+/// snapshot to compare.
+fn pass_score(pass: &Pass, should_demangle: bool, normalize: Normalize) -> Option<ChangeScore> {
+    if !pass.ir_changed || pass.deleted || pass.invalidated {
+        return None;
     }
+    let (before, after) = snapshots(pass, should_demangle, normalize);
+    Some(change_score::score(&before, &after))
+}
+
+/// The header of a pass in the terminal: its number out of all the passes, its name as shown
+/// (e.g. linked to its documentation), and then the IR unit it ran on, the function, its run,
+/// and what it did to the function.
+fn pass_header(
+    i: usize,
+    total: usize,
+    name: &str,
+    func_name: &str,
+    pass: &Pass,
+    renamed: &str,
+    score: Option<ChangeScore>,
+) -> String {
+    format!(
+        "[{}/{}] {}{} — {}{}",
+        i + 1,
+        total,
+        name,
+        renamed,
+        pass_details(func_name, pass, score).join(" · "),
+        time_label(pass)
+    )
+}
+
+/// The header of a pass for `--format plain`, the same details between commas rather than dots.
+fn plain_header(
+    i: usize,
+    total: usize,
+    func_name: &str,
+    pass: &Pass,
+    renamed: &str,
+    score: Option<ChangeScore>,
+) -> String {
+    format!(
+        "PASS: {} of {}, {}{}, {}{}",
+        i + 1,
+        total,
+        pass.name,
+        renamed,
+        pass_details(func_name, pass, score).join(", "),
+        time_label(pass)
+    )
+}
 
-    let mut output = Vec::new();
-    let options = demangle::DemangleBuilder::new().build();
-    if demangle::demangle_line(&mut output, text.as_bytes(), options).is_ok() {
-        String::from_utf8_lossy(&output).to_string()
+/// The scope of the pass, the function, the run and what the pass did to it.
+fn pass_details(func_name: &str, pass: &Pass, score: Option<ChangeScore>) -> Vec<String> {
+    let mut details = optpipeline::scope(pass)
+        .into_iter()
+        .map(str::to_string)
+        .collect_vec();
+    details.push(func_name.to_string());
+    if pass.runs > 1 {
+        details.push(format!("run {} of {}", pass.run, pass.runs));
+    }
+    details.push(if pass.deleted {
+        "deleted the function".to_string()
+    } else if pass.invalidated {
+        "invalidated the IR".to_string()
+    } else if pass.ir_changed || pass.before != pass.after {
+        format!("changed{}", score_label(score))
     } else {
-        text.to_string()
+        "unchanged".to_string()
+    });
+    details
+}
+
+fn score_label(score: Option<ChangeScore>) -> String {
+    match score {
+        Some(score) => format!(" [{}]", score),
+        None => String::new(),
     }
 }
 
-fn print_func(
+/// Leaves out the passes that changed less than `min_change`, and puts the ones that changed the
+/// function the most first if `sorted`, along with their scores. Passes that left no snapshot to
+/// compare are kept.
+fn rank_by_change(
+    pipeline: &[Pass],
+    shown: &mut Vec<usize>,
+    scores: &mut Vec<Option<ChangeScore>>,
+    min_change: Option<MinChange>,
+    sorted: bool,
+) {
+    let mut scored = shown
+        .iter()
+        .copied()
+        .zip(scores.iter().copied())
+        .collect_vec();
+    if let Some(min_change) = min_change {
+        scored.retain(|&(i, score)| match score {
+            Some(score) => min_change.is_met(score),
+            None => pipeline[i].deleted || pipeline[i].invalidated,
+        });
+    }
+    if sorted {
+        scored.sort_by_key(|(_, score)| std::cmp::Reverse(score.map(ChangeScore::key)));
+    }
+    (*shown, *scores) = scored.into_iter().unzip();
+}
+
+/// Prints the diffs by pass for `--group-by pass`: each pass under a heading, in the order they
+/// first ran, followed by its diffs in the functions it ran on. The runs of a pass in a function
+/// are shown together.
+fn print_by_pass(
+    names: &[String],
+    functions: &[Function],
+    pipelines: &[(usize, Cow<[Pass]>)],
+    args: &Args,
+    normalize: Normalize,
+    renderer: &Renderer,
+    output: &Output,
+) -> Result<()> {
+    // The functions each pass is shown in, with the runs shown
+    let mut groups = Vec::new();
+    let mut group_of = HashMap::new();
+    for (k, (_, pipeline)) in pipelines.iter().enumerate() {
+        let shown = select_passes(
+            pipeline,
+            0,
+            args.skip_unchanged,
+            args.pass.as_deref(),
+            args.extended_regex,
+            args.demangle,
+            normalize,
+        )?;
+        for j in shown {
+            let name = demangle_text(&pipeline[j].name, args.demangle);
+            // The IR unit the pass ran on isn't part of the pass
+            let name = name.split_once(" on ").map_or(name.as_str(), |(id, _)| id);
+            let group = *group_of.entry(name.to_string()).or_insert_with(|| {
+                groups.push((name.to_string(), Vec::new()));
+                groups.len() - 1
+            });
+            let functions: &mut Vec<(usize, Vec<usize>)> = &mut groups[group].1;
+            match functions.last_mut() {
+                Some((function, runs)) if *function == k => runs.push(j),
+                _ => functions.push((k, vec![j])),
+            }
+        }
+    }
+    let mut stdout = io::stdout();
+    for (name, shown_in) in groups {
+        if output.quit() {
+            break;
+        }
+        let count = match shown_in.len() {
+            1 => "1 function".to_string(),
+            count => format!("{} functions", count),
+        };
+        match renderer {
+            // Patches are left as they are, for the tools they're piped into
+            Renderer::Patch => {}
+            Renderer::Plain(_) => cli_writeln!(stdout, "GROUP: {}, {}\n", name, count)?,
+            Renderer::Markdown => cli_writeln!(stdout, "## {} ({})\n", name, count)?,
+            Renderer::Builtin(_) | Renderer::Stat(Some(_)) => cli_writeln!(
+                stdout,
+                "{}\n",
+                cformat!("<s><u>{}</u> — {}</s>", name, count)
+            )?,
+            _ => cli_writeln!(stdout, "{} — {}\n", name, count)?,
+        }
+        let batch = shown_in
+            .into_iter()
+            .map(|(k, shown)| {
+                let (i, pipeline) = &pipelines[k];
+                (*i, Cow::Borrowed(pipeline.as_ref()), shown)
+            })
+            .collect_vec();
+        print_batch(names, functions, &batch, args, normalize, renderer, output)?;
+    }
+    Ok(())
+}
+
+/// Lists the passes of a pipeline, numbered as in the titles of their diffs, with how much they
+/// changed the function. With `names_only`, only those that changed it, each on a line of its own
+/// with the function.
+#[allow(clippy::too_many_arguments)]
+fn print_pass_list(
     func_name: &str,
     pipeline: &[Pass],
     skip_unchanged: bool,
     pass_filter: Option<&str>,
     use_regex: bool,
     should_demangle: bool,
+    normalize: Normalize,
+    min_change: Option<MinChange>,
+    sorted: bool,
+    names_only: bool,
 ) -> Result<()> {
+    let mut shown = Vec::new();
     for (i, pass) in pipeline.iter().enumerate() {
-        let demangled_name = demangle_text(&pass.name, should_demangle);
-
+        let name = demangle_text(&pass.name, should_demangle) + &run_label(pass);
         if let Some(filter) = pass_filter {
-            if !matches_pattern(&demangled_name, filter, use_regex)? {
+            if !matches_pattern(&name, filter, use_regex)? {
                 continue;
             }
         }
-
-        if skip_unchanged && pass.before == pass.after {
+        if (skip_unchanged || names_only) && !pass.ir_changed {
             continue;
         }
+        shown.push(i);
+    }
+    let mut scores = parallel_map(&shown, |&i| {
+        pass_score(&pipeline[i], should_demangle, normalize)
+    });
+    rank_by_change(pipeline, &mut shown, &mut scores, min_change, sorted);
 
-        let demangled_before = demangle_text(&pass.before, should_demangle) + "\n";
-        let demangled_after = demangle_text(&pass.after, should_demangle) + "\n";
+    let mut stdout = io::stdout();
+    if names_only {
+        for i in shown {
+            let pass = &pipeline[i];
+            cli_writeln!(
+                stdout,
+                "{}\t{}\t{}{}",
+                func_name,
+                i + 1,
+                demangle_text(&pass.name, should_demangle),
+                run_label(pass)
+            )?;
+        }
+        return Ok(());
+    }
+    cli_writeln!(stdout, "{}", func_name)?;
+    let number_width = pipeline.len().to_string().len();
+    for (i, score) in shown.into_iter().zip(scores) {
+        let pass = &pipeline[i];
+        let change = match (score, pass.deleted, pass.ir_changed) {
+            (_, true, _) => " (deleted the function)".to_string(),
+            (Some(score), _, _) => format!(" [{}]", score),
+            (None, _, true) => " (changed)".to_string(),
+            (None, _, false) => String::new(),
+        };
+        cli_writeln!(
+            stdout,
+            "  {:>w$} {}{}{}",
+            i + 1,
+            demangle_text(&pass.name, should_demangle) + &run_label(pass),
+            change,
+            time_label(pass),
+            w = number_width
+        )?;
+    }
+    Ok(())
+}
 
-        let diff = TextDiff::from_lines(&demangled_before, &demangled_after);
+/// Prints the passes of a pipeline indented under the adaptors they ran under.
+fn print_pass_tree(
+    func_name: &str,
+    pipeline: &[Pass],
+    skip_unchanged: bool,
+    pass_filter: Option<&str>,
+    use_regex: bool,
+    should_demangle: bool,
+) -> Result<()> {
+    let mut stdout = io::stdout();
+    cli_writeln!(stdout, "{}", func_name)?;
+    let mut adaptors: &[&str] = &[];
+    for pass in pipeline {
+        let name = demangle_text(&pass.name, should_demangle) + &run_label(pass);
+        if let Some(filter) = pass_filter {
+            if !matches_pattern(&name, filter, use_regex)? {
+                continue;
+            }
+        }
+        if skip_unchanged && !pass.ir_changed {
+            continue;
+        }
 
-        let title = format!("({}·{}) {}", i + 1, func_name, &pass.name);
-        let mut stdout = io::stdout();
-        cli_writeln!(stdout, "diff --git a/{} b/{}", title, title)?;
-        cli_writeln!(stdout, "--- a/{}", title)?;
-        cli_writeln!(stdout, "+++ b/{}", title)?;
-        cli_writeln!(stdout, "{}", diff.unified_diff().context_radius(10))?;
+        // Adaptors are printed once for the passes that follow each other under them
+        let common = adaptors
+            .iter()
+            .zip(&pass.adaptors)
+            .take_while(|(previous, adaptor)| previous == adaptor)
+            .count();
+        for (depth, adaptor) in pass.adaptors.iter().enumerate().skip(common) {
+            cli_writeln!(stdout, "{}{}", "  ".repeat(depth + 1), adaptor)?;
+        }
+        adaptors = &pass.adaptors;
+        let changed = if pass.ir_changed { " (changed)" } else { "" };
+        cli_writeln!(
+            stdout,
+            "{}{}{}{}",
+            "  ".repeat(pass.adaptors.len() + 1),
+            name,
+            changed,
+            time_label(pass)
+        )?;
     }
-
     Ok(())
 }
 
-fn auto_select_pager() -> Option<&'static str> {
-    if which::which("delta").is_ok() {
-        Some("delta")
-    } else if which::which("riff").is_ok() {
-        Some("riff")
-    } else if which::which("less").is_ok() {
-        Some("less -R")
-    } else {
-        None
+/// Prints what the diff below is of: the title of the pass as a file name for unified diffs, or
+/// its `header` on a line of its own for the others.
+fn print_title(
+    stdout: &mut dyn Write,
+    title: &str,
+    header: &str,
+    renderer: &Renderer,
+) -> Result<()> {
+    match renderer {
+        Renderer::Builtin(layout) if layout.engine == Engine::Unified => {
+            cli_writeln!(stdout, "diff --git a/{} b/{}", title, title)?;
+            cli_writeln!(stdout, "--- a/{}", title)?;
+            cli_writeln!(stdout, "+++ b/{}", title)?;
+        }
+        Renderer::Builtin(_) => cli_writeln!(stdout, "{}", cformat!("<s>{}</s>", header))?,
+        Renderer::Plain(_) => cli_writeln!(stdout, "{}", header)?,
+        Renderer::Tool(tool) if !tool.titled => cli_writeln!(stdout, "{}", header)?,
+        Renderer::Tool(_) => {}
+        Renderer::Ops => cli_writeln!(stdout, "{}", title)?,
+        Renderer::Stat(_) => {}
+        Renderer::Html(_)
+        | Renderer::Json(_)
+        | Renderer::Patch
+        | Renderer::Markdown
+        | Renderer::Csv(_)
+        | Renderer::GhSummary(_)
+        | Renderer::Sqlite(_)
+        | Renderer::Dot(_)
+        | Renderer::Trace(_) => {}
     }
+    Ok(())
 }
 
-#[cfg(unix)]
-fn enter_pager(pager: Option<&str>) {
-    if io::stdout().is_terminal() {
-        let pager = match pager {
-            None => auto_select_pager(),
-            Some(pager) if pager.trim().is_empty() => None,
-            Some(pager) => Some(pager),
-        };
-        if let Some(pager) = pager {
-            Pager::with_default_pager(pager).setup();
-        }
+/// Maps the items on as many threads as there are cores, the results in the order of the items.
+fn parallel_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let threads = thread::available_parallelism()
+        .map_or(1, |threads| threads.get())
+        .min(items.len());
+    if threads <= 1 {
+        return items.iter().map(f).collect();
     }
+    // Items are handed out one at a time, their cost varies a lot
+    let next = AtomicUsize::new(0);
+    let mut results = thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(i) else {
+                            return results;
+                        };
+                        results.push((i, f(item)));
+                    }
+                })
+            })
+            .collect_vec();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect_vec()
+    });
+    results.sort_unstable_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
 }
 
-#[cfg(not(unix))]
-fn enter_pager(_pager: Option<&str>) {}
+/// The file the snapshots of pass number `i` are written to in a batch, in pipeline order.
+fn batch_file_name(i: usize, pass: &Pass) -> String {
+    let name = file_name_part(&(pass.name.clone() + &run_label(pass)));
+    format!("{:03}-{}.{}", i + 1, name, snapshot_extension(pass))
+}
 
-fn list_functions(dump: &str, should_demangle: bool) -> HashSet<String> {
-    let mut functions = HashSet::new();
-    let haystack = dump.as_bytes();
-    {
-        let it = memmem::find_iter(haystack, b"define ");
-        for start in it {
-            let start = start + memchr::memchr(b'@', &haystack[start..]).unwrap() + 1;
-            let end = memchr::memchr(b'(', &haystack[start..]).unwrap();
-            let name = &dump[start..start + end];
-            let demangled = if should_demangle {
-                demangle_text(name, true)
-            } else {
-                name.to_string()
-            };
-            functions.insert(demangled);
-        }
+/// Finds the pass given by number (from 1) or name at or after pass number `first`.
+fn find_pass(
+    pipeline: &[Pass],
+    first: usize,
+    pass: &str,
+    use_regex: bool,
+    should_demangle: bool,
+) -> Result<Option<usize>> {
+    if let Ok(number) = pass.parse::<usize>() {
+        return Ok((number > first && number <= pipeline.len()).then(|| number - 1));
     }
-    {
-        let it = memmem::find_iter(haystack, b"# Machine code for function ");
-        for start in it {
-            let start = start + b"# Machine code for function ".len();
-            let end = memchr::memchr(b':', &haystack[start..]).unwrap();
-            let name = &dump[start..start + end];
-            let demangled = if should_demangle {
-                demangle_text(name, true)
-            } else {
-                name.to_string()
-            };
-            functions.insert(demangled);
+    for (i, candidate) in pipeline.iter().enumerate().skip(first) {
+        let name = demangle_text(&candidate.name, should_demangle) + &run_label(candidate);
+        if matches_pattern(&name, pass, use_regex)? {
+            return Ok(Some(i));
         }
     }
-    functions
+    Ok(None)
 }
 
-fn main() -> Result<()> {
-    color_eyre::install()?;
-
-    let args = Args::parse();
-    let dump = read_input(&args).wrap_err_with(|| match &args.input {
-        None => "Failed to read from stdin".to_string(),
-        Some(path) => format!("Failed to read from file: {}", path.display()),
-    })?;
-
-    if !dump.contains("IR Dump Before") {
-        return Err(eyre!("Did you forget to add `-mllvm -print-before-all`?"));
+/// The pipeline of a function as shown, its nested passes collapsed with `--depth`, and merged
+/// into one pass with `--net`, `--from` and `--to`. None if the range isn't found in it.
+fn shape_pipeline<'a>(pipeline: &'a [Pass], args: &Args) -> Result<Option<Cow<'a, [Pass]>>> {
+    let pipeline = match args.depth {
+        Some(depth) => Cow::Owned(optpipeline::collapse_nested_passes(pipeline, depth)),
+        None => Cow::Borrowed(pipeline),
+    };
+    if !args.view.net && args.view.from.is_none() && args.view.to.is_none() {
+        return Ok(Some(pipeline));
     }
+    let found = pass_range(
+        &pipeline,
+        args.view.from.as_deref(),
+        args.view.to.as_deref(),
+        args.extended_regex,
+        args.demangle,
+    )?;
+    Ok(found.map(|found| Cow::Owned(vec![found])))
+}
 
-    if !dump.contains("IR Dump After") {
-        return Err(eyre!("Did you forget to add `-mllvm -print-after-all`?"));
-    }
+/// A pass standing for the passes of the pipeline from `--from` to `--to`, none if the pipeline
+/// doesn't run them.
+fn pass_range(
+    pipeline: &[Pass],
+    from: Option<&str>,
+    to: Option<&str>,
+    use_regex: bool,
+    should_demangle: bool,
+) -> Result<Option<Pass>> {
+    let first = match from {
+        Some(from) => find_pass(pipeline, 0, from, use_regex, should_demangle)?,
+        None => (!pipeline.is_empty()).then_some(0),
+    };
+    let Some(first) = first else {
+        return Ok(None);
+    };
+    let last = match to {
+        Some(to) => find_pass(pipeline, first, to, use_regex, should_demangle)?,
+        None => Some(pipeline.len() - 1),
+    };
+    let Some(last) = last else {
+        return Ok(None);
+    };
+    let (start, end) = (&pipeline[first], &pipeline[last]);
+    let range = &pipeline[first..=last];
+    Ok(Some(Pass {
+        name: format!(
+            "{}{} to {}{} ({} passes)",
+            start.name,
+            run_label(start),
+            end.name,
+            run_label(end),
+            range.len()
+        ),
+        after: end.after.clone(),
+        ir_changed: start.before != end.after,
+        invalidated: end.invalidated,
+        deleted: end.deleted,
+        after_unknown: end.after_unknown,
+        renamed_to: range.iter().rev().find_map(|pass| pass.renamed_to.clone()),
+        run: 1,
+        runs: 1,
+        time: None,
+        remarks: range.iter().flat_map(|pass| pass.remarks.clone()).collect(),
+        skipped_before: Vec::new(),
+        skipped_after: Vec::new(),
+        ..start.clone()
+    }))
+}
 
-    if args.list {
-        for func in list_functions(&dump, args.demangle).into_iter().sorted() {
-            cli_writeln!(io::stdout(), "{func}")?;
+/// The diff of a pass as rendered by the built-in engines, none for passes without snapshots to
+/// compare. With `by_block`, the blocks that changed are diffed one by one, under their label.
+fn render_diff(
+    pass: &Pass,
+    should_demangle: bool,
+    normalize: Normalize,
+    layout: &Layout,
+) -> Option<String> {
+    if pass.deleted || pass.invalidated {
+        return None;
+    }
+    let (demangled_before, demangled_after) = snapshots(pass, should_demangle, normalize);
+    let profile = Profile::of(pass.machine);
+    let (context, theme) = (layout.context, &layout.theme);
+    let render = |before: &str, after: &str| match layout.engine {
+        Engine::SideBySide => {
+            side_by_side::render(before, after, layout.columns, profile, context, theme)
         }
-        return Ok(());
+        Engine::Inline => side_by_side::render_inline(before, after, profile, context, theme),
+        _ => {
+            let diff = TextDiff::from_lines(before, after);
+            let moves = code_motion::moves(&diff);
+            diff.unified_diff().context_radius(context).to_string() + &code_motion::notes(&moves)
+        }
+    };
+    if !layout.by_block {
+        return Some(render(&demangled_before, &demangled_after));
     }
+    let mut rendered = String::new();
+    for segment in block_matching::changed_segments(&demangled_before, &demangled_after) {
+        rendered += &format!("# bb: {}\n", segment.name);
+        rendered += &render(&segment.before, &segment.after);
+    }
+    Some(rendered)
+}
 
-    let (prefix, result) = optpipeline::process(&dump, true).wrap_err("Parsing error")?;
-    cli_write!(io::stderr(), "{}", prefix)?;
+/// The diff of a pass as a patch for `--format unified`, the snapshots named after the function
+/// and the pass. Passes that didn't change the function have none.
+fn render_patch(
+    func_name: &str,
+    i: usize,
+    pass: &Pass,
+    should_demangle: bool,
+    normalize: Normalize,
+) -> Option<String> {
+    if pass.deleted || pass.invalidated {
+        return None;
+    }
+    let (before, after) = snapshots(pass, should_demangle, normalize);
+    if before == after {
+        return None;
+    }
+    let file = format!("{}/{}", file_name_part(func_name), batch_file_name(i, pass));
+    let diff = TextDiff::from_lines(&before, &after);
+    let patch = diff
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", file), &format!("b/{}", file))
+        .to_string();
+    Some(patch)
+}
 
-    if let Some(expected) = args.function {
-        let (func_name, pipeline) = if args.extended_regex {
-            let regex = Regex::new(&expected)
-                .wrap_err_with(|| format!("Invalid regex pattern: {}", expected))?;
-            result
-                .iter()
-                .map(|(func_name, pipeline)| (demangle_text(func_name, args.demangle), pipeline))
-                .find(|(func_name,_)| regex.is_match(func_name))
-                .ok_or_else(|| {
-                    eyre!(
-                        "No function matching regex '{}' was found in the input, use option `--list/-l` to find out all available functions",
-                        expected
-                    )
-                })?
-        } else {
-            result
-                .iter()
-                .map(|(func_name, pipeline)| (demangle_text(func_name, args.demangle), pipeline))
-                .find(|(func_name,_)| func_name == &expected)
-                .ok_or_else(|| eyre!("Function '{}' was not found in the input, use option `--list/-l` to find out all available functions", expected))?
-        };
+/// The diff of a pass for `--format plain`: the position of each hunk, then its lines behind
+/// `+`, `-` or blanks.
+fn render_plain(
+    pass: &Pass,
+    should_demangle: bool,
+    normalize: Normalize,
+    context: usize,
+) -> Option<String> {
+    if pass.deleted || pass.invalidated {
+        return None;
+    }
+    let (before, after) = snapshots(pass, should_demangle, normalize);
+    let diff = TextDiff::from_lines(&before, &after);
+    let lines = |range: std::ops::Range<usize>| match range.len() {
+        0 => format!("no lines after line {}", range.start),
+        1 => format!("line {}", range.end),
+        _ => format!("lines {}-{}", range.start + 1, range.end),
+    };
+    let mut rendered = String::new();
+    for group in diff.grouped_ops(context) {
+        let (first, last) = (&group[0], &group[group.len() - 1]);
+        rendered += &format!(
+            "HUNK: {} before, {} after\n",
+            lines(first.old_range().start..last.old_range().end),
+            lines(first.new_range().start..last.new_range().end)
+        );
+        for op in &group {
+            for change in diff.iter_changes(op) {
+                let marker = match change.tag() {
+                    ChangeTag::Delete => '-',
+                    ChangeTag::Insert => '+',
+                    ChangeTag::Equal => ' ',
+                };
+                let line = format!("{} {}", marker, change.value().trim_end_matches('\n'));
+                // No blanks trailing the empty lines, for logs that would flag them
+                rendered += line.trim_end_matches(' ');
+                rendered += "\n";
+            }
+        }
+    }
+    Some(rendered)
+}
+
+/// The instructions the pass added and removed by opcode, for `--stat ops`.
+fn render_ops(pass: &Pass, should_demangle: bool, normalize: Normalize) -> Option<String> {
+    if pass.deleted || pass.invalidated {
+        return None;
+    }
+    let (before, after) = snapshots(pass, should_demangle, normalize);
+    let histogram = opcodes::histogram(&before, &after);
+    Some(match histogram.is_empty() {
+        true => String::new(),
+        false => format!("# {}\n", opcodes::summary(&histogram)),
+    })
+}
+
+/// The passes of a pipeline to show, from pass number `first` on, as picked by `--pass` and
+/// `--skip-unchanged`.
+fn select_passes(
+    pipeline: &[Pass],
+    first: usize,
+    skip_unchanged: bool,
+    pass_filter: Option<&str>,
+    use_regex: bool,
+    should_demangle: bool,
+    normalize: Normalize,
+) -> Result<Vec<usize>> {
+    let mut shown = Vec::new();
+    for (i, pass) in pipeline.iter().enumerate().skip(first) {
+        let demangled_name = demangle_text(&pass.name, should_demangle) + &run_label(pass);
 
-        enter_pager(args.pager.as_deref());
+        if let Some(filter) = pass_filter {
+            if !matches_pattern(&demangled_name, filter, use_regex)? {
+                continue;
+            }
+        }
+
+        if skip_unchanged && !pass.ir_changed {
+            continue;
+        }
+        // Passes whose changes are all normalized away are left out as well
+        if skip_unchanged && normalize.is_any() && pass.ir_changed && !pass.deleted {
+            let (before, after) = snapshots(pass, false, normalize);
+            if before == after {
+                continue;
+            }
+        }
+        shown.push(i);
+    }
+    Ok(shown)
+}
+
+/// How many passes are diffed together before they're printed: enough to keep the cores busy,
+/// few enough for the first diffs to show up early.
+const BATCH_PASSES: usize = 1024;
+
+/// A function to print: its index in `names`, its pipeline and the passes shown of it.
+type ShownFunction<'a> = (usize, Cow<'a, [Pass]>, Vec<usize>);
+
+/// Prints the diffs of a batch of functions.
+fn print_batch(
+    names: &[String],
+    functions: &[Function],
+    batch: &[ShownFunction],
+    args: &Args,
+    normalize: Normalize,
+    renderer: &Renderer,
+    output: &Output,
+) -> Result<()> {
+    if output.quit() {
+        return Ok(());
+    }
+    let passes = batch
+        .iter()
+        .map(|(i, pipeline, shown)| (names[*i].as_str(), pipeline.as_ref(), shown.clone()))
+        .collect_vec();
+    let ranked = rank_passes(
+        &passes,
+        args.demangle,
+        normalize,
+        args.min_change,
+        args.sort_by_change,
+        renderer,
+    );
+    for ((i, pipeline, _), ranked) in batch.iter().zip(ranked) {
+        if output.quit() {
+            break;
+        }
         print_func(
-            &func_name,
+            &names[*i],
             pipeline,
-            args.skip_unchanged,
-            args.pass.as_deref(),
-            args.extended_regex,
+            0,
+            ranked,
+            functions[*i]
+                .coroutine
+                .as_deref()
+                .zip(functions[*i].created_by),
             args.demangle,
+            normalize,
+            args.describe,
+            renderer,
+            output,
         )?;
+    }
+    Ok(())
+}
+
+/// The passes of a function to print, in the order they're printed, with how much they changed
+/// the function and their diffs as rendered (none for renderers that print the passes otherwise).
+struct RankedPasses {
+    shown: Vec<usize>,
+    scores: Vec<Option<ChangeScore>>,
+    diffs: Vec<Option<String>>,
+}
+
+/// Renders the diff of a pass, given with the function it ran on and its number.
+type RenderPass<'a> = dyn Fn(&str, usize, &Pass, Option<ChangeScore>) -> Option<String> + Sync + 'a;
+
+/// Scores, ranks and renders the passes `shown` of each function, given with its name and its
+/// pipeline. Diffing is the bulk of the work, it's spread over the passes of all the functions,
+/// so that a function with a long pipeline doesn't leave the other cores idle. External tools run
+/// one at a time when printing, they share the snapshot files.
+fn rank_passes(
+    functions: &[(&str, &[Pass], Vec<usize>)],
+    should_demangle: bool,
+    normalize: Normalize,
+    min_change: Option<MinChange>,
+    sorted: bool,
+    renderer: &Renderer,
+) -> Vec<RankedPasses> {
+    let passes = functions
+        .iter()
+        .enumerate()
+        .flat_map(|(k, (_, _, shown))| shown.iter().map(move |&i| (k, i)))
+        .collect_vec();
+    let mut scores = parallel_map(&passes, |&(k, i)| {
+        pass_score(&functions[k].1[i], should_demangle, normalize)
+    })
+    .into_iter();
+    let mut ranked = functions
+        .iter()
+        .map(|(_, pipeline, shown)| {
+            let mut shown = shown.clone();
+            let mut scores = scores.by_ref().take(shown.len()).collect_vec();
+            rank_by_change(pipeline, &mut shown, &mut scores, min_change, sorted);
+            RankedPasses {
+                shown,
+                scores,
+                diffs: Vec::new(),
+            }
+        })
+        .collect_vec();
+
+    let render: Box<RenderPass<'_>> = match renderer {
+        Renderer::Tool(_)
+        | Renderer::Stat(_)
+        | Renderer::Dot(_)
+        | Renderer::Trace(_)
+        | Renderer::Sqlite(_) => {
+            return ranked;
+        }
+        Renderer::Ops => {
+            Box::new(move |_, _, pass, _| render_ops(pass, should_demangle, normalize))
+        }
+        Renderer::Markdown | Renderer::GhSummary(_) => Box::new(move |_, _, pass, _| {
+            if pass.deleted || pass.invalidated {
+                return None;
+            }
+            let (before, after) = snapshots(pass, should_demangle, normalize);
+            Some(markdown::diff_block(&before, &after))
+        }),
+        Renderer::Patch => Box::new(move |func_name, i, pass, _| {
+            render_patch(func_name, i, pass, should_demangle, normalize)
+        }),
+        Renderer::Csv(_) => Box::new(move |func_name, i, pass, _| {
+            let snapshots = (!pass.deleted && !pass.invalidated)
+                .then(|| snapshots(pass, should_demangle, normalize));
+            let snapshots = snapshots
+                .as_ref()
+                .map(|(before, after)| (before.as_str(), after.as_str()));
+            Some(csv_output::row(func_name, i + 1, pass, snapshots))
+        }),
+        Renderer::Json(_) => Box::new(move |_, i, pass, score| {
+            let snapshots = (!pass.deleted && !pass.invalidated)
+                .then(|| snapshots(pass, should_demangle, normalize));
+            let snapshots = snapshots
+                .as_ref()
+                .map(|(before, after)| (before.as_str(), after.as_str()));
+            Some(json_output::pass(i + 1, &pass.name, pass, snapshots, score))
+        }),
+        Renderer::Html(_) => Box::new(move |_, _, pass, _| {
+            if pass.deleted || pass.invalidated {
+                return None;
+            }
+            let (before, after) = snapshots(pass, should_demangle, normalize);
+            Some(html_report::diff_table(&before, &after))
+        }),
+        Renderer::Builtin(layout) => {
+            Box::new(move |_, _, pass, _| render_diff(pass, should_demangle, normalize, layout))
+        }
+        Renderer::Plain(context) => {
+            let context = *context;
+            Box::new(move |_, _, pass, _| render_plain(pass, should_demangle, normalize, context))
+        }
+    };
+    let passes = ranked
+        .iter()
+        .enumerate()
+        .flat_map(|(k, ranked)| {
+            ranked
+                .shown
+                .iter()
+                .zip(&ranked.scores)
+                .map(move |(&i, &score)| (k, i, score))
+        })
+        .collect_vec();
+    let mut diffs = parallel_map(&passes, |&(k, i, score)| {
+        let (func_name, pipeline, _) = &functions[k];
+        render(func_name, i, &pipeline[i], score)
+    })
+    .into_iter();
+    for ranked in &mut ranked {
+        ranked.diffs = diffs.by_ref().take(ranked.shown.len()).collect();
+    }
+    ranked
+}
+
+/// Prints the diffs of the passes of a pipeline as ranked by [`rank_passes`], the pipeline starts
+/// at pass number `first`.
+#[allow(clippy::too_many_arguments)]
+fn print_func(
+    func_name: &str,
+    pipeline: &[Pass],
+    first: usize,
+    ranked: RankedPasses,
+    split_from: Option<(&str, &str)>,
+    should_demangle: bool,
+    normalize: Normalize,
+    describe: bool,
+    renderer: &Renderer,
+    output: &Output,
+) -> Result<()> {
+    output.start_function(func_name)?;
+    let RankedPasses {
+        shown,
+        scores,
+        diffs,
+    } = ranked;
+    if let Renderer::Dot(graph) = renderer {
+        // The passes are linked in the order they ran, whatever order they're ranked in
+        let passes = shown
+            .iter()
+            .zip(&scores)
+            .sorted_by_key(|(&i, _)| i)
+            .map(|(&i, score)| {
+                let pass = &pipeline[i];
+                let label = format!(
+                    "{}. {}{}\n{}",
+                    i + 1,
+                    demangle_text(&pass.name, should_demangle),
+                    run_label(pass),
+                    score_label(*score).trim_start()
+                );
+                (i, pass, label.trim_end().to_string())
+            })
+            .collect_vec();
+        graph.borrow_mut().add_function(func_name, &passes);
+        return Ok(());
+    }
+    if let Renderer::Trace(trace) = renderer {
+        let passes = shown
+            .iter()
+            .sorted()
+            .map(|&i| (i, &pipeline[i]))
+            .collect_vec();
+        trace.borrow_mut().add_function(func_name, &passes);
+        return Ok(());
+    }
+    if let Renderer::Sqlite(database) = renderer {
+        let passes = shown
+            .iter()
+            .zip(&scores)
+            .map(|(&i, &score)| {
+                let pass = &pipeline[i];
+                sqlite_export::PassRow {
+                    number: i + 1,
+                    pass,
+                    snapshots: (!pass.deleted && !pass.invalidated)
+                        .then(|| snapshots(pass, should_demangle, normalize)),
+                    score: score.unwrap_or_default(),
+                }
+            })
+            .collect_vec();
+        database.borrow_mut().add_function(func_name, &passes)?;
+        return Ok(());
+    }
+    if let Renderer::Stat(theme) = renderer {
+        let rows = shown
+            .iter()
+            .zip(&scores)
+            .map(|(&i, &score)| {
+                let pass = &pipeline[i];
+                diff_stat::Row {
+                    name: format!("({}·{}) {}{}", i + 1, func_name, pass.name, run_label(pass)),
+                    score,
+                    placeholder: if pass.deleted {
+                        Some("deleted the function")
+                    } else if pass.invalidated {
+                        Some("invalidated the IR")
+                    } else {
+                        None
+                    },
+                }
+            })
+            .collect_vec();
+        let mut stdout = output.writer(func_name, None)?;
+        cli_write!(
+            stdout,
+            "{}",
+            diff_stat::render(func_name, &rows, theme.as_ref())
+        )?;
+        return Ok(());
+    }
+    // The files of a batch are named after the passes, the tool shows them as the titles
+    if let Renderer::Tool(tool) = renderer {
+        if tool.batch {
+            let passes = shown
+                .iter()
+                .map(|&i| (i, &pipeline[i]))
+                .filter(|(_, pass)| !pass.deleted && !pass.invalidated)
+                .map(|(i, pass)| {
+                    let (before, after) = snapshots(pass, should_demangle, normalize);
+                    (batch_file_name(i, pass), before, after)
+                })
+                .collect_vec();
+            if !tool.titled {
+                cli_writeln!(io::stdout(), "{}", func_name)?;
+            }
+            tool.run_batch(func_name, &passes)?;
+            return Ok(());
+        }
+    }
+    let summary_rows = match renderer {
+        Renderer::GhSummary(_) => shown
+            .iter()
+            .zip(&scores)
+            .filter_map(|(&i, &score)| gh_summary::row(i + 1, &pipeline[i], score))
+            .collect_vec(),
+        _ => Vec::new(),
+    };
+    let mut diffs = diffs.into_iter();
+    let mut sections = Vec::new();
+    let mut rows = Vec::new();
+
+    for (&i, score) in shown.iter().zip(scores) {
+        if output.quit() {
+            break;
+        }
+        let diff = diffs.next().flatten();
+        match renderer {
+            Renderer::Json(_) | Renderer::Csv(_) => {
+                rows.extend(diff);
+                continue;
+            }
+            Renderer::Patch => {
+                if let Some(patch) = diff {
+                    let mut stdout = output.writer(func_name, Some((i, &pipeline[i])))?;
+                    cli_write!(stdout, "{}", patch)?;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        let pass = &pipeline[i];
+        let run = run_label(pass);
+        let renamed = match &pass.renamed_to {
+            Some(new_name) => format!(" (renamed to {})", demangle_text(new_name, should_demangle)),
+            None => String::new(),
+        };
+        let title = format!(
+            "({}·{}) {}{}{}{}{}",
+            i + 1,
+            func_name,
+            &pass.name,
+            run,
+            renamed,
+            time_label(pass),
+            score_label(score)
+        );
+        let header = match renderer {
+            Renderer::Plain(_) => plain_header(i, pipeline.len(), func_name, pass, &renamed, score),
+            Renderer::Builtin(layout) => {
+                let name = layout.links.link(&pass.name);
+                pass_header(i, pipeline.len(), &name, func_name, pass, &renamed, score)
+            }
+            _ => pass_header(
+                i,
+                pipeline.len(),
+                &pass.name,
+                func_name,
+                pass,
+                &renamed,
+                score,
+            ),
+        };
+        // Notes are comments of the diffs, or marked as such in plain ones
+        let marker = match renderer {
+            Renderer::Plain(_) => "NOTE:",
+            _ => "#",
+        };
+        let description = describe
+            .then(|| pass_descriptions::describe(&pass.name))
+            .flatten();
+        let placeholder = if pass.deleted {
+            Some("The function was deleted by this pass")
+        } else if pass.invalidated {
+            Some("The pass invalidated the IR it ran on, there is no snapshot to compare")
+        } else {
+            None
+        };
+        if let (
+            Some(placeholder),
+            Renderer::Html(_) | Renderer::Markdown | Renderer::GhSummary(_),
+        ) = (placeholder, renderer)
+        {
+            sections.push(html_report::PassSection {
+                title,
+                changed: pass.ir_changed,
+                notes: description
+                    .into_iter()
+                    .chain([placeholder.to_string()])
+                    .chain(pass.remarks.iter().cloned())
+                    .collect(),
+                diff: None,
+            });
+            continue;
+        }
+        if let Some(placeholder) = placeholder {
+            let mut stdout = output.writer(func_name, Some((i, pass)))?;
+            print_title(&mut stdout, &title, &header, renderer)?;
+            if let Some(description) = &description {
+                cli_writeln!(stdout, "{} {}", marker, description)?;
+            }
+            cli_writeln!(stdout, "{} {}", marker, placeholder)?;
+            for remark in &pass.remarks {
+                cli_writeln!(stdout, "{} {}", marker, remark)?;
+            }
+            cli_writeln!(stdout, "")?;
+            continue;
+        }
+
+        // Passes dumped on one side only are compared with the closest snapshot on the other
+        let note = if let (0, Some((coroutine, pass))) = (i, split_from) {
+            Some(format!(
+                "Split from the coroutine {} by {}",
+                coroutine, pass
+            ))
+        } else if pass.before_unknown && pass.before.is_empty() {
+            Some("No snapshot was dumped before this pass".to_string())
+        } else if pass.before_unknown {
+            Some("No snapshot was dumped right before this pass, compared with the closest earlier one".to_string())
+        } else if pass.after_unknown {
+            Some(
+                "No snapshot was dumped right after this pass, compared with the closest later one"
+                    .to_string(),
+            )
+        } else {
+            None
+        };
+
+        if let Renderer::Html(_) | Renderer::Markdown | Renderer::GhSummary(_) = renderer {
+            let mut notes = description.into_iter().chain(note).collect_vec();
+            if !pass.skipped_before.is_empty() {
+                notes.push(format!(
+                    "Passes of the reference pipeline that weren't dumped before this one: {}",
+                    pass.skipped_before.join(", ")
+                ));
+            }
+            notes.extend(pass.remarks.iter().cloned());
+            if !pass.skipped_after.is_empty() {
+                notes.push(format!(
+                    "Passes of the reference pipeline that weren't dumped after this one: {}",
+                    pass.skipped_after.join(", ")
+                ));
+            }
+            sections.push(html_report::PassSection {
+                title,
+                changed: pass.ir_changed,
+                notes,
+                diff,
+            });
+            continue;
+        }
+
+        let mut stdout = output.writer(func_name, Some((i, pass)))?;
+        print_title(&mut stdout, &title, &header, renderer)?;
+        if let Some(description) = description {
+            cli_writeln!(stdout, "{} {}", marker, description)?;
+        }
+        if let Some(note) = note {
+            cli_writeln!(stdout, "{} {}", marker, note)?;
+        }
+        if !pass.skipped_before.is_empty() {
+            cli_writeln!(
+                stdout,
+                "{} Passes of the reference pipeline that weren't dumped before this one: {}",
+                marker,
+                pass.skipped_before.join(", ")
+            )?;
+        }
+        match (renderer, diff) {
+            (Renderer::Tool(tool), _) => {
+                let (demangled_before, demangled_after) =
+                    snapshots(pass, should_demangle, normalize);
+                tool.run(
+                    &title,
+                    &demangled_before,
+                    &demangled_after,
+                    snapshot_extension(pass),
+                )?;
+            }
+            (_, Some(diff)) => cli_writeln!(stdout, "{}", diff)?,
+            (_, None) => {}
+        }
+        if !pass.remarks.is_empty() {
+            for remark in &pass.remarks {
+                cli_writeln!(stdout, "{} {}", marker, remark)?;
+            }
+            cli_writeln!(stdout, "")?;
+        }
+        if !pass.skipped_after.is_empty() {
+            cli_writeln!(
+                stdout,
+                "{} Passes of the reference pipeline that weren't dumped after this one: {}\n",
+                marker,
+                pass.skipped_after.join(", ")
+            )?;
+        }
+    }
+    match renderer {
+        Renderer::Html(report) => report.borrow_mut().add_function(
+            func_name,
+            sections,
+            instruction_chart(pipeline, first, should_demangle),
+        ),
+        Renderer::Json(document) => document.borrow_mut().add_function(func_name, rows),
+        Renderer::Csv(table) => table.borrow_mut().add_rows(rows),
+        Renderer::Markdown => {
+            let mut stdout = output.writer(func_name, None)?;
+            cli_write!(stdout, "{}", markdown::function(func_name, &sections))?
+        }
+        Renderer::GhSummary(summary) => {
+            summary
+                .borrow_mut()
+                .add_function(func_name, summary_rows, sections)
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn auto_select_pager() -> Option<&'static str> {
+    if which::which("delta").is_ok() {
+        Some("delta")
+    } else if which::which("riff").is_ok() {
+        Some("riff")
+    } else if which::which("less").is_ok() {
+        Some("less -R")
+    } else if cfg!(not(unix)) {
+        Some(builtin_pager::NAME)
+    } else {
+        None
+    }
+}
+
+/// The pager of `--pager` or `OPTDIFF_PAGER`, an empty one for `--no-pager`.
+fn pager_arg(args: &Args) -> Option<&str> {
+    match args.no_pager {
+        true => Some(""),
+        false => args.pager.as_deref(),
+    }
+}
+
+/// The pager asked for, else the one of `PAGER` or the one found if none was, an empty one for
+/// none at all.
+fn select_pager(pager: Option<&str>) -> Option<String> {
+    let pager = match pager {
+        Some(pager) => Some(pager.to_string()),
+        None => env::var("PAGER").ok(),
+    };
+    match pager {
+        None => auto_select_pager().map(str::to_string),
+        Some(pager) if pager.trim().is_empty() => None,
+        Some(pager) => Some(pager),
+    }
+}
+
+#[cfg(unix)]
+fn enter_pager(pager: Option<&str>) {
+    if io::stdout().is_terminal() && !builtin_pager::is_selected(select_pager(pager).as_deref()) {
+        if let Some(pager) = select_pager(pager) {
+            // less shows the colours with `-R`, which the `PAGER` of many users doesn't have
+            let envs = env::var_os("LESS").is_none().then_some("LESS=R");
+            Pager::with_pager(&pager).pager_envs(envs).setup();
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn enter_pager(_pager: Option<&str>) {}
+
+/// Unified diffs are left to delta or riff to highlight, on a terminal without them the diffs
+/// are rendered side by side instead.
+fn select_renderer(args: &Args, pager: Option<&str>) -> Result<Renderer> {
+    if let Some(path) = &args.export.sqlite {
+        let database = sqlite_export::Database::open(path, &input_names(args))?;
+        return Ok(Renderer::Sqlite(RefCell::new(database)));
+    }
+    if args.export.dot {
+        return Ok(Renderer::Dot(RefCell::default()));
+    }
+    if args.export.trace.is_some() {
+        return Ok(Renderer::Trace(RefCell::default()));
+    }
+    match args.stat {
+        Some(Stat::Ops) => return Ok(Renderer::Ops),
+        Some(Stat::Lines) => {
+            let colored = match args.color {
+                When::Auto => builtin_pager::to_terminal() && env::var_os("NO_COLOR").is_none(),
+                When::Always => true,
+                When::Never => false,
+            };
+            let theme = colored.then(|| theme::load(args.theme)).transpose()?;
+            return Ok(Renderer::Stat(theme));
+        }
+        None => {}
+    }
+    match args.view.format {
+        Format::Html => return Ok(Renderer::Html(RefCell::default())),
+        Format::Json => return Ok(Renderer::Json(RefCell::default())),
+        Format::Unified => return Ok(Renderer::Patch),
+        Format::Markdown => return Ok(Renderer::Markdown),
+        Format::Csv => return Ok(Renderer::Csv(RefCell::default())),
+        Format::GhSummary => return Ok(Renderer::GhSummary(RefCell::default())),
+        Format::Plain => return Ok(Renderer::Plain(context_lines(args))),
+        Format::Text => {}
+    }
+    let command = match (&args.diff_tool, &args.difft_args) {
+        (Some(command), _) => Some(command.clone()),
+        (None, Some(difft_args)) => Some(format!("difft {}", difft_args)),
+        (None, None) => None,
+    };
+    if let Some(command) = command {
+        let tool = DiffTool::new(&command, args.diff_tool_batch)
+            .ok_or_else(|| eyre!("Option `--diff-tool` is empty"))?;
+        return Ok(Renderer::Tool(tool));
+    }
+    let engine = match args.engine {
+        // Files get plain unified diffs, colours are for terminals
+        Engine::Auto if args.view.output.as_deref().is_some_and(output_dir::is_dir) => {
+            Engine::Unified
+        }
+        Engine::Auto => default_engine(pager, side_by_side_columns(args).width),
+        engine => engine,
+    };
+    Ok(Renderer::Builtin(Layout {
+        engine,
+        by_block: args.by_block,
+        context: context_lines(args),
+        columns: side_by_side_columns(args),
+        theme: theme::load(args.theme)?,
+        links: Links::load(args.hyperlinks, builtin_pager::to_terminal())?,
+    }))
+}
+
+fn side_by_side_columns(args: &Args) -> side_by_side::Columns {
+    side_by_side::Columns {
+        width: args.width.unwrap_or_else(side_by_side::terminal_width),
+        wrap: args.wrap,
+    }
+}
+
+/// The unchanged lines shown around the changes. The whole snapshot is as many as the diffs can
+/// take without overflowing when they add up the context on both sides.
+fn context_lines(args: &Args) -> usize {
+    match args.full_context {
+        true => usize::MAX / 4,
+        false => args.context,
+    }
+}
+
+fn default_engine(pager: Option<&str>, width: usize) -> Engine {
+    let highlighted = cfg!(unix)
+        && select_pager(pager).is_some_and(|pager| {
+            let program = pager.split_whitespace().next().unwrap_or_default();
+            let program = program.rsplit(['/', '\\']).next().unwrap_or(program);
+            program == "delta" || program == "riff"
+        });
+    if !builtin_pager::to_terminal() || highlighted {
+        Engine::Unified
+    } else if width < side_by_side::MIN_WIDTH {
+        Engine::Inline
     } else {
-        enter_pager(args.pager.as_deref());
-        for (func, pipeline) in result.iter().sorted_by_key(|(func, _)| *func) {
+        Engine::SideBySide
+    }
+}
+
+/// The dumps read, as named in reports.
+fn input_names(args: &Args) -> Vec<String> {
+    match args.input.is_empty() {
+        true => vec!["stdin".to_string()],
+        false => args
+            .input
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect(),
+    }
+}
+
+/// A function as listed and printed, its name already demangled.
+struct Function<'a> {
+    module: &'a str,
+    name: String,
+    /// CoroSplit creates `foo.resume`, `foo.destroy` and `foo.cleanup` out of the coroutine
+    /// `foo`, they are shown right after it
+    coroutine: Option<String>,
+    /// The pass that created the function, if known
+    created_by: Option<&'a str>,
+}
+
+/// Returns the coroutine `name` was split from, with the suffixes of the switch lowering
+/// (`.resume`) and the others (`.resume.0`).
+fn coroutine_of(name: &str) -> Option<&str> {
+    [".resume", ".destroy", ".cleanup"]
+        .iter()
+        .find_map(|suffix| {
+            let (coroutine, rest) = name.rsplit_once(suffix)?;
+            let numbered = rest
+                .strip_prefix('.')
+                .is_some_and(|number| number.parse::<u32>().is_ok());
+            (rest.is_empty() || numbered).then_some(coroutine)
+        })
+        .filter(|coroutine| !coroutine.is_empty())
+}
+
+/// Sorts the functions of a module by name, the ones split from a coroutine right after it.
+/// Returns the coroutine of each function along with it.
+fn sort_functions<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<(&'a str, Option<&'a str>)> {
+    let names: HashSet<&str> = names.into_iter().collect();
+    names
+        .iter()
+        .map(|name| {
+            let coroutine = coroutine_of(name).filter(|coroutine| names.contains(coroutine));
+            (
+                coroutine.unwrap_or(name),
+                coroutine.is_some(),
+                *name,
+                coroutine,
+            )
+        })
+        .sorted()
+        .map(|(_, _, name, coroutine)| (name, coroutine))
+        .collect()
+}
+
+/// Where a function goes when the functions of several files are sorted together: by name, the
+/// ones split from a coroutine right after it, in the same module.
+fn merged_order<'a>(func: &'a Function) -> (&'a str, &'a str, bool, &'a str) {
+    (
+        func.coroutine.as_deref().unwrap_or(&func.name),
+        func.module,
+        func.coroutine.is_some(),
+        &func.name,
+    )
+}
+
+/// Functions defined in several modules are named after their module too, `foo [a.ll]`.
+fn display_names(functions: &[Function]) -> Vec<String> {
+    let counts = functions.iter().map(|func| &func.name).counts();
+    functions
+        .iter()
+        .map(|func| match counts[&func.name] {
+            1 => func.name.clone(),
+            _ => format!("{} [{}]", func.name, func.module),
+        })
+        .collect()
+}
+
+/// What was printed of a dump being followed.
+#[derive(Default)]
+struct Followed {
+    /// The passes printed of each function, by module and function
+    passes: HashMap<(String, String), usize>,
+    diagnostics: usize,
+    invalid_utf8: bool,
+}
+
+/// Prints the passes of the dump that completed since the last call.
+fn print_completed(
+    stream: &DumpStream,
+    args: &Args,
+    function: Option<&Regex>,
+    renderer: &Renderer,
+    followed: &mut Followed,
+) -> Result<()> {
+    let Some(dump) = stream.completed() else {
+        return Ok(());
+    };
+    if !dump.dialect.is_llvm() {
+        return Err(eyre!("Option `--follow` only reads LLVM pass dumps"));
+    }
+    for diagnostic in &dump.diagnostics[followed.diagnostics..] {
+        cli_writeln!(
+            io::stderr(),
+            "warning: {}, the snapshot is left out",
+            diagnostic
+        )?;
+    }
+    followed.diagnostics = dump.diagnostics.len();
+
+    let grouped = dump.modules.len() > 1;
+    for module in dump.modules {
+        let module_name = module.name.clone();
+        let result = module.pipelines().wrap_err("Parsing error")?;
+        // The passes dumped since the last read, of each function
+        let mut updated = Vec::new();
+        for (name, _) in sort_functions(result.keys().map(String::as_str)) {
+            let demangled = demangle_text(name, args.demangle);
+            let selected = match (&args.function, function) {
+                (_, Some(regex)) => regex.is_match(&demangled),
+                (Some(expected), None) => demangled == *expected,
+                (None, None) => true,
+            };
+            if !selected {
+                continue;
+            }
+            let display_name = match grouped {
+                true => format!("{} [{}]", demangled, module_name),
+                false => demangled,
+            };
+            let pipeline = &result[name];
+            let printed = followed
+                .passes
+                .entry((module_name.clone(), name.to_string()))
+                .or_default();
+            let normalize = Normalize::from_args(args);
+            let shown = select_passes(
+                pipeline,
+                *printed,
+                args.skip_unchanged,
+                args.pass.as_deref(),
+                args.extended_regex,
+                args.demangle,
+                normalize,
+            )?;
+            updated.push((display_name, pipeline.as_slice(), shown, *printed));
+            *printed = pipeline.len();
+        }
+        let normalize = Normalize::from_args(args);
+        let functions = updated
+            .iter()
+            .map(|(name, pipeline, shown, _)| (name.as_str(), *pipeline, shown.clone()))
+            .collect_vec();
+        let ranked = rank_passes(
+            &functions,
+            args.demangle,
+            normalize,
+            args.min_change,
+            args.sort_by_change,
+            renderer,
+        );
+        for ((name, pipeline, _, first), ranked) in updated.iter().zip(ranked) {
             print_func(
-                func,
+                name,
                 pipeline,
+                *first,
+                ranked,
+                None,
+                args.demangle,
+                normalize,
+                args.describe,
+                renderer,
+                &Output::Stdout,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a dump the compiler is still writing, the diffs of its passes are printed as the
+/// snapshots complete. Runs until interrupted.
+fn follow(path: &Path, args: &Args, options: &ProcessOptions) -> Result<()> {
+    let read_error = || format!("Failed to read from file: {}", path.display());
+    let function = match (&args.function, args.extended_regex) {
+        (Some(pattern), true) => Some(
+            Regex::new(pattern).wrap_err_with(|| format!("Invalid regex pattern: {}", pattern))?,
+        ),
+        _ => None,
+    };
+    // The diffs being followed aren't paged
+    let renderer = select_renderer(args, Some(""))?;
+    let mut reader = BufReader::new(File::open(path).wrap_err_with(read_error)?);
+    let mut stream = DumpStream::new(true, options);
+    let mut followed = Followed::default();
+    let mut line = Vec::new();
+    let mut offset = 0;
+    let mut invalid_at = None;
+    let mut unprinted = false;
+    loop {
+        // A line still being written is kept until its line break shows up
+        reader
+            .read_until(b'\n', &mut line)
+            .wrap_err_with(read_error)?;
+        if line.ends_with(b"\n") {
+            stream.push_line(&decode_lossy(&line, offset, &mut invalid_at));
+            offset += line.len() as u64;
+            line.clear();
+            unprinted = true;
+            continue;
+        }
+        if let (Some(offset), false) = (invalid_at, followed.invalid_utf8) {
+            cli_writeln!(
+                io::stderr(),
+                "warning: {} isn't valid UTF-8 at byte {}, invalid bytes are shown as U+FFFD",
+                path.display(),
+                offset
+            )?;
+            followed.invalid_utf8 = true;
+        }
+        if std::mem::take(&mut unprinted) {
+            print_completed(&stream, args, function.as_ref(), &renderer, &mut followed)?;
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+}
+
+/// Writes the snapshot of the selected function before or after the pass of `--before` or
+/// `--after` for `optdiff extract`, as it was dumped and with what it refers to in its module, to
+/// feed to `opt`, `llc` or a reducer.
+fn extract_snapshot(
+    functions: &[Function],
+    names: &[String],
+    selected: &[usize],
+    pipelines: &[&Vec<Pass>],
+    scopes: &HashMap<String, extract::ModuleScope>,
+    args: &Args,
+) -> Result<()> {
+    let (pattern, after) = match (&args.extract.before, &args.extract.after) {
+        (Some(pass), _) => (pass, false),
+        (None, Some(pass)) => (pass, true),
+        (None, None) => unreachable!("checked before the dump is read"),
+    };
+    let mut found = Vec::new();
+    for &i in selected {
+        if let Some(j) = find_pass(pipelines[i], 0, pattern, args.extended_regex, args.demangle)? {
+            found.push((i, j));
+        }
+    }
+    let Some(&(i, j)) = found.first() else {
+        return Err(eyre!(
+            "No pass matching '{}' ran on {}, use option `--list-passes` to find out the passes of the function",
+            pattern,
+            names[selected[0]]
+        ));
+    };
+    if found.len() > 1 {
+        cli_writeln!(
+            io::stderr(),
+            "warning: '{}' ran on {} functions, the snapshot is of {}",
+            pattern,
+            found.len(),
+            names[i]
+        )?;
+    }
+    let pass = &pipelines[i][j];
+    let name = demangle_text(&pass.name, args.demangle) + &run_label(pass);
+    let snapshot = match after {
+        true if pass.deleted => {
+            return Err(eyre!(
+                "{} deleted the function, there is no snapshot after it",
+                name
+            ))
+        }
+        true if pass.invalidated => {
+            return Err(eyre!(
+                "{} invalidated the IR it ran on, there is no snapshot after it",
+                name
+            ))
+        }
+        true => {
+            if pass.after_unknown {
+                cli_writeln!(
+                    io::stderr(),
+                    "warning: No snapshot was dumped right after {}, this is the closest later one",
+                    name
+                )?;
+            }
+            &pass.after
+        }
+        false if pass.before_unknown && pass.before.is_empty() => {
+            return Err(eyre!("No snapshot was dumped before {}", name))
+        }
+        false => {
+            if pass.before_unknown {
+                cli_writeln!(
+                    io::stderr(),
+                    "warning: No snapshot was dumped right before {}, this is the closest earlier one",
+                    name
+                )?;
+            }
+            &pass.before
+        }
+    };
+    let (snapshot, missing) = match scopes.get(functions[i].module) {
+        Some(scope) => scope.standalone(snapshot),
+        None => (snapshot.clone(), Vec::new()),
+    };
+    if !missing.is_empty() {
+        cli_writeln!(
+            io::stderr(),
+            "warning: The dump doesn't show {}, which {} refers to, the snapshot won't read on its own without it",
+            missing.join(", "),
+            names[i]
+        )?;
+    }
+    let mut snapshot = snapshot;
+    if !snapshot.ends_with('\n') {
+        snapshot.push('\n');
+    }
+    match &args.view.output {
+        Some(path) => std::fs::write(path, snapshot)
+            .wrap_err_with(|| format!("Failed to write the snapshot to {}", path.display()))?,
+        None => cli_write!(io::stdout(), "{}", snapshot)?,
+    }
+    Ok(())
+}
+
+/// Lists the functions of every module, in order.
+fn print_function_list(functions: &[Function], grouped: bool) -> Result<()> {
+    let mut current_module = None;
+    for (func, name) in functions.iter().zip(display_names(functions)) {
+        if grouped && current_module != Some(func.module) {
+            cli_writeln!(io::stdout(), "{}:", func.module)?;
+            current_module = Some(func.module);
+        }
+        let indent = match (grouped, func.coroutine.is_some()) {
+            (false, false) => "",
+            (true, true) => "    ",
+            _ => "  ",
+        };
+        match func.created_by {
+            Some(pass) => cli_writeln!(io::stdout(), "{indent}{name} (split by {pass})")?,
+            None => cli_writeln!(io::stdout(), "{indent}{name}")?,
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+
+    let cli = Cli::parse();
+    let extract = matches!(cli.command, Some(Command::Extract(_)));
+    let (args, compared) = match cli.command {
+        Some(Command::Compare(CompareArgs { dumps, args })) => (args, dumps),
+        Some(Command::Export(export)) => {
+            let mut args = export.args;
+            args.input = export.input;
+            args.export = export.to;
+            args.view.per_file = export.per_file;
+            args.view.output = export.output;
+            (args, Vec::new())
+        }
+        Some(Command::Extract(extract)) => {
+            let mut args = extract.args;
+            args.input = extract.input;
+            args.extract = extract.pass;
+            args.view.output = extract.output;
+            (args, Vec::new())
+        }
+        None => {
+            let mut args = cli.args;
+            args.input = cli.input;
+            args.view = cli.view;
+            (args, Vec::new())
+        }
+    };
+    let options = ProcessOptions {
+        full_module: args.full_module,
+        keep_noise: args.keep_noise,
+        globals: args.globals,
+        expand_attributes: args.expand_attributes,
+        keep_dumped: args.view.tui,
+    };
+    let output_dir = args
+        .view
+        .output
+        .as_deref()
+        .filter(|path| output_dir::is_dir(path));
+    if output_dir.is_some()
+        && (!matches!(
+            args.view.format,
+            Format::Text | Format::Unified | Format::Plain | Format::Markdown
+        ) || args.diff_tool.is_some()
+            || args.difft_args.is_some()
+            || args.view.follow)
+    {
+        return Err(eyre!(
+            "Option `--output` writes the diffs of `--format text`, `unified`, `plain` or `markdown` to a directory, not of external tools or `--follow`"
+        ));
+    }
+    if output_dir.is_some()
+        && args.view.format == Format::Markdown
+        && args.view.split_by == Split::Pass
+    {
+        return Err(eyre!(
+            "Option `--format markdown` writes a document per function, it can't be split by pass"
+        ));
+    }
+    let document = matches!(
+        args.view.format,
+        Format::Html | Format::Json | Format::Csv | Format::GhSummary
+    );
+    if args.view.step && document {
+        return Err(eyre!(
+            "Option `--step` shows the diffs, not the documents of `--format html`, `json`, `csv` or `gh-summary`"
+        ));
+    }
+    if args.view.group_by == GroupBy::Pass && document {
+        return Err(eyre!(
+            "Option `--group-by pass` prints the diffs, not the documents of `--format html`, `json`, `csv` or `gh-summary`"
+        ));
+    }
+    if output_dir.is_none()
+        && args.view.output.is_some()
+        && !extract
+        && !document
+        && !args.export.dot
+    {
+        return Err(eyre!(
+            "Option `--output` writes the output of `--format html`, `json`, `csv` or `gh-summary` or of `--dot`, or the diffs to a directory ending with `/`"
+        ));
+    }
+    // The built-in pager shows the output of another run, before this one reads the dumps
+    let paged = !compared.is_empty()
+        || (matches!(
+            args.view.format,
+            Format::Text | Format::Unified | Format::Plain
+        ) && args.export.sqlite.is_none()
+            && !args.export.dot
+            && args.export.trace.is_none()
+            && !args.view.step
+            && !args.view.tui
+            && args.view.output.is_none());
+    if paged
+        && !args.view.follow
+        && !args.view.list
+        && !extract
+        && !builtin_pager::is_paged()
+        && io::stdout().is_terminal()
+        && builtin_pager::is_selected(select_pager(pager_arg(&args)).as_deref())
+    {
+        let code = builtin_pager::run().wrap_err("Failed to run the built-in pager")?;
+        std::process::exit(code);
+    }
+    if args.view.follow {
+        let [path] = args.input.as_slice() else {
+            return Err(eyre!("Option `--follow` needs a single dump file to read"));
+        };
+        if path.is_dir() {
+            return Err(eyre!(
+                "Option `--follow` needs a dump file, not a directory"
+            ));
+        }
+        return follow(path, &args, &options);
+    }
+    if !compared.is_empty() {
+        return compare::compare(&compared, &args, &options);
+    }
+
+    let inputs = match args.input.is_empty() {
+        true => vec![None],
+        false => args.input.iter().map(|path| Some(path.as_path())).collect(),
+    };
+    let several_inputs = inputs.len() > 1;
+    let mut progress = Progress::new(!args.no_progress);
+    let mut dialect = None;
+    let mut prefix = String::new();
+    let mut modules = Vec::new();
+    for input in inputs {
+        // The snapshots extracted are written as they were dumped, the filters aren't applied
+        let mut stream = DumpStream::new(!extract, &options);
+        read_input(input, &args, &mut stream, &mut progress)?;
+        progress.step("Splitting the snapshots by function");
+        let dump = stream.finish();
+        progress.clear();
+
+        // Dumps of several files are only read as one if they're of the same kind
+        if dialect.is_some_and(|dialect| dialect != dump.dialect) {
+            return Err(eyre!(
+                "{} isn't the same kind of dump as the files before it",
+                input.unwrap().display()
+            ));
+        }
+        dialect = Some(dump.dialect);
+        // `-print-after-all` alone is enough, each snapshot is then compared with the previous one
+        if dump.dialect == Dialect::PassDump && !dump.has_pass_dumps {
+            return Err(eyre!("{}", input_kind::explain(&dump.prefix, input)));
+        }
+
+        // Warnings name the file they're about when there are several
+        let source = match several_inputs {
+            true => format!("{}: ", input.unwrap().display()),
+            false => String::new(),
+        };
+        // Snapshots of threads can be sorted out as long as they didn't print at the same time
+        if let Some((line, function)) = &dump.interleaving {
+            cli_writeln!(
+                io::stderr(),
+                "warning: {}line {} starts a snapshot within the function {}, the dump seems to be written by several threads at once. Rerun with a single backend job (e.g. `-Wl,--thinlto-jobs=1`), or `-j1`",
+                source,
+                line,
+                function
+            )?;
+        }
+
+        for diagnostic in &dump.diagnostics {
+            cli_writeln!(
+                io::stderr(),
+                "warning: {}{}, the snapshot is left out",
+                source,
+                diagnostic
+            )?;
+        }
+
+        prefix += &dump.prefix;
+        // The modules of several files are named after their file, and their own name if the
+        // file holds more than one
+        let mut file_modules = dump.modules;
+        if let (true, Some(path)) = (several_inputs, input) {
+            let file = path.display().to_string();
+            let single = file_modules.len() == 1;
+            for module in &mut file_modules {
+                module.name = match single || module.name.is_empty() {
+                    true => file.clone(),
+                    false => format!("{} ({})", file, module.name),
+                };
+            }
+        }
+        modules.extend(file_modules);
+    }
+    let dialect = dialect.unwrap();
+
+    if let Some(pattern) = &args.module {
+        let mut matched = Vec::new();
+        for module in modules {
+            if matches_pattern(&module.name, pattern, args.extended_regex)? {
+                matched.push(module);
+            }
+        }
+        if matched.is_empty() {
+            return Err(eyre!(
+                "No module matching '{}' was found in the input, use option `--list/-l` to find out all available modules",
+                pattern
+            ));
+        }
+        modules = matched;
+    }
+    // Functions are grouped by module when there's more than one, the functions of several files
+    // are sorted together unless asked otherwise
+    let merged = several_inputs && !args.view.per_file;
+    let grouped = modules.len() > 1 && !merged;
+
+    if args.view.list && dialect.is_llvm() && !args.globals {
+        let mut functions = Vec::new();
+        for module in &modules {
+            for (name, coroutine) in sort_functions(module.functions.iter().map(String::as_str)) {
+                functions.push(Function {
+                    module: &module.name,
+                    name: demangle_text(name, args.demangle),
+                    coroutine: coroutine.map(|coroutine| demangle_text(coroutine, args.demangle)),
+                    created_by: None,
+                });
+            }
+        }
+        if merged {
+            functions.sort_by(|a, b| merged_order(a).cmp(&merged_order(b)));
+        }
+        // The pass that split the coroutines is only known once the dump is parsed
+        if functions.iter().all(|func| func.coroutine.is_none()) {
+            return print_function_list(&functions, grouped);
+        }
+    }
+
+    if args.globals && dialect != Dialect::PassDump {
+        return Err(eyre!(
+            "Option `--globals` needs a `-print-before-all`/`-print-after-all` style LLVM dump"
+        ));
+    }
+    let mut results = Vec::new();
+    // What the snapshots extracted or written from the viewer refer to outside of the function,
+    // by module
+    let mut scopes = HashMap::new();
+    // The pipelines of the snapshots as dumped, before the filters, for those the viewer writes
+    let mut dumped = HashMap::new();
+    progress.step("Pairing the snapshots of the passes");
+    for mut module in modules {
+        let name = module.name.clone();
+        if extract || args.view.tui {
+            scopes.insert(name.clone(), extract::ModuleScope::new(module.snapshots()));
+        }
+        let parsing_error = || match grouped {
+            true => format!("Parsing error in module {}", name),
+            false => "Parsing error".to_string(),
+        };
+        if let Some(result) = module.dumped_pipelines().wrap_err_with(parsing_error)? {
+            dumped.insert(name.clone(), result);
+        }
+        let result = module.pipelines().wrap_err_with(parsing_error)?;
+        results.push((name, result));
+    }
+    progress.clear();
+    if let Some(path) = &args.view.remarks {
+        let yaml = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read remarks from file: {}", path.display()))?;
+        let remarks = remarks::parse(&yaml);
+        let by_function = remarks
+            .iter()
+            .into_group_map_by(|remark| remark.function.as_str());
+        let mut attached = 0;
+        for (_, result) in &mut results {
+            for (name, pipeline) in result.iter_mut() {
+                if let Some(remarks) = by_function.get(name.as_str()) {
+                    attached += remarks::attach(remarks, pipeline);
+                }
+            }
+        }
+        if attached < remarks.len() {
+            cli_writeln!(
+                io::stderr(),
+                "warning: {} of the {} remarks don't match any pass of the dump",
+                remarks.len() - attached,
+                remarks.len()
+            )?;
+        }
+    }
+    if let Some(reference) = &args.view.reference {
+        let pipeline = match std::fs::read_to_string(reference) {
+            Ok(pipeline) => pipeline,
+            Err(_) => reference.clone(),
+        };
+        if pipeline_reference::is_alias(&pipeline) {
+            return Err(eyre!(
+                "`{}` names a whole pipeline, use the passes printed by `opt -passes='{}' -print-pipeline-passes` instead",
+                pipeline.trim(),
+                pipeline.trim()
+            ));
+        }
+        let reference = pipeline_reference::parse(&pipeline);
+        for (module, result) in &mut results {
+            // The first function found out of place, demangled and by name for a stable report
+            let mut deviations = Vec::new();
+            for (name, pipeline) in result.iter_mut() {
+                if name == optpipeline::GLOBALS || name == optpipeline::MODULE_PREAMBLE {
+                    continue;
+                }
+                let comparison = pipeline_reference::compare(&reference, pipeline);
+                for (i, skipped) in comparison.skipped {
+                    pipeline[i].skipped_before = skipped;
+                }
+                if let Some(last) = pipeline.last_mut() {
+                    last.skipped_after = comparison.skipped_at_end;
+                }
+                if let Some(&i) = comparison.unexpected.first() {
+                    deviations.push((name.clone(), pipeline[i].name.clone()));
+                }
+            }
+            deviations.sort();
+            if let Some((function, pass)) = deviations.first() {
+                let functions = match deviations.len() {
+                    1 => "1 function".to_string(),
+                    count => format!("{} functions", count),
+                };
+                let module = match grouped {
+                    true => format!(" of module {}", module),
+                    false => String::new(),
+                };
+                cli_writeln!(
+                    io::stderr(),
+                    "warning: the passes of {}{} don't run in the order of the reference pipeline, e.g. `{}` in {}",
+                    functions,
+                    module,
+                    pass,
+                    demangle_text(function, args.demangle)
+                )?;
+            }
+        }
+    }
+    if args.globals && results.iter().all(|(_, result)| result.is_empty()) {
+        return Err(eyre!(
+            "No snapshot of the whole module was found, `--globals` needs module passes in the dump"
+        ));
+    }
+    // Pipelines are keyed by module and function, in that order
+    let mut functions = Vec::new();
+    let mut pipelines = Vec::new();
+    let mut dumped_pipelines = Vec::new();
+    for (module, result) in &results {
+        for (name, coroutine) in sort_functions(result.keys().map(String::as_str)) {
+            let pipeline = &result[name];
+            functions.push(Function {
+                module,
+                name: demangle_text(name, args.demangle),
+                coroutine: coroutine.map(|coroutine| demangle_text(coroutine, args.demangle)),
+                created_by: coroutine.and_then(|coroutine| {
+                    // The new functions may only show up in the dump of a later pass
+                    result[coroutine]
+                        .iter()
+                        .find(|pass| pass.name.starts_with("CoroSplit"))
+                        .or(pipeline.first())
+                        .map(|pass| pass.name.as_str())
+                }),
+            });
+            pipelines.push(pipeline);
+            dumped_pipelines.push(dumped.get(module).and_then(|result| result.get(name)));
+        }
+    }
+
+    if merged {
+        let mut sorted = functions
+            .into_iter()
+            .zip(pipelines)
+            .zip(dumped_pipelines)
+            .collect_vec();
+        sorted.sort_by(|((a, _), _), ((b, _), _)| merged_order(a).cmp(&merged_order(b)));
+        ((functions, pipelines), dumped_pipelines) = sorted.into_iter().unzip();
+    }
+
+    // Only LLVM IR is simple enough to find the functions without parsing the dump
+    if args.view.list {
+        return print_function_list(&functions, grouped);
+    }
+    let renderer = select_renderer(&args, pager_arg(&args))?;
+    let inputs = input_names(&args);
+    let output = match output_dir {
+        Some(dir) => {
+            let extension = match args.view.format {
+                Format::Markdown => "md",
+                _ => "diff",
+            };
+            Output::dir(dir, args.view.split_by, extension)?
+        }
+        None if args.view.step => Output::Step(step::Stepper::new()?),
+        None => Output::Stdout,
+    };
+    let normalize = Normalize::from_args(&args);
+    cli_write!(io::stderr(), "{}", prefix)?;
+
+    let names = display_names(&functions);
+    let selected = if let Some(expected) = &args.function {
+        let regex = match args.extended_regex {
+            true => Some(
+                Regex::new(expected)
+                    .wrap_err_with(|| format!("Invalid regex pattern: {}", expected))?,
+            ),
+            false => None,
+        };
+        // Either name selects the function, the plain one in every module it's found in, and
+        // so do the names it was renamed to
+        let is_match = |name: &str| match &regex {
+            Some(regex) => regex.is_match(name),
+            None => name == expected,
+        };
+        let mut selected = Vec::new();
+        let mut modules_found = HashSet::new();
+        for (i, (func, pipeline)) in functions.iter().zip(&pipelines).enumerate() {
+            let renamed = pipeline
+                .iter()
+                .filter_map(|pass| pass.renamed_to.as_deref())
+                .any(|new_name| is_match(&demangle_text(new_name, args.demangle)));
+            if (is_match(&func.name) || is_match(&names[i]) || renamed)
+                && modules_found.insert(func.module)
+            {
+                selected.push(i);
+                // A coroutine comes with the functions split from it
+                let split = functions[i + 1..].iter().take_while(|split| {
+                    split.module == func.module && split.coroutine.as_ref() == Some(&func.name)
+                });
+                selected.extend((i + 1..).take(split.count()));
+            }
+        }
+        if selected.is_empty() {
+            return Err(match regex {
+                Some(_) => eyre!(
+                    "No function matching regex '{}' was found in the input, use option `--list/-l` to find out all available functions",
+                    expected
+                ),
+                None => eyre!("Function '{}' was not found in the input, use option `--list/-l` to find out all available functions", expected),
+            });
+        }
+        selected
+    } else {
+        (0..pipelines.len()).collect()
+    };
+
+    if extract {
+        return extract_snapshot(&functions, &names, &selected, &pipelines, &scopes, &args);
+    }
+    if matches!(
+        args.view.format,
+        Format::Text | Format::Unified | Format::Plain
+    ) && args.export.sqlite.is_none()
+        && !args.export.dot
+        && args.export.trace.is_none()
+        && !args.view.step
+        && !args.view.tui
+        && output_dir.is_none()
+    {
+        enter_pager(pager_arg(&args));
+    }
+    // The diffs are only followed while they go elsewhere than the terminal
+    if builtin_pager::to_terminal()
+        && args.view.output.is_none()
+        && args.export.sqlite.is_none()
+        && !args.export.dot
+        && args.export.trace.is_none()
+    {
+        progress.disable();
+    }
+    let total = selected.len();
+    let mut passes_diffed = 0;
+    let mut ranges_found = 0;
+    // With `--group-by pass`, the pipelines are printed once they're all read
+    let mut by_pass = Vec::new();
+    // Likewise browsed with `--tui`
+    let mut browsed = Vec::new();
+    // The functions diffed together, and how many of their passes are shown
+    let mut batch = Vec::new();
+    let mut batch_passes = 0;
+    for (done, i) in selected.into_iter().enumerate() {
+        progress.diffing(done, total, passes_diffed);
+        passes_diffed += pipelines[i].len();
+        let Some(pipeline) = shape_pipeline(pipelines[i], &args)? else {
+            continue;
+        };
+        ranges_found += 1;
+        if args.view.list_passes || args.view.names_only {
+            print_pass_list(
+                &names[i],
+                &pipeline,
+                args.skip_unchanged,
+                args.pass.as_deref(),
+                args.extended_regex,
+                args.demangle,
+                normalize,
+                args.min_change,
+                args.sort_by_change,
+                args.view.names_only,
+            )?;
+            continue;
+        }
+        if args.view.tree {
+            print_pass_tree(
+                &names[i],
+                &pipeline,
                 args.skip_unchanged,
                 args.pass.as_deref(),
                 args.extended_regex,
                 args.demangle,
             )?;
+            continue;
+        }
+        if args.view.group_by == GroupBy::Pass {
+            by_pass.push((i, pipeline));
+            continue;
+        }
+        let shown = select_passes(
+            &pipeline,
+            0,
+            args.skip_unchanged,
+            args.pass.as_deref(),
+            args.extended_regex,
+            args.demangle,
+            normalize,
+        )?;
+        if args.view.tui {
+            // The pipeline as dumped is shaped as the one shown, for its passes to match
+            let dumped = match dumped_pipelines[i] {
+                Some(dumped) => shape_pipeline(dumped, &args)?,
+                None => None,
+            };
+            browsed.push(tui::Function {
+                name: &names[i],
+                pipeline,
+                shown,
+                dumped,
+                scope: scopes.get(functions[i].module),
+            });
+            continue;
+        }
+        batch_passes += shown.len();
+        batch.push((i, pipeline, shown));
+        if batch_passes >= BATCH_PASSES {
+            print_batch(
+                &names, &functions, &batch, &args, normalize, &renderer, &output,
+            )?;
+            batch.clear();
+            batch_passes = 0;
+            if output.quit() {
+                break;
+            }
+        }
+    }
+    print_batch(
+        &names, &functions, &batch, &args, normalize, &renderer, &output,
+    )?;
+    progress.clear();
+    if args.view.tui {
+        tui::run(browsed, args.demangle, normalize)?;
+        return Ok(());
+    }
+    if args.view.group_by == GroupBy::Pass {
+        print_by_pass(
+            &names, &functions, &by_pass, &args, normalize, &renderer, &output,
+        )?;
+    }
+    if (args.view.from.is_some() || args.view.to.is_some()) && ranges_found == 0 {
+        cli_writeln!(
+            io::stderr(),
+            "warning: No function ran the passes given to `--from` and `--to`"
+        )?;
+    }
+    let page = match &renderer {
+        Renderer::Html(report) => Some(report.borrow().render(&inputs.join(", "))),
+        Renderer::Json(document) => Some(document.borrow().render(&inputs)),
+        Renderer::Csv(table) => Some(table.borrow().render()),
+        Renderer::Dot(graph) => Some(graph.borrow().render()),
+        _ => None,
+    };
+    if let Some(page) = page {
+        match &args.view.output {
+            Some(path) => std::fs::write(path, page)
+                .wrap_err_with(|| format!("Failed to write the report to {}", path.display()))?,
+            None => cli_write!(io::stdout(), "{}", page)?,
+        }
+    }
+    if let Renderer::GhSummary(summary) = &renderer {
+        let summary = summary.borrow();
+        cli_write!(io::stdout(), "{}", summary.annotations())?;
+        let page = summary.render(&inputs.join(", "));
+        // Each step of a job appends to its summary
+        let written = match (&args.view.output, env::var_os("GITHUB_STEP_SUMMARY")) {
+            (Some(path), _) => Some((path.clone(), std::fs::write(path, &page))),
+            (None, Some(path)) => {
+                let appended = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .and_then(|mut file| file.write_all(page.as_bytes()));
+                Some((PathBuf::from(path), appended))
+            }
+            (None, None) => None,
+        };
+        match written {
+            Some((path, result)) => result
+                .wrap_err_with(|| format!("Failed to write the summary to {}", path.display()))?,
+            None => cli_write!(io::stdout(), "{}", page)?,
+        }
+    }
+    if let (Renderer::Trace(trace), Some(path)) = (&renderer, &args.export.trace) {
+        let trace = trace.borrow();
+        if !trace.is_timed() {
+            cli_writeln!(
+                io::stderr(),
+                "warning: No pass was timed, the dump needs a `-time-passes` report"
+            )?;
         }
+        std::fs::write(path, trace.render(&inputs.join(", ")))
+            .wrap_err_with(|| format!("Failed to write the trace to {}", path.display()))?;
+    }
+    if let Renderer::Sqlite(database) = renderer {
+        database.into_inner().finish()?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Cli, Command};
+    use clap::{CommandFactory, Parser};
+
+    #[test]
+    fn command_line_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn compare_is_a_command() {
+        let cli = Cli::parse_from(["optdiff", "compare", "old.txt", "new.txt", "-s"]);
+        let Some(Command::Compare(compare)) = cli.command else {
+            panic!("`compare` isn't parsed as a command");
+        };
+        assert_eq!(
+            compare.dumps,
+            ["old.txt", "new.txt"].map(Into::<std::path::PathBuf>::into)
+        );
+        assert!(compare.args.skip_unchanged);
+
+        // A dump named like the command is still read as a dump
+        let cli = Cli::parse_from(["optdiff", "./compare"]);
+        assert!(cli.command.is_none());
+        assert_eq!(
+            cli.input,
+            ["./compare"].map(Into::<std::path::PathBuf>::into)
+        );
+        assert!(
+            Cli::try_parse_from(["optdiff", "compare", "old.txt", "new.txt", "--tui"]).is_err()
+        );
+    }
+
+    #[test]
+    fn export_is_a_command() {
+        let cli = Cli::parse_from(["optdiff", "export", "--dot", "dump.txt", "-o", "dump.dot"]);
+        let Some(Command::Export(export)) = cli.command else {
+            panic!("`export` isn't parsed as a command");
+        };
+        assert!(export.to.dot);
+        assert_eq!(
+            export.input,
+            ["dump.txt"].map(Into::<std::path::PathBuf>::into)
+        );
+
+        // It needs something to export to, and only it exports
+        assert!(Cli::try_parse_from(["optdiff", "export", "dump.txt"]).is_err());
+        assert!(Cli::try_parse_from(["optdiff", "--sqlite", "session.db", "dump.txt"]).is_err());
+        assert!(Cli::try_parse_from(["optdiff", "--dot", "dump.txt"]).is_err());
+        assert!(
+            Cli::try_parse_from(["optdiff", "export", "--sqlite", "session.db", "-o", "x"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn extract_is_a_command() {
+        let cli = Cli::parse_from([
+            "optdiff", "extract", "dump.txt", "-f", "foo", "--after", "5",
+        ]);
+        let Some(Command::Extract(extract)) = cli.command else {
+            panic!("`extract` isn't parsed as a command");
+        };
+        assert_eq!(extract.args.function.as_deref(), Some("foo"));
+        assert_eq!(extract.pass.after.as_deref(), Some("5"));
+
+        // It needs a function and a single pass, and only it takes them
+        assert!(Cli::try_parse_from(["optdiff", "extract", "dump.txt", "--after", "5"]).is_err());
+        assert!(Cli::try_parse_from(["optdiff", "extract", "dump.txt", "-f", "foo"]).is_err());
+        assert!(Cli::try_parse_from([
+            "optdiff", "extract", "dump.txt", "-f", "foo", "--before", "4", "--after", "5"
+        ])
+        .is_err());
+        assert!(Cli::try_parse_from(["optdiff", "dump.txt", "-f", "foo", "--after", "5"]).is_err());
+    }
+}
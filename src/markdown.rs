@@ -0,0 +1,63 @@
+//! The diffs as Markdown, with `--format markdown`, to paste into a pull request or an issue
+//! when discussing a codegen change. Each pass that changed a function is a collapsed
+//! `<details>` section with its diff in a fenced block, cut short so that a few of them fit in
+//! a GitHub or GitLab comment.
+
+use crate::html_report::{escape, PassSection};
+use similar::TextDiff;
+
+/// The lines of a diff shown at most, the rest is left out with a note.
+const MAX_LINES: usize = 200;
+
+/// The diff of a pass as a fenced `diff` block, with 3 lines of context as `diff -u` has.
+pub fn diff_block(before: &str, after: &str) -> String {
+    let diff = TextDiff::from_lines(before, after);
+    let unified = diff.unified_diff().context_radius(3).to_string();
+    let lines = unified.lines().collect::<Vec<_>>();
+    // The fence has to be longer than any run of backticks in the diff
+    let longest_run = unified
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let mut block = format!("{}diff\n", fence);
+    for line in lines.iter().take(MAX_LINES) {
+        block += line;
+        block += "\n";
+    }
+    block += &fence;
+    block += "\n";
+    if lines.len() > MAX_LINES {
+        block += &format!("\n*{} more lines left out*\n", lines.len() - MAX_LINES);
+    }
+    block
+}
+
+/// The passes that changed a function under a heading, nothing if none did.
+pub fn function(name: &str, passes: &[PassSection]) -> String {
+    if !passes.iter().any(|pass| pass.changed) {
+        return String::new();
+    }
+    format!("### `{}`\n\n{}", name.replace('`', "'"), sections(passes))
+}
+
+/// The passes that changed a function, each a collapsed section with its notes and its diff.
+pub fn sections(passes: &[PassSection]) -> String {
+    let mut text = String::new();
+    for pass in passes.iter().filter(|pass| pass.changed) {
+        text += &format!("<details>\n<summary>{}</summary>\n\n", escape(&pass.title));
+        for note in &pass.notes {
+            text += &format!("> {}\n", escape(note));
+        }
+        if !pass.notes.is_empty() {
+            text += "\n";
+        }
+        if let Some(diff) = &pass.diff {
+            text += diff;
+            text += "\n";
+        }
+        text += "</details>\n\n";
+    }
+    text
+}
@@ -0,0 +1,92 @@
+//! The instructions a pass added and removed, counted by opcode (`+3 load, -5 store`), for a
+//! summary of a pass without reading its diff.
+
+use crate::change_score::is_instruction;
+use similar::{DiffOp, TextDiff};
+use std::collections::HashMap;
+
+/// Words printed before the opcode: flags of MIR instructions and call markers of LLVM IR.
+const PREFIXES: &[&str] = &[
+    "tail",
+    "musttail",
+    "notail",
+    "frame-setup",
+    "frame-destroy",
+    "nofpexcept",
+    "nsw",
+    "nuw",
+    "exact",
+    "nnan",
+    "ninf",
+    "nsz",
+    "arcp",
+    "contract",
+    "afn",
+    "reassoc",
+];
+
+/// The opcode of the instruction on `line`, the word after the value it defines if any: `load`
+/// of `%1 = load i32, ptr %p`, `ADD32rr` of MIR or `arith.addi` of MLIR.
+fn opcode(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let instruction = match line.split_once(" = ") {
+        Some((defined, rest)) if !defined.contains(' ') || defined.starts_with('%') => rest,
+        _ => line,
+    };
+    let opcode = instruction
+        .split_whitespace()
+        .find(|word| !PREFIXES.contains(word))?
+        .trim_end_matches([';', ',']);
+    let is_word = opcode
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && opcode
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-');
+    is_word.then_some(opcode)
+}
+
+/// The number of instructions added and removed by opcode, the opcodes most changed first.
+pub fn histogram<'a>(before: &'a str, after: &'a str) -> Vec<(&'a str, usize, usize)> {
+    let diff = TextDiff::from_lines(before, after);
+    let (old, new) = (diff.old_slices(), diff.new_slices());
+    let mut counts = HashMap::<&str, (usize, usize)>::new();
+    for op in diff.ops() {
+        if let DiffOp::Equal { .. } = op {
+            continue;
+        }
+        let instructions = |lines: &[&'a str], range: std::ops::Range<usize>| {
+            range
+                .map(|i| lines[i])
+                .filter(|line| is_instruction(line))
+                .filter_map(opcode)
+                .collect::<Vec<_>>()
+        };
+        for opcode in instructions(new, op.new_range()) {
+            counts.entry(opcode).or_default().0 += 1;
+        }
+        for opcode in instructions(old, op.old_range()) {
+            counts.entry(opcode).or_default().1 += 1;
+        }
+    }
+    let mut histogram = counts
+        .into_iter()
+        .map(|(opcode, (added, removed))| (opcode, added, removed))
+        .collect::<Vec<_>>();
+    histogram.sort_by(|a, b| (b.1 + b.2).cmp(&(a.1 + a.2)).then(a.0.cmp(b.0)));
+    histogram
+}
+
+/// The histogram as printed, `+3 load, -5 store, +1 -1 call`.
+pub fn summary(histogram: &[(&str, usize, usize)]) -> String {
+    histogram
+        .iter()
+        .map(|&(opcode, added, removed)| match (added, removed) {
+            (0, removed) => format!("-{} {}", removed, opcode),
+            (added, 0) => format!("+{} {}", added, opcode),
+            (added, removed) => format!("+{} -{} {}", added, removed, opcode),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -1,22 +1,156 @@
 use itertools::Itertools;
-use memchr::memchr_iter;
 use regex::Regex;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use thiserror::Error;
 
-#[derive(Debug)]
+mod attribute_groups;
+mod build_noise;
+mod debug_noise;
+pub mod gcc;
+mod mlir;
+mod nesting;
+mod print_changed;
+mod sil;
+mod stream;
+mod time_report;
+
+pub use nesting::{collapse_nested_passes, scope};
+pub use stream::DumpStream;
+
+#[derive(Debug, Clone)]
 pub struct Pass {
     pub name: String,
     pub machine: bool,
     pub after: String,
     pub before: String,
     pub ir_changed: bool,
+    /// The pass invalidated the IR unit it ran on (e.g. a deleted loop), so its
+    /// "after" snapshot can't be compared with the "before" one
+    pub invalidated: bool,
+    /// The function was deleted by this pass
+    pub deleted: bool,
+    /// Only the snapshot after this pass was dumped, `before` is the closest earlier snapshot
+    /// of the function (empty if there is none)
+    pub before_unknown: bool,
+    /// Only the snapshot before this pass was dumped, `after` is the closest later snapshot of
+    /// the function (the same as `before` if there is none)
+    pub after_unknown: bool,
+    /// The pass renamed the function, e.g. `foo` to `foo.llvm.123`
+    pub renamed_to: Option<String>,
+    /// The pass ran `runs` times on the function, this is run number `run`, starting at 1
+    pub run: usize,
+    pub runs: usize,
+    /// The adaptors of the new pass manager the pass ran under, outermost first
+    pub adaptors: Vec<&'static str>,
+    /// Wall time of the pass, when the dump comes with a `-time-passes` report
+    pub time: Option<PassTime>,
+    /// Optimization remarks emitted by the pass on the function
+    pub remarks: Vec<String>,
+    /// Passes of the reference pipeline expected right before this one, but not dumped
+    pub skipped_before: Vec<String>,
+    /// Passes of the reference pipeline expected after this one, the last of the pipeline
+    pub skipped_after: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PassTime {
+    /// Seconds spent in this run of the pass (`-time-passes-per-run`)
+    Run(f64),
+    /// Seconds spent in every run of the pass, on every function
+    AllRuns(f64),
 }
 
+/// Snapshots that can't be attributed to a function are collected under this name, e.g. legacy
+/// pass manager loop dumps with no function processed before them (`-print-after=licm` alone).
+const UNKNOWN_FUNCTION: &str = "<unknown function>";
+
+/// The pipeline of the global variables, aliases and ifuncs of the module.
+pub const GLOBALS: &str = "<globals>";
+
+/// The pipeline of the data layout, target triple, module flags and named metadata.
+pub const MODULE_PREAMBLE: &str = "<module>";
+
+/// The label starting a block, `loop:` or `"a b":`.
+pub const LABEL: &str = r#"^("[^"]*"|[\w.$-]+):"#;
+
 type OptPipelineResults = HashMap<String, Vec<Pass>>;
 
+/// The flavour of pass dump, decided by the banners found in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// `-print-before-all`/`-print-after-all` style dumps
+    PassDump,
+    /// `-print-changed`, a snapshot only after passes that changed the IR
+    PrintChanged,
+    /// `-print-changed=diff` and `cdiff`, LLVM's own diff after passes that changed the IR
+    PrintChangedDiff,
+    /// `mlir-opt --mlir-print-ir-before-all --mlir-print-ir-after-all`
+    Mlir,
+    /// `swiftc -Xllvm -sil-print-all`
+    Sil,
+    /// `flang -mmlir --mlir-print-ir-after-all`, MLIR dumps of FIR and HLFIR
+    Fir,
+    /// `gcc -fdump-tree-all -fdump-rtl-all`, one dump file per pass
+    Gcc,
+}
+
+impl Dialect {
+    /// Dumps of LLVM itself, as opposed to other compilers printing their own IR
+    pub fn is_llvm(self) -> bool {
+        matches!(
+            self,
+            Dialect::PassDump | Dialect::PrintChanged | Dialect::PrintChangedDiff
+        )
+    }
+}
+
+/// The name of the global a symbol (`foo` of `@foo`) starts with, and the rest of the symbol.
+/// Quoted names (`@"foo bar"`) are taken as printed within the quotes, escapes included, and
+/// unnamed globals by their number (`@0`).
+fn global_name(symbol: &str) -> Option<(&str, &str)> {
+    if let Some(quoted) = symbol.strip_prefix('"') {
+        // Quotes within the name are escaped as `\22`
+        let end = quoted.find('"')?;
+        return Some((&quoted[..end], &quoted[end + 1..]));
+    }
+    let end = symbol
+        .find(|c: char| !c.is_ascii_alphanumeric() && !"-$._".contains(c))
+        .unwrap_or(symbol.len());
+    (end > 0).then(|| symbol.split_at(end))
+}
+
+/// A name as printed in the IR, within quotes if need be. Banners and machine functions print
+/// it as is, control characters, quotes and backslashes included.
+fn escape_name(name: &str) -> Cow<'_, str> {
+    let printable = |byte: u8| (0x20..0x7f).contains(&byte) && byte != b'\\' && byte != b'"';
+    if name.bytes().all(printable) {
+        return Cow::Borrowed(name);
+    }
+    let mut escaped = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        match printable(byte) {
+            true => escaped.push(byte as char),
+            false => escaped += &format!("\\{:02X}", byte),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// The function defined by a `define` line, or the machine function that follows.
+fn defined_function(line: &str) -> Option<Cow<'_, str>> {
+    if let Some(define) = line.strip_prefix("define ") {
+        let (name, rest) = global_name(&define[define.find('@')? + 1..])?;
+        return rest.starts_with('(').then_some(Cow::Borrowed(name));
+    }
+    // The properties after the name never hold a colon, the name may
+    line.strip_prefix("# Machine code for function ")?
+        .rsplit_once(':')
+        .map(|(name, _)| escape_name(name))
+}
+
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 struct OptPipelineBackendOptions {
     filter_debug_info: bool,
     filter_ir_metadata: bool,
@@ -25,26 +159,66 @@ struct OptPipelineBackendOptions {
     demangle: bool,
     library_functions: bool,
     apply_filters: bool,
+    /// Keep the text found between the snapshots that isn't part of the dumps
+    keep_noise: bool,
+    globals: bool,
+    dialect: Dialect,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct PassDump {
     header: String,
     affected_function: Option<String>,
     machine: bool,
+    invalidated: bool,
+    deleted: bool,
+    /// `-print-changed` reported that the pass didn't change the IR
+    unchanged: bool,
+    /// The function is known under this name from this pass on
+    renamed_to: Option<String>,
     lines: String,
 }
 
-#[derive(Debug)]
+impl PassDump {
+    /// A plain snapshot, as found in dumps of other compilers
+    fn new(header: &str, lines: String) -> Self {
+        PassDump {
+            header: header.to_string(),
+            affected_function: None,
+            machine: false,
+            invalidated: false,
+            deleted: false,
+            unchanged: false,
+            renamed_to: None,
+            lines,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 struct SplitPassDump {
     header: String,
+    affected_function: Option<String>,
+    invalidated: bool,
+    /// The snapshot is of the blocks of a loop
+    loop_snapshot: bool,
+    functions: HashMap<String, FunctionDump>,
+}
+
+/// A single function's snapshot within a pass dump. `machine` is decided by
+/// the snapshot itself (`# Machine code for function`), not by the banner, so
+/// MIR and IR snapshots of the same function form separate streams.
+#[derive(Debug, Clone)]
+struct FunctionDump {
     machine: bool,
-    functions: HashMap<String, Vec<String>>,
+    lines: Vec<String>,
 }
 
 pub struct LlvmPassDumpParser {
     ir_dump_header: Regex,
     machine_code_dump_header: Regex,
+    loop_pass_header: Regex,
+    color_escape: Regex,
     // function_define: Regex,
     // machine_function_begin: Regex,
     function_end: Regex,
@@ -66,6 +240,35 @@ pub enum PassDumpError {
         after_header: String,
     },
 }
+/// A part of the dump that couldn't be made sense of, it's left out up to the next banner.
+#[derive(Debug, Clone, Error)]
+#[error("line {line}: {problem}")]
+pub struct Diagnostic {
+    pub line: usize,
+    pub problem: Problem,
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum Problem {
+    #[error("the banner isn't closed by `***`")]
+    UnclosedBanner,
+    #[error("unexpected banner `{0}` in a `-print-before-all`/`-print-after-all` dump")]
+    UnexpectedBanner(String),
+    #[error("the definition doesn't name a function")]
+    UnnamedFunction,
+    #[error("the dump ends within the function {0}")]
+    TruncatedSnapshot(String),
+}
+
+/// The header of a banner, between its `***`s, or `None` if the banner is cut off.
+fn banner_header(line: &str) -> Option<&str> {
+    let header = match line.starts_with(';') || line.starts_with('#') {
+        true => line.get("; *** ".len()..)?,
+        false => line.get("*** ".len()..)?,
+    };
+    header.find(" ***").map(|end| &header[..end])
+}
+
 impl LlvmPassDumpParser {
     fn new() -> Self {
         LlvmPassDumpParser {
@@ -74,12 +277,16 @@ impl LlvmPassDumpParser {
             )
             .unwrap(),
             machine_code_dump_header: Regex::new(r"^# \*{3} (.+) \*{3}:$").unwrap(),
+            loop_pass_header: Regex::new(
+                r"^(.+) on loop (%.+?) in function (.+?)(?: \(invalidated\))?$",
+            )
+            .unwrap(),
+            color_escape: Regex::new(r"\x1b\[[0-9;]*m").unwrap(),
             // function_define: Regex::new(r"^define .+ @([\w.]+|'[^']+')\(.+$").unwrap(),
             // machine_function_begin: Regex::new(r"^# Machine code for function ([\w$.]+):.*$")
             //     .unwrap(),
             function_end: Regex::new(r"^}$").unwrap(),
-            machine_function_end: Regex::new(r"^# End machine code for function ([\w$.]+).$")
-                .unwrap(),
+            machine_function_end: Regex::new(r"^# End machine code for function (.+)\.$").unwrap(),
         }
     }
 
@@ -97,29 +304,67 @@ impl LlvmPassDumpParser {
                 if let Some(current_pass) = pass.take() {
                     raw_passes.push(current_pass);
                 }
-                let header_prefix = if line.starts_with(';') || line.starts_with("#") {
-                    "; *** "
-                } else {
-                    "*** "
+                let Some(header) = banner_header(line) else {
+                    continue;
                 };
-                let header = &line[header_prefix.len()..];
-                let header = &header[..header.find(" ***").unwrap()];
+                let mut header = header.to_string();
 
-                let affected_function =
-                    if let Some(idx) = line.find("(function: ").or(line.find("(loop: ")) {
+                // Older releases print `*** IR Pass X invalidated ***` without naming the IR unit
+                if let Some(pass_id) = header
+                    .strip_prefix("IR Pass ")
+                    .and_then(|header| header.strip_suffix(" invalidated"))
+                {
+                    header = format!("IR Dump After {} (invalidated)", pass_id);
+                }
+                let invalidated = header.ends_with(" (invalidated)");
+
+                // `-print-changed` banners carry the outcome of the pass at the end, and
+                // don't print anything for ignored pass managers or filtered out passes
+                if (header.starts_with("IR Pass ") && header.ends_with(" ignored"))
+                    || header.ends_with(" filtered out")
+                {
+                    continue;
+                }
+                let unchanged = match header.strip_suffix(" omitted because no change") {
+                    Some(stripped) => {
+                        header = stripped.to_string();
+                        true
+                    }
+                    None => false,
+                };
+                let deleted = match header.strip_prefix("IR Deleted After ") {
+                    Some(stripped) => {
+                        header = format!("IR Dump After {}", stripped);
+                        true
+                    }
+                    None => false,
+                };
+
+                let mut affected_function = line
+                    .find("(function: ")
+                    .or(line.find("(loop: "))
+                    .and_then(|idx| {
                         let content = &line[idx + 1..];
-                        Some(
-                            content[content.find(' ').unwrap() + 1..content.find(')').unwrap()]
-                                .to_string(),
-                        )
-                    } else {
-                        None
-                    };
+                        content.get(content.find(' ')? + 1..content.find(')')?)
+                    })
+                    .map(str::to_string);
+
+                // New PM loop banners look like `LICMPass on loop %for.body in function foo`.
+                // The snapshot belongs to `foo`, and the pass is labelled with its loop only.
+                if let Some(caps) = self.loop_pass_header.captures(&header) {
+                    affected_function = Some(escape_name(&caps[3]).into_owned());
+                    let suffix = if invalidated { " (invalidated)" } else { "" };
+                    header = format!("{} on loop {}{}", &caps[1], &caps[2], suffix);
+                }
 
                 pass = Some(PassDump {
-                    header: header.to_string(),
+                    header,
                     affected_function,
                     machine: line.starts_with("#"),
+                    invalidated,
+                    deleted,
+                    unchanged,
+                    renamed_to: None,
                     lines: String::new(),
                 });
 
@@ -146,55 +391,79 @@ impl LlvmPassDumpParser {
 
     fn breakdown_pass_dumps_into_functions(&self, dump: PassDump) -> SplitPassDump {
         let mut pass = SplitPassDump {
+            loop_snapshot: is_loop_snapshot(&dump),
             header: dump.header,
-            machine: dump.machine,
+            affected_function: dump.affected_function,
+            invalidated: dump.invalidated,
             functions: HashMap::new(),
         };
-        let mut func: Option<(String, Vec<String>)> = None;
-        let mut is_machine_function_open = false;
+        let mut func: Option<(String, FunctionDump)> = None;
 
         for line in dump.lines.lines() {
             let line = line.to_string();
-            let is_ir_fn = line.starts_with("define ");
-            let is_machine_fn = line.starts_with("# Machine code for function ");
-
-            if is_ir_fn {
-                if func.is_some() {
-                    let (name, lines) = func.take().unwrap();
-                    pass.functions.insert(name, lines);
+            if let Some(name) = defined_function(&line) {
+                let name = name.into_owned();
+                if let Some((name, function)) = func.take() {
+                    pass.functions.insert(name, function);
                 }
-                let name = &line[line.find('@').unwrap() + 1..];
-                let name = &name[..name.find('(').unwrap()];
-                func = Some((name.to_string(), vec![line]));
-
-                is_machine_function_open = false;
-            } else if is_machine_fn {
-                if func.is_some() {
-                    let (name, lines) = func.take().unwrap();
-                    pass.functions.insert(name, lines);
-                }
-                let name = &line["# Machine code for function ".len()..line.find(':').unwrap()];
-                func = Some((name.to_string(), vec![line]));
-                is_machine_function_open = true;
-            } else if line.starts_with("; Preheader:") {
+                func = Some((
+                    name,
+                    FunctionDump {
+                        machine: !line.starts_with("define "),
+                        lines: vec![line],
+                    },
+                ));
+            } else if line.starts_with("; Preheader:") || line.starts_with("; Loop:") {
+                // Loops without a preheader start directly at `; Loop:`
                 if func.is_none() {
-                    func = Some(("<loop>".to_string(), vec![line]));
+                    let name = match pass.affected_function.as_deref() {
+                        Some(function) if !function.starts_with('%') => function,
+                        _ => "<loop>",
+                    };
+                    func = Some((
+                        name.to_string(),
+                        FunctionDump {
+                            machine: false,
+                            lines: vec![line],
+                        },
+                    ));
                 }
-            } else if let Some((ref mut name, ref mut lines)) = func {
-                if (!is_machine_function_open && self.function_end.is_match(line.trim()))
-                    || (is_machine_function_open && self.machine_function_end.is_match(line.trim()))
-                {
-                    lines.push(line);
-                    pass.functions.insert(name.clone(), lines.clone());
-                    func = None;
+            } else if let Some((ref mut name, ref mut function)) = func {
+                let end = if function.machine {
+                    &self.machine_function_end
                 } else {
-                    lines.push(line);
+                    &self.function_end
+                };
+                function.lines.push(line);
+                if end.is_match(function.lines.last().unwrap().trim()) {
+                    pass.functions.insert(
+                        std::mem::take(name),
+                        FunctionDump {
+                            machine: function.machine,
+                            lines: std::mem::take(&mut function.lines),
+                        },
+                    );
+                    func = None;
                 }
             }
         }
 
-        if let Some((name, lines)) = func {
-            pass.functions.insert(name, lines);
+        if let Some((name, function)) = func {
+            pass.functions.insert(name, function);
+        }
+
+        // Once the IR unit a pass ran on is invalidated LLVM prints the whole module instead,
+        // only the functions the pass actually ran on belong to this snapshot.
+        if pass.invalidated {
+            if let Some(units) = ir_units(&pass.header, pass.affected_function.as_deref()) {
+                pass.functions.retain(|name, _| units.contains(name));
+                for unit in units {
+                    pass.functions.entry(unit).or_insert(FunctionDump {
+                        machine: false,
+                        lines: Vec::new(),
+                    });
+                }
+            }
         }
 
         pass
@@ -206,14 +475,77 @@ impl LlvmPassDumpParser {
     ) -> HashMap<String, Vec<PassDump>> {
         let mut pass_dumps_by_function = HashMap::new();
         let mut previous_function: Option<String> = None;
+        let mut previous_before: Option<(String, Vec<(String, bool)>)> = None;
+        // Pipelines follow renamed functions, keyed by the name they were first seen with
+        let mut renames: HashMap<String, String> = HashMap::new();
+        let mut just_renamed: HashSet<String> = HashSet::new();
 
-        for pass in pass_dumps {
-            for (function_name, lines) in pass.functions {
+        for mut pass in pass_dumps {
+            if !pass.invalidated {
+                if let Some(units) = module_scope_units(&pass, &previous_function) {
+                    pass.functions.retain(|name, _| units.contains(name));
+                }
+            }
+
+            // A function present before a pass but missing from the dump right after it, on
+            // the very same IR unit, is a function that pass deleted
+            if let Some((header, functions)) = previous_before.take() {
+                if pass.header.strip_prefix("IR Dump After ")
+                    == header.strip_prefix("IR Dump Before ")
+                {
+                    for (name, machine) in functions {
+                        if name != "<loop>" && !pass.functions.contains_key(&name) {
+                            // `foo` turning into `foo.llvm.123` (or the like) wasn't deleted
+                            let prefix = format!("{}.", name);
+                            let mut new_names = pass
+                                .functions
+                                .keys()
+                                .filter(|new_name| new_name.starts_with(&prefix));
+                            if let (Some(new_name), None) = (new_names.next(), new_names.next()) {
+                                let original = renames.get(&name).unwrap_or(&name).clone();
+                                just_renamed.insert(new_name.clone());
+                                renames.insert(new_name.clone(), original);
+                                continue;
+                            }
+                            pass_dumps_by_function
+                                .entry(name)
+                                .or_insert_with(Vec::new)
+                                .push(PassDump {
+                                    header: pass.header.clone(),
+                                    affected_function: None,
+                                    machine,
+                                    invalidated: false,
+                                    deleted: true,
+                                    unchanged: false,
+                                    renamed_to: None,
+                                    lines: String::new(),
+                                });
+                        }
+                    }
+                }
+            }
+            if pass.header.starts_with("IR Dump Before ") {
+                previous_before = Some((
+                    pass.header.clone(),
+                    pass.functions
+                        .iter()
+                        .map(|(name, function)| (name.clone(), function.machine))
+                        .collect(),
+                ));
+            }
+
+            for (function_name, function) in pass.functions {
                 let name = if function_name == "<loop>" {
-                    previous_function.clone().unwrap()
+                    previous_function
+                        .clone()
+                        .unwrap_or_else(|| UNKNOWN_FUNCTION.to_string())
                 } else {
-                    function_name.clone()
+                    renames
+                        .get(&function_name)
+                        .unwrap_or(&function_name)
+                        .clone()
                 };
+                let renamed = just_renamed.remove(&function_name);
                 if !pass_dumps_by_function.contains_key(&name) {
                     pass_dumps_by_function.insert(name.clone(), Vec::new());
                 }
@@ -223,8 +555,12 @@ impl LlvmPassDumpParser {
                     .push(PassDump {
                         header: pass.header.clone(),
                         affected_function: None,
-                        machine: pass.machine,
-                        lines: lines.join("\n"),
+                        machine: function.machine,
+                        invalidated: pass.invalidated,
+                        deleted: !pass.invalidated && function.lines.is_empty(),
+                        unchanged: false,
+                        renamed_to: renamed.then(|| function_name.clone()),
+                        lines: function.lines.join("\n"),
                     });
                 if function_name != "<loop>" {
                     previous_function = Some(name);
@@ -241,9 +577,11 @@ impl LlvmPassDumpParser {
         let mut pass_dumps_by_function = HashMap::new();
 
         for pass in &pass_dumps {
-            if let Some(ref func) = pass.affected_function {
-                if !pass_dumps_by_function.contains_key(func) {
-                    pass_dumps_by_function.insert(func.clone(), Vec::new());
+            for unit in
+                ir_units(&pass.header, pass.affected_function.as_deref()).unwrap_or_default()
+            {
+                if unit != "<loop>" && !pass_dumps_by_function.contains_key(&unit) {
+                    pass_dumps_by_function.insert(unit, Vec::new());
                 }
             }
         }
@@ -252,28 +590,43 @@ impl LlvmPassDumpParser {
         let mut previous_function: Option<String> = None;
 
         for pass in pass_dumps {
-            if let Some(ref func) = pass.affected_function {
-                let func_name = if func.starts_with('%') {
-                    previous_function.clone().unwrap()
-                } else {
-                    func.clone()
-                };
-                pass_dumps_by_function
-                    .get_mut(&func_name)
-                    .unwrap()
-                    .push(PassDump {
-                        header: format!("{} ({})", pass.header, func_name),
-                        affected_function: Some(func_name.clone()),
-                        machine: pass.machine,
-                        lines: pass.lines.clone(),
-                    });
-                previous_function = Some(func_name);
+            if let Some(units) = ir_units(&pass.header, pass.affected_function.as_deref()) {
+                let is_loop = units.iter().any(|unit| unit == "<loop>");
+                for unit in units {
+                    let func_name = if unit == "<loop>" {
+                        previous_function
+                            .clone()
+                            .unwrap_or_else(|| UNKNOWN_FUNCTION.to_string())
+                    } else {
+                        unit
+                    };
+                    pass_dumps_by_function
+                        .entry(func_name.clone())
+                        .or_default()
+                        .push(PassDump {
+                            header: pass.header.clone(),
+                            affected_function: Some(func_name.clone()),
+                            machine: pass.machine,
+                            invalidated: pass.invalidated,
+                            deleted: false,
+                            unchanged: false,
+                            renamed_to: None,
+                            lines: pass.lines.clone(),
+                        });
+                    if !is_loop {
+                        previous_function = Some(func_name);
+                    }
+                }
             } else {
                 for (_, entry) in pass_dumps_by_function.iter_mut() {
                     entry.push(PassDump {
                         header: pass.header.clone(),
                         affected_function: None,
                         machine: pass.machine,
+                        invalidated: pass.invalidated,
+                        deleted: false,
+                        unchanged: false,
+                        renamed_to: None,
                         lines: pass.lines.clone(),
                     });
                 }
@@ -286,6 +639,7 @@ impl LlvmPassDumpParser {
     fn match_pass_dumps(
         &self,
         pass_dumps_by_function: HashMap<String, Vec<PassDump>>,
+        pairing: Pairing,
     ) -> Result<OptPipelineResults, PassDumpError> {
         let mut final_output = HashMap::new();
 
@@ -293,6 +647,47 @@ impl LlvmPassDumpParser {
             let mut passes: Vec<Pass> = Vec::new();
             let mut i = 0;
 
+            // Closest snapshots of the same kind, for passes only dumped on one side. Loops are
+            // only compared with loops, functions with functions.
+            let same_kind = |dump: &PassDump, other: &PassDump| {
+                !dump.invalidated
+                    && dump.machine == other.machine
+                    && is_loop_snapshot(dump) == is_loop_snapshot(other)
+            };
+            let function_snapshots = match pairing {
+                Pairing::Complete => Vec::new(),
+                Pairing::AfterOnly | Pairing::Selective => function_snapshots(&pass_dumps),
+            };
+            let previous_snapshot = |i: usize| {
+                let dump = &pass_dumps[i];
+                // The function as the loop passes before it left it, or the loop as it had it
+                let function = function_snapshots[..i]
+                    .iter()
+                    .rposition(Option::is_some)
+                    .filter(|_| !dump.machine)
+                    .map(|index| (index, function_snapshots[index].as_deref().unwrap()));
+                match function {
+                    Some((index, function)) if is_loop_snapshot(dump) => {
+                        let function = print_changed::Blocks::parse(function);
+                        let loop_dump = print_changed::Blocks::parse(&dump.lines);
+                        return Some((index, function.loop_part(&loop_dump, &[]).text()));
+                    }
+                    Some((index, function)) => return Some((index, function.to_string())),
+                    None if !dump.machine && !is_loop_snapshot(dump) => return None,
+                    None => {}
+                }
+                pass_dumps[..i]
+                    .iter()
+                    .rposition(|dump| same_kind(dump, &pass_dumps[i]))
+                    .map(|index| (index, pass_dumps[index].lines.clone()))
+            };
+            let next_snapshot = |i: usize| {
+                pass_dumps[i + 1..]
+                    .iter()
+                    .find(|dump| same_kind(dump, &pass_dumps[i]))
+                    .map(|dump| dump.lines.clone())
+            };
+
             while i < pass_dumps.len() {
                 let mut pass = Pass {
                     name: "".to_string(),
@@ -300,35 +695,72 @@ impl LlvmPassDumpParser {
                     after: String::new(),
                     before: String::new(),
                     ir_changed: true,
+                    invalidated: false,
+                    deleted: false,
+                    before_unknown: false,
+                    after_unknown: false,
+                    renamed_to: None,
+                    run: 1,
+                    runs: 1,
+                    adaptors: Vec::new(),
+                    time: None,
+                    remarks: Vec::new(),
+                    skipped_before: Vec::new(),
+                    skipped_after: Vec::new(),
                 };
                 let current_dump = &pass_dumps[i];
-                let next_dump = if i < pass_dumps.len() - 1 {
-                    Some(&pass_dumps[i + 1])
-                } else {
-                    None
+                // A "before" snapshot is only ever paired with an "after" snapshot of the same
+                // kind, so MIR is never diffed against LLVM IR.
+                let next_dump = pass_dumps.get(i + 1).filter(|next_dump| {
+                    current_dump.header.starts_with("IR Dump Before ")
+                        && next_dump.header.starts_with("IR Dump After ")
+                        && (next_dump.machine == current_dump.machine || next_dump.invalidated)
+                });
+                let paired = match next_dump {
+                    // Only some passes are dumped, the next one may well be another pass
+                    Some(next_dump) if pairing == Pairing::Selective => {
+                        passes_match(&current_dump.header, &next_dump.header).is_ok()
+                    }
+                    Some(next_dump) => {
+                        passes_match(&current_dump.header, &next_dump.header)?;
+                        true
+                    }
+                    None => false,
                 };
 
                 if current_dump.header.starts_with("IR Dump After ") {
-                    pass.name = current_dump.header["IR Dump After ".len()..].to_string();
+                    let name = &current_dump.header["IR Dump After ".len()..];
+                    pass.name = name
+                        .strip_suffix(" (invalidated)")
+                        .unwrap_or(name)
+                        .to_string();
                     pass.after = current_dump.lines.clone();
+                    pass.invalidated = current_dump.invalidated;
+                    pass.deleted = current_dump.deleted;
+                    pass.renamed_to = current_dump.renamed_to.clone();
+                    if pairing != Pairing::Complete {
+                        let previous = previous_snapshot(i);
+                        // With `-print-after-all` alone the previous pass left the IR as is,
+                        // unless another kind of snapshot was dumped in between
+                        pass.before_unknown = pairing == Pairing::Selective
+                            || previous.as_ref().is_none_or(|(index, _)| index + 1 != i);
+                        pass.before = previous.map(|(_, lines)| lines).unwrap_or_default();
+                    }
                     i += 1;
                 } else if current_dump.header.starts_with("IR Dump Before ") {
-                    if let Some(next_dump) = next_dump {
-                        if next_dump.header.starts_with("IR Dump After ") {
-                            passes_match(&current_dump.header, &next_dump.header)?;
-                            assert!(current_dump.machine == next_dump.machine);
-                            pass.name = current_dump.header["IR Dump Before ".len()..].to_string();
-                            pass.before = current_dump.lines.clone();
-                            pass.after = next_dump.lines.clone();
-                            i += 2;
-                        } else {
-                            pass.name = current_dump.header["IR Dump Before ".len()..].to_string();
-                            pass.before = current_dump.lines.clone();
-                            i += 1;
-                        }
+                    pass.name = current_dump.header["IR Dump Before ".len()..].to_string();
+                    pass.before = current_dump.lines.clone();
+                    if let Some(next_dump) = next_dump.filter(|_| paired) {
+                        pass.after = next_dump.lines.clone();
+                        pass.invalidated = next_dump.invalidated;
+                        pass.deleted = next_dump.deleted;
+                        pass.renamed_to = next_dump.renamed_to.clone();
+                        i += 2;
                     } else {
-                        pass.name = current_dump.header["IR Dump Before ".len()..].to_string();
-                        pass.before = current_dump.lines.clone();
+                        if pairing == Pairing::Selective {
+                            pass.after = next_snapshot(i).unwrap_or_else(|| pass.before.clone());
+                            pass.after_unknown = true;
+                        }
                         i += 1;
                     }
                 } else {
@@ -336,14 +768,11 @@ impl LlvmPassDumpParser {
                 }
                 pass.machine = current_dump.machine;
 
-                // handle isel diff, and NOT handle machine-outliner (before != after)
-                if let Some(previous_pass) = passes.last() {
-                    if !previous_pass.machine && pass.machine && pass.before != pass.after {
-                        pass.before = previous_pass.after.clone();
-                    }
+                pass.ir_changed = pass.before != pass.after || pass.invalidated || pass.deleted;
+                // Nothing to compare the first snapshot of a function with
+                if pass.before_unknown && pass.before.is_empty() {
+                    pass.ir_changed = false;
                 }
-
-                pass.ir_changed = pass.before != pass.after;
                 passes.push(pass);
             }
 
@@ -357,27 +786,74 @@ impl LlvmPassDumpParser {
         ir: &str,
         opt_pipeline_options: &OptPipelineBackendOptions,
     ) -> Result<OptPipelineResults, PassDumpError> {
+        match opt_pipeline_options.dialect {
+            Dialect::Mlir => return mlir::breakdown_output(self, ir, false),
+            Dialect::Fir => return mlir::breakdown_output(self, ir, true),
+            Dialect::Sil => return sil::breakdown_output(self, ir),
+            Dialect::Gcc => return gcc::breakdown_output(self, ir),
+            _ => {}
+        }
+
         let raw_passes = self.breakdown_output_into_pass_dumps(ir);
+        self.breakdown_pass_dumps(raw_passes, opt_pipeline_options)
+    }
 
-        if opt_pipeline_options.full_module {
+    fn breakdown_pass_dumps(
+        &self,
+        raw_passes: Vec<PassDump>,
+        opt_pipeline_options: &OptPipelineBackendOptions,
+    ) -> Result<OptPipelineResults, PassDumpError> {
+        if opt_pipeline_options.dialect != Dialect::PassDump {
+            let embedded_diffs = opt_pipeline_options.dialect == Dialect::PrintChangedDiff;
+            Ok(print_changed::breakdown_output(
+                self,
+                raw_passes,
+                embedded_diffs,
+            ))
+        } else if opt_pipeline_options.globals {
+            let pairing = Pairing::of(&raw_passes);
+            let global_dumps = without_invalidated_passes(raw_passes)
+                .into_iter()
+                .filter_map(|mut dump| {
+                    let globals = module_globals(&dump)?;
+                    dump.lines = globals;
+                    Some(dump)
+                })
+                .collect_vec();
+            let mut pass_dumps_by_function = HashMap::new();
+            if !global_dumps.is_empty() {
+                pass_dumps_by_function.insert(GLOBALS.to_string(), global_dumps);
+            }
+            Ok(self.match_pass_dumps(pass_dumps_by_function, pairing)?)
+        } else if opt_pipeline_options.full_module {
+            let pairing = Pairing::of(&raw_passes);
             let pass_dumps_by_function = self.associate_full_dumps_with_functions(raw_passes);
-            Ok(self.match_pass_dumps(pass_dumps_by_function)?)
+            Ok(self.match_pass_dumps(pass_dumps_by_function, pairing)?)
         } else {
             let pass_dumps = raw_passes
                 .into_iter()
                 .map(|dump| self.breakdown_pass_dumps_into_functions(dump))
                 .collect();
-            let pass_dumps_by_function = self.breakdown_into_pass_dumps_by_function(pass_dumps);
-            Ok(self.match_pass_dumps(pass_dumps_by_function)?)
+            self.breakdown_split_pass_dumps(pass_dumps)
         }
     }
 
-    fn apply_ir_filters(
+    fn breakdown_split_pass_dumps(
         &self,
-        ir: &str,
-        opt_pipeline_options: &OptPipelineBackendOptions,
-    ) -> String {
-        let mut inline_filters = vec![r"(?m),? #\d+( \{)?$"];
+        pass_dumps: Vec<SplitPassDump>,
+    ) -> Result<OptPipelineResults, PassDumpError> {
+        let pairing = Pairing::of_banners(
+            pass_dumps
+                .iter()
+                .map(|dump| (dump.header.as_str(), dump.loop_snapshot)),
+        );
+        let pass_dumps_by_function = self.breakdown_into_pass_dumps_by_function(pass_dumps);
+        self.match_pass_dumps(pass_dumps_by_function, pairing)
+    }
+
+    /// Matches what the filters leave out of the IR.
+    fn ir_filter(&self, opt_pipeline_options: &OptPipelineBackendOptions) -> Regex {
+        let mut inline_filters = vec![r"(?m),? #\d+(?P<brace> \{)?$"];
         let mut line_filters = vec![
             r"; ModuleID = '.+'",
             r"(source_filename|target datalayout|target triple) = '.+'",
@@ -391,6 +867,7 @@ impl LlvmPassDumpParser {
 
         let debug_line_filters = [
             r"\s+(tail\s)?call void @llvm\.dbg.+",
+            r"[ \t]+#dbg_\w+\(.+",
             r"[ \t]+DBG_.+",
             r"(!\d+) = (?:distinct )?!DI([A-Za-z]+)\(([^)]+?)\).*", // appended .*
             r"(!\d+) = (?:distinct )?!\{.*\}.*",                    // appended .*
@@ -420,40 +897,32 @@ impl LlvmPassDumpParser {
             .to_string();
 
         let combined = format!("(:?{})|(:?{})", line_re, inline_re);
-        let re = Regex::new(&combined).unwrap();
-
-        re.replace_all(ir, "").to_string()
+        Regex::new(&combined).unwrap()
     }
 
-    fn process<'a>(
+    /// Returns the pipeline of the module preamble, if any pass changed it. The snapshots are
+    /// taken before the filters, which drop the metadata.
+    fn breakdown_module_preamble(
         &self,
-        output: &'a str,
-        opt_pipeline_options: &OptPipelineBackendOptions,
-    ) -> Result<(&'a str, OptPipelineResults), PassDumpError> {
-        let offset = {
-            let mut pos = 0;
-            let newlines = memchr_iter(b'\n', output.as_bytes());
-
-            for newline_pos in newlines {
-                let line = &output[pos..newline_pos];
-                if self.ir_dump_header.is_match(line)
-                    || self.machine_code_dump_header.is_match(line)
-                {
-                    break;
-                }
-                pos = newline_pos + 1;
-            }
-            pos
-        };
-        let ir = &output[offset..];
-        let ir = match opt_pipeline_options.apply_filters {
-            true => &self.apply_ir_filters(ir, opt_pipeline_options),
-            false => ir,
-        };
-        Ok((
-            &output[..offset],
-            self.breakdown_output(ir, opt_pipeline_options)?,
-        ))
+        raw_passes: Vec<PassDump>,
+    ) -> Result<Option<Vec<Pass>>, PassDumpError> {
+        let pairing = Pairing::of(&raw_passes);
+        let preamble_dumps = without_invalidated_passes(raw_passes)
+            .into_iter()
+            .filter_map(|mut dump| {
+                dump.lines = module_preamble(&dump)?;
+                Some(dump)
+            })
+            .collect_vec();
+        if preamble_dumps.is_empty() {
+            return Ok(None);
+        }
+        let mut results = self.match_pass_dumps(
+            HashMap::from([(MODULE_PREAMBLE.to_string(), preamble_dumps)]),
+            pairing,
+        )?;
+        let passes = results.remove(MODULE_PREAMBLE).unwrap_or_default();
+        Ok(passes.iter().any(|pass| pass.ir_changed).then_some(passes))
     }
 }
 
@@ -466,7 +935,19 @@ fn passes_match(before: &str, after: &str) -> Result<(), PassDumpError> {
         after = &after[..after.len() - " (invalidated)".len()];
     }
 
-    if before == after {
+    // CGSCC passes may split or merge the SCC they ran on, so the member list in
+    // the "after" banner can legitimately differ from the one in the "before" banner.
+    let same_scc_pass = matches!(
+        (scc_pass_id(before), scc_pass_id(after)),
+        (Some(before), Some(after)) if before == after
+    );
+
+    // Banners that don't name the IR unit (`IR Pass X invalidated`) only carry the pass ID
+    let unnamed_unit = before
+        .strip_prefix(after)
+        .is_some_and(|unit| unit.starts_with(" on "));
+
+    if before == after || same_scc_pass || unnamed_unit {
         Ok(())
     } else {
         Err(PassDumpError::PassMismatch {
@@ -476,21 +957,557 @@ fn passes_match(before: &str, after: &str) -> Result<(), PassDumpError> {
     }
 }
 
-pub fn process(
-    dump: &str,
-    apply_filters: bool,
-) -> Result<(&str, OptPipelineResults), PassDumpError> {
-    let llvm_pass_dump_parser = LlvmPassDumpParser::new();
-    llvm_pass_dump_parser.process(
-        dump,
-        &OptPipelineBackendOptions {
-            filter_debug_info: true,
-            filter_ir_metadata: true,
-            full_module: false,
-            no_discard_value_names: false,
-            demangle: false,
-            library_functions: false,
-            apply_filters,
-        },
-    )
+/// Returns the functions a pass ran on, or `None` when it ran on the whole module (or the
+/// banner doesn't say), in which case every function in the snapshot counts.
+fn ir_units(header: &str, affected_function: Option<&str>) -> Option<Vec<String>> {
+    if let Some(function) = affected_function {
+        // Legacy `(loop: %x)` banners name the loop, not its function
+        return if function.starts_with('%') {
+            Some(vec!["<loop>".to_string()])
+        } else {
+            Some(vec![function.to_string()])
+        };
+    }
+    let header = header.strip_suffix(" (invalidated)").unwrap_or(header);
+    let (_, unit) = header.rsplit_once(" on ")?;
+    // Unnamed functions (`@0`) have no name to print
+    if unit == "[module]" || unit.is_empty() {
+        None
+    } else if unit.starts_with("Loop at depth ") {
+        // Older new PM releases label loops by depth and block names only
+        Some(vec!["<loop>".to_string()])
+    } else if let Some(members) = unit
+        .strip_prefix('(')
+        .and_then(|unit| unit.strip_suffix(')'))
+    {
+        Some(
+            members
+                .split(", ")
+                .map(|member| escape_name(member).into_owned())
+                .collect(),
+        )
+    } else {
+        Some(vec![escape_name(unit).into_owned()])
+    }
+}
+
+/// With `-print-module-scope` every snapshot is the whole module, returns the functions the
+/// pass actually ran on when the snapshot holds others as well.
+fn module_scope_units(
+    pass: &SplitPassDump,
+    previous_function: &Option<String>,
+) -> Option<Vec<String>> {
+    let units = ir_units(&pass.header, pass.affected_function.as_deref())?
+        .into_iter()
+        .map(|unit| match unit.as_str() {
+            // Loops of older releases belong to the function processed before
+            "<loop>" => previous_function.clone().unwrap_or(unit),
+            _ => unit,
+        })
+        .collect_vec();
+    let known = units.iter().all(|unit| pass.functions.contains_key(unit));
+    (known && pass.functions.len() > units.len()).then_some(units)
+}
+
+/// How "before" and "after" snapshots follow each other in a dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pairing {
+    /// `-print-before-all -print-after-all`, every pass is dumped on both sides. Only loop
+    /// passes that deleted their loop lack an "after" dump.
+    Complete,
+    /// `-print-before=`/`-print-after=`, there are gaps between snapshots and a "before" dump
+    /// isn't necessarily followed by its "after" dump
+    Selective,
+    /// `-print-after-all` alone, each snapshot is the "before" of the next pass
+    AfterOnly,
+}
+
+impl Pairing {
+    fn of(pass_dumps: &[PassDump]) -> Self {
+        Pairing::of_banners(
+            pass_dumps
+                .iter()
+                .map(|dump| (dump.header.as_str(), is_loop_snapshot(dump))),
+        )
+    }
+
+    /// Tells the pairing from the banners of the snapshots, and whether each one is of a loop.
+    fn of_banners<'a>(snapshots: impl IntoIterator<Item = (&'a str, bool)>) -> Self {
+        let pass_id = |header: &str| {
+            let header = header.strip_suffix(" (invalidated)").unwrap_or(header);
+            header
+                .rsplit_once(" on ")
+                .map_or(header, |(pass_id, _)| pass_id)
+                .to_string()
+        };
+        let mut before = HashSet::new();
+        let mut before_without_loops = HashSet::new();
+        let mut after = HashSet::new();
+        for (header, loop_snapshot) in snapshots {
+            if let Some(header) = header.strip_prefix("IR Dump Before ") {
+                before.insert(pass_id(header));
+                if !loop_snapshot {
+                    before_without_loops.insert(pass_id(header));
+                }
+            } else if let Some(header) = header.strip_prefix("IR Dump After ") {
+                after.insert(pass_id(header));
+            }
+        }
+        if before.is_empty() {
+            Pairing::AfterOnly
+        } else if !after.is_subset(&before) || !before_without_loops.is_subset(&after) {
+            Pairing::Selective
+        } else {
+            Pairing::Complete
+        }
+    }
+}
+
+fn is_loop_snapshot(dump: &PassDump) -> bool {
+    let lines = dump.lines.trim_start();
+    lines.starts_with("; Preheader:") || lines.starts_with("; Loop:")
+}
+
+/// The IR of a function after each of its dumps, when passes aren't dumped before they run: a
+/// loop pass only dumps its loop, which is spliced into the function as the previous snapshot
+/// left it, so the next function pass isn't blamed for what the loop pass did. None after the
+/// dumps that leave the function unknown, and after those of Machine IR.
+fn function_snapshots(pass_dumps: &[PassDump]) -> Vec<Option<String>> {
+    let mut function: Option<String> = None;
+    pass_dumps
+        .iter()
+        .map(|dump| {
+            if dump.machine {
+                return None;
+            }
+            function = match is_loop_snapshot(dump) {
+                _ if dump.invalidated || dump.deleted => None,
+                true => function.as_ref().map(|function| {
+                    print_changed::Blocks::parse(function)
+                        .with_loop(&print_changed::Blocks::parse(&dump.lines), &[])
+                        .text()
+                }),
+                false => Some(dump.lines.clone()),
+            };
+            function.clone()
+        })
+        .collect()
+}
+
+/// Leaves out the passes whose "after" snapshot was invalidated, they show nothing of the module.
+fn without_invalidated_passes(raw_passes: Vec<PassDump>) -> Vec<PassDump> {
+    let mut dumps: Vec<PassDump> = Vec::new();
+    for dump in raw_passes {
+        if dump.invalidated {
+            if dumps
+                .last()
+                .is_some_and(|last| last.header.starts_with("IR Dump Before "))
+            {
+                dumps.pop();
+            }
+        } else {
+            dumps.push(dump);
+        }
+    }
+    dumps
+}
+
+/// Returns the global variables, aliases and ifuncs defined in a snapshot of the whole module,
+/// or `None` if the snapshot is only of some functions.
+fn module_globals(dump: &PassDump) -> Option<String> {
+    if dump.machine || dump.invalidated {
+        return None;
+    }
+    let mut globals = Vec::new();
+    let mut in_function = false;
+    for line in dump.lines.lines() {
+        if line.starts_with("define ") {
+            in_function = true;
+        } else if line.starts_with('}') {
+            in_function = false;
+        } else if !in_function && line.starts_with('@') {
+            globals.push(line);
+        }
+    }
+    let module_pass = ir_units(&dump.header, dump.affected_function.as_deref()).is_none();
+    (module_pass || !globals.is_empty()).then(|| globals.join("\n"))
+}
+
+/// Leaves out the functions of a snapshot, the lines the module preamble is taken from remain.
+fn outside_functions(dump: PassDump) -> PassDump {
+    let mut lines = String::new();
+    let mut in_function = false;
+    for line in dump.lines.lines() {
+        if line.starts_with("define ") {
+            in_function = true;
+        } else if line.starts_with('}') {
+            in_function = false;
+        } else if !in_function {
+            lines += line;
+            lines.push('\n');
+        }
+    }
+    PassDump { lines, ..dump }
+}
+
+/// Returns the target description and the named metadata of a module snapshot, with the nodes
+/// the metadata refers to inlined so that renumbering them doesn't count as a change.
+fn module_preamble(dump: &PassDump) -> Option<String> {
+    if dump.machine || dump.invalidated {
+        return None;
+    }
+    let named_metadata = Regex::new(r"^!([A-Za-z._$][\w.$-]*) = (?:distinct )?!\{(.*)\}$").unwrap();
+    let node_ref = Regex::new(r"!\d+").unwrap();
+
+    let mut targets = Vec::new();
+    let mut named = Vec::new();
+    let mut nodes = HashMap::new();
+    let mut in_function = false;
+    for line in dump.lines.lines() {
+        if line.starts_with("define ") {
+            in_function = true;
+        } else if line.starts_with('}') {
+            in_function = false;
+        } else if in_function {
+            continue;
+        } else if line.starts_with("source_filename = ") || line.starts_with("target ") {
+            targets.push(line.to_string());
+        } else if let Some(caps) = named_metadata.captures(line) {
+            named.push((caps.get(1).unwrap().as_str(), caps.get(2).unwrap().as_str()));
+        } else if let Some((node, value)) = line.split_once(" = ") {
+            if node.len() > 1
+                && node.starts_with('!')
+                && node[1..].bytes().all(|b| b.is_ascii_digit())
+            {
+                nodes.insert(node, value);
+            }
+        }
+    }
+
+    let mut preamble = targets;
+    for (name, operands) in named {
+        preamble.push(format!("!{}", name));
+        for operand in node_ref.find_iter(operands) {
+            let value = nodes
+                .get(operand.as_str())
+                .copied()
+                .unwrap_or(operand.as_str());
+            preamble.push(format!("  {}", value));
+        }
+    }
+    let module_pass = ir_units(&dump.header, dump.affected_function.as_deref()).is_none();
+    (module_pass || !preamble.is_empty()).then(|| preamble.join("\n"))
+}
+
+/// Counts how many times each pass ran in a pipeline. The IR unit a pass ran on isn't part of
+/// the pass, `LICMPass on loop %a` and `LICMPass on loop %b` are two runs of the same pass.
+fn number_runs(passes: &mut [Pass]) {
+    let pass_id = |name: &str| {
+        name.split_once(" on ")
+            .map_or(name, |(id, _)| id)
+            .to_string()
+    };
+    let mut runs: HashMap<String, usize> = HashMap::new();
+    for pass in passes.iter_mut() {
+        let run = runs.entry(pass_id(&pass.name)).or_default();
+        *run += 1;
+        pass.run = *run;
+    }
+    for pass in passes.iter_mut() {
+        pass.runs = runs[&pass_id(&pass.name)];
+    }
+}
+
+/// Returns the pass ID of a CGSCC banner such as `InlinerPass on (foo, bar)`.
+fn scc_pass_id(header: &str) -> Option<&str> {
+    let (pass_id, members) = header.rsplit_once(" on (")?;
+    members.ends_with(')').then_some(pass_id)
+}
+
+/// How the dump is broken down into pipelines.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProcessOptions {
+    /// Diff whole-module snapshots instead of the functions in them
+    pub full_module: bool,
+    /// Keep the text found between the snapshots that isn't part of the dumps
+    pub keep_noise: bool,
+    /// A single pipeline of the module-level definitions, instead of one per function
+    pub globals: bool,
+    /// Show the attributes of the attribute groups where they're used
+    pub expand_attributes: bool,
+    /// Keep the snapshots as dumped too, before the filters, for those the viewer writes
+    pub keep_dumped: bool,
+}
+
+/// The pipelines of a dump read as `optdiff` reads a file, for the tests of the dialects.
+#[cfg(test)]
+fn parse(dump: &str) -> OptPipelineResults {
+    let mut stream = DumpStream::new(true, &ProcessOptions::default());
+    for line in dump.split_inclusive('\n') {
+        stream.push_line(line);
+    }
+    stream
+        .finish()
+        .modules
+        .into_iter()
+        .flat_map(|module| module.pipelines().unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, DumpStream, ProcessOptions};
+
+    #[test]
+    fn before_and_after_snapshots_are_paired() {
+        let pipelines = parse(
+            "\
+*** IR Dump Before InlinerPass on (f) ***
+define i32 @f(i32 %x) {
+  %r = call i32 @g(i32 %x)
+  ret i32 %r
+}
+*** IR Dump After InlinerPass on (f) ***
+define i32 @f(i32 %x) {
+  ret i32 %x
+}
+*** IR Dump Before InstCombinePass on f ***
+define i32 @f(i32 %x) {
+  ret i32 %x
+}
+*** IR Dump After InstCombinePass on f ***
+define i32 @f(i32 %x) {
+  ret i32 %x
+}
+",
+        );
+        let passes = &pipelines["f"];
+        let names: Vec<_> = passes.iter().map(|pass| pass.name.as_str()).collect();
+        assert_eq!(names, ["InlinerPass on (f)", "InstCombinePass on f"]);
+        assert!(passes[0].ir_changed);
+        assert_eq!(
+            passes[0].before,
+            "define i32 @f(i32 %x) {\n  %r = call i32 @g(i32 %x)\n  ret i32 %r\n}"
+        );
+        assert_eq!(passes[0].after, "define i32 @f(i32 %x) {\n  ret i32 %x\n}");
+        assert!(!passes[1].ir_changed);
+        assert!(!passes[0].before_unknown && !passes[1].before_unknown);
+    }
+
+    #[test]
+    fn after_snapshots_alone_are_diffed_against_the_previous_one() {
+        let pipelines = parse(
+            "\
+*** IR Dump After InstCombinePass on f ***
+define i32 @f(i32 %x) {
+  %a = add i32 %x, 1
+  ret i32 %a
+}
+*** IR Dump After GVNPass on f ***
+define i32 @f(i32 %x) {
+  ret i32 %x
+}
+",
+        );
+        let passes = &pipelines["f"];
+        assert!(passes[0].before_unknown);
+        assert_eq!(passes[0].before, "");
+        assert!(!passes[1].before_unknown);
+        assert_eq!(passes[1].before, passes[0].after);
+        assert!(passes[1].ir_changed);
+    }
+
+    #[test]
+    fn loop_passes_dumped_alone_are_spliced_into_their_function() {
+        let pipelines = parse(
+            "\
+*** IR Dump After LCSSAPass on f ***
+define i32 @f(i32 %n) {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %inc, %loop ]
+  %m = mul i32 %n, 3
+  %inc = add i32 %i, %m
+  br i1 true, label %loop, label %exit
+
+exit:
+  ret i32 %i
+}
+*** IR Dump After LICMPass on loop %loop in function f ***
+; Preheader:
+entry:
+  %m = mul i32 %n, 3
+  br label %loop
+
+; Loop:
+loop:
+  %i = phi i32 [ 0, %entry ], [ %inc, %loop ]
+  %inc = add i32 %i, %m
+  br i1 true, label %loop, label %exit
+*** IR Dump After SimplifyCFGPass on f ***
+define i32 @f(i32 %n) {
+entry:
+  %m = mul i32 %n, 3
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %inc, %loop ]
+  %inc = add i32 %i, %m
+  br i1 true, label %loop, label %exit
+
+exit:
+  ret i32 %i
+}
+",
+        );
+        let passes = &pipelines["f"];
+        let names: Vec<_> = passes.iter().map(|pass| pass.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "LCSSAPass on f",
+                "LICMPass on loop %loop",
+                "SimplifyCFGPass on f"
+            ]
+        );
+        assert!(!passes[1].before_unknown && passes[1].ir_changed);
+        assert!(passes[1]
+            .before
+            .starts_with("; Preheader:\nentry:\n  br label %loop"));
+        assert!(!passes[2].before_unknown);
+        assert!(!passes[2].ir_changed);
+        assert!(passes[2]
+            .before
+            .starts_with("define i32 @f(i32 %n) {\nentry:\n  %m = mul i32 %n, 3"));
+    }
+
+    #[test]
+    fn before_snapshots_take_over_from_earlier_after_snapshots() {
+        let pipelines = parse(
+            "\
+*** IR Dump After InstCombinePass on f ***
+define i32 @f(i32 %x) {
+  %a = add i32 %x, 0
+  ret i32 %a
+}
+*** IR Dump Before GVNPass on f ***
+define i32 @f(i32 %x) {
+  %a = add i32 %x, 1
+  ret i32 %a
+}
+*** IR Dump After GVNPass on f ***
+define i32 @f(i32 %x) {
+  ret i32 %x
+}
+",
+        );
+        let passes = &pipelines["f"];
+        assert_eq!(passes.len(), 2);
+        assert!(passes[0].before_unknown);
+        assert!(!passes[1].before_unknown);
+        assert_eq!(
+            passes[1].before,
+            "define i32 @f(i32 %x) {\n  %a = add i32 %x, 1\n  ret i32 %a\n}"
+        );
+        assert_eq!(passes[1].after, "define i32 @f(i32 %x) {\n  ret i32 %x\n}");
+    }
+
+    #[test]
+    fn loop_banners_belong_to_their_function() {
+        let pipelines = parse(
+            "\
+*** IR Dump Before LICMPass on loop %loop in function f ***
+; Preheader:
+entry:
+  br label %loop
+
+; Loop:
+loop:
+  %i = phi i32 [ 0, %entry ], [ %inc, %loop ]
+  %m = mul i32 %n, 3
+  %inc = add i32 %i, %m
+  br i1 true, label %loop, label %exit
+*** IR Dump After LICMPass on loop %loop in function f ***
+; Preheader:
+entry:
+  %m = mul i32 %n, 3
+  br label %loop
+
+; Loop:
+loop:
+  %i = phi i32 [ 0, %entry ], [ %inc, %loop ]
+  %inc = add i32 %i, %m
+  br i1 true, label %loop, label %exit
+*** IR Dump Before LoopDeletionPass on loop %loop in function f ***
+; Preheader:
+entry:
+  %m = mul i32 %n, 3
+  br label %loop
+*** IR Dump After LoopDeletionPass on loop %loop in function f (invalidated) ***
+",
+        );
+        let passes = &pipelines["f"];
+        let names: Vec<_> = passes.iter().map(|pass| pass.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["LICMPass on loop %loop", "LoopDeletionPass on loop %loop"]
+        );
+        assert!(passes[0].ir_changed && !passes[0].invalidated);
+        assert!(passes[0]
+            .after
+            .starts_with("; Preheader:\nentry:\n  %m = mul i32 %n, 3"));
+        assert!(passes[1].invalidated);
+        assert_eq!(passes[1].after, "");
+    }
+
+    #[test]
+    fn define_lines_keep_their_brace_without_the_attribute_group() {
+        let pipelines = parse(
+            "\
+*** IR Dump Before InstCombinePass on f ***
+define i32 @f(i32 %x) #0 {
+  %a = add i32 %x, 0
+  ret i32 %a
+}
+*** IR Dump After InstCombinePass on f ***
+define i32 @f(i32 %x) #0 {
+  ret i32 %x
+}
+",
+        );
+        let pass = &pipelines["f"][0];
+        assert_eq!(
+            pass.before,
+            "define i32 @f(i32 %x) {\n  %a = add i32 %x, 0\n  ret i32 %a\n}"
+        );
+        assert_eq!(pass.after, "define i32 @f(i32 %x) {\n  ret i32 %x\n}");
+    }
+
+    #[test]
+    fn snapshots_are_kept_as_dumped_for_the_viewer() {
+        let options = ProcessOptions {
+            keep_dumped: true,
+            ..ProcessOptions::default()
+        };
+        let mut stream = DumpStream::new(true, &options);
+        let dump = "\
+*** IR Dump After InstCombinePass on f ***
+define i32 @f(i32 %x) !dbg !4 {
+  ret i32 %x, !dbg !5
+}
+";
+        for line in dump.split_inclusive('\n') {
+            stream.push_line(line);
+        }
+        let mut module = stream.finish().modules.pop().unwrap();
+        let dumped = module.dumped_pipelines().unwrap().unwrap();
+        let shown = module.pipelines().unwrap();
+        assert_eq!(
+            dumped["f"][0].after,
+            "define i32 @f(i32 %x) !dbg !4 {\n  ret i32 %x, !dbg !5\n}"
+        );
+        assert!(!shown["f"][0].after.contains("!dbg"));
+    }
 }
@@ -0,0 +1,64 @@
+//! Functions and calls refer to their attributes by group (`#0`), defined at the end of the
+//! module. A pass that adds an attribute moves the function to another group, which only shows as
+//! `#0` becoming `#1`. The groups are expanded where they're used so that the attributes
+//! themselves are compared.
+//!
+//! Snapshots of a function don't hold the groups. The attributes of a definition are taken from
+//! the `; Function Attrs:` comment printed above it, the groups of calls from the last snapshot of
+//! the module that defined them.
+
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+
+pub(super) struct AttributeGroups {
+    definition: Regex,
+    reference: Regex,
+    /// The attributes of the groups defined so far, by number
+    groups: HashMap<String, String>,
+}
+
+impl AttributeGroups {
+    pub(super) fn new() -> Self {
+        AttributeGroups {
+            definition: Regex::new(r"(?m)^attributes #(\d+) = \{ (.*) \}$").unwrap(),
+            reference: Regex::new(r" #(\d+)\b").unwrap(),
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Replaces the attribute groups the snapshot refers to with the attributes they hold, the
+    /// groups not known yet are kept.
+    pub(super) fn expand(&mut self, ir: &str) -> String {
+        for caps in self.definition.captures_iter(ir) {
+            self.groups.insert(caps[1].to_string(), caps[2].to_string());
+        }
+        let mut expanded = String::with_capacity(ir.len());
+        let mut function_attrs = None;
+        for line in ir.lines() {
+            if let Some(attrs) = line.strip_prefix("; Function Attrs: ") {
+                function_attrs = Some(attrs);
+            } else if !line.starts_with("attributes #") {
+                let of_definition = line.starts_with("define ");
+                let attrs = |caps: &Captures<'_>| match (of_definition, function_attrs) {
+                    (true, Some(attrs)) => format!(" {}", attrs),
+                    _ => match self.groups.get(&caps[1]) {
+                        Some(attrs) => format!(" {}", attrs),
+                        None => caps[0].to_string(),
+                    },
+                };
+                expanded += &self.reference.replace_all(line, attrs);
+                expanded.push('\n');
+                if !line.starts_with(';') {
+                    function_attrs = None;
+                }
+                continue;
+            }
+            expanded += line;
+            expanded.push('\n');
+        }
+        if !ir.ends_with('\n') {
+            expanded.pop();
+        }
+        expanded
+    }
+}
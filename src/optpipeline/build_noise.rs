@@ -0,0 +1,76 @@
+//! Piping `cargo build` into optdiff interleaves the dumps with what cargo and rustc print on
+//! their own: status lines (`   Compiling foo v0.1.0`), the progress bar and diagnostics.
+
+use regex::Regex;
+use std::borrow::Cow;
+
+pub(super) struct BuildNoise {
+    status: Regex,
+    diagnostic: Regex,
+    diagnostic_body: Regex,
+    rustc_hint: Regex,
+}
+
+impl BuildNoise {
+    pub(super) fn new() -> Self {
+        BuildNoise {
+            // Cargo right-aligns its status in 12 columns
+            status: Regex::new(
+                r"^ *(?:Adding|Blocking|Building|Checking|Compiling|Documenting|Downloaded|Downloading|Finished|Fresh|Installing|Locking|Removing|Running|Updating|Waiting) \S",
+            )
+            .unwrap(),
+            diagnostic: Regex::new(r"^(?:warning|error)(?:\[\w+\])?: ").unwrap(),
+            // Source snippets, `-->` locations and `= note:` lines
+            diagnostic_body: Regex::new(
+                r"^\s*(?:-->|:::|\||=|\.\.\.|\d+ +\||(?:note|help)(?:\[\w+\])?:)",
+            )
+            .unwrap(),
+            rustc_hint: Regex::new(
+                r"^(?:For more information about (?:this|an) error|Some errors have detailed explanations)",
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Drops the lines cargo and rustc print between the dumps.
+    pub(super) fn strip<'a>(&self, ir: &'a str) -> Cow<'a, str> {
+        let BuildNoise {
+            status,
+            diagnostic,
+            diagnostic_body,
+            rustc_hint,
+        } = self;
+        let is_noise =
+            |line: &str| line.contains('\r') || status.is_match(line) || diagnostic.is_match(line);
+        if !ir.lines().any(is_noise) {
+            return Cow::Borrowed(ir);
+        }
+
+        let mut stripped = String::with_capacity(ir.len());
+        let mut in_diagnostic = false;
+        for line in ir.lines() {
+            // The progress bar is redrawn with carriage returns, and erased before anything else
+            let line = line
+                .rsplit('\r')
+                .next()
+                .unwrap_or(line)
+                .trim_start_matches("\x1b[K");
+            if diagnostic.is_match(line) {
+                in_diagnostic = true;
+                continue;
+            }
+            if in_diagnostic {
+                if line.trim().is_empty() || diagnostic_body.is_match(line) {
+                    continue;
+                }
+                in_diagnostic = false;
+            }
+            if status.is_match(line) || rustc_hint.is_match(line) {
+                continue;
+            }
+            stripped += line;
+            stripped.push('\n');
+        }
+        Cow::Owned(stripped)
+    }
+}
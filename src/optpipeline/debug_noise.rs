@@ -0,0 +1,109 @@
+//! `-debug`, `-debug-only=...` and `-pass-remarks` print to stderr as the passes run, so their
+//! output ends up between the snapshots. Snapshots only hold IR, everything around the functions
+//! and machine functions that isn't module-level IR is left out.
+
+use regex::Regex;
+use std::borrow::Cow;
+
+#[derive(PartialEq)]
+enum State {
+    /// Between the functions of a snapshot, or after the last one
+    Outside,
+    /// Loop passes print the blocks of the loop on their own
+    LoopBlocks,
+    Function,
+    MachineFunction,
+}
+
+/// Whether `line` can be found in a module outside of the functions.
+fn is_module_level(line: &str) -> bool {
+    const PREFIXES: &[&str] = &[
+        ";",
+        "@",
+        "%",
+        "$",
+        "!",
+        "^",
+        "declare ",
+        "attributes ",
+        "source_filename ",
+        "target ",
+        "module asm ",
+        "uselistorder",
+    ];
+    line.trim().is_empty() || PREFIXES.iter().any(|prefix| line.starts_with(prefix))
+}
+
+pub(super) struct DebugNoise {
+    /// Block labels, as loop passes print them
+    label: Regex,
+}
+
+impl DebugNoise {
+    pub(super) fn new() -> Self {
+        DebugNoise {
+            label: Regex::new(r#"^(?:"[^"]*"|[\w.$-]+):\s*(?:;.*)?$"#).unwrap(),
+        }
+    }
+
+    /// Drops what was printed between the snapshots.
+    pub(super) fn strip<'a>(&self, ir: &'a str) -> Cow<'a, str> {
+        let mut stripped = String::with_capacity(ir.len());
+        let mut state = State::Outside;
+        let mut dropped = false;
+        for line in ir.lines() {
+            let is_banner = line.starts_with("*** ")
+                || line.starts_with("; *** ")
+                || line.starts_with("# *** ");
+            let keep = if is_banner {
+                state = State::Outside;
+                true
+            } else {
+                match state {
+                    State::Function => {
+                        if line.starts_with('}') {
+                            state = State::Outside;
+                        }
+                        true
+                    }
+                    State::MachineFunction => {
+                        if line.starts_with("# End machine code for function ") {
+                            state = State::Outside;
+                        }
+                        true
+                    }
+                    State::Outside | State::LoopBlocks => {
+                        if line.starts_with("define ") {
+                            state = State::Function;
+                            true
+                        } else if line.starts_with("# Machine code for function ") {
+                            state = State::MachineFunction;
+                            true
+                        } else if line.starts_with("; Preheader:") || line.starts_with("; Loop:") {
+                            state = State::LoopBlocks;
+                            true
+                        } else if state == State::LoopBlocks
+                            && (line.starts_with([' ', '\t']) || self.label.is_match(line))
+                        {
+                            true
+                        } else {
+                            is_module_level(line)
+                        }
+                    }
+                }
+            };
+            if keep {
+                stripped += line;
+                stripped.push('\n');
+            } else {
+                dropped = true;
+            }
+        }
+
+        if dropped {
+            Cow::Owned(stripped)
+        } else {
+            Cow::Borrowed(ir)
+        }
+    }
+}
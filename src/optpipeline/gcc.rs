@@ -0,0 +1,223 @@
+//! GCC's `-fdump-tree-all` and `-fdump-rtl-all` write the IR after every pass to its own file,
+//! named `<source>.<pass number><t|r>.<pass>`. The files are put back together in pass order,
+//! each one behind a `;;; GCC dump file: 034t.ccp1` banner, and split into their
+//! `;; Function name (asm name, ...)` sections.
+
+use super::{LlvmPassDumpParser, OptPipelineResults, Pairing, PassDump, PassDumpError};
+use itertools::Itertools;
+use regex::Regex;
+use std::collections::HashMap;
+
+const BANNER_PREFIX: &str = ";;; GCC dump file: ";
+
+/// The banner put in front of the contents of a dump file, `pass` being e.g. `034t.ccp1`.
+pub fn banner(pass: &str) -> String {
+    format!("{}{}", BANNER_PREFIX, pass)
+}
+
+pub(super) fn is_banner(line: &str) -> bool {
+    line.starts_with(BANNER_PREFIX)
+}
+
+/// Finds the GIMPLE bodies, `int square (int x)` followed by a block, and the lines of their
+/// signatures. Passes log what they did around them.
+fn tree_bodies<'a>(lines: &[&'a str]) -> Vec<(Option<&'a str>, Vec<&'a str>)> {
+    let mut bodies: Vec<(Option<&str>, Vec<&str>)> = Vec::new();
+    let mut body: Option<Vec<&str>> = None;
+    let mut previous: Option<&str> = None;
+    for &line in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(lines) = body.as_mut() {
+            lines.push(line);
+            if line.starts_with('}') {
+                let signature = lines.first().copied().filter(|line| !line.starts_with('{'));
+                bodies.push((signature, body.take().unwrap()));
+            }
+        } else if line.starts_with('{') {
+            // `.original` dumps only print the block
+            body = Some(match previous.filter(|line| !line.starts_with(";;")) {
+                Some(signature) => vec![signature, line],
+                None => vec![line],
+            });
+        }
+        previous = Some(line);
+    }
+    bodies
+}
+
+/// Keeps the insns of an RTL dump, the first line of an insn starts with a parenthesis and the
+/// others are indented. Some passes print the insn chain more than once, the last one is the IR
+/// after the pass.
+fn rtl_insns<'a>(lines: &[&'a str]) -> Vec<&'a str> {
+    let mut insns = Vec::new();
+    let mut in_insn = false;
+    for &line in lines {
+        if line.starts_with('(') {
+            in_insn = true;
+        } else if !(in_insn && line.starts_with([' ', '\t'])) {
+            in_insn = false;
+            continue;
+        }
+        insns.push(line);
+    }
+    // The first insn of a chain has no previous insn, `(note 1 0 4 NOTE_INSN_DELETED)`
+    let chain_start = insns.iter().rposition(|line| {
+        let mut fields = line.split(' ').skip(2);
+        line.starts_with('(') && fields.next() == Some("0")
+    });
+    insns.split_off(chain_start.unwrap_or(0))
+}
+
+/// Leaves out what the pass logged, the IR is whatever is left when there's none.
+fn function_ir(lines: &[&str], rtl: bool) -> String {
+    let ir = if rtl {
+        rtl_insns(lines)
+    } else {
+        tree_bodies(lines)
+            .into_iter()
+            .flat_map(|(_, body)| body)
+            .collect()
+    };
+    if ir.is_empty() {
+        lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .join("\n")
+    } else {
+        ir.join("\n")
+    }
+}
+
+/// Splits a dump file into its functions, in order.
+fn split_functions(
+    function_header: &Regex,
+    signature: &Regex,
+    lines: &[&str],
+    rtl: bool,
+) -> Vec<(String, String)> {
+    let mut functions: Vec<(String, Vec<&str>)> = Vec::new();
+    for &line in lines {
+        if let Some(caps) = function_header.captures(line) {
+            // The assembler name tells overloads apart, `.original` dumps don't have one yet
+            let name = match &caps[2] {
+                "null" => &caps[1],
+                asm_name => asm_name,
+            };
+            functions.push((name.to_string(), Vec::new()));
+        } else if let Some((_, body)) = functions.last_mut() {
+            body.push(line);
+        }
+    }
+
+    // The `.gimple` dump has no function headers, only the functions one after the other
+    if functions.is_empty() && !rtl {
+        return tree_bodies(lines)
+            .into_iter()
+            .filter_map(|(line, body)| {
+                let name = signature.captures(line?)?[1].to_string();
+                Some((name, body.join("\n")))
+            })
+            .collect();
+    }
+
+    functions
+        .into_iter()
+        .map(|(name, body)| (name, function_ir(&body, rtl)))
+        .collect()
+}
+
+pub(super) fn breakdown_output(
+    parser: &LlvmPassDumpParser,
+    ir: &str,
+) -> Result<OptPipelineResults, PassDumpError> {
+    let function_header = Regex::new(r"^;; Function (.+) \(([^,()]+)(?:,[^()]*)?\)\s*$").unwrap();
+    let signature = Regex::new(r"([\w.$]+) \(").unwrap();
+
+    // Every file is the IR after its pass
+    let mut snapshots: Vec<(String, Vec<&str>)> = Vec::new();
+    for line in ir.lines() {
+        if let Some(pass) = line.strip_prefix(BANNER_PREFIX) {
+            snapshots.push((pass.trim().to_string(), Vec::new()));
+        } else if let Some((_, lines)) = snapshots.last_mut() {
+            lines.push(line);
+        }
+    }
+
+    let mut pass_dumps_by_function: HashMap<String, Vec<PassDump>> = HashMap::new();
+    for (pass, lines) in snapshots {
+        // RTL is only compared with RTL, the first RTL pass is the expansion of GIMPLE
+        let rtl = pass
+            .split_once('.')
+            .is_some_and(|(number, _)| number.ends_with('r'));
+        let header = format!("IR Dump After {}", pass);
+        for (name, function) in split_functions(&function_header, &signature, &lines, rtl) {
+            let mut dump = PassDump::new(&header, function);
+            dump.machine = rtl;
+            pass_dumps_by_function.entry(name).or_default().push(dump);
+        }
+    }
+
+    parser.match_pass_dumps(pass_dumps_by_function, Pairing::AfterOnly)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::optpipeline::parse;
+
+    #[test]
+    fn dump_files_follow_each_other() {
+        let pipelines = parse(
+            "\
+;;; GCC dump file: 034t.ccp1
+
+;; Function sq (sq, funcdef_no=0, decl_uid=2738, cgraph_uid=1, symbol_order=0)
+
+int sq (int x)
+{
+  int y;
+  int _3;
+
+  <bb 2> :
+  _3 = x_1(D) * x_1(D);
+  return _3;
+
+}
+
+
+;;; GCC dump file: 252t.optimized
+
+;; Function sq (sq, funcdef_no=0, decl_uid=2738, cgraph_uid=1, symbol_order=0)
+
+int sq (int x)
+{
+  int _2;
+
+  <bb 2> [local count: 1073741824]:
+  _2 = x_1(D) * x_1(D);
+  return _2;
+
+}
+
+",
+        );
+        let passes = &pipelines["sq"];
+        let names: Vec<_> = passes.iter().map(|pass| pass.name.as_str()).collect();
+        assert_eq!(names, ["034t.ccp1", "252t.optimized"]);
+        assert!(passes[0].before_unknown);
+        assert_eq!(passes[1].before, passes[0].after);
+        assert!(passes[1].ir_changed);
+        assert_eq!(
+            passes[1].after,
+            "\
+int sq (int x)
+{
+  int _2;
+  <bb 2> [local count: 1073741824]:
+  _2 = x_1(D) * x_1(D);
+  return _2;
+}"
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! `mlir-opt --mlir-print-ir-before-all --mlir-print-ir-after-all` prints banners such as
+//! `// -----// IR Dump Before CSE (cse) //----- //`, followed by the operation the pass ran on.
+//! Snapshots are split into the top-level operations carrying a symbol name (`func.func @foo`,
+//! `gpu.module @kernels`, ...), and then paired like LLVM dumps.
+//!
+//! Flang's FIR and HLFIR dumps are MLIR dumps as well. Fortran modules are flattened into
+//! mangled names (`_QMmodPsub`), and the module is full of globals for constants and type
+//! descriptors, so only procedures get a pipeline.
+
+use super::{
+    LlvmPassDumpParser, OptPipelineResults, Pairing, PassDump, PassDumpError, UNKNOWN_FUNCTION,
+};
+use regex::Regex;
+use std::collections::HashMap;
+
+const BANNER_PREFIX: &str = "// -----// ";
+const BANNER_SUFFIX: &str = " //----- //";
+
+pub(super) fn is_banner(line: &str) -> bool {
+    line.starts_with(BANNER_PREFIX) && line.ends_with(BANNER_SUFFIX)
+}
+
+struct Snapshot {
+    header: String,
+    /// With `--mlir-print-ir-module-scope` the banner names the symbol the pass ran on
+    symbol: Option<String>,
+    lines: Vec<String>,
+}
+
+fn breakdown_output_into_snapshots(ir: &str) -> Vec<Snapshot> {
+    let mut snapshots: Vec<Snapshot> = Vec::new();
+    for line in ir.lines() {
+        if is_banner(line) {
+            let banner = &line[BANNER_PREFIX.len()..line.len() - BANNER_SUFFIX.len()];
+            // `IR Dump After CSE Failed (cse)` is still the IR after the pass
+            let banner = banner.replacen(" Failed (", " (", 1);
+            let (header, symbol) = match banner.rsplit_once(" ('") {
+                Some((header, operation)) => (
+                    header.to_string(),
+                    operation
+                        .split_once(": @")
+                        .map(|(_, symbol)| symbol.trim_end_matches(')').to_string()),
+                ),
+                None => (banner, None),
+            };
+            snapshots.push(Snapshot {
+                header,
+                symbol,
+                lines: Vec::new(),
+            });
+        } else if let Some(snapshot) = snapshots.last_mut() {
+            if !line.trim().is_empty() {
+                snapshot.lines.push(line.to_string());
+            }
+        }
+    }
+    snapshots
+}
+
+/// Splits a snapshot into its top-level operations with a symbol name, in order.
+fn split_operations(
+    symbol_op: &Regex,
+    lines: &[String],
+    procedures_only: bool,
+) -> Vec<(String, String)> {
+    // Functions are indented in a module, but not when printed on their own
+    let Some(indent) = lines
+        .iter()
+        .filter_map(|line| symbol_op.captures(line))
+        .map(|caps| caps[1].len())
+        .min()
+    else {
+        return Vec::new();
+    };
+
+    let mut operations = Vec::new();
+    let mut operation: Option<(String, Vec<&str>)> = None;
+    for line in lines {
+        // Indented or not, the same operation should read the same
+        let line = line.get(indent..).unwrap_or(line.trim_start());
+        if let Some((name, body)) = operation.as_mut() {
+            body.push(line);
+            if line.starts_with('}') {
+                operations.push((std::mem::take(name), body.join("\n")));
+                operation = None;
+            }
+            continue;
+        }
+        let Some(caps) = symbol_op.captures(line).filter(|caps| caps[1].is_empty()) else {
+            continue;
+        };
+        let name = caps[3].trim_matches('"').to_string();
+        if procedures_only && &caps[2] != "func.func" {
+            continue;
+        }
+        if line.trim_end().ends_with('{') {
+            operation = Some((name, vec![line]));
+        } else {
+            // Declarations and globals fit on a single line
+            operations.push((name, line.to_string()));
+        }
+    }
+    if let Some((name, body)) = operation {
+        operations.push((name, body.join("\n")));
+    }
+    operations
+}
+
+pub(super) fn breakdown_output(
+    parser: &LlvmPassDumpParser,
+    ir: &str,
+    procedures_only: bool,
+) -> Result<OptPipelineResults, PassDumpError> {
+    let symbol_op =
+        Regex::new(r#"^(\s*)([a-z_][\w.]*)(?: [\w"]+)*? @("[^"]*"|[\w$.\-]+)"#).unwrap();
+
+    let snapshots = breakdown_output_into_snapshots(ir);
+    let pairing = Pairing::of(
+        &snapshots
+            .iter()
+            .map(|snapshot| PassDump::new(&snapshot.header, String::new()))
+            .collect::<Vec<_>>(),
+    );
+
+    let mut pass_dumps_by_function: HashMap<String, Vec<PassDump>> = HashMap::new();
+    for snapshot in snapshots {
+        let mut operations = split_operations(&symbol_op, &snapshot.lines, procedures_only);
+        if operations.is_empty() {
+            operations.push((UNKNOWN_FUNCTION.to_string(), snapshot.lines.join("\n")));
+        }
+        // At module scope, the rest of the module is only context
+        if let Some(ref symbol) = snapshot.symbol {
+            if operations.iter().any(|(name, _)| name == symbol) {
+                operations.retain(|(name, _)| name == symbol);
+            }
+        }
+
+        for (name, lines) in operations {
+            pass_dumps_by_function
+                .entry(name)
+                .or_default()
+                .push(PassDump::new(&snapshot.header, lines));
+        }
+    }
+
+    parser.match_pass_dumps(pass_dumps_by_function, pairing)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::optpipeline::parse;
+
+    #[test]
+    fn operations_of_the_module_get_their_own_pipeline() {
+        let pipelines = parse(
+            "\
+// -----// IR Dump Before CSE (cse) //----- //
+module {
+  func.func @f(%arg0: i32) -> i32 {
+    %0 = arith.addi %arg0, %arg0 : i32
+    %1 = arith.addi %arg0, %arg0 : i32
+    %2 = arith.muli %0, %1 : i32
+    return %2 : i32
+  }
+  func.func private @g(i32) -> i32
+}
+
+// -----// IR Dump After CSE (cse) //----- //
+module {
+  func.func @f(%arg0: i32) -> i32 {
+    %0 = arith.addi %arg0, %arg0 : i32
+    %1 = arith.muli %0, %0 : i32
+    return %1 : i32
+  }
+  func.func private @g(i32) -> i32
+}
+
+",
+        );
+        let f = &pipelines["f"];
+        assert_eq!(f.len(), 1);
+        assert_eq!(f[0].name, "CSE (cse)");
+        assert!(f[0].ir_changed);
+        assert_eq!(
+            f[0].after,
+            "\
+func.func @f(%arg0: i32) -> i32 {
+  %0 = arith.addi %arg0, %arg0 : i32
+  %1 = arith.muli %0, %0 : i32
+  return %1 : i32
+}"
+        );
+        assert_eq!(pipelines["g"][0].after, "func.func private @g(i32) -> i32");
+        assert!(!pipelines["g"][0].ir_changed);
+    }
+
+    #[test]
+    fn fir_globals_are_left_out() {
+        let pipelines = parse(
+            "\
+// -----// IR Dump Before CSE (cse) //----- //
+module attributes {fir.defaultkind = \"a1c4d8i4l4r4\"} {
+  func.func @_QPsub(%arg0: !fir.ref<i32>) {
+    %0 = fir.load %arg0 : !fir.ref<i32>
+    %1 = fir.load %arg0 : !fir.ref<i32>
+    %2 = arith.addi %0, %1 : i32
+    fir.store %2 to %arg0 : !fir.ref<i32>
+    return
+  }
+  fir.global internal @_QFsubEx : i32 {
+    %c0 = arith.constant 0 : i32
+    fir.has_value %c0 : i32
+  }
+}
+
+// -----// IR Dump After CSE (cse) //----- //
+module attributes {fir.defaultkind = \"a1c4d8i4l4r4\"} {
+  func.func @_QPsub(%arg0: !fir.ref<i32>) {
+    %0 = fir.load %arg0 : !fir.ref<i32>
+    %1 = arith.addi %0, %0 : i32
+    fir.store %1 to %arg0 : !fir.ref<i32>
+    return
+  }
+  fir.global internal @_QFsubEx : i32 {
+    %c0 = arith.constant 0 : i32
+    fir.has_value %c0 : i32
+  }
+}
+
+",
+        );
+        assert_eq!(pipelines.keys().collect::<Vec<_>>(), ["_QPsub"]);
+        let pass = &pipelines["_QPsub"][0];
+        assert!(pass.ir_changed);
+        assert!(pass.before.contains("%2 = arith.addi %0, %1 : i32"));
+        assert!(pass.after.contains("%1 = arith.addi %0, %0 : i32"));
+    }
+}
@@ -0,0 +1,124 @@
+//! The new pass manager runs nested pipelines through adaptors: `ModuleToFunctionPassAdaptor`
+//! runs function passes on every function, `ModuleToPostOrderCGSCCPassAdaptor` runs CGSCC
+//! passes (and `CGSCCToFunctionPassAdaptor` their function passes) SCC by SCC, and
+//! `FunctionToLoopPassAdaptor` runs loop passes on every loop. The dumps leave the adaptors out,
+//! but the IR unit of a pass tells which one it ran under.
+
+use super::{Pass, PassTime};
+
+pub const MODULE_TO_FUNCTION: &str = "ModuleToFunctionPassAdaptor";
+pub const MODULE_TO_CGSCC: &str = "ModuleToPostOrderCGSCCPassAdaptor";
+pub const CGSCC_TO_FUNCTION: &str = "CGSCCToFunctionPassAdaptor";
+pub const FUNCTION_TO_LOOP: &str = "FunctionToLoopPassAdaptor";
+
+/// Fills in the adaptors each pass of a pipeline ran under. Passes of the legacy pass manager
+/// and machine passes don't name their IR unit, they are left at the top.
+pub(super) fn nest_passes(passes: &mut [Pass]) {
+    // Function passes after a CGSCC pass belong to the CGSCC pipeline, until the next module pass
+    let mut in_cgscc = false;
+    for pass in passes {
+        let Some((_, unit)) = pass.name.split_once(" on ") else {
+            continue;
+        };
+        if pass.machine {
+            continue;
+        }
+        let function_adaptors = match in_cgscc {
+            true => vec![MODULE_TO_CGSCC, CGSCC_TO_FUNCTION],
+            false => vec![MODULE_TO_FUNCTION],
+        };
+        pass.adaptors = if unit == "[module]" {
+            in_cgscc = false;
+            Vec::new()
+        } else if unit.starts_with('(') {
+            in_cgscc = true;
+            vec![MODULE_TO_CGSCC]
+        } else if unit.starts_with("loop ") || unit.starts_with("Loop at depth ") {
+            [function_adaptors, vec![FUNCTION_TO_LOOP]].concat()
+        } else {
+            function_adaptors
+        };
+    }
+}
+
+/// The IR unit the pass ran on: `module`, `cgscc`, `function`, `loop` or `machine`. Passes of the
+/// legacy pass manager don't name theirs.
+pub fn scope(pass: &Pass) -> Option<&'static str> {
+    if pass.machine {
+        return Some("machine");
+    }
+    let (_, unit) = pass.name.split_once(" on ")?;
+    Some(if unit == "[module]" {
+        "module"
+    } else if unit.starts_with('(') {
+        "cgscc"
+    } else if unit.starts_with("loop ") || unit.starts_with("Loop at depth ") {
+        "loop"
+    } else {
+        "function"
+    })
+}
+
+/// Merges the passes nested under more than `depth` adaptors into a single pass named after the
+/// adaptor at that depth, diffing the IR before the first of them with the IR after the last.
+pub fn collapse_nested_passes(passes: &[Pass], depth: usize) -> Vec<Pass> {
+    let mut collapsed: Vec<Pass> = Vec::new();
+    let mut nested: Vec<&Pass> = Vec::new();
+    for pass in passes {
+        let same_adaptor = nested
+            .first()
+            .is_some_and(|first| first.adaptors.get(..=depth) == pass.adaptors.get(..=depth));
+        if !same_adaptor && !nested.is_empty() {
+            collapsed.push(merge(&nested, depth));
+            nested.clear();
+        }
+        if pass.adaptors.len() > depth {
+            nested.push(pass);
+        } else {
+            collapsed.push(pass.clone());
+        }
+    }
+    if !nested.is_empty() {
+        collapsed.push(merge(&nested, depth));
+    }
+    collapsed
+}
+
+fn merge(passes: &[&Pass], depth: usize) -> Pass {
+    let (first, last) = (passes[0], passes[passes.len() - 1]);
+    let adaptor = first.adaptors[depth];
+    let after = passes.iter().rev().find(|pass| !pass.invalidated);
+    Pass {
+        name: match passes.len() {
+            1 => format!("{} (1 pass)", adaptor),
+            count => format!("{} ({} passes)", adaptor, count),
+        },
+        machine: first.machine,
+        after: after.map(|pass| pass.after.clone()).unwrap_or_default(),
+        before: first.before.clone(),
+        ir_changed: passes.iter().any(|pass| pass.ir_changed),
+        invalidated: after.is_none(),
+        deleted: last.deleted,
+        before_unknown: first.before_unknown,
+        after_unknown: last.after_unknown,
+        renamed_to: passes.iter().find_map(|pass| pass.renamed_to.clone()),
+        run: 1,
+        runs: 1,
+        adaptors: first.adaptors[..depth].to_vec(),
+        // The time of the runs adds up, but not that of every run of the passes
+        time: passes
+            .iter()
+            .map(|pass| match pass.time {
+                Some(PassTime::Run(time)) => Some(time),
+                _ => None,
+            })
+            .sum::<Option<f64>>()
+            .map(PassTime::Run),
+        remarks: passes
+            .iter()
+            .flat_map(|pass| pass.remarks.iter().cloned())
+            .collect(),
+        skipped_before: first.skipped_before.clone(),
+        skipped_after: last.skipped_after.clone(),
+    }
+}
@@ -0,0 +1,745 @@
+//! `-print-changed` only prints a snapshot after the passes that changed the IR, so each pass is
+//! diffed against the previous snapshot of the same function instead of its own "before" dump.
+//!
+//! Loop passes only print the blocks of their loop. Their "before" is cut out of the snapshot of
+//! the function, and their "after" is put back in it block by block, so the next pass of the
+//! function is diffed against the loop as the loop pass left it.
+//!
+//! `-print-changed=diff` (and `cdiff`) prints LLVM's own diff of the function body in place of
+//! the snapshot. Every line of the body is part of it, so both sides are rebuilt from the diff.
+
+use super::{
+    escape_name, ir_units, LlvmPassDumpParser, OptPipelineResults, Pass, PassDump, LABEL,
+    UNKNOWN_FUNCTION,
+};
+use itertools::Itertools;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+pub(super) fn breakdown_output(
+    parser: &LlvmPassDumpParser,
+    pass_dumps: Vec<PassDump>,
+    embedded_diffs: bool,
+) -> OptPipelineResults {
+    let mut pipelines: OptPipelineResults = HashMap::new();
+    // Latest snapshot of every function, IR and MIR are tracked separately
+    let mut snapshots: HashMap<(String, bool), String> = HashMap::new();
+    // The blocks of the last loop of every function named by a banner, older releases list
+    // them as `Loop at depth 1 containing: %a<header>,%b<latch>`
+    let mut loop_blocks: HashMap<String, Vec<String>> = HashMap::new();
+    // The functions whose IR a pass invalidated without printing it, the next snapshot of
+    // them isn't the one right before the next pass
+    let mut invalidated_functions: HashSet<String> = HashSet::new();
+    let mut previous_function: Option<String> = None;
+    // Passes over several functions follow their banner with an `IR for function` section per
+    // changed function. This is the pass they belong to, and the functions seen so far.
+    let mut diffed_pass: Option<String> = None;
+    let mut diffed_functions: HashSet<String> = HashSet::new();
+
+    for dump in pass_dumps {
+        if dump.header.starts_with("IR Dump At Start") {
+            for (name, function) in parser.breakdown_pass_dumps_into_functions(dump).functions {
+                let snapshot = if embedded_diffs {
+                    function_body(&function.lines)
+                } else {
+                    function.lines.join("\n")
+                };
+                snapshots.insert((name, function.machine), snapshot);
+            }
+            continue;
+        }
+
+        if let Some(section) = dump.header.strip_prefix("IR for function ") {
+            let Some(pass_name) = diffed_pass.clone() else {
+                continue;
+            };
+            let (before, after) = split_embedded_diff(&dump.lines);
+            // Sections are named after the IR unit of the pass rather than the function, so
+            // the function is the one whose body the diff starts from
+            let matched = snapshots
+                .iter()
+                .filter(|((name, machine), _)| !machine && !diffed_functions.contains(name))
+                .find(|(_, snapshot)| **snapshot == before)
+                .map(|((name, _), _)| name.clone());
+            let name = matched.unwrap_or_else(|| {
+                match section.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+                    Some(scc) if !scc.contains(", ") => scc.to_string(),
+                    None if section != "[module]" => escape_name(section).into_owned(),
+                    _ => UNKNOWN_FUNCTION.to_string(),
+                }
+            });
+            diffed_functions.insert(name.clone());
+            snapshots.insert((name.clone(), false), after.clone());
+            pipelines.entry(name).or_default().push(Pass {
+                name: pass_name,
+                machine: false,
+                ir_changed: before != after,
+                before,
+                after,
+                invalidated: false,
+                deleted: false,
+                before_unknown: false,
+                after_unknown: false,
+                renamed_to: None,
+                run: 1,
+                runs: 1,
+                adaptors: Vec::new(),
+                time: None,
+                remarks: Vec::new(),
+                skipped_before: Vec::new(),
+                skipped_after: Vec::new(),
+            });
+            continue;
+        }
+        diffed_pass = None;
+        diffed_functions.clear();
+
+        let Some(pass_name) = dump.header.strip_prefix("IR Dump After ") else {
+            continue;
+        };
+        let pass_name = pass_name
+            .strip_suffix(" (invalidated)")
+            .unwrap_or(pass_name)
+            .to_string();
+        let is_loop_pass = pass_name.contains(" on loop %") || pass_name.contains(" on Loop at ");
+        let is_module_pass = pass_name.ends_with(" on [module]");
+
+        let units = match ir_units(&dump.header, dump.affected_function.as_deref()) {
+            Some(units) => units
+                .into_iter()
+                .map(|name| resolve(name, &previous_function))
+                .collect_vec(),
+            None if is_module_pass => snapshots
+                .keys()
+                .map(|(name, _)| name.clone())
+                .sorted()
+                .dedup()
+                .collect_vec(),
+            // Older releases print `IR Pass LoopDeletionPass invalidated` without the IR unit,
+            // it's the function being processed
+            None if dump.invalidated && !is_pass_manager(&pass_name) => {
+                previous_function.iter().cloned().collect_vec()
+            }
+            // Pass managers and adaptors reported without an IR unit, nothing to attribute
+            None => Vec::new(),
+        };
+
+        // The blocks the loop had before the pass, as the last banner of the loop listed them
+        let blocks_before = match is_loop_pass {
+            true => {
+                let function = units.first().cloned().unwrap_or_default();
+                match listed_loop_blocks(&pass_name) {
+                    Some(blocks) => loop_blocks.insert(function, blocks),
+                    None => loop_blocks.get(&function).cloned(),
+                }
+            }
+            false => None,
+        };
+
+        let new_pass = |machine: bool, before: String, after: String| Pass {
+            name: pass_name.clone(),
+            machine,
+            ir_changed: before != after,
+            before,
+            after,
+            invalidated: false,
+            deleted: false,
+            before_unknown: false,
+            after_unknown: false,
+            renamed_to: None,
+            run: 1,
+            runs: 1,
+            adaptors: Vec::new(),
+            time: None,
+            remarks: Vec::new(),
+            skipped_before: Vec::new(),
+            skipped_after: Vec::new(),
+        };
+
+        if dump.invalidated || dump.deleted || dump.unchanged {
+            for name in units {
+                let machine = snapshots.contains_key(&(name.clone(), true));
+                let snapshot = snapshots
+                    .get(&(name.clone(), machine))
+                    .cloned()
+                    .unwrap_or_default();
+                let mut pass = new_pass(machine, snapshot.clone(), snapshot);
+                pass.before_unknown = invalidated_functions.remove(&name);
+                if dump.deleted {
+                    pass.after = String::new();
+                    pass.deleted = true;
+                    snapshots.remove(&(name.clone(), machine));
+                } else if dump.invalidated {
+                    pass.invalidated = true;
+                    invalidated_functions.insert(name.clone());
+                }
+                pass.ir_changed = !dump.unchanged;
+                if !is_loop_pass && !is_module_pass {
+                    previous_function = Some(name.clone());
+                }
+                pipelines.entry(name).or_default().push(pass);
+            }
+            continue;
+        }
+
+        if embedded_diffs {
+            let (before, after) = split_embedded_diff(&dump.lines);
+            // The diff of a loop pass covers the whole function the loop is in
+            let name = match units.as_slice() {
+                [name] if !is_module_pass && (!before.is_empty() || !after.is_empty()) => {
+                    name.clone()
+                }
+                _ => {
+                    diffed_pass = Some(pass_name);
+                    continue;
+                }
+            };
+            let machine = after.starts_with("# Machine code for function ");
+            snapshots.insert((name.clone(), machine), after.clone());
+            if !is_loop_pass {
+                previous_function = Some(name.clone());
+            }
+            let mut pass = new_pass(machine, before, after);
+            pass.before_unknown = invalidated_functions.remove(&name);
+            pipelines.entry(name).or_default().push(pass);
+            continue;
+        }
+
+        let split = parser.breakdown_pass_dumps_into_functions(dump);
+
+        // A module pass prints the whole module, functions missing from it were deleted
+        if is_module_pass {
+            for name in units {
+                if !split.functions.contains_key(&name) {
+                    if let Some(before) = snapshots.remove(&(name.clone(), false)) {
+                        let mut pass = new_pass(false, before, String::new());
+                        pass.deleted = true;
+                        pipelines.entry(name).or_default().push(pass);
+                    }
+                }
+            }
+        }
+
+        for (name, function) in split.functions {
+            let name = resolve(name, &previous_function);
+            let after = function.lines.join("\n");
+            let before = if is_loop_pass {
+                let key = (name.clone(), false);
+                let snapshot = snapshots.get(&key).cloned().unwrap_or_default();
+                let loop_dump = Blocks::parse(&after);
+                let function = Blocks::parse(&snapshot);
+                // A listing of another loop of the function shares no block with this one
+                let removed = blocks_before
+                    .as_ref()
+                    .filter(|blocks| blocks.iter().any(|block| loop_dump.has(block)))
+                    .into_iter()
+                    .flatten()
+                    .filter(|block| !loop_dump.has(block))
+                    .cloned()
+                    .collect_vec();
+                snapshots.insert(key, function.with_loop(&loop_dump, &removed).text());
+                Some(function.loop_part(&loop_dump, &removed).text())
+            } else {
+                snapshots.insert((name.clone(), function.machine), after.clone())
+            };
+            if !is_loop_pass && !is_module_pass {
+                previous_function = Some(name.clone());
+            }
+            let mut pass = new_pass(function.machine, before.unwrap_or_default(), after);
+            pass.before_unknown = invalidated_functions.remove(&name);
+            pipelines.entry(name).or_default().push(pass);
+        }
+    }
+
+    pipelines
+}
+
+/// Pass managers and adaptors run other passes, what they invalidate is the doing of those.
+fn is_pass_manager(pass_name: &str) -> bool {
+    pass_name.starts_with("PassManager<") || pass_name.ends_with("PassAdaptor")
+}
+
+/// The blocks of the loop listed by a banner of older releases, `LICMPass on Loop at depth 1
+/// containing: %for.cond<header><exiting>,%for.body<latch>`.
+fn listed_loop_blocks(pass_name: &str) -> Option<Vec<String>> {
+    let (_, blocks) = pass_name.split_once(" containing: ")?;
+    Some(
+        blocks
+            .split(',')
+            .map(|block| {
+                let block = block.strip_prefix('%').unwrap_or(block);
+                block.split('<').next().unwrap_or(block).to_string()
+            })
+            .collect(),
+    )
+}
+
+/// A line of a snapshot, or a block starting at its label and running to the next blank line.
+#[derive(Clone)]
+enum Piece<'a> {
+    Line(&'a str),
+    /// The entry block of a function may have no label, its label is then empty
+    Block(String, Vec<&'a str>),
+}
+
+/// A snapshot of a function or a loop cut into blocks. Loops are printed as `; Preheader:`,
+/// `; Loop:` and `; Exit blocks` followed by their blocks, as the function prints them.
+pub(super) struct Blocks<'a> {
+    pieces: Vec<Piece<'a>>,
+}
+
+impl<'a> Blocks<'a> {
+    pub(super) fn parse(snapshot: &'a str) -> Self {
+        let label = Regex::new(LABEL).unwrap();
+        let mut pieces = Vec::new();
+        for line in snapshot.lines() {
+            if let Some(caps) = label.captures(line) {
+                pieces.push(Piece::Block(caps[1].to_string(), vec![line]));
+            } else if line.trim().is_empty()
+                || line.starts_with(';')
+                || line.starts_with("define ")
+                || line.starts_with('}')
+            {
+                pieces.push(Piece::Line(line));
+            } else if let Some(Piece::Block(_, lines)) = pieces.last_mut() {
+                lines.push(line);
+            } else {
+                pieces.push(Piece::Block(String::new(), vec![line]));
+            }
+        }
+        Blocks { pieces }
+    }
+
+    fn block(&self, label: &str) -> Option<&Vec<&'a str>> {
+        self.pieces.iter().find_map(|piece| match piece {
+            Piece::Block(name, lines) if name == label => Some(lines),
+            _ => None,
+        })
+    }
+
+    fn has(&self, label: &str) -> bool {
+        self.block(label).is_some()
+    }
+
+    /// The loop of `loop_dump` as this function had it: the blocks the function has, and those
+    /// `removed` from the loop since, in place of the blocks of the dump.
+    pub(super) fn loop_part(&self, loop_dump: &Blocks<'a>, removed: &[String]) -> Blocks<'a> {
+        let mut pieces = Vec::new();
+        let mut removed = removed
+            .iter()
+            .filter_map(|label| Some(Piece::Block(label.clone(), self.block(label)?.clone())))
+            .collect_vec();
+        for piece in &loop_dump.pieces {
+            match piece {
+                Piece::Line(line) => {
+                    // The removed blocks were in the loop itself, they end its section
+                    if line.starts_with("; Exit blocks") {
+                        for block in removed.drain(..) {
+                            pieces.extend([block, Piece::Line("")]);
+                        }
+                    }
+                    pieces.push(Piece::Line(line));
+                }
+                Piece::Block(label, _) => {
+                    if let Some(lines) = self.block(label) {
+                        pieces.push(Piece::Block(label.clone(), lines.clone()));
+                    }
+                }
+            }
+        }
+        for block in removed {
+            pieces.extend([Piece::Line(""), block]);
+        }
+        Blocks { pieces }
+    }
+
+    /// This function with the blocks of `loop_dump` in place of its own and without those
+    /// `removed` from the loop. New blocks go after the block they follow in the loop, or
+    /// before the one they precede.
+    pub(super) fn with_loop(&self, loop_dump: &Blocks<'a>, removed: &[String]) -> Blocks<'a> {
+        let mut pieces = self.pieces.clone();
+        let position = |pieces: &[Piece], label: &str| {
+            pieces
+                .iter()
+                .position(|piece| matches!(piece, Piece::Block(name, _) if name == label))
+        };
+        let loop_blocks = loop_dump
+            .pieces
+            .iter()
+            .filter_map(|piece| match piece {
+                Piece::Block(label, lines) => Some((label, lines)),
+                Piece::Line(_) => None,
+            })
+            .collect_vec();
+        for (i, &(label, lines)) in loop_blocks.iter().enumerate() {
+            let block = Piece::Block(label.clone(), lines.clone());
+            if let Some(at) = position(&pieces, label) {
+                pieces[at] = block;
+                continue;
+            }
+            let after = loop_blocks[..i]
+                .iter()
+                .rev()
+                .find_map(|(label, _)| position(&pieces, label));
+            let before = loop_blocks[i + 1..]
+                .iter()
+                .find_map(|(label, _)| position(&pieces, label));
+            // Blocks are separated by a blank line
+            let (at, new) = match (after, before) {
+                (Some(after), _) => (after + 1, [Piece::Line(""), block]),
+                (None, Some(before)) => (before, [block, Piece::Line("")]),
+                (None, None) => match pieces
+                    .iter()
+                    .rposition(|piece| matches!(piece, Piece::Line(line) if line.starts_with('}')))
+                {
+                    Some(end) => (end, [Piece::Line(""), block]),
+                    None => (pieces.len(), [Piece::Line(""), block]),
+                },
+            };
+            pieces.splice(at..at, new);
+        }
+        for label in removed {
+            if let Some(at) = position(&pieces, label) {
+                pieces.remove(at);
+                // With the blank line that separated it from the block before
+                if at > 0 && matches!(pieces[at - 1], Piece::Line(line) if line.trim().is_empty()) {
+                    pieces.remove(at - 1);
+                }
+            }
+        }
+        Blocks { pieces }
+    }
+
+    /// The snapshot, without blank lines at its ends or following each other.
+    pub(super) fn text(&self) -> String {
+        let lines = self
+            .pieces
+            .iter()
+            .flat_map(|piece| match piece {
+                Piece::Line(line) => vec![*line],
+                Piece::Block(_, lines) => lines.clone(),
+            })
+            .coalesce(|a, b| match a.trim().is_empty() && b.trim().is_empty() {
+                true => Ok(a),
+                false => Err((a, b)),
+            })
+            .collect_vec();
+        trim_blank_lines(&lines)
+    }
+}
+
+/// Older releases don't name the function a loop is in, it's the one being processed.
+fn resolve(name: String, previous_function: &Option<String>) -> String {
+    if name == "<loop>" {
+        previous_function
+            .clone()
+            .unwrap_or_else(|| UNKNOWN_FUNCTION.to_string())
+    } else {
+        name
+    }
+}
+
+/// Rebuilds both sides of an embedded diff, where every line is prefixed by ` `, `-` or `+`.
+fn split_embedded_diff(diff: &str) -> (String, String) {
+    let mut before = Vec::new();
+    let mut after = Vec::new();
+    for line in diff.lines() {
+        match line.split_at_checked(1) {
+            Some(("-", line)) => before.push(line),
+            Some(("+", line)) => after.push(line),
+            Some((" ", line)) => {
+                before.push(line);
+                after.push(line);
+            }
+            Some(_) => {
+                before.push(line);
+                after.push(line);
+            }
+            // Empty lines only separate the sections
+            None => {}
+        }
+    }
+    (trim_blank_lines(&before), trim_blank_lines(&after))
+}
+
+/// Embedded diffs don't include the `define` line and the closing brace of the function.
+fn function_body(lines: &[String]) -> String {
+    match lines {
+        [_define, body @ .., _end] => trim_blank_lines(body),
+        _ => trim_blank_lines(lines),
+    }
+}
+
+fn trim_blank_lines<S: AsRef<str>>(lines: &[S]) -> String {
+    let is_blank = |line: &&S| line.as_ref().trim().is_empty();
+    let start = lines.iter().position(|line| !is_blank(&line));
+    let end = lines.iter().rposition(|line| !is_blank(&line));
+    match (start, end) {
+        (Some(start), Some(end)) => lines[start..=end]
+            .iter()
+            .map(|line| line.as_ref().trim_end())
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::optpipeline::parse;
+
+    const LOOP: &str = "\
+*** IR Dump At Start ***
+define i32 @f(i32 %n) {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %inc, %loop ]
+  %m = mul i32 %n, 3
+  %inc = add i32 %i, %m
+  %cmp = icmp slt i32 %inc, %n
+  br i1 %cmp, label %loop, label %exit
+
+exit:
+  ret i32 %inc
+}
+*** IR Dump After SROAPass on f omitted because no change ***
+*** IR Dump After LICMPass on Loop at depth 1 containing: %loop<header><latch><exiting> ***
+
+; Preheader:
+entry:
+  %m = mul i32 %n, 3
+  br label %loop
+
+; Loop:
+loop:
+  %i = phi i32 [ 0, %entry ], [ %inc, %loop ]
+  %inc = add i32 %i, %m
+  %cmp = icmp slt i32 %inc, %n
+  br i1 %cmp, label %loop, label %exit
+
+; Exit blocks
+exit:
+  ret i32 %inc
+*** IR Pass LoopDeletionPass invalidated ***
+*** IR Pass PassManager<llvm::Loop> invalidated ***
+*** IR Dump After GVNPass on f ***
+define i32 @f(i32 %n) {
+entry:
+  %m = mul i32 %n, 3
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %inc, %loop ]
+  %inc = add nuw i32 %i, %m
+  %cmp = icmp slt i32 %inc, %n
+  br i1 %cmp, label %loop, label %exit
+
+exit:
+  ret i32 %inc
+}
+";
+
+    /// The passes of `f`, without the ones that didn't change it.
+    fn changed(dump: &str) -> Vec<crate::optpipeline::Pass> {
+        let mut pipelines = parse(dump);
+        let mut passes = pipelines.remove("f").unwrap();
+        passes.retain(|pass| pass.ir_changed);
+        passes
+    }
+
+    #[test]
+    fn loop_passes_are_diffed_against_the_function() {
+        let passes = changed(LOOP);
+        assert_eq!(
+            passes[0].name,
+            "LICMPass on Loop at depth 1 containing: %loop<header><latch><exiting>"
+        );
+        assert_eq!(
+            passes[0].before,
+            "\
+; Preheader:
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %inc, %loop ]
+  %m = mul i32 %n, 3
+  %inc = add i32 %i, %m
+  %cmp = icmp slt i32 %inc, %n
+  br i1 %cmp, label %loop, label %exit
+
+; Exit blocks
+exit:
+  ret i32 %inc"
+        );
+        // Only the hoisted instruction moved
+        let diff = similar::TextDiff::from_lines(&passes[0].before, &passes[0].after);
+        let changes = diff
+            .iter_all_changes()
+            .filter(|change| change.tag() != similar::ChangeTag::Equal)
+            .map(|change| change.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            ["  %m = mul i32 %n, 3\n", "  %m = mul i32 %n, 3\n"]
+        );
+    }
+
+    #[test]
+    fn loop_passes_update_the_function() {
+        let passes = changed(LOOP);
+        let gvn = passes
+            .iter()
+            .find(|pass| pass.name == "GVNPass on f")
+            .unwrap();
+        // The snapshot LICM left, GVN only adds the flag
+        let diff = similar::TextDiff::from_lines(&gvn.before, &gvn.after);
+        let changes = diff
+            .iter_all_changes()
+            .filter(|change| change.tag() != similar::ChangeTag::Equal)
+            .map(|change| change.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            ["  %inc = add i32 %i, %m\n", "  %inc = add nuw i32 %i, %m\n"]
+        );
+    }
+
+    #[test]
+    fn invalidated_banners_without_unit_belong_to_the_function() {
+        let passes = changed(LOOP);
+        let names = passes
+            .iter()
+            .map(|pass| pass.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "LICMPass on Loop at depth 1 containing: %loop<header><latch><exiting>",
+                "LoopDeletionPass",
+                "GVNPass on f"
+            ]
+        );
+        assert!(passes[1].invalidated);
+        // What LoopDeletion did wasn't printed
+        assert!(passes[2].before_unknown);
+    }
+
+    const ROTATED: &str = "\
+*** IR Dump At Start ***
+define i32 @f(i32 %n) {
+entry:
+  br label %cond
+
+cond:
+  %i = phi i32 [ 0, %entry ], [ %inc, %body ]
+  %cmp = icmp slt i32 %i, %n
+  br i1 %cmp, label %body, label %end
+
+body:
+  %inc = add i32 %i, 1
+  br label %cond
+
+end:
+  ret i32 %i
+}
+*** IR Dump After SROAPass on f omitted because no change ***
+*** IR Dump After LICMPass on Loop at depth 1 containing: %cond<header><exiting>,%body<latch> omitted because no change ***
+*** IR Dump After LoopRotatePass on Loop at depth 1 containing: %body<header><latch><exiting> ***
+
+; Preheader:
+body.lr.ph:
+  br label %body
+
+; Loop:
+body:
+  %i1 = phi i32 [ 0, %body.lr.ph ], [ %inc, %body ]
+  %inc = add i32 %i1, 1
+  %cmp = icmp slt i32 %inc, %n
+  br i1 %cmp, label %body, label %end
+
+; Exit blocks
+end:
+  ret i32 %i1
+";
+
+    #[test]
+    fn loop_passes_add_and_remove_blocks_of_the_function() {
+        let dump = ROTATED.to_string()
+            + "\
+*** IR Dump After InstCombinePass on f omitted because no change ***
+*** IR Dump After GVNPass on f ***
+define i32 @f(i32 %n) {
+}
+";
+        let passes = changed(&dump);
+        // The loop before rotation, with the block it merged
+        assert_eq!(
+            passes[0].before,
+            "\
+; Preheader:
+
+body:
+  %inc = add i32 %i, 1
+  br label %cond
+
+cond:
+  %i = phi i32 [ 0, %entry ], [ %inc, %body ]
+  %cmp = icmp slt i32 %i, %n
+  br i1 %cmp, label %body, label %end
+
+; Exit blocks
+end:
+  ret i32 %i"
+        );
+        assert_eq!(
+            passes[1].before,
+            "\
+define i32 @f(i32 %n) {
+entry:
+  br label %cond
+
+body.lr.ph:
+  br label %body
+
+body:
+  %i1 = phi i32 [ 0, %body.lr.ph ], [ %inc, %body ]
+  %inc = add i32 %i1, 1
+  %cmp = icmp slt i32 %inc, %n
+  br i1 %cmp, label %body, label %end
+
+end:
+  ret i32 %i1
+}"
+        );
+    }
+
+    #[test]
+    fn embedded_diffs_are_split_into_both_sides() {
+        let pipelines = parse(
+            "\
+*** IR Dump At Start ***
+define internal i32 @g(i32 %x) {
+  %a = add i32 %x, 0
+  ret i32 %a
+}
+*** IR Dump After VerifierPass on [module] omitted because no change ***
+*** IR Dump After InstCombinePass on g ***
+ 
+-  %a = add i32 %x, 0
+-  ret i32 %a
++  ret i32 %x
+
+*** IR Pass PassManager<llvm::Function> on g ignored ***
+",
+        );
+        let pass = pipelines["g"]
+            .iter()
+            .find(|pass| pass.name == "InstCombinePass on g")
+            .unwrap();
+        assert!(pass.ir_changed);
+        assert_eq!(pass.before, "  %a = add i32 %x, 0\n  ret i32 %a");
+        assert_eq!(pass.after, "  ret i32 %x");
+    }
+}
@@ -0,0 +1,148 @@
+//! `swiftc -Xllvm -sil-print-all` prints a banner such as
+//! `*** SIL function after  #4, stage Mandatory Diagnostic Passes, pass 3: SimplifyCFG (simplify-cfg)`
+//! followed by the function the pass ran on, or `*** SIL module after ...` followed by the whole
+//! module. Snapshots are split into `sil @name` functions and paired like LLVM dumps.
+
+use super::{LlvmPassDumpParser, OptPipelineResults, Pairing, PassDump, PassDumpError};
+use std::collections::HashMap;
+
+/// Returns whether the snapshot is taken before the pass, and the pass name.
+fn parse_banner(line: &str) -> Option<(bool, &str)> {
+    let banner = line.trim_start().strip_prefix("*** SIL ")?;
+    let banner = banner
+        .strip_prefix("function ")
+        .or_else(|| banner.strip_prefix("module "))?;
+    let (before, info) = match banner.split_once(' ')? {
+        ("before", info) => (true, info),
+        ("after", info) => (false, info),
+        _ => return None,
+    };
+    let info = info.trim().trim_end_matches(" ***");
+    // The pass counter and the pipeline stage are left out of the name
+    let name = info.rsplit_once(": ").map_or(info, |(_, name)| name);
+    Some((before, name))
+}
+
+pub(super) fn is_banner(line: &str) -> bool {
+    parse_banner(line).is_some()
+}
+
+/// Returns the name of the function defined by a `sil [attributes] @name : $type` line.
+fn function_name(line: &str) -> Option<&str> {
+    let line = line.strip_prefix("sil ")?;
+    let name = &line[line.find('@')? + 1..];
+    let end = name.find([' ', ':']).unwrap_or(name.len());
+    Some(&name[..end])
+}
+
+/// Splits a snapshot into its functions, in order. Declarations have no body.
+fn split_functions(lines: &str) -> Vec<(String, String)> {
+    let mut functions = Vec::new();
+    let mut function: Option<(String, Vec<&str>)> = None;
+    for line in lines.lines() {
+        if let Some((name, body)) = function.as_mut() {
+            body.push(line);
+            if line.starts_with('}') {
+                functions.push((std::mem::take(name), body.join("\n")));
+                function = None;
+            }
+        } else if let Some(name) = function_name(line) {
+            if line.trim_end().ends_with('{') {
+                function = Some((name.to_string(), vec![line]));
+            }
+        }
+    }
+    if let Some((name, body)) = function {
+        functions.push((name, body.join("\n")));
+    }
+    functions
+}
+
+pub(super) fn breakdown_output(
+    parser: &LlvmPassDumpParser,
+    ir: &str,
+) -> Result<OptPipelineResults, PassDumpError> {
+    let mut snapshots: Vec<(String, String)> = Vec::new();
+    for line in ir.lines() {
+        if let Some((before, name)) = parse_banner(line) {
+            let prefix = if before {
+                "IR Dump Before "
+            } else {
+                "IR Dump After "
+            };
+            snapshots.push((format!("{}{}", prefix, name), String::new()));
+        } else if let Some((_, lines)) = snapshots.last_mut() {
+            if !line.trim().is_empty() {
+                *lines += line;
+                *lines += "\n";
+            }
+        }
+    }
+
+    let pairing = Pairing::of(
+        &snapshots
+            .iter()
+            .map(|(header, _)| PassDump::new(header, String::new()))
+            .collect::<Vec<_>>(),
+    );
+
+    let mut pass_dumps_by_function: HashMap<String, Vec<PassDump>> = HashMap::new();
+    for (header, lines) in snapshots {
+        for (name, function) in split_functions(&lines) {
+            pass_dumps_by_function
+                .entry(name)
+                .or_default()
+                .push(PassDump::new(&header, function));
+        }
+    }
+
+    parser.match_pass_dumps(pass_dumps_by_function, pairing)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::optpipeline::parse;
+
+    #[test]
+    fn functions_are_diffed_against_the_previous_pass() {
+        let pipelines = parse(
+            "\
+*** SIL function after  #1, stage Mandatory Diagnostic Passes, pass 0: DiagnoseInvalidEscapingCaptures (diagnose-invalid-escaping-captures)
+// foo(_:)
+sil hidden [ossa] @$s4main3fooyS2iF : $@convention(thin) (Int) -> Int {
+bb0(%0 : $Int):
+  %1 = copy_value %0 : $Int
+  return %1 : $Int
+}
+
+*** SIL function after  #2, stage Mandatory Diagnostic Passes, pass 1: SemanticARCOpts (semantic-arc-opts)
+// foo(_:)
+sil hidden [ossa] @$s4main3fooyS2iF : $@convention(thin) (Int) -> Int {
+bb0(%0 : $Int):
+  return %0 : $Int
+}
+
+",
+        );
+        let passes = &pipelines["$s4main3fooyS2iF"];
+        let names: Vec<_> = passes.iter().map(|pass| pass.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "DiagnoseInvalidEscapingCaptures (diagnose-invalid-escaping-captures)",
+                "SemanticARCOpts (semantic-arc-opts)"
+            ]
+        );
+        assert!(passes[0].before_unknown);
+        assert_eq!(passes[1].before, passes[0].after);
+        assert!(passes[1].ir_changed);
+        assert_eq!(
+            passes[1].after,
+            "\
+sil hidden [ossa] @$s4main3fooyS2iF : $@convention(thin) (Int) -> Int {
+bb0(%0 : $Int):
+  return %0 : $Int
+}"
+        );
+    }
+}
@@ -0,0 +1,745 @@
+//! Dumps run into gigabytes, so they are read a line at a time instead of as a whole. Each
+//! snapshot is cleaned up and split into its functions as soon as the next banner shows up, only
+//! matching the snapshots into pipelines waits for the end of the dump.
+//!
+//! A dump may hold several modules, e.g. the host and device side of a CUDA compile, or ThinLTO
+//! backend jobs. A new module starts with a snapshot of a different `; ModuleID` or target
+//! triple. Snapshots of the same module found apart are put together, which takes apart the
+//! output of parallel ThinLTO backends: snapshots of a single function go to the only module seen
+//! defining it.
+
+use super::attribute_groups::AttributeGroups;
+use super::build_noise::BuildNoise;
+use super::debug_noise::DebugNoise;
+use super::time_report::{self, Timings};
+use super::{
+    banner_header, defined_function, gcc, is_loop_snapshot, mlir, nesting, number_runs,
+    outside_functions, sil, Diagnostic, Dialect, LlvmPassDumpParser, OptPipelineBackendOptions,
+    OptPipelineResults, PassDump, PassDumpError, Problem, ProcessOptions, SplitPassDump,
+    MODULE_PREAMBLE,
+};
+use memchr::memmem;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+/// The `ModuleID` and target triple of a module, as far as they were printed.
+type ModuleKey = (Option<String>, Option<String>);
+
+fn is_banner(line: &str) -> bool {
+    line.starts_with("*** ") || line.starts_with("; *** ") || line.starts_with("# *** ")
+}
+
+/// What the parser can't make sense of on a line of an LLVM snapshot, if anything.
+fn malformed(line: &str, dialect: Dialect) -> Option<Problem> {
+    if is_banner(line) {
+        let Some(header) = banner_header(line) else {
+            return Some(Problem::UnclosedBanner);
+        };
+        let expected = [
+            "IR Dump Before ",
+            "IR Dump After ",
+            "IR Deleted After ",
+            "IR Pass ",
+        ]
+        .iter()
+        .any(|kind| header.starts_with(kind));
+        return (dialect == Dialect::PassDump && !expected)
+            .then(|| Problem::UnexpectedBanner(header.to_string()));
+    }
+    let definition =
+        line.starts_with("define ") || line.starts_with("# Machine code for function ");
+    (definition && defined_function(line).is_none()).then_some(Problem::UnnamedFunction)
+}
+
+/// The functions defined on a line, found without parsing the snapshot around it.
+fn listed_functions(line: &str) -> Vec<Cow<'_, str>> {
+    memmem::find_iter(line.as_bytes(), b"define ")
+        .chain(memmem::find_iter(
+            line.as_bytes(),
+            b"# Machine code for function ",
+        ))
+        .filter_map(|start| defined_function(&line[start..]))
+        .collect()
+}
+
+/// Tells the dialect from the first banners. The first one decides, but `-print-changed` only
+/// shows whether it embeds diffs with the first snapshot of a pass that changed the IR.
+#[derive(Default)]
+struct DialectDetector {
+    print_changed: bool,
+    changed_snapshot: bool,
+}
+
+impl DialectDetector {
+    fn observe(&mut self, line: &str) -> Option<Dialect> {
+        // Snapshots start with IR, embedded diffs with a prefixed (and maybe coloured) line or
+        // the section of the first function
+        if self.changed_snapshot {
+            if line.is_empty() {
+                return None;
+            }
+            return match line.starts_with([' ', '+', '-', '\x1b'])
+                || line.starts_with("*** IR for function ")
+            {
+                true => Some(Dialect::PrintChangedDiff),
+                false => Some(Dialect::PrintChanged),
+            };
+        }
+        if mlir::is_banner(line) {
+            // Flang dumps are told apart once the whole dump is read
+            return Some(Dialect::Mlir);
+        }
+        if sil::is_banner(line) {
+            return Some(Dialect::Sil);
+        }
+        if gcc::is_banner(line) {
+            return Some(Dialect::Gcc);
+        }
+        if !is_banner(line) {
+            return None;
+        }
+        if !self.print_changed {
+            if !line.contains("*** IR Dump At Start")
+                && !line.ends_with(" omitted because no change ***")
+            {
+                return Some(Dialect::PassDump);
+            }
+            self.print_changed = true;
+        }
+        self.changed_snapshot = line.starts_with("*** IR Dump After ")
+            && !line.ends_with(" omitted because no change ***")
+            && !line.ends_with(" (invalidated) ***");
+        None
+    }
+
+    fn finish(&self) -> Dialect {
+        match self.print_changed {
+            true => Dialect::PrintChanged,
+            false => Dialect::PassDump,
+        }
+    }
+}
+
+/// What was read of a module, its snapshots cleaned up and broken down.
+#[derive(Default, Clone)]
+struct ModuleDump {
+    functions: HashSet<String>,
+    timings: Timings,
+    /// Snapshots split into their functions
+    split_dumps: Vec<SplitPassDump>,
+    /// Whole snapshots, for `--globals`, `--full-module` and `-print-changed`
+    dumps: Vec<PassDump>,
+    /// Snapshots before the filters, only what's outside of the functions
+    preamble_dumps: Vec<PassDump>,
+    /// The split and the whole snapshots before the filters, when they're kept too
+    dumped_split_dumps: Vec<SplitPassDump>,
+    dumped_dumps: Vec<PassDump>,
+    /// Dumps of other compilers are broken down as a whole
+    text: String,
+}
+
+impl ModuleDump {
+    fn append(&mut self, other: ModuleDump) {
+        self.functions.extend(other.functions);
+        self.timings.merge(other.timings);
+        self.split_dumps.extend(other.split_dumps);
+        self.dumps.extend(other.dumps);
+        self.preamble_dumps.extend(other.preamble_dumps);
+        self.dumped_split_dumps.extend(other.dumped_split_dumps);
+        self.dumped_dumps.extend(other.dumped_dumps);
+        self.text += &other.text;
+    }
+
+    /// Leaves out the last snapshot if it's a "before" one, the pass is still running.
+    fn hold_back_before(&mut self) {
+        let before = |header: &str| header.starts_with("IR Dump Before ");
+        if self
+            .split_dumps
+            .last()
+            .is_some_and(|dump| before(&dump.header))
+        {
+            self.split_dumps.pop();
+        }
+        if self.dumps.last().is_some_and(|dump| before(&dump.header)) {
+            self.dumps.pop();
+        }
+        if self
+            .preamble_dumps
+            .last()
+            .is_some_and(|dump| before(&dump.header))
+        {
+            self.preamble_dumps.pop();
+        }
+        if self
+            .dumped_split_dumps
+            .last()
+            .is_some_and(|dump| before(&dump.header))
+        {
+            self.dumped_split_dumps.pop();
+        }
+        if self
+            .dumped_dumps
+            .last()
+            .is_some_and(|dump| before(&dump.header))
+        {
+            self.dumped_dumps.pop();
+        }
+    }
+}
+
+/// A module of the dump, read but not yet broken down into pipelines.
+pub struct DumpModule {
+    /// The `ModuleID`, followed by the target triple when it's shared by several modules
+    pub name: String,
+    /// The functions defined in the module, as found without parsing the snapshots
+    pub functions: HashSet<String>,
+    dump: ModuleDump,
+    options: OptPipelineBackendOptions,
+}
+
+impl DumpModule {
+    /// The snapshots of the module in the order they were dumped, what's outside of their
+    /// functions and the lines of their functions. They're as printed if the stream doesn't apply
+    /// the filters or keeps the snapshots as dumped too, and only kept when the snapshots are
+    /// split by function.
+    pub fn snapshots(&self) -> impl Iterator<Item = (&str, Vec<&[String]>)> {
+        let dump = &self.dump;
+        let split_dumps = match dump.dumped_split_dumps.is_empty() {
+            true => &dump.split_dumps,
+            false => &dump.dumped_split_dumps,
+        };
+        dump.preamble_dumps
+            .iter()
+            .zip(split_dumps)
+            .map(|(outside, split)| {
+                let outside =
+                    match outside.machine || outside.invalidated || is_loop_snapshot(outside) {
+                        true => "",
+                        false => outside.lines.as_str(),
+                    };
+                let functions = split
+                    .functions
+                    .values()
+                    .filter(|function| !function.machine)
+                    .map(|function| function.lines.as_slice())
+                    .collect();
+                (outside, functions)
+            })
+    }
+
+    /// Matches the snapshots of the module as dumped, before the filters, into the pipelines of
+    /// its functions, for the snapshots the viewer writes. None unless the stream kept them, which
+    /// it doesn't when it applies no filters to them.
+    pub fn dumped_pipelines(&mut self) -> Result<Option<OptPipelineResults>, PassDumpError> {
+        let dump = &mut self.dump;
+        if dump.dumped_split_dumps.is_empty() && dump.dumped_dumps.is_empty() {
+            return Ok(None);
+        }
+        let parser = LlvmPassDumpParser::new();
+        let mut results = match dump.dumped_dumps.is_empty() {
+            true => {
+                parser.breakdown_split_pass_dumps(std::mem::take(&mut dump.dumped_split_dumps))?
+            }
+            false => parser
+                .breakdown_pass_dumps(std::mem::take(&mut dump.dumped_dumps), &self.options)?,
+        };
+        for passes in results.values_mut() {
+            number_runs(passes);
+            nesting::nest_passes(passes);
+        }
+        Ok(Some(results))
+    }
+
+    /// Matches the snapshots of the module into the pipelines of its functions.
+    pub fn pipelines(self) -> Result<OptPipelineResults, PassDumpError> {
+        let parser = LlvmPassDumpParser::new();
+        let options = &self.options;
+        let by_function =
+            options.dialect == Dialect::PassDump && !options.globals && !options.full_module;
+        let mut results = if !options.dialect.is_llvm() {
+            let text = self.dump.text;
+            let ir: &str = match options.keep_noise {
+                true => &text,
+                false => &BuildNoise::new().strip(&text),
+            };
+            parser.breakdown_output(ir, options)?
+        } else if by_function {
+            parser.breakdown_split_pass_dumps(self.dump.split_dumps)?
+        } else {
+            parser.breakdown_pass_dumps(self.dump.dumps, options)?
+        };
+        if by_function {
+            let preamble = parser.breakdown_module_preamble(self.dump.preamble_dumps)?;
+            results.extend(preamble.map(|passes| (MODULE_PREAMBLE.to_string(), passes)));
+        }
+        let timings = self.dump.timings;
+        for passes in results.values_mut() {
+            number_runs(passes);
+            nesting::nest_passes(passes);
+            if !timings.is_empty() {
+                time_report::annotate(passes, &timings);
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// A dump as read by [`DumpStream`].
+pub struct Dump {
+    pub dialect: Dialect,
+    /// What was printed before the first snapshot
+    pub prefix: String,
+    /// An `IR Dump Before` or `IR Dump After` banner was found
+    pub has_pass_dumps: bool,
+    /// The line where a snapshot starts within a function, and that function, as found in dumps
+    /// written by several threads at once
+    pub interleaving: Option<(usize, String)>,
+    /// The parts of the dump that were left out, in the order they were found
+    pub diagnostics: Vec<Diagnostic>,
+    pub modules: Vec<DumpModule>,
+}
+
+/// Reads a dump a line at a time.
+pub struct DumpStream {
+    parser: LlvmPassDumpParser,
+    build_noise: BuildNoise,
+    debug_noise: DebugNoise,
+    attribute_groups: AttributeGroups,
+    apply_filters: bool,
+    process_options: ProcessOptions,
+    detector: DialectDetector,
+    /// Set once the dialect is known, the lines read before are held until then
+    options: Option<OptPipelineBackendOptions>,
+    held_lines: Vec<String>,
+    ir_filter: Option<Regex>,
+    prefix: String,
+    in_snapshots: bool,
+    has_pass_dumps: bool,
+    line_number: usize,
+    /// The lines handed over to the dialect so far, the ones held come first
+    dispatched_lines: usize,
+    /// The function whose definition is being read
+    open_function: Option<String>,
+    /// The functions defined so far, for the progress of big dumps
+    functions_found: HashSet<String>,
+    interleaving: Option<(usize, String)>,
+    diagnostics: Vec<Diagnostic>,
+    /// A malformed snapshot was found, the lines up to the next banner are left out
+    skipping: bool,
+    key: ModuleKey,
+    modules_of_function: HashMap<String, Vec<ModuleKey>>,
+    /// The modules read so far, and the section of a module being read. The section goes to its
+    /// module once the next one starts, as the key of a section may be printed in any of its
+    /// snapshots.
+    modules: Vec<(ModuleKey, ModuleDump)>,
+    section: ModuleDump,
+    /// Where the last snapshot went, the section or the module it was handed over to
+    flushed_in_section: bool,
+    last_flushed_module: Option<ModuleKey>,
+    /// The snapshot being read, it's only broken down once complete
+    snapshot: String,
+    pos: usize,
+    section_start: usize,
+    banner_start: usize,
+}
+
+impl DumpStream {
+    pub fn new(apply_filters: bool, options: &ProcessOptions) -> Self {
+        DumpStream {
+            parser: LlvmPassDumpParser::new(),
+            build_noise: BuildNoise::new(),
+            debug_noise: DebugNoise::new(),
+            attribute_groups: AttributeGroups::new(),
+            apply_filters,
+            process_options: *options,
+            detector: DialectDetector::default(),
+            options: None,
+            held_lines: Vec::new(),
+            ir_filter: None,
+            prefix: String::new(),
+            in_snapshots: false,
+            has_pass_dumps: false,
+            line_number: 0,
+            dispatched_lines: 0,
+            open_function: None,
+            functions_found: HashSet::new(),
+            interleaving: None,
+            diagnostics: Vec::new(),
+            skipping: false,
+            key: (None, None),
+            modules_of_function: HashMap::new(),
+            modules: Vec::new(),
+            section: ModuleDump::default(),
+            flushed_in_section: false,
+            last_flushed_module: None,
+            snapshot: String::new(),
+            pos: 0,
+            section_start: 0,
+            banner_start: 0,
+        }
+    }
+
+    /// The number of functions defined in the lines read so far.
+    pub fn functions_found(&self) -> usize {
+        self.functions_found.len()
+    }
+
+    /// Reads the next line of the dump, with its line break.
+    pub fn push_line(&mut self, line: &str) {
+        let content = line.strip_suffix('\n').unwrap_or(line);
+        let content = content.strip_suffix('\r').unwrap_or(content);
+        self.line_number += 1;
+        self.track_interleaving(content);
+        if !self.has_pass_dumps {
+            self.has_pass_dumps =
+                content.contains("IR Dump Before") || content.contains("IR Dump After");
+        }
+
+        if self.options.is_some() {
+            self.dispatch(line);
+            return;
+        }
+        self.held_lines.push(line.to_string());
+        if let Some(dialect) = self.detector.observe(content) {
+            self.start(dialect);
+        }
+    }
+
+    /// Finds the first snapshot that starts within a function, and follows the function being
+    /// defined up to the end of the dump.
+    fn track_interleaving(&mut self, line: &str) {
+        let starts_function = defined_function(line);
+        if let (Some(function), true, None) = (
+            &self.open_function,
+            is_banner(line) || starts_function.is_some(),
+            &self.interleaving,
+        ) {
+            self.interleaving = Some((self.line_number, function.clone()));
+        }
+        if let Some(function) = starts_function {
+            if !self.functions_found.contains(&*function) {
+                self.functions_found.insert(function.to_string());
+            }
+            self.open_function = Some(function.into_owned());
+        } else if is_banner(line)
+            || line.starts_with('}')
+            || line.starts_with("# End machine code for function ")
+        {
+            self.open_function = None;
+        }
+    }
+
+    fn start(&mut self, dialect: Dialect) {
+        let options = OptPipelineBackendOptions {
+            filter_debug_info: true,
+            filter_ir_metadata: true,
+            full_module: self.process_options.full_module,
+            no_discard_value_names: false,
+            demangle: false,
+            library_functions: false,
+            apply_filters: self.apply_filters,
+            keep_noise: self.process_options.keep_noise,
+            globals: self.process_options.globals,
+            dialect,
+        };
+        // The filters only know about LLVM IR
+        if options.apply_filters && dialect.is_llvm() {
+            self.ir_filter = Some(self.parser.ir_filter(&options));
+        }
+        self.options = Some(options);
+        for line in std::mem::take(&mut self.held_lines) {
+            self.dispatch(&line);
+        }
+    }
+
+    fn dispatch(&mut self, line: &str) {
+        let Some(options) = self.options else {
+            return;
+        };
+        let content = line.strip_suffix('\n').unwrap_or(line);
+        self.dispatched_lines += 1;
+        // Whatever is printed before the first snapshot is passed through
+        self.in_snapshots = self.in_snapshots
+            || self.parser.ir_dump_header.is_match(content)
+            || self.parser.machine_code_dump_header.is_match(content)
+            || mlir::is_banner(content)
+            || sil::is_banner(content)
+            || gcc::is_banner(content);
+        let prefix = !self.in_snapshots;
+        if prefix {
+            self.prefix += line;
+        }
+        if !options.dialect.is_llvm() {
+            if !prefix {
+                self.section.text += line;
+            }
+            return;
+        }
+        self.route(line, prefix);
+    }
+
+    /// Follows the modules through the dump, the lines of the prefix only tell which module
+    /// comes first.
+    fn route(&mut self, line: &str, prefix: bool) {
+        let start = self.pos;
+        self.pos += line.len();
+        let content = line.trim_end();
+        let problem = match prefix {
+            true => None,
+            false => self
+                .options
+                .and_then(|options| malformed(content, options.dialect)),
+        };
+        if is_banner(content) {
+            self.flush_snapshot();
+            self.banner_start = start;
+            self.skipping = false;
+        } else if let Some(function) = defined_function(content) {
+            let module = match self.modules_of_function.get(&*function).map(Vec::as_slice) {
+                Some([module]) if *module != self.key => Some(module.clone()),
+                _ => None,
+            };
+            if let Some(module) = module {
+                if self.banner_start > self.section_start {
+                    self.close_section();
+                    self.key = module;
+                }
+            }
+            if self.key.0.is_some() {
+                let modules = self
+                    .modules_of_function
+                    .entry(function.into_owned())
+                    .or_default();
+                if !modules.contains(&self.key) {
+                    modules.push(self.key.clone());
+                }
+            }
+        } else {
+            let (id, triple) = if let Some(id) = content.strip_prefix("; ModuleID = ") {
+                (Some(id.trim_matches('\'')), None)
+            } else if let Some(triple) = content.strip_prefix("target triple = ") {
+                (None, Some(triple.trim_matches('"')))
+            } else {
+                (None, None)
+            };
+            let differs = matches!((&self.key.0, id), (Some(a), Some(b)) if a != b)
+                || matches!((&self.key.1, triple), (Some(a), Some(b)) if a != b);
+            if differs && self.banner_start > self.section_start {
+                self.close_section();
+                self.key = (None, None);
+            }
+            if self.key.0.is_none() {
+                self.key.0 = id.map(str::to_string);
+            }
+            if self.key.1.is_none() {
+                self.key.1 = triple.map(str::to_string);
+            }
+        }
+
+        // The rest of the snapshot can't be told apart from the next one, it's left out whole
+        if let Some(problem) = problem {
+            self.diagnostics.push(Diagnostic {
+                line: self.dispatched_lines,
+                problem,
+            });
+            self.snapshot.clear();
+            self.skipping = true;
+        }
+
+        if prefix {
+            let functions = listed_functions(line).into_iter().map(Cow::into_owned);
+            self.section.functions.extend(functions);
+        } else if !self.skipping {
+            self.snapshot += line;
+        }
+    }
+
+    /// Hands the section over to its module.
+    fn close_section(&mut self) {
+        let section = std::mem::take(&mut self.section);
+        self.section_start = self.banner_start;
+        if std::mem::take(&mut self.flushed_in_section) {
+            self.last_flushed_module = Some(self.key.clone());
+        }
+        match self.modules.iter_mut().find(|(key, _)| *key == self.key) {
+            Some((_, module)) => module.append(section),
+            None => self.modules.push((self.key.clone(), section)),
+        }
+    }
+
+    /// Breaks down the snapshot read last.
+    fn flush_snapshot(&mut self) {
+        let Some(options) = self.options else {
+            return;
+        };
+        if self.snapshot.is_empty() {
+            return;
+        }
+        let snapshot = std::mem::take(&mut self.snapshot);
+        self.flushed_in_section = true;
+        let section = &mut self.section;
+        for line in snapshot.lines() {
+            let functions = listed_functions(line).into_iter().map(Cow::into_owned);
+            section.functions.extend(functions);
+        }
+        // The timing report is printed after the dumps, it isn't part of any snapshot
+        section.timings.merge(time_report::parse(&snapshot));
+
+        let ir: &str = match options.keep_noise {
+            true => &snapshot,
+            false => &self.build_noise.strip(&snapshot),
+        };
+        // Debug output and remarks are told apart from the IR, embedded diffs aren't IR
+        let ir: &str = match options.dialect {
+            Dialect::PassDump | Dialect::PrintChanged if !options.keep_noise => {
+                &self.debug_noise.strip(ir)
+            }
+            _ => ir,
+        };
+        let by_function =
+            options.dialect == Dialect::PassDump && !options.globals && !options.full_module;
+        // The filters drop the metadata, so the preamble is taken from the dump as printed
+        if by_function {
+            let dumps = self.parser.breakdown_output_into_pass_dumps(ir);
+            section
+                .preamble_dumps
+                .extend(dumps.into_iter().map(outside_functions));
+        }
+        // `-print-changed=cdiff` colours the removed and added lines
+        let ir: &str = match options.dialect {
+            Dialect::PrintChangedDiff => &self.parser.color_escape.replace_all(ir, ""),
+            _ => ir,
+        };
+        // The snapshots the viewer writes are kept as dumped too
+        if self.process_options.keep_dumped && self.ir_filter.is_some() {
+            let dumps = self.parser.breakdown_output_into_pass_dumps(ir);
+            match by_function {
+                true => section.dumped_split_dumps.extend(
+                    dumps
+                        .into_iter()
+                        .map(|dump| self.parser.breakdown_pass_dumps_into_functions(dump)),
+                ),
+                false => section.dumped_dumps.extend(dumps),
+            }
+        }
+        // The filters drop the attribute groups, they're expanded beforehand if asked to
+        let ir: &str = match self.process_options.expand_attributes && self.ir_filter.is_some() {
+            true => &self.attribute_groups.expand(ir),
+            false => ir,
+        };
+        let ir: &str = match &self.ir_filter {
+            Some(filter) => &filter.replace_all(ir, "${brace}"),
+            None => ir,
+        };
+        let dumps = self.parser.breakdown_output_into_pass_dumps(ir);
+        if by_function {
+            let split_dumps = dumps
+                .into_iter()
+                .map(|dump| self.parser.breakdown_pass_dumps_into_functions(dump));
+            section.split_dumps.extend(split_dumps);
+        } else {
+            section.dumps.extend(dumps);
+        }
+    }
+
+    /// Breaks down what's left of the dump.
+    pub fn finish(mut self) -> Dump {
+        if self.options.is_none() {
+            let dialect = self.detector.finish();
+            self.start(dialect);
+        }
+        let options = self.options.unwrap();
+        // A dump cut off within a function would show the rest of it deleted by the last pass
+        if let Some(function) = self.open_function.take() {
+            if options.dialect.is_llvm() && !self.skipping && !self.snapshot.is_empty() {
+                self.diagnostics.push(Diagnostic {
+                    line: self.line_number,
+                    problem: Problem::TruncatedSnapshot(function),
+                });
+                self.snapshot.clear();
+            }
+        }
+        self.flush_snapshot();
+        self.close_section();
+        let modules = std::mem::take(&mut self.modules);
+        self.dump(modules, options)
+    }
+
+    /// The dump as far as its snapshots are complete, for dumps still being written. A snapshot
+    /// is complete once the next one starts, and its pass once the "after" snapshot is.
+    pub fn completed(&self) -> Option<Dump> {
+        let options = self.options?;
+        let mut modules = self.modules.clone();
+        let mut section = self.section.clone();
+        if self.flushed_in_section {
+            section.hold_back_before();
+        } else if let Some((_, module)) = modules
+            .iter_mut()
+            .find(|(key, _)| Some(key) == self.last_flushed_module.as_ref())
+        {
+            module.hold_back_before();
+        }
+        match modules.iter_mut().find(|(key, _)| *key == self.key) {
+            Some((_, module)) => module.append(section),
+            None => modules.push((self.key.clone(), section)),
+        }
+        Some(self.dump(modules, options))
+    }
+
+    fn dump(
+        &self,
+        modules: Vec<(ModuleKey, ModuleDump)>,
+        mut options: OptPipelineBackendOptions,
+    ) -> Dump {
+        if options.dialect == Dialect::Mlir {
+            // Flang mangles all its symbols with a `_Q` prefix
+            let fir = modules
+                .iter()
+                .any(|(_, module)| module.text.contains("func.func @_Q"));
+            if fir {
+                options.dialect = Dialect::Fir;
+            }
+        }
+
+        let ids = modules
+            .iter()
+            .map(|((id, _), _)| id.clone())
+            .collect::<Vec<_>>();
+        let modules = modules
+            .into_iter()
+            .map(|((id, triple), mut dump)| {
+                let id = id.unwrap_or_default();
+                let name = match triple {
+                    Some(triple)
+                        if ids
+                            .iter()
+                            .filter(|other| other.as_deref().unwrap_or_default() == id)
+                            .count()
+                            > 1 =>
+                    {
+                        format!("{} ({})", id, triple)
+                    }
+                    _ => id,
+                };
+                DumpModule {
+                    name,
+                    functions: std::mem::take(&mut dump.functions),
+                    dump,
+                    options,
+                }
+            })
+            .collect();
+        Dump {
+            dialect: options.dialect,
+            prefix: self.prefix.clone(),
+            has_pass_dumps: self.has_pass_dumps,
+            interleaving: self
+                .interleaving
+                .clone()
+                .filter(|_| options.dialect.is_llvm()),
+            diagnostics: self.diagnostics.clone(),
+            modules,
+        }
+    }
+}
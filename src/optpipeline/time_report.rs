@@ -0,0 +1,122 @@
+//! `-time-passes` (`-ftime-report` in clang) prints a table of the time spent in every pass once
+//! the compilation is over:
+//!
+//! ```text
+//!    ---User Time---   --System Time--   --User+System--   ---Wall Time---  --- Name ---
+//!    0.0011 ( 28.8%)   0.0006 ( 33.1%)   0.0017 ( 30.1%)   0.0017 ( 28.7%)  InstCombinePass
+//! ```
+//!
+//! A row covers every run of the pass, on every function. With `-time-passes-per-run` each
+//! instance of the pass in the pipeline (`InstCombinePass #3`) gets its own row, which is the
+//! third run of the pass on each function it ran on.
+
+use super::{Pass, PassTime};
+use regex::Regex;
+use std::collections::HashMap;
+
+#[derive(Debug, Default, Clone)]
+pub(super) struct Timings {
+    all_runs: HashMap<String, f64>,
+    per_run: HashMap<(String, usize), f64>,
+}
+
+impl Timings {
+    pub(super) fn is_empty(&self) -> bool {
+        self.all_runs.is_empty() && self.per_run.is_empty()
+    }
+
+    /// Adds up the timings of the reports found in another part of the dump.
+    pub(super) fn merge(&mut self, other: Timings) {
+        for (name, time) in other.all_runs {
+            *self.all_runs.entry(name).or_default() += time;
+        }
+        for (instance, time) in other.per_run {
+            *self.per_run.entry(instance).or_default() += time;
+        }
+    }
+}
+
+/// Collects the wall time of the passes from the pass execution timing reports in the dump.
+pub(super) fn parse(ir: &str) -> Timings {
+    let mut timings = Timings::default();
+    if !ir.contains("... Pass execution timing report ...") {
+        return timings;
+    }
+    let header = Regex::new(r"-+[A-Za-z+ ]+-+").unwrap();
+    let column = Regex::new(r"^\s*([\d.]+) \(\s*[\d.]+%\)").unwrap();
+    let instance = Regex::new(r"^(.+) #(\d+)$").unwrap();
+
+    let mut in_report = false;
+    // Columns are left out when the time isn't measured, the header says which one is which
+    let mut wall_column = None;
+    for line in ir.lines() {
+        if line.contains("... Pass execution timing report ...") {
+            in_report = true;
+            wall_column = None;
+            continue;
+        }
+        if !in_report {
+            continue;
+        }
+        if line.contains("--- Name ---") {
+            wall_column = header
+                .find_iter(line)
+                .position(|header| header.as_str().contains("Wall"));
+            continue;
+        }
+        let Some(wall_column) = wall_column else {
+            continue;
+        };
+
+        let mut rest = line;
+        let mut times = Vec::new();
+        while let Some(caps) = column.captures(rest) {
+            times.push(caps[1].parse::<f64>().unwrap_or_default());
+            rest = &rest[caps[0].len()..];
+        }
+        let name = rest.trim();
+        let Some(&wall_time) = times.get(wall_column) else {
+            // The report ends with the total, anything else is past it
+            if !line.trim().is_empty() {
+                in_report = false;
+            }
+            continue;
+        };
+        if name == "Total" {
+            in_report = false;
+            continue;
+        }
+        match instance.captures(name) {
+            Some(caps) => {
+                let run = caps[2].parse().unwrap_or_default();
+                *timings
+                    .per_run
+                    .entry((caps[1].to_string(), run))
+                    .or_default() += wall_time;
+            }
+            None => *timings.all_runs.entry(name.to_string()).or_default() += wall_time,
+        }
+    }
+    timings
+}
+
+/// Attaches the wall time of its pass to each pass of a pipeline.
+pub(super) fn annotate(passes: &mut [Pass], timings: &Timings) {
+    for pass in passes {
+        let name = pass.name.as_str();
+        // New pass manager passes are timed by pass ID, legacy ones by description
+        let id = match name.split_once(" on ") {
+            Some((id, _)) => id,
+            None => name
+                .rsplit_once(" (")
+                .map_or(name, |(description, _)| description),
+        };
+        pass.time = match timings.per_run.get(&(id.to_string(), pass.run)) {
+            Some(&time) => Some(PassTime::Run(time)),
+            None => timings
+                .all_runs
+                .get(id)
+                .map(|&time| PassTime::AllRuns(time)),
+        };
+    }
+}
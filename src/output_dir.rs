@@ -0,0 +1,145 @@
+//! Diffs written to a directory with `-o dir/` instead of stdout, a file per function or per
+//! pass, so that the results of a huge dump can be browsed with the usual file tools, grepped,
+//! and archived function by function.
+//!
+//! ```text
+//! dir/foo.diff                           --split-by function
+//! dir/foo/003-SROAPass_on_foo.diff       --split-by pass
+//! dir/foo.md                             --format markdown
+//! ```
+
+use crate::optpipeline::Pass;
+use crate::snapshot::{file_name_part, run_label};
+use crate::step::Stepper;
+use clap::ValueEnum;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// What a file of the directory holds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Split {
+    /// The diffs of all the passes of a function
+    #[default]
+    Function,
+    /// The diff of one pass, in a directory per function
+    Pass,
+}
+
+/// Where the diffs of the functions are written.
+pub enum Output {
+    Stdout,
+    Dir {
+        dir: PathBuf,
+        split: Split,
+        /// Of the files, `diff` or `md` for Markdown
+        extension: &'static str,
+    },
+    /// Stdout, a pass at a time with `--step`
+    Step(Stepper),
+}
+
+/// `-o` names a directory if it ends with a slash or is one already.
+pub fn is_dir(path: &Path) -> bool {
+    path.as_os_str().to_string_lossy().ends_with(['/', '\\']) || path.is_dir()
+}
+
+fn failed(path: &Path, err: io::Error) -> io::Error {
+    io::Error::new(
+        err.kind(),
+        format!("Failed to write {}: {}", path.display(), err),
+    )
+}
+
+impl Output {
+    pub fn dir(dir: &Path, split: Split, extension: &'static str) -> io::Result<Self> {
+        fs::create_dir_all(dir).map_err(|err| failed(dir, err))?;
+        Ok(Output::Dir {
+            dir: dir.to_path_buf(),
+            split,
+            extension,
+        })
+    }
+
+    fn path(dir: &Path, extension: &str, func_name: &str, pass: Option<(usize, &Pass)>) -> PathBuf {
+        let function = file_name_part(func_name);
+        match pass {
+            Some((i, pass)) => dir.join(function).join(format!(
+                "{:03}-{}.{}",
+                i + 1,
+                file_name_part(&(pass.name.clone() + &run_label(pass))),
+                extension
+            )),
+            None => dir.join(format!("{}.{}", function, extension)),
+        }
+    }
+
+    /// Whether `--step` was quit, the diffs left aren't shown.
+    pub fn quit(&self) -> bool {
+        matches!(self, Output::Step(stepper) if stepper.quit())
+    }
+
+    /// Starts the file of a function afresh, or its directory when split by pass.
+    pub fn start_function(&self, func_name: &str) -> io::Result<()> {
+        match self {
+            Output::Stdout | Output::Step(_) => Ok(()),
+            Output::Dir {
+                dir,
+                split: Split::Function,
+                extension,
+            } => {
+                let path = Self::path(dir, extension, func_name, None);
+                File::create(&path).map_err(|err| failed(&path, err))?;
+                Ok(())
+            }
+            Output::Dir {
+                dir,
+                split: Split::Pass,
+                ..
+            } => {
+                let path = dir.join(file_name_part(func_name));
+                fs::create_dir_all(&path).map_err(|err| failed(&path, err))
+            }
+        }
+    }
+
+    /// Where to write what's printed of a function, of its pass number `i` if there is one.
+    pub fn writer(
+        &self,
+        func_name: &str,
+        pass: Option<(usize, &Pass)>,
+    ) -> io::Result<Box<dyn Write>> {
+        let (dir, split, extension) = match self {
+            Output::Stdout => return Ok(Box::new(io::stdout())),
+            Output::Step(stepper) => {
+                if let Some((i, pass)) = pass {
+                    stepper.next(func_name, i, pass)?;
+                }
+                return match stepper.quit() {
+                    true => Ok(Box::new(io::sink())),
+                    false => Ok(Box::new(io::stdout())),
+                };
+            }
+            Output::Dir {
+                dir,
+                split,
+                extension,
+            } => (dir, *split, *extension),
+        };
+        // The file of a function was started afresh, the passes add to it
+        let file = match pass.filter(|_| split == Split::Pass) {
+            Some(pass) => {
+                let path = Self::path(dir, extension, func_name, Some(pass));
+                File::create(&path).map_err(|err| failed(&path, err))?
+            }
+            None => {
+                let path = Self::path(dir, extension, func_name, None);
+                OpenOptions::new()
+                    .append(true)
+                    .open(&path)
+                    .map_err(|err| failed(&path, err))?
+            }
+        };
+        Ok(Box::new(BufWriter::new(file)))
+    }
+}
@@ -0,0 +1,416 @@
+//! One-line descriptions of the common passes of LLVM, shown under the headers of the passes
+//! with `--describe` for those learning what the optimizer does.
+
+/// The passes by the name they're dumped with, with their short name and what they do.
+const DESCRIPTIONS: &[(&str, &str, &str)] = &[
+    // Module passes
+    (
+        "AlwaysInlinerPass",
+        "AlwaysInliner",
+        "inlines the functions marked `alwaysinline`",
+    ),
+    (
+        "Annotation2MetadataPass",
+        "Annotation2Metadata",
+        "turns `llvm.global.annotations` into metadata",
+    ),
+    (
+        "CalledValuePropagationPass",
+        "CalledValuePropagation",
+        "attaches the possible callees to indirect calls",
+    ),
+    (
+        "ConstantMergePass",
+        "ConstantMerge",
+        "merges duplicate global constants",
+    ),
+    (
+        "CoroCleanupPass",
+        "CoroCleanup",
+        "lowers the coroutine intrinsics left after the splitting",
+    ),
+    (
+        "CoroEarlyPass",
+        "CoroEarly",
+        "lowers the coroutine intrinsics that hide the details of the frame",
+    ),
+    (
+        "DeadArgumentEliminationPass",
+        "DeadArgElim",
+        "removes the arguments and return values internal functions never use",
+    ),
+    (
+        "ElimAvailExternPass",
+        "ElimAvailExtern",
+        "drops the bodies of `available_externally` functions once inlining is done",
+    ),
+    (
+        "ForceFunctionAttrsPass",
+        "ForceFunctionAttrs",
+        "adds the attributes forced from the command line",
+    ),
+    (
+        "GlobalDCEPass",
+        "GlobalDCE",
+        "removes the globals and functions nothing uses",
+    ),
+    (
+        "GlobalOptPass",
+        "GlobalOpt",
+        "optimizes the globals: constant folds, shrinks and localizes them",
+    ),
+    (
+        "InferFunctionAttrsPass",
+        "InferFunctionAttrs",
+        "adds the attributes of known library functions",
+    ),
+    (
+        "InlinerPass",
+        "Inliner",
+        "inlines calls where the cost model says it pays off",
+    ),
+    (
+        "IPSCCPPass",
+        "IPSCCP",
+        "propagates constants across functions, pruning the branches never taken",
+    ),
+    (
+        "ModuleInlinerWrapperPass",
+        "ModuleInlinerWrapper",
+        "runs the inliner and the function simplifications over the call graph",
+    ),
+    (
+        "OpenMPOptPass",
+        "OpenMPOpt",
+        "optimizes the OpenMP runtime calls and parallel regions",
+    ),
+    (
+        "RelLookupTableConverterPass",
+        "RelLookupTableConverter",
+        "turns lookup tables of pointers into relative offsets",
+    ),
+    (
+        "WholeProgramDevirtPass",
+        "WholeProgramDevirt",
+        "devirtualizes calls using the whole program's type hierarchy",
+    ),
+    // CGSCC passes
+    (
+        "ArgumentPromotionPass",
+        "ArgumentPromotion",
+        "passes by value the arguments passed by pointer that are only read",
+    ),
+    (
+        "CoroSplitPass",
+        "CoroSplit",
+        "splits coroutines into their ramp, resume and destroy functions",
+    ),
+    (
+        "PostOrderFunctionAttrsPass",
+        "FunctionAttrs",
+        "infers attributes such as `nounwind` or `readonly` bottom-up the call graph",
+    ),
+    // Function passes
+    (
+        "ADCEPass",
+        "ADCE",
+        "removes the instructions that don't affect the result, aggressively",
+    ),
+    (
+        "AggressiveInstCombinePass",
+        "AggressiveInstCombine",
+        "combines costlier patterns than InstCombine, e.g. into narrower types",
+    ),
+    (
+        "AlignmentFromAssumptionsPass",
+        "AlignmentFromAssumptions",
+        "raises the alignment of memory accesses from `llvm.assume`",
+    ),
+    (
+        "BDCEPass",
+        "BDCE",
+        "removes the computations of bits that are never used",
+    ),
+    (
+        "CallSiteSplittingPass",
+        "CallSiteSplitting",
+        "duplicates calls so constant arguments reach each of them",
+    ),
+    (
+        "ConstraintEliminationPass",
+        "ConstraintElimination",
+        "removes the comparisons implied by the conditions dominating them",
+    ),
+    (
+        "CorrelatedValuePropagationPass",
+        "CVP",
+        "simplifies with the value ranges implied by the branches taken",
+    ),
+    (
+        "DivRemPairsPass",
+        "DivRemPairs",
+        "shares the work of a division and the remainder of the same operands",
+    ),
+    (
+        "DSEPass",
+        "DSE",
+        "removes the stores overwritten or never read",
+    ),
+    (
+        "EarlyCSEPass",
+        "EarlyCSE",
+        "removes redundant instructions and loads, cheaply",
+    ),
+    (
+        "GVNPass",
+        "GVN",
+        "removes redundant instructions and loads across blocks, numbering values",
+    ),
+    (
+        "InstCombinePass",
+        "InstCombine",
+        "combines and simplifies instructions with peephole rewrites",
+    ),
+    (
+        "InstSimplifyPass",
+        "InstSimplify",
+        "folds instructions into existing values, creating none",
+    ),
+    (
+        "JumpThreadingPass",
+        "JumpThreading",
+        "threads the branches whose outcome is known on some paths",
+    ),
+    (
+        "LibCallsShrinkWrapPass",
+        "LibCallsShrinkWrap",
+        "guards library calls so they're only made when they set `errno`",
+    ),
+    (
+        "LoopDistributePass",
+        "LoopDistribute",
+        "splits loops so their parts can be vectorized apart",
+    ),
+    (
+        "LoopLoadEliminationPass",
+        "LoopLoadElimination",
+        "forwards the stores of an iteration to the loads of the next",
+    ),
+    (
+        "LoopSinkPass",
+        "LoopSink",
+        "sinks the instructions hoisted out of loops back into the cold blocks using them",
+    ),
+    (
+        "LoopVectorizePass",
+        "LoopVectorize",
+        "vectorizes loops, processing several iterations at once",
+    ),
+    (
+        "LowerExpectIntrinsicPass",
+        "LowerExpectIntrinsic",
+        "turns `llvm.expect` into branch weights",
+    ),
+    (
+        "MemCpyOptPass",
+        "MemCpyOpt",
+        "merges and removes `memcpy` and `memset` calls and the stores they stand for",
+    ),
+    (
+        "MergedLoadStoreMotionPass",
+        "MergedLoadStoreMotion",
+        "hoists and sinks the loads and stores both sides of a branch make",
+    ),
+    (
+        "NewGVNPass",
+        "NewGVN",
+        "removes redundant instructions, numbering values over the whole function",
+    ),
+    (
+        "PromotePass",
+        "Mem2Reg",
+        "promotes the allocas only loaded and stored to SSA registers",
+    ),
+    (
+        "ReassociatePass",
+        "Reassociate",
+        "reorders the operands of commutative expressions to expose constants",
+    ),
+    (
+        "SCCPPass",
+        "SCCP",
+        "propagates constants, pruning the branches never taken",
+    ),
+    (
+        "SimplifyCFGPass",
+        "SimplifyCFG",
+        "merges and removes blocks and branches, and turns branches into selects",
+    ),
+    (
+        "SinkingPass",
+        "Sink",
+        "moves instructions into the successors that use them",
+    ),
+    (
+        "SLPVectorizerPass",
+        "SLPVectorizer",
+        "combines similar independent instructions into vector ones",
+    ),
+    (
+        "SpeculativeExecutionPass",
+        "SpeculativeExecution",
+        "hoists cheap instructions out of conditional blocks",
+    ),
+    (
+        "SROAPass",
+        "SROA",
+        "splits aggregates into scalar SSA values",
+    ),
+    (
+        "TailCallElimPass",
+        "TailCallElim",
+        "turns self-recursive tail calls into loops and marks tail calls",
+    ),
+    (
+        "VectorCombinePass",
+        "VectorCombine",
+        "combines vector and scalar instructions, cheaper with vectors",
+    ),
+    // Loop passes
+    (
+        "IndVarSimplifyPass",
+        "IndVarSimplify",
+        "canonicalizes induction variables and rewrites exit values",
+    ),
+    (
+        "LICMPass",
+        "LICM",
+        "hoists loop-invariant code out of loops and sinks it after them",
+    ),
+    (
+        "LoopDeletionPass",
+        "LoopDeletion",
+        "removes the loops that compute nothing used",
+    ),
+    (
+        "LoopFullUnrollPass",
+        "LoopFullUnroll",
+        "unrolls the loops with a small constant trip count entirely",
+    ),
+    (
+        "LoopIdiomRecognizePass",
+        "LoopIdiom",
+        "turns loops into calls such as `memset` and `memcpy`",
+    ),
+    (
+        "LoopInstSimplifyPass",
+        "LoopInstSimplify",
+        "folds the instructions of loops",
+    ),
+    (
+        "LoopRotatePass",
+        "LoopRotate",
+        "turns loops into do-while loops, guarded by the first test",
+    ),
+    (
+        "LoopSimplifyCFGPass",
+        "LoopSimplifyCFG",
+        "merges and removes the blocks of loops",
+    ),
+    (
+        "LoopUnrollPass",
+        "LoopUnroll",
+        "repeats the bodies of loops to run fewer iterations",
+    ),
+    (
+        "SimpleLoopUnswitchPass",
+        "LoopUnswitch",
+        "moves loop-invariant conditions out of loops, duplicating them",
+    ),
+    (
+        "LoopSimplifyPass",
+        "LoopSimplify",
+        "gives loops a preheader, a single backedge and dedicated exits",
+    ),
+    (
+        "LCSSAPass",
+        "LCSSA",
+        "adds phis for the values of loops used after them",
+    ),
+    // Machine passes, named as the legacy pass manager prints them
+    (
+        "Machine Common Subexpression Elimination",
+        "MachineCSE",
+        "removes redundant machine instructions",
+    ),
+    (
+        "Machine Loop Invariant Code Motion",
+        "MachineLICM",
+        "hoists loop-invariant machine instructions out of loops",
+    ),
+    (
+        "Machine code sinking",
+        "MachineSink",
+        "moves machine instructions into the successors that use them",
+    ),
+    (
+        "Peephole Optimizations",
+        "PeepholeOpt",
+        "folds and rewrites machine instructions with target peepholes",
+    ),
+    (
+        "Two-Address instruction pass",
+        "TwoAddressInstruction",
+        "ties the defined registers of two-address instructions to a use",
+    ),
+    (
+        "Register Coalescer",
+        "RegisterCoalescer",
+        "removes copies by merging the live ranges of registers",
+    ),
+    (
+        "Machine Instruction Scheduler",
+        "MachineScheduler",
+        "reorders the instructions of blocks for the target's pipeline",
+    ),
+    (
+        "Greedy Register Allocator",
+        "RegAllocGreedy",
+        "assigns physical registers, splitting and spilling live ranges",
+    ),
+    (
+        "Virtual Register Rewriter",
+        "VirtRegRewriter",
+        "replaces the virtual registers with the physical ones assigned",
+    ),
+    (
+        "Prologue/Epilogue Insertion & Frame Finalization",
+        "PrologEpilogInserter",
+        "inserts the prologue and the epilogue and resolves the frame indices",
+    ),
+    (
+        "Branch Probability Basic Block Placement",
+        "MachineBlockPlacement",
+        "lays the blocks out in the order they're most likely to run",
+    ),
+    (
+        "Post-RA pseudo instruction expansion pass",
+        "ExpandPostRAPseudos",
+        "expands the pseudo instructions left after register allocation",
+    ),
+];
+
+/// What the pass does, `SROA: splits aggregates into scalar SSA values`, by the name it's
+/// dumped with. None for the passes not known.
+pub fn describe(name: &str) -> Option<String> {
+    let pass = name.split_once(" on ").map_or(name, |(pass, _)| pass);
+    // Machine passes come with their argument, `Register Coalescer (register-coalescer)`
+    let pass = match pass.strip_suffix(')') {
+        Some(pass) => pass.rsplit_once(" (").map_or(pass, |(pass, _)| pass),
+        None => pass,
+    };
+    DESCRIPTIONS
+        .iter()
+        .find(|(known, _, _)| *known == pass)
+        .map(|(_, short, description)| format!("{}: {}", short, description))
+}
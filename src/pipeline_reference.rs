@@ -0,0 +1,208 @@
+//! `opt -passes='default<O2>' -print-pipeline-passes` prints the pipeline the new pass manager
+//! runs as a textual pipeline, e.g. `verify,function(sroa,loop(indvars)),globaldce`. It names
+//! passes by their command line name, the dumps by their class name (`IndVarSimplifyPass`).
+//!
+//! The passes of a function are lined up with the reference pipeline to find the passes that
+//! were expected but never dumped (filtered out, or skipped as there was nothing to run them on),
+//! and the passes that don't run where the reference says they would.
+
+use crate::optpipeline::Pass;
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+/// Command line names of passes whose class name reads differently.
+const PASS_CLASSES: &[(&str, &str)] = &[
+    ("verify", "Verifier"),
+    ("forceattrs", "ForceFunctionAttrs"),
+    ("inferattrs", "InferFunctionAttrs"),
+    ("lower-expect", "LowerExpectIntrinsic"),
+    ("mem2reg", "Promote"),
+    ("deadargelim", "DeadArgumentElimination"),
+    ("inline", "Inliner"),
+    ("always-inline", "AlwaysInliner"),
+    ("function-attrs", "PostOrderFunctionAttrs"),
+    ("rpo-function-attrs", "ReversePostOrderFunctionAttrs"),
+    ("argpromotion", "ArgumentPromotion"),
+    ("correlated-propagation", "CorrelatedValuePropagation"),
+    ("loop-idiom", "LoopIdiomRecognize"),
+    ("indvars", "IndVarSimplify"),
+    ("loop-unroll-full", "LoopFullUnroll"),
+    ("loop-load-elim", "LoopLoadElimination"),
+    ("loop-fusion", "LoopFuse"),
+    ("mldst-motion", "MergedLoadStoreMotion"),
+    ("elim-avail-extern", "EliminateAvailableExternally"),
+    ("transform-warning", "WarnMissedTransformations"),
+    ("constmerge", "ConstantMerge"),
+    ("mergefunc", "MergeFunctions"),
+    ("hotcoldsplit", "HotColdSplitting"),
+    ("ee-instrument", "EntryExitInstrumenter"),
+    ("post-inline-ee-instrument", "EntryExitInstrumenter"),
+    ("chr", "ControlHeightReduction"),
+    ("sample-profile", "SampleProfileLoader"),
+    ("require", "RequireAnalysis"),
+    ("invalidate", "InvalidateAnalysis"),
+];
+
+/// Passes the tools run after the pipeline, to write the module out.
+const OUTPUT_PASSES: &[&str] = &["PrintModulePass", "BitcodeWriterPass"];
+
+/// Loop pass managers start with these function passes, which put the loops in shape.
+const LOOP_CANONICALIZATION: &[&str] = &["loop-simplify", "lcssa"];
+
+/// Reads a textual pipeline into the passes it runs, in order, leaving out the pass managers
+/// and adaptors they are nested in.
+pub fn parse(pipeline: &str) -> Vec<String> {
+    let mut passes = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let pipeline = pipeline.trim();
+    for (i, c) in pipeline.char_indices() {
+        match c {
+            '(' | '<' => depth += 1,
+            ')' | '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parse_element(&pipeline[start..i], &mut passes);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parse_element(&pipeline[start..], &mut passes);
+    passes
+}
+
+fn parse_element(element: &str, passes: &mut Vec<String>) {
+    let element = element.trim();
+    if element.is_empty() {
+        return;
+    }
+    let name_end = element.find(['<', '(']).unwrap_or(element.len());
+    let name = &element[..name_end];
+    match element.find('(') {
+        Some(open) if element.ends_with(')') => {
+            if name == "loop" || name == "loop-mssa" {
+                passes.extend(LOOP_CANONICALIZATION.iter().map(|pass| pass.to_string()));
+            }
+            passes.extend(parse(&element[open + 1..element.len() - 1]));
+        }
+        _ => passes.push(name.to_string()),
+    }
+}
+
+/// Whether the reference is an alias such as `default<O2>`, as opposed to spelled out passes.
+pub fn is_alias(pipeline: &str) -> bool {
+    let pipeline = pipeline.trim();
+    [
+        "default<",
+        "thinlto-pre-link<",
+        "thinlto<",
+        "lto-pre-link<",
+        "lto<",
+    ]
+    .iter()
+    .any(|alias| pipeline.starts_with(alias) && pipeline.ends_with('>'))
+}
+
+fn normalize(name: &str) -> String {
+    let name = name.split('<').next().unwrap_or(name);
+    let name = name.to_lowercase().replace('-', "");
+    name.strip_suffix("pass")
+        .map(str::to_string)
+        .unwrap_or(name)
+}
+
+fn class_key(command_line_name: &str) -> String {
+    match PASS_CLASSES
+        .iter()
+        .find(|(name, _)| *name == command_line_name)
+    {
+        Some((_, class)) => normalize(class),
+        None => normalize(command_line_name),
+    }
+}
+
+/// How a pipeline compares with the reference.
+#[derive(Debug, Default)]
+pub struct Comparison {
+    /// Passes of the reference missing before the pass at this index of the pipeline
+    pub skipped: Vec<(usize, Vec<String>)>,
+    /// Passes of the reference missing after the last pass of the pipeline
+    pub skipped_at_end: Vec<String>,
+    /// Passes of the pipeline that don't run where the reference says, by index
+    pub unexpected: Vec<usize>,
+}
+
+/// Lines up the passes of a function with the reference. Passes of the legacy pass manager and
+/// machine passes aren't part of it.
+pub fn compare(reference: &[String], pipeline: &[Pass]) -> Comparison {
+    // A loop pass manager runs its passes on one loop after the other, each of its runs is
+    // compared on the first loop only
+    let mut indices = Vec::new();
+    let mut current_loop: Option<&str> = None;
+    for (i, pass) in pipeline.iter().enumerate() {
+        let Some((_, unit)) = pass.name.split_once(" on ") else {
+            continue;
+        };
+        if pass.machine
+            || OUTPUT_PASSES
+                .iter()
+                .any(|output| pass.name.starts_with(output))
+        {
+            continue;
+        }
+        let is_loop = unit.starts_with("loop ") || unit.starts_with("Loop at depth ");
+        if !is_loop {
+            current_loop = None;
+        } else if current_loop.is_some_and(|current| current != unit) {
+            continue;
+        } else {
+            current_loop = Some(unit);
+        }
+        indices.push(i);
+    }
+
+    let expected = reference
+        .iter()
+        .map(|pass| class_key(pass))
+        .collect::<Vec<_>>();
+    let dumped = indices
+        .iter()
+        .map(|&i| {
+            let name = &pipeline[i].name;
+            normalize(name.split_once(" on ").map_or(name.as_str(), |(id, _)| id))
+        })
+        .collect::<Vec<_>>();
+
+    let mut comparison = Comparison::default();
+    let mut skip = |old_index: usize, old_len: usize, new_index: usize| {
+        let passes = reference[old_index..old_index + old_len].to_vec();
+        match indices.get(new_index) {
+            Some(&i) => comparison.skipped.push((i, passes)),
+            None => comparison.skipped_at_end.extend(passes),
+        }
+    };
+    let mut unexpected = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, &expected, &dumped) {
+        match op {
+            DiffOp::Equal { .. } => {}
+            DiffOp::Delete {
+                old_index,
+                old_len,
+                new_index,
+            } => skip(old_index, old_len, new_index),
+            DiffOp::Insert {
+                new_index, new_len, ..
+            } => unexpected.extend(&indices[new_index..new_index + new_len]),
+            DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            } => {
+                skip(old_index, old_len, new_index);
+                unexpected.extend(&indices[new_index..new_index + new_len]);
+            }
+        }
+    }
+    comparison.unexpected = unexpected;
+    comparison
+}
@@ -0,0 +1,159 @@
+//! Progress of runs over big dumps on stderr, which may take minutes to parse and diff: the bytes
+//! read and the functions found so far, then the functions and passes diffed.
+//!
+//! The line is redrawn in place, a few times a second at most, and only once the run has taken
+//! long enough to be worth it. It's never drawn when stderr isn't a terminal, nor over the
+//! pager: the diffs are only followed while they're written elsewhere than the terminal.
+
+use is_terminal::IsTerminal;
+use std::cell::Cell;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// How long a run goes on before its progress is shown.
+const DELAY: Duration = Duration::from_millis(500);
+/// The least time between two redraws.
+const INTERVAL: Duration = Duration::from_millis(100);
+const BAR_WIDTH: usize = 30;
+
+/// A reader counting the bytes read through it, before they're decompressed.
+pub struct Counted<R> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R> Counted<R> {
+    pub fn new(inner: R, count: &Rc<Cell<u64>>) -> Self {
+        Counted {
+            inner,
+            count: count.clone(),
+        }
+    }
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.set(self.count.get() + read as u64);
+        Ok(read)
+    }
+}
+
+fn bytes(count: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    let mut size = count as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+    match unit {
+        "B" => format!("{} B", count),
+        unit => format!("{:.1} {}", size, unit),
+    }
+}
+
+fn bar(done: u64, total: u64) -> String {
+    let filled = (done.min(total) * BAR_WIDTH as u64 / total.max(1)) as usize;
+    format!(
+        "▕{}{}▏ {:>3}%",
+        "█".repeat(filled),
+        "░".repeat(BAR_WIDTH - filled),
+        done.min(total) * 100 / total.max(1)
+    )
+}
+
+/// The progress line on stderr.
+pub struct Progress {
+    enabled: bool,
+    started: Instant,
+    drawn: Option<Instant>,
+    /// The dump being read, its size if known, and the bytes read from it
+    input: (String, Option<u64>, Rc<Cell<u64>>),
+}
+
+impl Progress {
+    /// Progress shown if stderr is a terminal and `enabled`.
+    pub fn new(enabled: bool) -> Self {
+        Progress {
+            enabled: enabled && io::stderr().is_terminal(),
+            started: Instant::now(),
+            drawn: None,
+            input: Default::default(),
+        }
+    }
+
+    /// Starts reading the dump `name` of `size` bytes. The bytes read are to be counted with
+    /// [`Counted`].
+    pub fn reading(&mut self, name: String, size: Option<u64>) -> Rc<Cell<u64>> {
+        self.input = (name, size, Rc::default());
+        self.input.2.clone()
+    }
+
+    /// Stops showing the progress, e.g. once the diffs go to the terminal.
+    pub fn disable(&mut self) {
+        self.clear();
+        self.enabled = false;
+    }
+
+    fn draw(&mut self, line: impl FnOnce() -> String) {
+        if !self.enabled || self.started.elapsed() < DELAY {
+            return;
+        }
+        if self.drawn.is_some_and(|drawn| drawn.elapsed() < INTERVAL) {
+            return;
+        }
+        // Progress is no reason to fail, a stderr gone is left to the diffs to notice
+        let _ = write!(io::stderr(), "\r\x1b[K{}", line());
+        self.drawn = Some(Instant::now());
+    }
+
+    /// The dump read was parsed up to where it was read, with `functions` found.
+    pub fn parsed(&mut self, functions: usize) {
+        let (name, total, read) = &self.input;
+        let (name, total, read) = (name.clone(), *total, read.get());
+        self.draw(|| {
+            let read = match total {
+                Some(total) => format!("{} {} of {}", bar(read, total), bytes(read), bytes(total)),
+                None => bytes(read),
+            };
+            format!("Parsing {}: {}, {} functions", name, read, functions)
+        });
+    }
+
+    /// Starts a step of the run that reports no progress of its own.
+    pub fn step(&mut self, what: &str) {
+        self.drawn = None;
+        self.draw(|| format!("{}…", what));
+    }
+
+    /// `done` functions out of `total` were diffed, `passes` passes in all.
+    pub fn diffing(&mut self, done: usize, total: usize, passes: usize) {
+        self.draw(|| {
+            format!(
+                "Diffing {} {} of {} functions, {} passes",
+                bar(done as u64, total as u64),
+                done,
+                total,
+                passes
+            )
+        });
+    }
+
+    /// Erases the line, for the warnings and errors to be printed on a clean one.
+    pub fn clear(&mut self) {
+        if self.drawn.take().is_some() {
+            let _ = write!(io::stderr(), "\r\x1b[K");
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
@@ -0,0 +1,188 @@
+//! `-fsave-optimization-record` (`-pass-remarks-output` in opt) writes the optimization remarks
+//! to a YAML file, one document per remark:
+//!
+//! ```yaml
+//! --- !Passed
+//! Pass:            inline
+//! Name:            Inlined
+//! DebugLoc:        { File: a.c, Line: 5, Column: 10 }
+//! Function:        f
+//! Args:
+//!   - Callee:          g
+//!   - String:          ' inlined into '
+//!   - Caller:          f
+//! ...
+//! ```
+//!
+//! Remarks name the pass by its command line name, the dumps of the new pass manager by its
+//! class name.
+
+use crate::optpipeline::Pass;
+use itertools::Itertools;
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+pub struct Remark {
+    /// `Passed`, `Missed`, `Analysis`, ...
+    pub kind: String,
+    pub pass: String,
+    pub name: String,
+    pub function: String,
+    pub message: String,
+    pub location: Option<String>,
+}
+
+impl Remark {
+    fn render(&self) -> String {
+        let location = match &self.location {
+            Some(location) => format!(" at {}", location),
+            None => String::new(),
+        };
+        format!(
+            "{} ({}): {}{}",
+            self.kind, self.name, self.message, location
+        )
+    }
+}
+
+/// Command line names of passes whose class name reads differently.
+const PASS_CLASSES: &[(&str, &[&str])] = &[
+    ("inline", &["Inliner", "AlwaysInliner", "ModuleInliner"]),
+    ("loop-unroll", &["LoopUnroll", "LoopFullUnroll"]),
+    ("loop-idiom", &["LoopIdiomRecognize"]),
+    ("loop-delete", &["LoopDeletion"]),
+    ("openmp-opt", &["OpenMPOpt", "OpenMPOptCGSCC"]),
+    ("hotcoldsplit", &["HotColdSplitting"]),
+];
+
+/// Arguments LLVM leaves out of the message, as they only make sense to tools.
+const EXTRA_ARGS: &[&str] = &["FromBlock", "ToBlock"];
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    if let Some(value) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        value.replace("''", "'")
+    } else if let Some(value) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        value.replace("\\\"", "\"").replace("\\\\", "\\")
+    } else {
+        value.to_string()
+    }
+}
+
+/// Reads `{ File: a.c, Line: 5, Column: 10 }` as `a.c:5:10`.
+fn location(value: &str) -> Option<String> {
+    let fields = value.trim().strip_prefix('{')?.strip_suffix('}')?;
+    let mut fields: HashMap<&str, String> = fields
+        .split(", ")
+        .filter_map(|field| field.split_once(':'))
+        .map(|(key, value)| (key.trim(), unquote(value)))
+        .collect();
+    let file = fields.remove("File")?;
+    match (fields.remove("Line"), fields.remove("Column")) {
+        (Some(line), Some(column)) => Some(format!("{}:{}:{}", file, line, column)),
+        (Some(line), None) => Some(format!("{}:{}", file, line)),
+        _ => Some(file),
+    }
+}
+
+pub fn parse(yaml: &str) -> Vec<Remark> {
+    let mut remarks = Vec::new();
+    let mut remark: Option<Remark> = None;
+    for line in yaml.lines() {
+        if let Some(kind) = line.strip_prefix("--- !") {
+            remarks.extend(remark.take());
+            remark = Some(Remark {
+                kind: kind.trim().to_string(),
+                ..Default::default()
+            });
+            continue;
+        }
+        let Some(remark) = remark.as_mut() else {
+            continue;
+        };
+        if let Some(arg) = line.strip_prefix("  - ") {
+            // The arguments spell out the message, the locations of the values aside
+            match arg.split_once(':') {
+                Some((key, _)) if EXTRA_ARGS.contains(&key) => {}
+                Some((_, value)) => remark.message += &unquote(value),
+                None => {}
+            }
+        } else if !line.starts_with(' ') {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            match key {
+                "Pass" => remark.pass = unquote(value),
+                "Name" => remark.name = unquote(value),
+                "Function" => remark.function = unquote(value),
+                "DebugLoc" => remark.location = location(value),
+                _ => {}
+            }
+        }
+    }
+    remarks.extend(remark);
+    remarks
+}
+
+/// Whether the remarks of the pass `remark_pass` come from the pass of the dump named `pass_name`.
+fn emitted_by(remark_pass: &str, pass_name: &str) -> bool {
+    // Legacy pass manager dumps name the pass by description and command line name
+    if let Some((_, argument)) = pass_name.rsplit_once(" (") {
+        if argument.strip_suffix(')') == Some(remark_pass) {
+            return true;
+        }
+    }
+    let Some((class, _)) = pass_name.split_once(" on ") else {
+        return false;
+    };
+    let normalize = |name: &str| {
+        let name = name.to_lowercase().replace('-', "");
+        name.strip_suffix("pass")
+            .map(str::to_string)
+            .unwrap_or(name)
+    };
+    let class = normalize(class);
+    let classes = PASS_CLASSES
+        .iter()
+        .find(|(name, _)| *name == remark_pass)
+        .map_or(&[][..], |(_, classes)| *classes);
+    normalize(remark_pass) == class || classes.iter().any(|name| normalize(name) == class)
+}
+
+/// Shows the remarks of a function under the passes that emitted them. The remarks don't say
+/// which run of a pass emitted them, so with several runs they all go to the first one that
+/// changed the function. Returns how many remarks were attached.
+pub fn attach(remarks: &[&Remark], pipeline: &mut [Pass]) -> usize {
+    let mut by_pass: Vec<(&str, Vec<&Remark>)> = Vec::new();
+    for remark in remarks {
+        match by_pass.iter_mut().find(|(pass, _)| *pass == remark.pass) {
+            Some((_, remarks)) => remarks.push(remark),
+            None => by_pass.push((&remark.pass, vec![remark])),
+        }
+    }
+
+    let mut attached = 0;
+    for (remark_pass, remarks) in by_pass {
+        let runs = pipeline
+            .iter()
+            .positions(|pass| emitted_by(remark_pass, &pass.name))
+            .collect::<Vec<_>>();
+        let Some(&first) = runs.first() else {
+            continue;
+        };
+        let run = runs
+            .iter()
+            .copied()
+            .find(|&i| pipeline[i].ir_changed)
+            .unwrap_or(first);
+        let pass = &mut pipeline[run];
+        if runs.len() > 1 {
+            pass.remarks
+                .push(format!("Remarks of the {} runs of the pass:", runs.len()));
+        }
+        pass.remarks
+            .extend(remarks.iter().map(|remark| remark.render()));
+        attached += remarks.len();
+    }
+    attached
+}
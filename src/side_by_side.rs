@@ -0,0 +1,504 @@
+//! Diffs rendered with the snapshot before the pass on the left and after it on the right, for
+//! terminals without delta or riff to highlight unified diffs. Lines that moved are coloured
+//! apart, under a note of where they went or came from.
+//!
+//! The lines around the changes are syntax highlighted, see [`highlight`].
+//!
+//! Terminals too narrow for two columns get the lines removed and added inline instead, one
+//! after the other, coloured the same way.
+//!
+//! Two variants of a snapshot, e.g. made by two candidate patches, are rendered in three columns
+//! along with the snapshot they both started from.
+
+use crate::code_motion;
+use crate::highlight::{self, Profile};
+use crate::theme::{Style, Theme};
+use color_print::cformat;
+use similar::{capture_diff_slices, Algorithm, DiffOp, TextDiff};
+use std::ops::Range;
+
+/// The narrowest terminal the snapshots are put side by side in by default, narrower ones get
+/// inline diffs.
+pub const MIN_WIDTH: usize = 100;
+
+/// The width to fit the diff in, `COLUMNS` if the shell exports it.
+pub fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(160)
+}
+
+/// How the two columns are laid out, from `--width` and `--wrap`.
+#[derive(Debug, Clone, Copy)]
+pub struct Columns {
+    pub width: usize,
+    /// Lines too wide for their half go on over more rows, rather than being cut off
+    pub wrap: bool,
+}
+
+/// A line fit into its half, tabs expanded and cut off with `…` if too wide.
+fn fit(line: &str, width: usize) -> String {
+    let line = line.trim_end_matches(['\n', '\r']).replace('\t', "    ");
+    if line.chars().count() <= width {
+        return format!("{:width$}", line);
+    }
+    let cut = line
+        .chars()
+        .take(width.saturating_sub(1))
+        .collect::<String>();
+    format!("{}…", cut)
+}
+
+/// The rows a line takes in its half: one, cut off if too wide, or as many as it takes wrapped.
+fn pieces(line: &str, width: usize, wrap: bool) -> Vec<String> {
+    let expanded = line.trim_end_matches(['\n', '\r']).replace('\t', "    ");
+    if !wrap || width == 0 || expanded.chars().count() <= width {
+        return vec![fit(line, width)];
+    }
+    let chars = expanded.chars().collect::<Vec<_>>();
+    chars
+        .chunks(width)
+        .map(|chunk| format!("{:width$}", chunk.iter().collect::<String>()))
+        .collect()
+}
+
+/// A line coloured as a change, or highlighted if it's context.
+fn paint(line: &str, style: Option<&Style>, profile: Profile, theme: &Theme) -> String {
+    match style {
+        Some(style) => style.paint(line),
+        None => highlight::line(line, profile, theme),
+    }
+}
+
+/// Renders the lines that changed between `before` and `after`, with `context` lines of context.
+pub fn render(
+    before: &str,
+    after: &str,
+    columns: Columns,
+    profile: Profile,
+    context: usize,
+    theme: &Theme,
+) -> String {
+    let diff = TextDiff::from_lines(before, after);
+    let (old, new) = (diff.old_slices(), diff.new_slices());
+    let number_width = old.len().max(new.len()).max(1).to_string().len();
+    // Line numbers on both sides, and the separator in between
+    let half = columns.width.saturating_sub(2 * (number_width + 1) + 3) / 2;
+    let separator = "┄".repeat(2 * (number_width + 1 + half) + 3);
+    let moves = code_motion::moves(&diff);
+    let moved_from = |index: usize| {
+        moves
+            .iter()
+            .find(|line_move| line_move.from.contains(&index))
+    };
+    let moved_to = |index: usize| moves.iter().find(|line_move| line_move.to.contains(&index));
+    // The rows of a line in its half, numbered on the first
+    let cells = |number: Option<usize>, line: &str, style: Option<&Style>, right: bool| {
+        let Some(number) = number else {
+            return Vec::new();
+        };
+        pieces(line, half, columns.wrap)
+            .into_iter()
+            .enumerate()
+            .map(|(k, piece)| {
+                let number = match k {
+                    0 => (number + 1).to_string(),
+                    _ => String::new(),
+                };
+                let piece = match right {
+                    true => piece.trim_end(),
+                    false => &piece,
+                };
+                cformat!(
+                    "<dim>{:>w$}</dim> {}",
+                    number,
+                    paint(piece, style, profile, theme),
+                    w = number_width
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let blank = " ".repeat(number_width + 1 + half);
+    let rows_of = |left: Vec<String>, right: Vec<String>| {
+        let mut rows = String::new();
+        for k in 0..left.len().max(right.len()) {
+            rows += &cformat!(
+                "{} <dim>│</dim> {}\n",
+                left.get(k).unwrap_or(&blank),
+                right.get(k).map_or("", String::as_str)
+            );
+        }
+        rows
+    };
+
+    let mut rendered = String::new();
+    for (i, group) in diff.grouped_ops(context).iter().enumerate() {
+        if i > 0 {
+            rendered += &cformat!("<dim>{}</dim>\n", separator);
+        }
+        for op in group {
+            let (removed, added) = match *op {
+                DiffOp::Equal {
+                    old_index,
+                    new_index,
+                    len,
+                } => {
+                    for offset in 0..len {
+                        let (old_index, new_index) = (old_index + offset, new_index + offset);
+                        rendered += &rows_of(
+                            cells(Some(old_index), old[old_index], None, false),
+                            cells(Some(new_index), new[new_index], None, true),
+                        );
+                    }
+                    continue;
+                }
+                DiffOp::Delete {
+                    old_index, old_len, ..
+                } => (old_index..old_index + old_len, 0..0),
+                DiffOp::Insert {
+                    new_index, new_len, ..
+                } => (0..0, new_index..new_index + new_len),
+                DiffOp::Replace {
+                    old_index,
+                    old_len,
+                    new_index,
+                    new_len,
+                } => (
+                    old_index..old_index + old_len,
+                    new_index..new_index + new_len,
+                ),
+            };
+            // Removed and added lines are paired up, the longer side goes on alone
+            let rows = removed.len().max(added.len());
+            let mut removed = removed.into_iter();
+            let mut added = added.into_iter();
+            for _ in 0..rows {
+                let (removed, added) = (removed.next(), added.next());
+                // Moves are noted above their first line
+                let left_note = match removed.and_then(moved_from) {
+                    Some(line_move) if removed == Some(line_move.from.start) => {
+                        format!("↓ moved to {}", code_motion::lines(&line_move.to))
+                    }
+                    _ => String::new(),
+                };
+                let right_note = match added.and_then(moved_to) {
+                    Some(line_move) if added == Some(line_move.to.start) => {
+                        format!("↓ moved from {}", code_motion::lines(&line_move.from))
+                    }
+                    _ => String::new(),
+                };
+                if !left_note.is_empty() || !right_note.is_empty() {
+                    rendered += &cformat!(
+                        "{} <dim>{}</dim> <dim>│</dim> {} <dim>{}</dim>\n",
+                        " ".repeat(number_width),
+                        fit(&left_note, half),
+                        " ".repeat(number_width),
+                        right_note
+                    );
+                }
+                let left = match removed {
+                    Some(index) if moved_from(index).is_some() => {
+                        cells(removed, old[index], Some(&theme.moved_from), false)
+                    }
+                    Some(index) => cells(removed, old[index], Some(&theme.removed), false),
+                    None => Vec::new(),
+                };
+                let right = match added {
+                    Some(index) if moved_to(index).is_some() => {
+                        cells(added, new[index], Some(&theme.moved_to), true)
+                    }
+                    Some(index) => cells(added, new[index], Some(&theme.added), true),
+                    None => Vec::new(),
+                };
+                rendered += &rows_of(left, right);
+            }
+        }
+    }
+    rendered
+}
+
+/// The lines removed and added one after the other, each with its line number before and after
+/// the pass.
+pub fn render_inline(
+    before: &str,
+    after: &str,
+    profile: Profile,
+    context: usize,
+    theme: &Theme,
+) -> String {
+    let diff = TextDiff::from_lines(before, after);
+    let (old, new) = (diff.old_slices(), diff.new_slices());
+    let w = old.len().max(new.len()).max(1).to_string().len();
+    let moves = code_motion::moves(&diff);
+    let line = |line: &str| line.trim_end_matches(['\n', '\r']).replace('\t', "    ");
+
+    let mut rendered = String::new();
+    for (i, group) in diff.grouped_ops(context).iter().enumerate() {
+        if i > 0 {
+            rendered += &cformat!("<dim>{}</dim>\n", "┄".repeat(2 * w + 4));
+        }
+        for op in group {
+            if let DiffOp::Equal {
+                old_index,
+                new_index,
+                len,
+            } = *op
+            {
+                for offset in 0..len {
+                    rendered += &cformat!(
+                        "<dim>{:>w$} {:>w$} │</dim>  {}\n",
+                        old_index + offset + 1,
+                        new_index + offset + 1,
+                        highlight::line(&line(old[old_index + offset]), profile, theme),
+                    );
+                }
+                continue;
+            }
+            for index in op.old_range() {
+                let line_move = moves
+                    .iter()
+                    .find(|line_move| line_move.from.contains(&index));
+                match line_move {
+                    Some(line_move) => {
+                        if index == line_move.from.start {
+                            rendered += &cformat!(
+                                "<dim>{:w$} │  ↓ moved to {}</dim>\n",
+                                "",
+                                code_motion::lines(&line_move.to),
+                                w = 2 * w + 1
+                            );
+                        }
+                        rendered += &cformat!(
+                            "<dim>{:>w$} {:w$} │</dim> {}\n",
+                            index + 1,
+                            "",
+                            theme.moved_from.paint(&format!("-{}", line(old[index])))
+                        );
+                    }
+                    None => {
+                        rendered += &cformat!(
+                            "<dim>{:>w$} {:w$} │</dim> {}\n",
+                            index + 1,
+                            "",
+                            theme.removed.paint(&format!("-{}", line(old[index])))
+                        )
+                    }
+                }
+            }
+            for index in op.new_range() {
+                let line_move = moves.iter().find(|line_move| line_move.to.contains(&index));
+                match line_move {
+                    Some(line_move) => {
+                        if index == line_move.to.start {
+                            rendered += &cformat!(
+                                "<dim>{:w$} │  ↓ moved from {}</dim>\n",
+                                "",
+                                code_motion::lines(&line_move.from),
+                                w = 2 * w + 1
+                            );
+                        }
+                        rendered += &cformat!(
+                            "<dim>{:w$} {:>w$} │</dim> {}\n",
+                            "",
+                            index + 1,
+                            theme.moved_to.paint(&format!("+{}", line(new[index])))
+                        );
+                    }
+                    None => {
+                        rendered += &cformat!(
+                            "<dim>{:w$} {:>w$} │</dim> {}\n",
+                            "",
+                            index + 1,
+                            theme.added.paint(&format!("+{}", line(new[index])))
+                        )
+                    }
+                }
+            }
+        }
+    }
+    rendered
+}
+
+/// A row of a three-way diff: a line kept by both variants, by index in each snapshot, or the
+/// lines in between, which either variant changed.
+enum Row {
+    Kept(usize, [usize; 2]),
+    Changed(Range<usize>, [Range<usize>; 2]),
+}
+
+/// The lines of `variant` matching the lines of `base`, by index of the line of `base`.
+fn matched_lines(base: &[&str], variant: &[&str]) -> Vec<Option<usize>> {
+    let mut matched = vec![None; base.len()];
+    for op in capture_diff_slices(Algorithm::Myers, base, variant) {
+        if let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = op
+        {
+            for offset in 0..len {
+                matched[old_index + offset] = Some(new_index + offset);
+            }
+        }
+    }
+    matched
+}
+
+/// A line after its number, coloured as given.
+fn cell(
+    number: usize,
+    line: &str,
+    number_width: usize,
+    style: Option<&Style>,
+    profile: Profile,
+    theme: &Theme,
+) -> String {
+    cformat!(
+        "<dim>{:>w$}</dim> {}",
+        number + 1,
+        paint(line, style, profile, theme),
+        w = number_width
+    )
+}
+
+/// Renders the lines two variants changed in `base` in three columns, the base on the left and
+/// the variants next to it, with `context` lines of context. Lines of the base either variant
+/// dropped are coloured as removed, lines a variant added as added.
+pub fn render_three_way(
+    base: &str,
+    first: &str,
+    second: &str,
+    width: usize,
+    profile: Profile,
+    context: usize,
+    theme: &Theme,
+) -> String {
+    let base = base.lines().collect::<Vec<_>>();
+    let variants = [
+        first.lines().collect::<Vec<_>>(),
+        second.lines().collect::<Vec<_>>(),
+    ];
+    let matched = [
+        matched_lines(&base, &variants[0]),
+        matched_lines(&base, &variants[1]),
+    ];
+    let number_width = base
+        .len()
+        .max(variants[0].len())
+        .max(variants[1].len())
+        .max(1)
+        .to_string()
+        .len();
+    let third = width.saturating_sub(3 * (number_width + 1) + 6) / 3;
+    let separator = "┄".repeat(3 * (number_width + 1 + third) + 6);
+    let blank = " ".repeat(number_width + 1 + third);
+
+    // The lines both variants kept split the snapshots into the rows
+    let mut rows = Vec::new();
+    let (mut i, mut next) = (0, [0, 0]);
+    loop {
+        let kept = (i..base.len()).find_map(|k| Some((k, [matched[0][k]?, matched[1][k]?])));
+        let (end, ends) = match kept {
+            Some((k, ends)) => (k, ends),
+            None => (base.len(), [variants[0].len(), variants[1].len()]),
+        };
+        if end > i || ends != next {
+            rows.push(Row::Changed(i..end, [next[0]..ends[0], next[1]..ends[1]]));
+        }
+        let Some((k, ends)) = kept else {
+            break;
+        };
+        rows.push(Row::Kept(k, ends));
+        (i, next) = (k + 1, [ends[0] + 1, ends[1] + 1]);
+    }
+
+    let changed = (0..rows.len())
+        .filter(|&row| matches!(rows[row], Row::Changed(..)))
+        .collect::<Vec<_>>();
+    let shown = |row: usize| changed.iter().any(|&i| i.abs_diff(row) <= context);
+    let mut rendered = String::new();
+    let mut skipped = false;
+    for (row_index, row) in rows.iter().enumerate() {
+        if !shown(row_index) {
+            skipped = true;
+            continue;
+        }
+        if std::mem::take(&mut skipped) && !rendered.is_empty() {
+            rendered += &cformat!("<dim>{}</dim>\n", separator);
+        }
+        match row {
+            Row::Kept(index, [first, second]) => {
+                rendered += &cformat!(
+                    "{} <dim>│</dim> {} <dim>│</dim> {}\n",
+                    cell(
+                        *index,
+                        &fit(base[*index], third),
+                        number_width,
+                        None,
+                        profile,
+                        theme
+                    ),
+                    cell(
+                        *first,
+                        &fit(variants[0][*first], third),
+                        number_width,
+                        None,
+                        profile,
+                        theme
+                    ),
+                    cell(
+                        *second,
+                        fit(variants[1][*second], third).trim_end(),
+                        number_width,
+                        None,
+                        profile,
+                        theme
+                    ),
+                );
+            }
+            Row::Changed(base_lines, variant_lines) => {
+                let lines = base_lines
+                    .len()
+                    .max(variant_lines[0].len())
+                    .max(variant_lines[1].len());
+                for offset in 0..lines {
+                    // Every line of the base here was dropped by a variant at least
+                    let left = match base_lines.clone().nth(offset) {
+                        Some(index) => cell(
+                            index,
+                            &fit(base[index], third),
+                            number_width,
+                            Some(&theme.removed),
+                            profile,
+                            theme,
+                        ),
+                        None => blank.clone(),
+                    };
+                    let [middle, right] = [0, 1].map(|v| {
+                        let Some(index) = variant_lines[v].clone().nth(offset) else {
+                            return String::new();
+                        };
+                        let style = match matched[v].contains(&Some(index)) {
+                            true => None,
+                            false => Some(&theme.added),
+                        };
+                        let line = fit(variants[v][index], third);
+                        let line = match v {
+                            0 => &line,
+                            _ => line.trim_end(),
+                        };
+                        cell(index, line, number_width, style, profile, theme)
+                    });
+                    let middle = match middle.is_empty() {
+                        true => blank.clone(),
+                        false => middle,
+                    };
+                    rendered +=
+                        &cformat!("{} <dim>│</dim> {} <dim>│</dim> {}\n", left, middle, right);
+                }
+            }
+        }
+    }
+    rendered
+}
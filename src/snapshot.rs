@@ -0,0 +1,159 @@
+//! The snapshots of a pass as they're compared, normalized as asked to, and as they're named
+//! when they're written to files.
+
+use crate::optpipeline::Pass;
+use crate::{block_matching, canonicalize, debug_info, demangle, flang_demangle, value_numbering};
+use similar::TextDiff;
+
+/// How the snapshots are normalized before they're compared.
+#[derive(Debug, Clone, Copy)]
+pub struct Normalize {
+    /// The unnamed values are renamed after each pass to match the snapshot before it
+    pub values: bool,
+    /// Likewise for the numbered blocks
+    pub blocks: bool,
+    /// Likewise for the metadata nodes and attribute groups
+    pub metadata_ids: bool,
+    /// Debug intrinsics, records and locations are left out
+    pub debug_info: bool,
+    /// Indentation is left out and runs of whitespace collapsed
+    pub whitespace: bool,
+    /// Comments are left out
+    pub comments: bool,
+    /// The blocks after each pass are put in the order of the blocks they match before it
+    pub match_blocks: bool,
+    /// Floating-point constants are written in hexadecimal
+    pub constants: bool,
+    /// The numbers appended to struct types of the same name are left out
+    pub types: bool,
+    /// The attributes of functions, calls and attribute groups are sorted
+    pub attributes: bool,
+    /// Only the lines the pass added are left to show, the lines it removed are put back after it
+    pub only_added: bool,
+    /// Only the lines the pass removed are left to show
+    pub only_removed: bool,
+}
+
+impl Normalize {
+    pub fn is_any(self) -> bool {
+        self.values
+            || self.blocks
+            || self.metadata_ids
+            || self.debug_info
+            || self.whitespace
+            || self.comments
+            || self.match_blocks
+            || self.constants
+            || self.types
+            || self.attributes
+            || self.only_added
+            || self.only_removed
+    }
+}
+
+/// `text` with its C++ and Fortran symbols demangled, if asked to.
+pub fn demangle_text(text: &str, should_demangle: bool) -> String {
+    if !should_demangle {
+        return text.to_string();
+    }
+
+    let mut output = Vec::new();
+    let options = demangle::DemangleBuilder::new().build();
+    let text = if demangle::demangle_line(&mut output, text.as_bytes(), options).is_ok() {
+        String::from_utf8_lossy(&output).to_string()
+    } else {
+        text.to_string()
+    };
+    flang_demangle::demangle_line(&text)
+}
+
+/// Repeated passes are told apart by their run, which filters can refer to.
+pub fn run_label(pass: &Pass) -> String {
+    match pass.runs {
+        1 => String::new(),
+        runs => format!(" (run {} of {})", pass.run, runs),
+    }
+}
+
+/// The extension of the snapshot files given to diff tools, `.mir` tells Machine IR apart.
+pub fn snapshot_extension(pass: &Pass) -> &'static str {
+    match pass.machine {
+        true => "mir",
+        false => "ll",
+    }
+}
+
+/// `text` with the characters that don't belong in a file name replaced.
+pub fn file_name_part(text: &str) -> String {
+    text.chars()
+        .map(|c| match c.is_ascii_alphanumeric() || "-._".contains(c) {
+            true => c,
+            false => '_',
+        })
+        .take(100)
+        .collect()
+}
+
+/// The snapshots of a pass as they're compared, demangled and renumbered if asked to.
+pub fn snapshots(pass: &Pass, should_demangle: bool, normalize: Normalize) -> (String, String) {
+    let mut before = demangle_text(&pass.before, should_demangle) + "\n";
+    let mut after = demangle_text(&pass.after, should_demangle) + "\n";
+    if normalize.debug_info {
+        before = debug_info::strip(&before);
+        after = debug_info::strip(&after);
+    }
+    if normalize.comments {
+        before = canonicalize::strip_comments(&before);
+        after = canonicalize::strip_comments(&after);
+    }
+    if normalize.whitespace {
+        before = canonicalize::collapse_whitespace(&before);
+        after = canonicalize::collapse_whitespace(&after);
+    }
+    if normalize.constants {
+        before = canonicalize::canonical_constants(&before);
+        after = canonicalize::canonical_constants(&after);
+    }
+    if normalize.types {
+        before = canonicalize::canonical_types(&before);
+        after = canonicalize::canonical_types(&after);
+    }
+    if normalize.attributes {
+        before = canonicalize::canonical_attributes(&before);
+        after = canonicalize::canonical_attributes(&after);
+    }
+    if normalize.match_blocks {
+        after = block_matching::reorder(&before, &after);
+    }
+    if normalize.values {
+        after = value_numbering::renumber_values(&before, &after);
+    }
+    if normalize.blocks {
+        after = value_numbering::renumber_blocks(&before, &after);
+    }
+    if normalize.metadata_ids {
+        after = value_numbering::renumber_metadata(&before, &after);
+    }
+    if normalize.only_added {
+        before = kept_lines(&before, &after);
+    }
+    if normalize.only_removed {
+        after = kept_lines(&before, &after);
+    }
+    (before, after)
+}
+
+/// The lines of `after` that were already in `before`, diffing either side with them shows the
+/// lines added or the lines removed alone.
+fn kept_lines(before: &str, after: &str) -> String {
+    let diff = TextDiff::from_lines(before, after);
+    diff.ops()
+        .iter()
+        .filter_map(|op| match *op {
+            similar::DiffOp::Equal { new_index, len, .. } => Some(new_index..new_index + len),
+            _ => None,
+        })
+        .flatten()
+        .map(|i| diff.new_slices()[i])
+        .collect()
+}
@@ -0,0 +1,224 @@
+//! The parsed session written to an SQLite database, with `optdiff export --sqlite session.db`,
+//! for ad-hoc SQL queries across dumps too big to page through, and across sessions: each export
+//! adds a session to the database rather than replacing it.
+//!
+//! The database is written with SQLite built into optdiff, in one transaction as the functions
+//! are diffed, so nothing needs to be installed for it.
+//!
+//! ```sql
+//! SELECT passes.name, sum(lines_added + lines_removed) AS changed
+//! FROM passes JOIN functions ON functions.id = function_id
+//! GROUP BY passes.name ORDER BY changed DESC;
+//! ```
+
+use crate::change_score::{is_instruction, ChangeScore};
+use crate::optpipeline::Pass;
+use rusqlite::{params, Connection};
+use std::io;
+use std::path::Path;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sessions (
+    id INTEGER PRIMARY KEY,
+    inputs TEXT NOT NULL,
+    created TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE TABLE IF NOT EXISTS functions (
+    id INTEGER PRIMARY KEY,
+    session_id INTEGER NOT NULL REFERENCES sessions(id),
+    name TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS passes (
+    id INTEGER PRIMARY KEY,
+    function_id INTEGER NOT NULL REFERENCES functions(id),
+    number INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    run INTEGER NOT NULL,
+    runs INTEGER NOT NULL,
+    changed INTEGER NOT NULL,
+    deleted INTEGER NOT NULL,
+    invalidated INTEGER NOT NULL,
+    lines_added INTEGER,
+    lines_removed INTEGER,
+    instructions_before INTEGER,
+    instructions_after INTEGER,
+    before TEXT,
+    after TEXT
+);
+CREATE INDEX IF NOT EXISTS passes_by_function ON passes(function_id);
+";
+
+/// A pass of a function, as a row of `passes`.
+pub struct PassRow<'a> {
+    pub number: usize,
+    pub pass: &'a Pass,
+    /// The snapshots as compared, none for passes that left nothing to compare
+    pub snapshots: Option<(String, String)>,
+    pub score: ChangeScore,
+}
+
+/// The session being written to the database, committed by [`Database::finish`].
+pub struct Database {
+    connection: Connection,
+    session: i64,
+}
+
+fn failed(err: rusqlite::Error) -> io::Error {
+    io::Error::other(format!("Failed to write the database: {}", err))
+}
+
+impl Database {
+    /// Opens the database at `path`, creating it if need be, and adds a session read from
+    /// `inputs` to it.
+    pub fn open(path: &Path, inputs: &[String]) -> io::Result<Self> {
+        let connection = Connection::open(path).map_err(|err| {
+            io::Error::other(format!("Failed to open {}: {}", path.display(), err))
+        })?;
+        connection
+            .execute_batch(&format!("{}BEGIN;", SCHEMA))
+            .map_err(failed)?;
+        connection
+            .execute(
+                "INSERT INTO sessions (inputs) VALUES (?1)",
+                [inputs.join(", ")],
+            )
+            .map_err(failed)?;
+        let session = connection.last_insert_rowid();
+        Ok(Database {
+            connection,
+            session,
+        })
+    }
+
+    /// Adds a function of the session with its passes.
+    pub fn add_function(&mut self, name: &str, passes: &[PassRow<'_>]) -> io::Result<()> {
+        self.connection
+            .execute(
+                "INSERT INTO functions (session_id, name) VALUES (?1, ?2)",
+                params![self.session, name],
+            )
+            .map_err(failed)?;
+        let function = self.connection.last_insert_rowid();
+        let mut insert = self
+            .connection
+            .prepare_cached(
+                "INSERT INTO passes (function_id, number, name, run, runs, changed, deleted,
+                    invalidated, lines_added, lines_removed, instructions_before,
+                    instructions_after, before, after)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            )
+            .map_err(failed)?;
+        let instructions =
+            |snapshot: &str| snapshot.lines().filter(|line| is_instruction(line)).count() as i64;
+        for row in passes {
+            let pass = row.pass;
+            let snapshots = row.snapshots.as_ref();
+            insert
+                .execute(params![
+                    function,
+                    row.number as i64,
+                    pass.name,
+                    pass.run as i64,
+                    pass.runs as i64,
+                    pass.ir_changed,
+                    pass.deleted,
+                    pass.invalidated,
+                    snapshots.map(|_| row.score.added as i64),
+                    snapshots.map(|_| row.score.removed as i64),
+                    snapshots.map(|(before, _)| instructions(before)),
+                    snapshots.map(|(_, after)| instructions(after)),
+                    snapshots.map(|(before, _)| before),
+                    snapshots.map(|(_, after)| after),
+                ])
+                .map_err(failed)?;
+        }
+        Ok(())
+    }
+
+    /// Commits the session, a database left without it keeps the sessions it had.
+    pub fn finish(self) -> io::Result<()> {
+        self.connection.execute_batch("COMMIT;").map_err(failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Database, PassRow};
+    use crate::change_score::ChangeScore;
+    use crate::optpipeline::Pass;
+    use rusqlite::Connection;
+
+    #[test]
+    fn sessions_are_added_with_their_passes() {
+        let path = std::env::temp_dir().join(format!("optdiff-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let pass = Pass {
+            name: "InstCombinePass on 'f'".to_string(),
+            machine: false,
+            after: String::new(),
+            before: String::new(),
+            ir_changed: true,
+            invalidated: false,
+            deleted: false,
+            before_unknown: false,
+            after_unknown: false,
+            renamed_to: None,
+            run: 1,
+            runs: 1,
+            adaptors: Vec::new(),
+            time: None,
+            remarks: Vec::new(),
+            skipped_before: Vec::new(),
+            skipped_after: Vec::new(),
+        };
+        for _ in 0..2 {
+            let mut database = Database::open(&path, &["dump.txt".to_string()]).unwrap();
+            let row = PassRow {
+                number: 3,
+                pass: &pass,
+                snapshots: Some(("  %a = add i32 %x, 0\n".into(), "".into())),
+                score: ChangeScore {
+                    removed: 1,
+                    ..ChangeScore::default()
+                },
+            };
+            database.add_function("o'f", &[row]).unwrap();
+            database.finish().unwrap();
+        }
+        let connection = Connection::open(&path).unwrap();
+        let rows = connection
+            .prepare(
+                "SELECT functions.session_id, functions.name, passes.name, number, lines_removed,
+                    instructions_before, instructions_after
+                FROM passes JOIN functions ON functions.id = function_id",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, i64>(5)?,
+                    row.get::<_, i64>(6)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let _ = std::fs::remove_file(&path);
+        let row = |session| {
+            (
+                session,
+                "o'f".into(),
+                "InstCombinePass on 'f'".into(),
+                3,
+                1,
+                1,
+                0,
+            )
+        };
+        assert_eq!(rows, [row(1), row(2)]);
+    }
+}
@@ -0,0 +1,68 @@
+//! The passes shown one at a time with `--step`, to walk a class or a team through a pipeline:
+//! each pass gets the screen to itself under a banner of the function and the number of the
+//! pass, and the next one comes once Enter is pressed. Quitting stops the diffs where they are,
+//! optdiff then ends as it would once they're all shown.
+
+use crate::builtin_pager;
+use crate::optpipeline::Pass;
+use std::cell::{Cell, RefCell};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+
+pub struct Stepper {
+    /// The terminal, stdin may well be the dump
+    keyboard: RefCell<BufReader<File>>,
+    started: Cell<bool>,
+    quit: Cell<bool>,
+}
+
+impl Stepper {
+    pub fn new() -> io::Result<Self> {
+        let keyboard = builtin_pager::keyboard().map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!(
+                    "Option `--step` needs a terminal to read the key presses from: {}",
+                    err
+                ),
+            )
+        })?;
+        Ok(Stepper {
+            keyboard: RefCell::new(keyboard),
+            started: Cell::new(false),
+            quit: Cell::new(false),
+        })
+    }
+
+    /// Whether the stepping was quit, nothing more is to be shown.
+    pub fn quit(&self) -> bool {
+        self.quit.get()
+    }
+
+    /// Waits for the pass before to be done with, then clears the screen for pass number `i`.
+    /// Once quit, it does nothing.
+    pub fn next(&self, func_name: &str, i: usize, pass: &Pass) -> io::Result<()> {
+        if self.quit.get() {
+            return Ok(());
+        }
+        let mut stdout = io::stdout();
+        if self.started.replace(true) {
+            write!(stdout, "\x1b[7m Enter: next pass, q: quit \x1b[0m ")?;
+            stdout.flush()?;
+            let mut command = String::new();
+            let read = self.keyboard.borrow_mut().read_line(&mut command)?;
+            if read == 0 || command.trim() == "q" {
+                self.quit.set(true);
+                return Ok(());
+            }
+        }
+        write!(stdout, "\x1b[H\x1b[2J")?;
+        writeln!(
+            stdout,
+            "\x1b[1;7m {} \x1b[0m\x1b[1m  pass {}: {}\x1b[0m\n",
+            func_name,
+            i + 1,
+            pass.name
+        )
+    }
+}
@@ -0,0 +1,202 @@
+//! The colours of the built-in engines, from `--theme` and the `[theme]` section of the config
+//! file, see [`crate::config`]:
+//!
+//! ```toml
+//! [theme]
+//! name = "light"
+//! added = "green bold"
+//! removed = "#d70000"
+//! ```
+//!
+//! The colours are written the way git's are, so those of `color.diff.new` and `color.diff.old`
+//! can be copied over from a `.gitconfig`. The keys set override the colours of the theme named.
+
+use crate::config::{self, invalid, Entry};
+use clap::ValueEnum;
+use std::io;
+use std::path::Path;
+
+/// The themes to start from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ThemeName {
+    /// For terminals with a dark background
+    Dark,
+    /// For terminals with a light background, without the yellow and cyan unreadable on white
+    Light,
+}
+
+/// A colour and attributes, as the parameters of an ANSI escape sequence. Empty for none.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Style(String);
+
+impl Style {
+    fn sgr(parameters: &str) -> Self {
+        Style(parameters.to_string())
+    }
+
+    /// `text` in this style.
+    pub fn paint(&self, text: &str) -> String {
+        match self.0.is_empty() {
+            true => text.to_string(),
+            false => format!("\x1b[{}m{}\x1b[0m", self.0, text),
+        }
+    }
+}
+
+/// The colours of the changes, and of the tokens of the lines around them. The default theme
+/// colours nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Theme {
+    pub removed: Style,
+    pub added: Style,
+    /// Lines moved elsewhere by the pass, where they were
+    pub moved_from: Style,
+    /// Lines moved elsewhere by the pass, where they went
+    pub moved_to: Style,
+    /// Keywords, opcodes and the labels of MIR blocks
+    pub keyword: Style,
+    pub r#type: Style,
+    /// Values, globals, and the virtual registers of MIR
+    pub value: Style,
+    /// The physical registers of MIR
+    pub register: Style,
+    /// Comments, metadata, attributes, and the flags of MIR operands
+    pub comment: Style,
+}
+
+impl Theme {
+    pub fn named(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Dark => Theme {
+                removed: Style::sgr("31"),
+                added: Style::sgr("32"),
+                moved_from: Style::sgr("35"),
+                moved_to: Style::sgr("36"),
+                keyword: Style::sgr("34"),
+                r#type: Style::sgr("33"),
+                value: Style::sgr("36"),
+                register: Style::sgr("33"),
+                comment: Style::sgr("2"),
+            },
+            ThemeName::Light => Theme {
+                removed: Style::sgr("38;5;124"),
+                added: Style::sgr("38;5;28"),
+                moved_from: Style::sgr("38;5;90"),
+                moved_to: Style::sgr("38;5;25"),
+                keyword: Style::sgr("38;5;19"),
+                r#type: Style::sgr("38;5;130"),
+                value: Style::sgr("38;5;30"),
+                register: Style::sgr("38;5;130"),
+                comment: Style::sgr("38;5;244"),
+            },
+        }
+    }
+
+    fn style_mut(&mut self, key: &str) -> Option<&mut Style> {
+        Some(match key {
+            "removed" => &mut self.removed,
+            "added" => &mut self.added,
+            "moved-from" | "moved_from" => &mut self.moved_from,
+            "moved-to" | "moved_to" => &mut self.moved_to,
+            "keyword" => &mut self.keyword,
+            "type" => &mut self.r#type,
+            "value" => &mut self.value,
+            "register" => &mut self.register,
+            "comment" => &mut self.comment,
+            _ => return None,
+        })
+    }
+}
+
+/// A colour as git takes them: a foreground and a background colour, by name (`red`,
+/// `brightgreen`), number out of 256 or `#rrggbb`, and attributes such as `bold` or `ul`.
+fn parse_color(value: &str) -> Option<Style> {
+    const NAMES: [&str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+    let mut parameters = Vec::new();
+    let mut colors = 0;
+    for word in value.split_whitespace() {
+        let attribute = match word {
+            "bold" => Some("1"),
+            "dim" => Some("2"),
+            "italic" => Some("3"),
+            "ul" | "underline" => Some("4"),
+            "blink" => Some("5"),
+            "reverse" => Some("7"),
+            "strike" => Some("9"),
+            _ => None,
+        };
+        if let Some(attribute) = attribute {
+            parameters.push(attribute.to_string());
+            continue;
+        }
+        // The first colour is the foreground, the second the background
+        let base = match colors {
+            0 => 30,
+            1 => 40,
+            _ => return None,
+        };
+        colors += 1;
+        let bright = word.strip_prefix("bright");
+        if let Some(i) = NAMES
+            .iter()
+            .position(|&name| name == bright.unwrap_or(word))
+        {
+            let offset = if bright.is_some() { 60 } else { 0 };
+            parameters.push((base + offset + i).to_string());
+        } else if word == "default" {
+            parameters.push((base + 9).to_string());
+        } else if let Ok(number) = word.parse::<u8>() {
+            parameters.push(format!("{};5;{}", base + 8, number));
+        } else if let Some(hex) = word.strip_prefix('#').filter(|hex| hex.len() == 6) {
+            let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+            parameters.push(format!(
+                "{};2;{};{};{}",
+                base + 8,
+                channel(0)?,
+                channel(2)?,
+                channel(4)?
+            ));
+        } else if word != "normal" {
+            return None;
+        }
+    }
+    Some(Style(parameters.join(";")))
+}
+
+/// The `[theme]` section of the config file applied to the theme named, unless `name` names
+/// one already.
+fn apply_config(entries: &[Entry], path: &Path, name: Option<ThemeName>) -> io::Result<Theme> {
+    let mut theme = Theme::named(name.unwrap_or(ThemeName::Dark));
+    let mut styles = Vec::new();
+    for Entry { line, key, value } in entries {
+        if key == "name" {
+            let named = ThemeName::from_str(value, true)
+                .map_err(|_| invalid(path, *line, format!("Unknown theme `{}`", value)))?;
+            if name.is_none() {
+                theme = Theme::named(named);
+            }
+            continue;
+        }
+        if theme.style_mut(key).is_none() {
+            return Err(invalid(path, *line, format!("Unknown colour `{}`", key)));
+        }
+        let style = parse_color(value)
+            .ok_or_else(|| invalid(path, *line, format!("Invalid colour `{}`", value)))?;
+        styles.push((key, style));
+    }
+    // The colours set apply to the theme named, wherever the name is in the section
+    for (key, style) in styles {
+        *theme.style_mut(key).expect("checked above") = style;
+    }
+    Ok(theme)
+}
+
+/// The theme of `--theme`, with the colours of the config file if there is one.
+pub fn load(name: Option<ThemeName>) -> io::Result<Theme> {
+    match config::section("theme")? {
+        Some((path, entries)) => apply_config(&entries, &path, name),
+        None => Ok(Theme::named(name.unwrap_or(ThemeName::Dark))),
+    }
+}
@@ -0,0 +1,121 @@
+//! The timings of the passes as a Chrome trace, with `optdiff export --trace`, to look at where
+//! the compile time goes in `chrome://tracing` or Perfetto (ui.perfetto.dev). The timings come
+//! from the `-time-passes` report of the dump, see [`crate::optpipeline`].
+//!
+//! Each function is a track with its passes one after the other, when the report has a row per
+//! run (`-time-passes-per-run`). The time of a run is that of the instance of the pass in the
+//! pipeline, on every function it ran on, the report doesn't split it further. Rows covering
+//! every run of a pass are laid out once each on a track of their own, in the order the passes
+//! first ran.
+
+use crate::json_output::string;
+use crate::optpipeline::{Pass, PassTime};
+use std::collections::HashSet;
+
+/// The track of the passes timed over all their runs.
+const ALL_RUNS: &str = "All runs of the passes";
+
+/// A complete event of `micros` microseconds from `start` on track `track`.
+fn event(track: usize, name: &str, start: f64, micros: f64, args: &str) -> String {
+    format!(
+        "{{\"name\": {}, \"cat\": \"pass\", \"ph\": \"X\", \"pid\": 1, \"tid\": {}, \"ts\": {:.3}, \"dur\": {:.3}, \"args\": {{{}}}}}",
+        string(name),
+        track,
+        start,
+        micros,
+        args
+    )
+}
+
+/// The event naming track `track`, and one ordering it.
+fn track_name(track: usize, name: &str) -> [String; 2] {
+    [
+        format!(
+            "{{\"name\": \"thread_name\", \"ph\": \"M\", \"pid\": 1, \"tid\": {}, \"args\": {{\"name\": {}}}}}",
+            track,
+            string(name)
+        ),
+        format!(
+            "{{\"name\": \"thread_sort_index\", \"ph\": \"M\", \"pid\": 1, \"tid\": {}, \"args\": {{\"sort_index\": {}}}}}",
+            track, track
+        ),
+    ]
+}
+
+#[derive(Default)]
+pub struct Trace {
+    events: Vec<String>,
+    /// Tracks of the functions so far, the track of all runs is 0
+    functions: usize,
+    /// The passes timed over all their runs, with their time in seconds
+    all_runs: Vec<(String, f64)>,
+    seen: HashSet<String>,
+}
+
+impl Trace {
+    /// Adds the passes of a function with their number, in the order they ran.
+    pub fn add_function(&mut self, name: &str, passes: &[(usize, &Pass)]) {
+        let track = self.functions + 1;
+        let mut events = Vec::new();
+        let mut start = 0.0;
+        for &(i, pass) in passes {
+            match pass.time {
+                Some(PassTime::Run(time)) => {
+                    let micros = time * 1e6;
+                    let args = format!(
+                        "\"number\": {}, \"run\": {}, \"runs\": {}, \"changed\": {}",
+                        i + 1,
+                        pass.run,
+                        pass.runs,
+                        pass.ir_changed
+                    );
+                    events.push(event(track, &pass.name, start, micros, &args));
+                    start += micros;
+                }
+                Some(PassTime::AllRuns(time)) => {
+                    let id = pass
+                        .name
+                        .split_once(" on ")
+                        .map_or(&*pass.name, |(id, _)| id);
+                    if self.seen.insert(id.to_string()) {
+                        self.all_runs.push((id.to_string(), time));
+                    }
+                }
+                None => {}
+            }
+        }
+        if events.is_empty() {
+            return;
+        }
+        self.functions += 1;
+        self.events.extend(track_name(track, name));
+        self.events.extend(events);
+    }
+
+    /// Whether any pass had a time.
+    pub fn is_timed(&self) -> bool {
+        !self.events.is_empty() || !self.all_runs.is_empty()
+    }
+
+    /// The trace, in the JSON object format of Chrome's trace viewer.
+    pub fn render(&self, inputs: &str) -> String {
+        let mut events = vec![format!(
+            "{{\"name\": \"process_name\", \"ph\": \"M\", \"pid\": 1, \"args\": {{\"name\": {}}}}}",
+            string(&format!("optdiff: {}", inputs))
+        )];
+        if !self.all_runs.is_empty() {
+            events.extend(track_name(0, ALL_RUNS));
+            let mut start = 0.0;
+            for (name, time) in &self.all_runs {
+                let micros = time * 1e6;
+                events.push(event(0, name, start, micros, "\"all_runs\": true"));
+                start += micros;
+            }
+        }
+        events.extend(self.events.iter().cloned());
+        format!(
+            "{{\"displayTimeUnit\": \"ms\", \"traceEvents\": [\n{}\n]}}\n",
+            events.join(",\n")
+        )
+    }
+}
@@ -0,0 +1,1489 @@
+//! An interactive viewer of the dump, with `--tui`, for pipelines too long to page through: the
+//! functions on the left, the passes of the function selected in the middle, marked if they
+//! changed it, and the diff of the pass selected on the right.
+//!
+//! - Up and Down (`k` and `j`) move in the pane focused, PgUp and PgDn (`b` and space) a page
+//! - Left and Right (`h` and `l`), Tab or Enter move the focus between the panes
+//! - `g` and `G` go to the top and to the bottom of the pane
+//! - `/regex` searches the names of the functions and of the passes, and the lines of the diffs
+//!   of the passes that changed a function
+//! - `]` and `[` select the next and the previous pass with a diff, skipping over the passes that
+//!   changed nothing, in the functions after and before too
+//! - `n` and `N` select the next and the previous match, or before any search do as `]` and `[`
+//! - `m` marks the snapshot after the pass selected, `M` the one before it, and `c` then diffs
+//!   the snapshot after the pass selected with the one marked, in any function, until pressed
+//!   again: "before LICM" against "after the whole loop pipeline" without rerunning with
+//!   `--from` and `--to`
+//! - `z` shows the whole function in the diffs or folds the lines far from the changes, `Z`
+//!   collapses the blocks a pass didn't change into a line each, `d` hides the debug info as
+//!   `--ignore-debug` does or shows it again and `s` switches between side by side and inline
+//!   diffs. Only the diffs of the passes viewed are computed again
+//! - `w` writes the snapshots before and after the pass selected to the current directory, as
+//!   they were dumped and with what they refer to in their module as `optdiff extract` does, `y`
+//!   copies the diff shown to the clipboard and `e` opens the snapshot after the pass in
+//!   `$VISUAL` or `$EDITOR`, without a round trip through `optdiff extract`
+//! - `q`, Escape or Ctrl-C quits
+//!
+//! The viewer is drawn with `ratatui` on a `crossterm` terminal, in raw mode on the alternate
+//! screen, so it runs in the terminals of Windows as well as in unix ones. The clipboard is the
+//! terminal's, set with the OSC 52 escape sequence, which works over ssh.
+
+use crate::extract::ModuleScope;
+use crate::optpipeline::{Pass, LABEL};
+use crate::snapshot::{file_name_part, run_label, snapshot_extension, snapshots, Normalize};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute};
+use is_terminal::IsTerminal;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::{self, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+use regex::Regex;
+use similar::{ChangeTag, TextDiff};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A function to browse, with the passes of its pipeline to list.
+pub struct Function<'a> {
+    pub name: &'a str,
+    pub pipeline: Cow<'a, [Pass]>,
+    pub shown: Vec<usize>,
+    /// The pipeline as dumped, before the filters, when they were applied to the one shown
+    pub dumped: Option<Cow<'a, [Pass]>>,
+    /// What the snapshots of the function refer to in its module
+    pub scope: Option<&'a ModuleScope>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Functions,
+    Passes,
+    Diff,
+}
+
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Tab,
+    BackTab,
+    Enter,
+    Backspace,
+    Escape,
+    Char(char),
+    Other,
+}
+
+/// A line of the diff pane.
+#[derive(Clone, Copy)]
+enum Line {
+    Equal,
+    Delete,
+    Insert,
+    Hunk,
+    Note,
+    /// A line of a function a pass didn't change
+    Snapshot,
+    /// A block the pass didn't change, collapsed
+    Folded,
+}
+
+/// The lines kept around the changes when the rest is folded, as `diff -u` does.
+const CONTEXT: usize = 3;
+
+/// How the diffs are shown, toggled while browsing.
+#[derive(Clone, Copy)]
+struct View {
+    /// Only the lines around the changes, in hunks
+    fold: bool,
+    /// The blocks a pass didn't change as a line each
+    collapse_blocks: bool,
+    side_by_side: bool,
+}
+
+/// A line of a diff, with the lines of the snapshots it stands for.
+struct Item {
+    line: Line,
+    text: String,
+    before: usize,
+    after: usize,
+}
+
+/// The blocks of the diff without changes collapsed into their label.
+fn collapse_blocks(items: Vec<Item>) -> Vec<Item> {
+    let label = Regex::new(LABEL).unwrap();
+    // The text of the snapshot, without the sign
+    let text = |item: &Item| item.text.get(1..).unwrap_or_default().to_string();
+    let mut collapsed = Vec::new();
+    let mut items = items.into_iter().peekable();
+    while let Some(item) = items.next() {
+        if !label.is_match(&text(&item)) {
+            collapsed.push(item);
+            continue;
+        }
+        // A block ends at the next label or at the end of the function
+        let mut block = vec![item];
+        while let Some(next) = items.next_if(|next| {
+            let next = text(next);
+            !label.is_match(&next) && !next.starts_with('}')
+        }) {
+            block.push(next);
+        }
+        if block.len() > 1 && block.iter().all(|item| matches!(item.line, Line::Equal)) {
+            let lines = block.len();
+            collapsed.push(Item {
+                line: Line::Folded,
+                text: format!(" {} ⋯ {} unchanged lines", text(&block[0]), lines - 1),
+                before: lines,
+                after: lines,
+            });
+        } else {
+            collapsed.extend(block);
+        }
+    }
+    collapsed
+}
+
+/// The lines of the diff of two snapshots.
+fn changes(before: &str, after: &str, view: View) -> Vec<(Line, String)> {
+    let diff = TextDiff::from_lines(before, after);
+    let mut items = diff
+        .iter_all_changes()
+        .map(|change| {
+            let (line, sign, before, after) = match change.tag() {
+                ChangeTag::Equal => (Line::Equal, ' ', 1, 1),
+                ChangeTag::Delete => (Line::Delete, '-', 1, 0),
+                ChangeTag::Insert => (Line::Insert, '+', 0, 1),
+            };
+            let text = change.value().trim_end_matches('\n');
+            Item {
+                line,
+                text: format!("{}{}", sign, text),
+                before,
+                after,
+            }
+        })
+        .collect::<Vec<_>>();
+    if view.collapse_blocks {
+        items = collapse_blocks(items);
+    }
+    if !view.fold {
+        return items
+            .into_iter()
+            .map(|item| (item.line, item.text))
+            .collect();
+    }
+    // The lines further than CONTEXT from a change are left out, the others make up hunks
+    let changed = items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| matches!(item.line, Line::Delete | Line::Insert))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let kept = |i: usize| {
+        let next = changed.partition_point(|&j| j < i);
+        changed.get(next).is_some_and(|&j| j - i <= CONTEXT)
+            || next
+                .checked_sub(1)
+                .is_some_and(|last| i - changed[last] <= CONTEXT)
+    };
+    let mut lines = Vec::new();
+    let (mut before_line, mut after_line) = (0, 0);
+    let mut i = 0;
+    while i < items.len() {
+        if !kept(i) {
+            before_line += items[i].before;
+            after_line += items[i].after;
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < items.len() && kept(i) {
+            i += 1;
+        }
+        let hunk = &items[start..i];
+        let before_lines = hunk.iter().map(|item| item.before).sum::<usize>();
+        let after_lines = hunk.iter().map(|item| item.after).sum::<usize>();
+        // An empty side starts at the line before it, as in unified diffs
+        let first = |line: usize, lines: usize| if lines == 0 { line } else { line + 1 };
+        lines.push((
+            Line::Hunk,
+            format!(
+                "@@ -{},{} +{},{} @@",
+                first(before_line, before_lines),
+                before_lines,
+                first(after_line, after_lines),
+                after_lines
+            ),
+        ));
+        lines.extend(hunk.iter().map(|item| (item.line, item.text.clone())));
+        before_line += before_lines;
+        after_line += after_lines;
+    }
+    lines
+}
+
+fn style(line: Line) -> Style {
+    match line {
+        Line::Equal | Line::Snapshot => Style::new(),
+        Line::Delete => Style::new().red(),
+        Line::Insert => Style::new().green(),
+        Line::Hunk => Style::new().cyan(),
+        Line::Note => Style::new().italic(),
+        Line::Folded => Style::new().dim(),
+    }
+}
+
+/// A line of the diff pane fitted to `width`, with the matches of the search reversed.
+fn styled(line: Line, text: &str, width: usize, search: Option<&Regex>) -> Vec<Span<'static>> {
+    let text = fit(text, width);
+    let style = style(line);
+    let Some(search) = search else {
+        return vec![Span::styled(text, style)];
+    };
+    let mut spans = Vec::new();
+    let mut end = 0;
+    for found in search.find_iter(&text).filter(|found| !found.is_empty()) {
+        spans.push(Span::styled(text[end..found.start()].to_string(), style));
+        spans.push(Span::styled(found.as_str().to_string(), style.reversed()));
+        end = found.end();
+    }
+    spans.push(Span::styled(text[end..].to_string(), style));
+    spans
+}
+
+/// The rows of a diff side by side, the lines removed on the left facing those added on the
+/// right.
+fn side_by_side(
+    lines: &[(Line, String)],
+    width: usize,
+    search: Option<&Regex>,
+) -> Vec<text::Line<'static>> {
+    let left = width.saturating_sub(1) / 2;
+    let right = width.saturating_sub(1) - left;
+    // Each side shows the text of its snapshot, without the sign
+    let half = |line: Option<&(Line, String)>, width: usize| match line {
+        Some((line, text)) => styled(*line, text.get(1..).unwrap_or_default(), width, search),
+        None => vec![Span::raw(fit("", width))],
+    };
+    let row = |left_half: Vec<Span<'static>>, right_half: Vec<Span<'static>>| {
+        let mut spans = left_half;
+        spans.push(Span::styled("│", Style::new().dim()));
+        spans.extend(right_half);
+        text::Line::from(spans)
+    };
+    let mut rows = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        match lines[i].0 {
+            Line::Hunk | Line::Note => {
+                rows.push(styled(lines[i].0, &lines[i].1, width, search).into());
+                i += 1;
+            }
+            Line::Delete | Line::Insert => {
+                let deleted = lines[i..]
+                    .iter()
+                    .take_while(|(line, _)| matches!(line, Line::Delete))
+                    .count();
+                let inserted = lines[i + deleted..]
+                    .iter()
+                    .take_while(|(line, _)| matches!(line, Line::Insert))
+                    .count();
+                let deletes = &lines[i..i + deleted];
+                let inserts = &lines[i + deleted..i + deleted + inserted];
+                for k in 0..deleted.max(inserted) {
+                    rows.push(row(half(deletes.get(k), left), half(inserts.get(k), right)));
+                }
+                i += deleted + inserted;
+            }
+            Line::Equal | Line::Snapshot | Line::Folded => {
+                rows.push(row(
+                    half(Some(&lines[i]), left),
+                    half(Some(&lines[i]), right),
+                ));
+                i += 1;
+            }
+        }
+    }
+    rows
+}
+
+/// A snapshot marked to compare the others with.
+#[derive(Clone, Copy)]
+struct Mark {
+    function: usize,
+    pass: usize,
+    /// The snapshot after the pass, else the one before it
+    after: bool,
+}
+
+/// The terminal in raw mode on the alternate screen, restored when dropped.
+struct Terminal {
+    terminal: ratatui::Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl Terminal {
+    fn open() -> io::Result<Self> {
+        // The keys are read from the terminal even when the dump comes through stdin
+        if !io::stdout().is_terminal() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Option `--tui` needs a terminal",
+            ));
+        }
+        let mut terminal = Terminal {
+            terminal: ratatui::Terminal::new(CrosstermBackend::new(io::stdout()))?,
+        };
+        terminal.enter()?;
+        Ok(terminal)
+    }
+
+    /// Switches to raw mode and to the alternate screen.
+    fn enter(&mut self) -> io::Result<()> {
+        terminal::enable_raw_mode()?;
+        execute!(
+            self.terminal.backend_mut(),
+            EnterAlternateScreen,
+            cursor::Hide
+        )?;
+        // The screen is drawn again from scratch, whatever was on it before
+        self.terminal.clear()
+    }
+
+    /// Restores the screen and the settings the terminal had.
+    fn leave(&mut self) -> io::Result<()> {
+        execute!(
+            self.terminal.backend_mut(),
+            cursor::Show,
+            LeaveAlternateScreen
+        )?;
+        terminal::disable_raw_mode()
+    }
+
+    /// Opens `path` in the editor of `$VISUAL` or `$EDITOR`, `vi` without either, on the
+    /// terminal given back until the editor exits.
+    fn edit(&mut self, path: &Path) -> io::Result<String> {
+        let editor = env::var("VISUAL")
+            .or_else(|_| env::var("EDITOR"))
+            .ok()
+            .filter(|editor| !editor.trim().is_empty())
+            .unwrap_or_else(|| "vi".to_string());
+        // The command is split at whitespace as `--diff-tool` is, e.g. `code --wait`
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+        self.leave()?;
+        let status = Command::new(program).args(words).arg(path).status();
+        self.enter()?;
+        Ok(match status {
+            Ok(status) if status.success() => format!("Edited {}", path.display()),
+            Ok(status) => format!(
+                "{} exited with {}, the snapshot is {}",
+                program,
+                status,
+                path.display()
+            ),
+            Err(err) => format!("Couldn't run {}: {}", program, err),
+        })
+    }
+
+    /// Copies `text` to the clipboard with the OSC 52 escape sequence, through the terminal, so
+    /// it works over ssh too.
+    fn copy(&mut self, text: &str) -> io::Result<()> {
+        let backend = self.terminal.backend_mut();
+        write!(backend, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+        backend.flush()
+    }
+
+    /// The rows of the terminal.
+    fn height(&self) -> usize {
+        match self.terminal.size() {
+            Ok(size) if size.height > 0 => size.height as usize,
+            _ => 24,
+        }
+    }
+
+    /// The next key pressed, once the terminal has been resized `Key::Other` to draw it again.
+    fn key(&mut self) -> io::Result<Key> {
+        loop {
+            let key = match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => key,
+                Event::Resize(..) => return Ok(Key::Other),
+                _ => continue,
+            };
+            let control = key.modifiers.contains(KeyModifiers::CONTROL);
+            return Ok(match key.code {
+                KeyCode::Up => Key::Up,
+                KeyCode::Down => Key::Down,
+                KeyCode::Left => Key::Left,
+                KeyCode::Right => Key::Right,
+                KeyCode::PageUp => Key::PageUp,
+                KeyCode::PageDown => Key::PageDown,
+                KeyCode::Home => Key::Home,
+                KeyCode::End => Key::End,
+                KeyCode::Tab => Key::Tab,
+                KeyCode::BackTab => Key::BackTab,
+                KeyCode::Enter => Key::Enter,
+                KeyCode::Backspace => Key::Backspace,
+                KeyCode::Esc => Key::Escape,
+                KeyCode::Char('c') if control => Key::Escape,
+                KeyCode::Char(c) if !control && !key.modifiers.contains(KeyModifiers::ALT) => {
+                    Key::Char(c)
+                }
+                _ => Key::Other,
+            });
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.leave();
+    }
+}
+
+/// `bytes` in base64, for the clipboard.
+fn base64(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            encoded.push(match i <= chunk.len() {
+                true => DIGITS[(group >> (18 - 6 * i) & 63) as usize] as char,
+                false => '=',
+            });
+        }
+    }
+    encoded
+}
+
+/// What the viewer needs the terminal for after a key.
+enum Action {
+    Quit,
+    /// Open the file in the editor
+    Edit(PathBuf),
+    /// Copy the text to the clipboard
+    Copy(String),
+}
+
+/// `text` cut or padded to `width` columns, its tabs expanded.
+fn fit(text: &str, width: usize) -> String {
+    let text = text.replace('\t', "    ");
+    let mut fitted = text.chars().take(width).collect::<String>();
+    let len = fitted.chars().count();
+    fitted.extend(std::iter::repeat_n(' ', width - len));
+    fitted
+}
+
+struct Viewer<'a> {
+    functions: Vec<Function<'a>>,
+    should_demangle: bool,
+    normalize: Normalize,
+    focus: Pane,
+    function: usize,
+    /// The pass selected, an index of the passes shown of the function
+    pass: usize,
+    function_offset: usize,
+    pass_offset: usize,
+    diff_offset: usize,
+    /// The lines of the diffs by function and pass, computed once per pass
+    diffs: HashMap<(usize, usize), Vec<(Line, String)>>,
+    /// The search being typed after `/`
+    prompt: Option<String>,
+    search: Option<Regex>,
+    /// The line of the diff of the last match, 0 for a name and the lines from 1
+    search_line: usize,
+    /// Shown instead of the help until the next key
+    message: Option<String>,
+    mark: Option<Mark>,
+    /// Whether the diff pane compares the pass selected with the mark
+    comparing: bool,
+    /// The lines of the comparison, and of which function and pass
+    comparison: Vec<(Line, String)>,
+    compared: Option<(usize, usize)>,
+    view: View,
+}
+
+impl<'a> Viewer<'a> {
+    fn new(functions: Vec<Function<'a>>, should_demangle: bool, normalize: Normalize) -> Self {
+        Viewer {
+            functions,
+            should_demangle,
+            normalize,
+            focus: Pane::Passes,
+            function: 0,
+            pass: 0,
+            function_offset: 0,
+            pass_offset: 0,
+            diff_offset: 0,
+            diffs: HashMap::new(),
+            prompt: None,
+            search: None,
+            search_line: 0,
+            message: None,
+            mark: None,
+            comparing: false,
+            comparison: Vec::new(),
+            compared: None,
+            view: View {
+                fold: true,
+                collapse_blocks: false,
+                side_by_side: false,
+            },
+        }
+    }
+
+    fn selected_pass(&self) -> &Pass {
+        let function = &self.functions[self.function];
+        &function.pipeline[function.shown[self.pass]]
+    }
+
+    /// The lines of the diff of the pass selected, or of its comparison with the mark.
+    fn diff_lines(&mut self) -> &[(Line, String)] {
+        let Some(mark) = self.mark.filter(|_| self.comparing) else {
+            return self.diff_of(self.function, self.pass);
+        };
+        let selected = Some((self.function, self.pass));
+        if self.compared != selected {
+            self.comparison = self.render_comparison(mark);
+            self.compared = selected;
+        }
+        &self.comparison
+    }
+
+    /// The lines of the diff of pass `pass` of function `function`.
+    fn diff_of(&mut self, function: usize, pass: usize) -> &[(Line, String)] {
+        if !self.diffs.contains_key(&(function, pass)) {
+            let lines = self.render_diff(function, pass);
+            self.diffs.insert((function, pass), lines);
+        }
+        &self.diffs[&(function, pass)]
+    }
+
+    fn render_diff(&self, function: usize, pass: usize) -> Vec<(Line, String)> {
+        let function = &self.functions[function];
+        let pass = &function.pipeline[function.shown[pass]];
+        if pass.deleted {
+            return vec![(
+                Line::Note,
+                "The function was deleted by this pass".to_string(),
+            )];
+        }
+        if pass.invalidated {
+            return vec![(
+                Line::Note,
+                "The pass invalidated the IR it ran on, there is no snapshot to compare"
+                    .to_string(),
+            )];
+        }
+        let (before, after) = snapshots(pass, self.should_demangle, self.normalize);
+        if before == after {
+            let mut lines = vec![
+                (
+                    Line::Note,
+                    "The pass didn't change the function".to_string(),
+                ),
+                (Line::Note, String::new()),
+            ];
+            lines.extend(
+                after
+                    .lines()
+                    .map(|line| (Line::Snapshot, format!(" {}", line))),
+            );
+            return lines;
+        }
+        changes(&before, &after, self.view)
+    }
+
+    /// The snapshot before or `after` a pass, none after a pass that left none.
+    fn snapshot(&self, function: usize, pass: usize, after: bool) -> Option<String> {
+        let function = &self.functions[function];
+        let pass = &function.pipeline[function.shown[pass]];
+        if after && (pass.deleted || pass.invalidated) {
+            return None;
+        }
+        let (before, after_pass) = snapshots(pass, self.should_demangle, self.normalize);
+        Some(if after { after_pass } else { before })
+    }
+
+    /// The marked snapshot, as `the snapshot after pass 12 of foo`.
+    fn mark_label(&self, mark: Mark) -> String {
+        let function = &self.functions[mark.function];
+        format!(
+            "the snapshot {} pass {} of {}",
+            if mark.after { "after" } else { "before" },
+            function.shown[mark.pass] + 1,
+            function.name
+        )
+    }
+
+    /// Marks the snapshot before or `after` the pass selected.
+    fn mark(&mut self, after: bool) {
+        if self.snapshot(self.function, self.pass, after).is_none() {
+            self.message = Some(
+                "There is no snapshot after this pass, it deleted the function or invalidated the IR"
+                    .to_string(),
+            );
+            return;
+        }
+        let mark = Mark {
+            function: self.function,
+            pass: self.pass,
+            after,
+        };
+        self.mark = Some(mark);
+        self.compared = None;
+        self.message = Some(format!(
+            "Marked {}, c compares the passes with it",
+            self.mark_label(mark)
+        ));
+    }
+
+    /// The diff of the snapshot marked with the one after the pass selected.
+    fn render_comparison(&self, mark: Mark) -> Vec<(Line, String)> {
+        let Some(current) = self.snapshot(self.function, self.pass, true) else {
+            return vec![(
+                Line::Note,
+                "There is no snapshot after this pass to compare with the one marked".to_string(),
+            )];
+        };
+        let marked = self
+            .snapshot(mark.function, mark.pass, mark.after)
+            .unwrap_or_default();
+        if marked == current {
+            return vec![(
+                Line::Note,
+                format!("The snapshot after this pass is {}", self.mark_label(mark)),
+            )];
+        }
+        changes(&marked, &current, self.view)
+    }
+
+    fn select_function(&mut self, function: usize) {
+        if function != self.function {
+            self.function = function;
+            self.pass = 0;
+            self.pass_offset = 0;
+            self.diff_offset = 0;
+            self.search_line = 0;
+        }
+    }
+
+    fn select_pass(&mut self, pass: usize) {
+        if pass != self.pass {
+            self.pass = pass;
+            self.diff_offset = 0;
+            self.search_line = 0;
+        }
+    }
+
+    /// The lines of the diff of a pass searched, none unless the pass changed the function:
+    /// the diff of the others is the whole function, which would match at every pass.
+    fn searched_lines(&mut self, function: usize, pass: usize) -> usize {
+        let lines = self.diff_of(function, pass);
+        match lines
+            .iter()
+            .any(|(line, _)| matches!(line, Line::Delete | Line::Insert))
+        {
+            true => lines.len(),
+            false => 0,
+        }
+    }
+
+    /// The position after `at`, or before it going `backward`. Positions are a function, a pass
+    /// and a line: 0 for the names, the lines of the diff from 1.
+    fn step(&mut self, at: (usize, usize, usize), backward: bool) -> Option<(usize, usize, usize)> {
+        let (function, pass, line) = at;
+        let passes = |viewer: &Self, function: usize| viewer.functions[function].shown.len();
+        if !backward {
+            if line < self.searched_lines(function, pass) {
+                Some((function, pass, line + 1))
+            } else if pass + 1 < passes(self, function) {
+                Some((function, pass + 1, 0))
+            } else if function + 1 < self.functions.len() {
+                Some((function + 1, 0, 0))
+            } else {
+                None
+            }
+        } else if line > 0 {
+            Some((function, pass, line - 1))
+        } else if pass > 0 {
+            Some((function, pass - 1, self.searched_lines(function, pass - 1)))
+        } else if function > 0 {
+            let last = passes(self, function - 1) - 1;
+            Some((function - 1, last, self.searched_lines(function - 1, last)))
+        } else {
+            None
+        }
+    }
+
+    fn is_match(&mut self, at: (usize, usize, usize), regex: &Regex) -> bool {
+        let (function, pass, line) = at;
+        if line > 0 {
+            return regex.is_match(&self.diff_of(function, pass)[line - 1].1);
+        }
+        let function = &self.functions[function];
+        let name = &function.pipeline[function.shown[pass]].name;
+        (pass == 0 && regex.is_match(function.name)) || regex.is_match(name)
+    }
+
+    /// Selects the next match of the search, or the previous one going `backward`.
+    fn select_match(&mut self, backward: bool) {
+        let Some(regex) = self.search.clone() else {
+            self.message = Some("No search to repeat".to_string());
+            return;
+        };
+        let mut at = (self.function, self.pass, self.search_line);
+        loop {
+            let Some(next) = self.step(at, backward) else {
+                self.message = Some(format!("Pattern not found: {}", regex));
+                return;
+            };
+            at = next;
+            if self.is_match(at, &regex) {
+                break;
+            }
+        }
+        let (function, pass, line) = at;
+        self.select_function(function);
+        self.select_pass(pass);
+        self.search_line = line;
+        // The line matched comes with a few lines above it
+        match line {
+            0 => self.focus = Pane::Passes,
+            line => {
+                self.diff_offset = (line - 1).saturating_sub(3);
+                self.focus = Pane::Diff;
+            }
+        }
+    }
+
+    /// Handles a key of the search being typed.
+    fn handle_prompt(&mut self, key: Key) {
+        let Some(prompt) = &mut self.prompt else {
+            return;
+        };
+        match key {
+            Key::Char(c) => prompt.push(c),
+            Key::Backspace if prompt.is_empty() => self.prompt = None,
+            Key::Backspace => {
+                prompt.pop();
+            }
+            Key::Escape => self.prompt = None,
+            Key::Enter => {
+                let pattern = self.prompt.take().unwrap_or_default();
+                // An empty search repeats the last one
+                if !pattern.is_empty() {
+                    match Regex::new(&pattern) {
+                        Ok(regex) => self.search = Some(regex),
+                        Err(err) => {
+                            self.message = Some(format!("Invalid regex: {}", err));
+                            return;
+                        }
+                    }
+                }
+                self.select_match(false);
+            }
+            _ => {}
+        }
+    }
+
+    /// Selects the next pass with a diff, in this function or those after it, or the previous
+    /// one going `backward`, skipping over the passes that changed nothing.
+    fn select_change(&mut self, backward: bool) {
+        let mut at = (self.function, self.pass);
+        loop {
+            let (function, pass) = at;
+            let next = if !backward {
+                if pass + 1 < self.functions[function].shown.len() {
+                    Some((function, pass + 1))
+                } else if function + 1 < self.functions.len() {
+                    Some((function + 1, 0))
+                } else {
+                    None
+                }
+            } else if pass > 0 {
+                Some((function, pass - 1))
+            } else if function > 0 {
+                Some((function - 1, self.functions[function - 1].shown.len() - 1))
+            } else {
+                None
+            };
+            let Some(next) = next else {
+                self.message = Some("No more passes that changed a function".to_string());
+                return;
+            };
+            at = next;
+            if self.is_change(at.0, at.1) {
+                break;
+            }
+        }
+        self.select_function(at.0);
+        self.select_pass(at.1);
+    }
+
+    /// Whether the diff of a pass isn't empty, or the pass deleted the function or invalidated
+    /// its IR.
+    fn is_change(&mut self, function: usize, pass: usize) -> bool {
+        let shown = &self.functions[function];
+        let shown = &shown.pipeline[shown.shown[pass]];
+        if shown.deleted || shown.invalidated {
+            return true;
+        }
+        if !shown.ir_changed && shown.before == shown.after {
+            return false;
+        }
+        self.searched_lines(function, pass) > 0
+    }
+
+    /// The file of the snapshot before or `after` pass `pass` of function `function`, as
+    /// `f-012-LICMPass_on_loop__l.before.ll`.
+    fn snapshot_file(&self, function: usize, pass: usize, after: bool) -> String {
+        let function = &self.functions[function];
+        let number = function.shown[pass];
+        let pass = &function.pipeline[number];
+        format!(
+            "{}-{:03}-{}.{}.{}",
+            file_name_part(function.name),
+            number + 1,
+            file_name_part(&(pass.name.clone() + &run_label(pass))),
+            if after { "after" } else { "before" },
+            snapshot_extension(pass)
+        )
+    }
+
+    /// The snapshots before and after the pass selected as they were dumped, with what they refer
+    /// to in their module, to feed to `opt` or `llc` as `optdiff extract` writes them, with the
+    /// names of their files, and what the dump doesn't show of what they refer to. Passes that
+    /// left no snapshot after them, and first passes dumped without the one before, have just one.
+    fn dumped_snapshots(&self) -> (Vec<(String, String)>, Vec<String>) {
+        let function = &self.functions[self.function];
+        let number = function.shown[self.pass];
+        let shown = &function.pipeline[number];
+        // The pass of the pipeline as dumped, the pipelines are shaped alike
+        let pass = function
+            .dumped
+            .as_ref()
+            .filter(|dumped| dumped.len() == function.pipeline.len())
+            .map(|dumped| &dumped[number])
+            .filter(|dumped| dumped.name == shown.name)
+            .unwrap_or(shown);
+        let mut snapshots = Vec::new();
+        if !(pass.before_unknown && pass.before.is_empty()) {
+            snapshots.push((
+                self.snapshot_file(self.function, self.pass, false),
+                &pass.before,
+            ));
+        }
+        if !pass.deleted && !pass.invalidated {
+            snapshots.push((
+                self.snapshot_file(self.function, self.pass, true),
+                &pass.after,
+            ));
+        }
+        let mut missing = Vec::new();
+        let snapshots = snapshots
+            .into_iter()
+            .map(|(file, snapshot)| {
+                let mut snapshot = match function.scope {
+                    Some(scope) => {
+                        let (snapshot, refers_to) = scope.standalone(snapshot);
+                        missing.extend(refers_to);
+                        snapshot
+                    }
+                    None => snapshot.clone(),
+                };
+                if !snapshot.ends_with('\n') {
+                    snapshot.push('\n');
+                }
+                (file, snapshot)
+            })
+            .collect();
+        missing.sort();
+        missing.dedup();
+        (snapshots, missing)
+    }
+
+    /// Writes the snapshots of the pass selected to the current directory.
+    fn write_snapshots(&mut self) {
+        let (snapshots, missing) = self.dumped_snapshots();
+        let written = snapshots
+            .into_iter()
+            .map(|(file, snapshot)| fs::write(&file, snapshot).map(|_| file))
+            .collect::<io::Result<Vec<_>>>();
+        self.message = Some(match written {
+            Ok(files) if files.is_empty() => "The pass has no snapshot to write".to_string(),
+            Ok(files) if !missing.is_empty() => format!(
+                "Wrote {}, but the dump doesn't show {}, which they refer to",
+                files.join(" and "),
+                missing.join(", ")
+            ),
+            Ok(files) => format!("Wrote {}", files.join(" and ")),
+            Err(err) => format!("Couldn't write the snapshots: {}", err),
+        });
+    }
+
+    /// The snapshot of the pass selected written to a temporary file for the editor, the one
+    /// after it unless it left none.
+    fn snapshot_to_edit(&mut self) -> Option<PathBuf> {
+        let Some((file, snapshot)) = self.dumped_snapshots().0.pop() else {
+            self.message = Some("The pass has no snapshot to open".to_string());
+            return None;
+        };
+        let dir = env::temp_dir().join(format!("optdiff-{}", std::process::id()));
+        let path = dir.join(file);
+        match fs::create_dir_all(&dir).and_then(|_| fs::write(&path, snapshot)) {
+            Ok(()) => Some(path),
+            Err(err) => {
+                self.message = Some(format!("Couldn't write the snapshot: {}", err));
+                None
+            }
+        }
+    }
+
+    /// The lines of the diff pane as text, without the colours.
+    fn diff_text(&mut self) -> String {
+        self.diff_lines()
+            .iter()
+            .map(|(_, text)| format!("{}\n", text))
+            .collect()
+    }
+
+    /// Shows the diffs as toggled, only those of the passes viewed from now on are computed
+    /// again.
+    fn toggled(&mut self, message: &str) {
+        self.diffs.clear();
+        self.compared = None;
+        self.diff_offset = 0;
+        self.search_line = 0;
+        self.message = Some(message.to_string());
+    }
+
+    /// Moves in the pane focused by `delta` rows, to its ends past them.
+    fn move_by(&mut self, delta: isize, diff_rows: usize) {
+        let moved = |at: usize, len: usize| at.saturating_add_signed(delta).min(len.max(1) - 1);
+        match self.focus {
+            Pane::Functions => self.select_function(moved(self.function, self.functions.len())),
+            Pane::Passes => {
+                let len = self.functions[self.function].shown.len();
+                self.select_pass(moved(self.pass, len));
+            }
+            Pane::Diff => {
+                let len = self.diff_lines().len();
+                self.diff_offset = moved(self.diff_offset, len.saturating_sub(diff_rows) + 1);
+            }
+        }
+    }
+
+    /// Handles a key, returns what's left to the terminal to do.
+    fn handle(&mut self, key: Key, rows: usize) -> Option<Action> {
+        self.message = None;
+        if self.prompt.is_some() {
+            self.handle_prompt(key);
+            return None;
+        }
+        let page = rows.max(2) as isize - 1;
+        let focus_next = |focus| match focus {
+            Pane::Functions => Pane::Passes,
+            Pane::Passes | Pane::Diff => Pane::Diff,
+        };
+        let focus_previous = |focus| match focus {
+            Pane::Functions | Pane::Passes => Pane::Functions,
+            Pane::Diff => Pane::Passes,
+        };
+        match key {
+            Key::Char('q') | Key::Escape => return Some(Action::Quit),
+            Key::Up | Key::Char('k') => self.move_by(-1, rows),
+            Key::Down | Key::Char('j') => self.move_by(1, rows),
+            Key::PageUp | Key::Char('b') => self.move_by(-page, rows),
+            Key::PageDown | Key::Char(' ') => self.move_by(page, rows),
+            Key::Home | Key::Char('g') => self.move_by(isize::MIN, rows),
+            Key::End | Key::Char('G') => self.move_by(isize::MAX, rows),
+            Key::Right | Key::Char('l') | Key::Enter => self.focus = focus_next(self.focus),
+            Key::Left | Key::Char('h') => self.focus = focus_previous(self.focus),
+            Key::Tab => {
+                self.focus = match self.focus {
+                    Pane::Functions => Pane::Passes,
+                    Pane::Passes => Pane::Diff,
+                    Pane::Diff => Pane::Functions,
+                }
+            }
+            Key::BackTab => {
+                self.focus = match self.focus {
+                    Pane::Functions => Pane::Diff,
+                    Pane::Passes => Pane::Functions,
+                    Pane::Diff => Pane::Passes,
+                }
+            }
+            Key::Char('/') => self.prompt = Some(String::new()),
+            Key::Char('m') => self.mark(true),
+            Key::Char('M') => self.mark(false),
+            Key::Char('c') if self.mark.is_none() => {
+                self.message =
+                    Some("No snapshot marked, m marks the one after the pass selected".to_string())
+            }
+            Key::Char('c') => {
+                self.comparing = !self.comparing;
+                self.diff_offset = 0;
+            }
+            Key::Char('z') => {
+                self.view.fold = !self.view.fold;
+                self.toggled(match self.view.fold {
+                    true => "Unchanged regions folded",
+                    false => "Unchanged regions shown",
+                });
+            }
+            Key::Char('Z') => {
+                self.view.collapse_blocks = !self.view.collapse_blocks;
+                self.toggled(match self.view.collapse_blocks {
+                    true => "Unchanged blocks collapsed",
+                    false => "Unchanged blocks shown",
+                });
+            }
+            Key::Char('d') => {
+                self.normalize.debug_info = !self.normalize.debug_info;
+                self.toggled(match self.normalize.debug_info {
+                    true => "Debug metadata hidden",
+                    false => "Debug metadata shown",
+                });
+            }
+            Key::Char('s') => {
+                self.view.side_by_side = !self.view.side_by_side;
+                self.message = Some(
+                    match self.view.side_by_side {
+                        true => "Diffs side by side",
+                        false => "Diffs inline",
+                    }
+                    .to_string(),
+                );
+            }
+            Key::Char('w') => self.write_snapshots(),
+            Key::Char('y') => return Some(Action::Copy(self.diff_text())),
+            Key::Char('e') => return self.snapshot_to_edit().map(Action::Edit),
+            Key::Char('n') if self.search.is_some() => self.select_match(false),
+            Key::Char('N') if self.search.is_some() => self.select_match(true),
+            Key::Char('n') | Key::Char(']') => self.select_change(false),
+            Key::Char('N') | Key::Char('[') => self.select_change(true),
+            Key::Char(_) | Key::Backspace | Key::Other => {}
+        }
+        None
+    }
+
+    /// A pane with its heading, reversed if it's focused, and a line on its left but for the
+    /// first one.
+    fn pane(&self, pane: Pane, heading: &str, width: u16) -> Block<'static> {
+        let style = match self.focus == pane {
+            true => Style::new().bold().reversed(),
+            false => Style::new().bold(),
+        };
+        let borders = match pane {
+            Pane::Functions => Borders::NONE,
+            Pane::Passes | Pane::Diff => Borders::LEFT,
+        };
+        let width = width.saturating_sub(borders.contains(Borders::LEFT) as u16) as usize;
+        Block::new()
+            .borders(borders)
+            .border_style(Style::new().dim())
+            .title(Span::styled(fit(heading, width), style))
+    }
+
+    /// A list of a pane, the row selected reversed if the pane is focused, bold if it isn't.
+    fn list(
+        &self,
+        pane: Pane,
+        items: Vec<ListItem<'static>>,
+        block: Block<'static>,
+    ) -> List<'static> {
+        let highlight = match self.focus == pane {
+            true => Style::new().add_modifier(Modifier::REVERSED),
+            false => Style::new().add_modifier(Modifier::BOLD),
+        };
+        List::new(items).block(block).highlight_style(highlight)
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [title_area, body, status_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let width = body.width as usize;
+        let functions_width = (width / 5).clamp(12, 32) as u16;
+        let passes_width = (width / 4).clamp(16, 48) as u16 + 1;
+        let [functions_area, passes_area, diff_area] = Layout::horizontal([
+            Constraint::Length(functions_width),
+            Constraint::Length(passes_width),
+            Constraint::Min(1),
+        ])
+        .areas(body);
+
+        let function = &self.functions[self.function];
+        let pass = self.selected_pass();
+        let mut title = format!(
+            " optdiff · {} · pass {} of {}: {}{}",
+            function.name,
+            function.shown[self.pass] + 1,
+            function.pipeline.len(),
+            pass.name,
+            run_label(pass)
+        );
+        let compared = self.mark.filter(|_| self.comparing);
+        if let Some(mark) = compared {
+            title += &format!(" · compared with {}", self.mark_label(mark));
+        }
+        frame.render_widget(Paragraph::new(fit(&title, width)).reversed(), title_area);
+
+        let function_items = self
+            .functions
+            .iter()
+            .map(|function| {
+                let changed = function
+                    .shown
+                    .iter()
+                    .any(|&j| function.pipeline[j].ir_changed);
+                let style = if changed {
+                    Style::new()
+                } else {
+                    Style::new().dim()
+                };
+                ListItem::new(format!(" {}", function.name)).style(style)
+            })
+            .collect();
+        let block = self.pane(
+            Pane::Functions,
+            &format!(" Functions ({})", self.functions.len()),
+            functions_width,
+        );
+        let mut state = ListState::default()
+            .with_offset(self.function_offset)
+            .with_selected(Some(self.function));
+        frame.render_stateful_widget(
+            self.list(Pane::Functions, function_items, block),
+            functions_area,
+            &mut state,
+        );
+        self.function_offset = state.offset();
+
+        let function = &self.functions[self.function];
+        let pass_items = function
+            .shown
+            .iter()
+            .enumerate()
+            .map(|(j, &i)| {
+                let pass = &function.pipeline[i];
+                let (marker, style) = if pass.deleted || pass.invalidated {
+                    ('✗', Style::new().red())
+                } else if pass.ir_changed {
+                    ('●', Style::new().green())
+                } else {
+                    ('·', Style::new().dim())
+                };
+                // The pass of the mark is starred
+                let marked = self
+                    .mark
+                    .is_some_and(|mark| (mark.function, mark.pass) == (self.function, j));
+                let star = if marked { '*' } else { ' ' };
+                ListItem::new(format!(
+                    "{}{}{:>3} {}{}",
+                    marker,
+                    star,
+                    i + 1,
+                    pass.name,
+                    run_label(pass)
+                ))
+                .style(style)
+            })
+            .collect();
+        let block = self.pane(
+            Pane::Passes,
+            &format!(" Passes ({})", function.shown.len()),
+            passes_width,
+        );
+        let mut state = ListState::default()
+            .with_offset(self.pass_offset)
+            .with_selected(Some(self.pass));
+        frame.render_stateful_widget(
+            self.list(Pane::Passes, pass_items, block),
+            passes_area,
+            &mut state,
+        );
+        self.pass_offset = state.offset();
+
+        let block = self.pane(
+            Pane::Diff,
+            match compared {
+                Some(_) => " Diff with the mark",
+                None => " Diff",
+            },
+            diff_area.width,
+        );
+        let Rect {
+            width: diff_width,
+            height: rows,
+            ..
+        } = block.inner(diff_area);
+        let (diff_width, rows) = (diff_width as usize, rows as usize);
+        let diff_offset = self.diff_offset;
+        let search = self.search.clone();
+        let side_by_side = self.view.side_by_side;
+        let lines = self.diff_lines();
+        let lines = &lines[diff_offset.min(lines.len())..];
+        let diff_rows = match side_by_side {
+            true => self::side_by_side(lines, diff_width, search.as_ref()),
+            false => lines
+                .iter()
+                .take(rows)
+                .map(|(line, text)| styled(*line, text, diff_width, search.as_ref()).into())
+                .collect(),
+        };
+        frame.render_widget(Paragraph::new(diff_rows).block(block), diff_area);
+
+        let status = match (&self.prompt, &self.message, &self.search) {
+            (Some(prompt), _, _) => format!("/{}", prompt),
+            (None, Some(message), _) => format!(" {}", message),
+            (None, None, Some(_)) => {
+                " ↑↓ move · ←→ Tab panes · PgUp PgDn page · ] [ changes · / search · n N matches · m mark · c compare · z Z d s view · w y e export · q quit"
+                    .to_string()
+            }
+            (None, None, None) => {
+                " ↑↓ move · ←→ Tab panes · PgUp PgDn page · ] [ changes · / search · m mark · c compare · z Z d s view · w y e export · q quit"
+                    .to_string()
+            }
+        };
+        frame.render_widget(Paragraph::new(fit(&status, width)).reversed(), status_area);
+    }
+}
+
+/// Browses the functions until quit, those without passes to list are left out.
+pub fn run(
+    functions: Vec<Function>,
+    should_demangle: bool,
+    normalize: Normalize,
+) -> io::Result<()> {
+    let functions = functions
+        .into_iter()
+        .filter(|function| !function.shown.is_empty())
+        .collect::<Vec<_>>();
+    if functions.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "No passes to browse, the filters left out all of them",
+        ));
+    }
+    let mut viewer = Viewer::new(functions, should_demangle, normalize);
+    let mut terminal = Terminal::open()?;
+    loop {
+        terminal.terminal.draw(|frame| viewer.draw(frame))?;
+        let key = terminal.key()?;
+        match viewer.handle(key, terminal.height().saturating_sub(3).max(1)) {
+            Some(Action::Quit) => return Ok(()),
+            Some(Action::Edit(path)) => viewer.message = Some(terminal.edit(&path)?),
+            Some(Action::Copy(text)) => {
+                terminal.copy(&text)?;
+                viewer.message = Some("Copied the diff to the clipboard".to_string());
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Function, Key, Line, Pane, Viewer};
+    use crate::optpipeline::Pass;
+    use crate::snapshot::Normalize;
+    use std::borrow::Cow;
+
+    fn pass(name: &str, before: &str, after: &str) -> Pass {
+        Pass {
+            name: name.to_string(),
+            machine: false,
+            after: after.to_string(),
+            before: before.to_string(),
+            ir_changed: before != after,
+            invalidated: false,
+            deleted: false,
+            before_unknown: false,
+            after_unknown: false,
+            renamed_to: None,
+            run: 1,
+            runs: 1,
+            adaptors: Vec::new(),
+            time: None,
+            remarks: Vec::new(),
+            skipped_before: Vec::new(),
+            skipped_after: Vec::new(),
+        }
+    }
+
+    const ADD: &str = "define i32 @f(i32 %x) {\n  %a = add i32 %x, 1\n  ret i32 %a\n}";
+    const MUL: &str = "define i32 @f(i32 %x) {\n  %a = mul i32 %x, 2\n  ret i32 %a\n}";
+    const DEBUG: &str =
+        "define i32 @f(i32 %x) {\n  %a = mul i32 %x, 2 loc(\"f.c\":1:2)\n  ret i32 %a\n}";
+    const RET: &str = "define i32 @g() {\n  ret i32 0\n}";
+    const RET_ONE: &str = "define i32 @g() {\n  ret i32 1\n}";
+
+    fn viewer(pipelines: &[Vec<Pass>; 2]) -> Viewer<'_> {
+        let functions = ["f", "g"]
+            .into_iter()
+            .zip(pipelines)
+            .map(|(name, pipeline)| Function {
+                name,
+                pipeline: Cow::Borrowed(pipeline),
+                shown: (0..pipeline.len()).collect(),
+                dumped: None,
+                scope: None,
+            })
+            .collect();
+        let normalize = Normalize {
+            values: false,
+            blocks: false,
+            metadata_ids: false,
+            debug_info: false,
+            whitespace: false,
+            comments: false,
+            match_blocks: false,
+            constants: false,
+            types: false,
+            attributes: false,
+            only_added: false,
+            only_removed: false,
+        };
+        Viewer::new(functions, false, normalize)
+    }
+
+    /// `f` changed by its first and last passes, `g` by its second.
+    fn pipelines() -> [Vec<Pass>; 2] {
+        [
+            vec![
+                pass("InstCombinePass", ADD, MUL),
+                pass("GVNPass", MUL, MUL),
+                pass("InstSimplifyPass", MUL, DEBUG),
+            ],
+            vec![pass("SROAPass", RET, RET), pass("SCCPPass", RET, RET_ONE)],
+        ]
+    }
+
+    fn press(viewer: &mut Viewer<'_>, keys: &str) {
+        for c in keys.chars() {
+            let key = match c {
+                '\n' => Key::Enter,
+                c => Key::Char(c),
+            };
+            viewer.handle(key, 20);
+        }
+    }
+
+    #[test]
+    fn searches_select_the_matches_in_turn() {
+        let pipelines = pipelines();
+        let mut viewer = viewer(&pipelines);
+        press(&mut viewer, "/mul\n");
+        assert_eq!((viewer.function, viewer.pass), (0, 0));
+        assert!(viewer.focus == Pane::Diff);
+        let first = viewer.search_line;
+        assert!(viewer.diff_lines()[first - 1].1.contains("mul"));
+        // The unchanged GVN isn't searched, the location added by InstSimplify is
+        press(&mut viewer, "n");
+        assert_eq!((viewer.function, viewer.pass), (0, 2));
+        press(&mut viewer, "n");
+        assert_eq!((viewer.function, viewer.pass), (0, 2));
+        press(&mut viewer, "n");
+        assert!(viewer
+            .message
+            .as_ref()
+            .unwrap()
+            .starts_with("Pattern not found"));
+        press(&mut viewer, "NNN");
+        assert_eq!(
+            (viewer.function, viewer.pass, viewer.search_line),
+            (0, 0, first)
+        );
+        press(&mut viewer, "/SCCP\n");
+        assert_eq!((viewer.function, viewer.pass), (1, 1));
+        assert!(viewer.focus == Pane::Passes);
+    }
+
+    #[test]
+    fn brackets_skip_the_passes_that_changed_nothing() {
+        let pipelines = pipelines();
+        let mut viewer = viewer(&pipelines);
+        press(&mut viewer, "]");
+        assert_eq!((viewer.function, viewer.pass), (0, 2));
+        press(&mut viewer, "]");
+        assert_eq!((viewer.function, viewer.pass), (1, 1));
+        press(&mut viewer, "]");
+        assert_eq!((viewer.function, viewer.pass), (1, 1));
+        assert!(viewer.message.is_some());
+        press(&mut viewer, "[[");
+        assert_eq!((viewer.function, viewer.pass), (0, 0));
+        // Before any search, n and N do as ] and [
+        press(&mut viewer, "nN");
+        assert_eq!((viewer.function, viewer.pass), (0, 0));
+    }
+
+    #[test]
+    fn marked_snapshots_are_compared_with_the_pass_selected() {
+        let pipelines = pipelines();
+        let mut viewer = viewer(&pipelines);
+        press(&mut viewer, "c");
+        assert!(!viewer.comparing);
+        // Before InstCombine against after InstSimplify
+        press(&mut viewer, "Mjjc");
+        assert!(viewer.comparing);
+        let lines = viewer.diff_lines().to_vec();
+        let text =
+            |(_, text): &(Line, String)| text.trim_start_matches(['-', '+']).trim().to_string();
+        let removed = lines
+            .iter()
+            .filter(|(line, _)| matches!(line, Line::Delete));
+        let added = lines
+            .iter()
+            .filter(|(line, _)| matches!(line, Line::Insert));
+        assert_eq!(
+            removed.map(text).collect::<Vec<_>>(),
+            ["%a = add i32 %x, 1"]
+        );
+        assert_eq!(
+            added.map(text).collect::<Vec<_>>(),
+            ["%a = mul i32 %x, 2 loc(\"f.c\":1:2)"]
+        );
+        press(&mut viewer, "c");
+        assert!(!viewer.comparing);
+    }
+
+    #[test]
+    fn toggles_change_the_diffs_shown() {
+        let pipelines = pipelines();
+        let mut viewer = viewer(&pipelines);
+        press(&mut viewer, "jj");
+        let shown = viewer.diff_text();
+        assert!(shown.contains("loc("));
+        press(&mut viewer, "d");
+        assert!(viewer.normalize.debug_info);
+        assert!(!viewer.diff_text().contains("loc("));
+        press(&mut viewer, "d");
+        assert_eq!(viewer.diff_text(), shown);
+        press(&mut viewer, "z");
+        assert!(!viewer.view.fold);
+        assert!(viewer.diffs.is_empty());
+        press(&mut viewer, "Zs");
+        assert!(viewer.view.collapse_blocks && viewer.view.side_by_side);
+    }
+}
@@ -0,0 +1,252 @@
+//! A pass that removes an instruction shifts the number of every unnamed value after it, which
+//! buries the change under lines that only differ by their numbers. The same goes for the blocks
+//! of the dialects that number them apart from the values, and for the metadata nodes and
+//! attribute groups, renumbered as they're added and dropped. The numbered names of the snapshot
+//! after the pass are renamed after the names they stand for before it, as told by the lines
+//! that are the same but for the numbers.
+
+use regex::{Captures, Regex};
+use similar::{Algorithm, DiffOp};
+use std::collections::{HashMap, HashSet};
+
+/// The digits of a numbered name, from the group that matched.
+fn number<'h>(captures: &Captures<'h>) -> regex::Match<'h> {
+    captures.iter().skip(1).flatten().next().unwrap()
+}
+
+/// Matches the names of the two snapshots along a pair of lines, unless one of them already is.
+fn pair<'a>(
+    before: &[&'a str],
+    after: &[&'a str],
+    renamed: &mut HashMap<&'a str, &'a str>,
+    taken: &mut HashSet<&'a str>,
+) {
+    for (&before, &after) in before.iter().zip(after) {
+        if !renamed.contains_key(after) && !taken.contains(before) {
+            renamed.insert(after, before);
+            taken.insert(before);
+        }
+    }
+}
+
+/// Renames the names of `after` matched by `names`, whose groups capture their number, to the
+/// numbers they have in `before`. Names only found after the pass are numbered past the others.
+fn renumber(before: &str, after: &str, names: &Regex) -> String {
+    let split = |text| {
+        let lines = str::split_inclusive(text, '\n').collect::<Vec<_>>();
+        let masked = lines
+            .iter()
+            .map(|line| names.replace_all(line, "_"))
+            .collect::<Vec<_>>();
+        let numbers = lines
+            .iter()
+            .map(|line| {
+                names
+                    .captures_iter(line)
+                    .map(|captures| number(&captures).as_str())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        (masked, numbers)
+    };
+    let (before_masked, before_numbers) = split(before);
+    let (after_masked, after_numbers) = split(after);
+
+    let ops = similar::capture_diff_slices(Algorithm::Myers, &before_masked, &after_masked);
+    let mut renamed = HashMap::new();
+    let mut taken = HashSet::new();
+    // Lines that are the same go first, then lines changed in place, e.g. an operand replaced
+    for op in &ops {
+        if let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = *op
+        {
+            for offset in 0..len {
+                let (before, after) = (
+                    &before_numbers[old_index + offset],
+                    &after_numbers[new_index + offset],
+                );
+                pair(before, after, &mut renamed, &mut taken);
+            }
+        }
+    }
+    for op in &ops {
+        if let DiffOp::Replace {
+            old_index,
+            old_len,
+            new_index,
+            new_len,
+        } = *op
+        {
+            for offset in 0..old_len.min(new_len) {
+                let (before, after) = (
+                    &before_numbers[old_index + offset],
+                    &after_numbers[new_index + offset],
+                );
+                if before.len() == after.len() {
+                    pair(before, after, &mut renamed, &mut taken);
+                }
+            }
+        }
+    }
+
+    let mut next = before_numbers
+        .iter()
+        .flatten()
+        .filter_map(|number| number.parse::<u64>().ok())
+        .max()
+        .map_or(0, |max| max + 1);
+    let mut fresh = HashMap::new();
+    names
+        .replace_all(after, |captures: &Captures<'_>| {
+            let whole = captures.get(0).unwrap();
+            let old = number(captures);
+            let new = match renamed.get(old.as_str()) {
+                Some(new) => new.to_string(),
+                None => fresh
+                    .entry(old.as_str().to_string())
+                    .or_insert_with(|| {
+                        next += 1;
+                        (next - 1).to_string()
+                    })
+                    .clone(),
+            };
+            // The number is replaced within the name, e.g. `^bb5` or `<bb 5>`
+            let text = whole.as_str();
+            let (start, end) = (old.start() - whole.start(), old.end() - whole.start());
+            format!("{}{}{}", &text[..start], new, &text[end..])
+        })
+        .into_owned()
+}
+
+/// Renames the unnamed values (`%5`) of `after` to the numbers they have in `before`, along with
+/// the numbered blocks of LLVM IR (`5:`), which share their numbering.
+pub fn renumber_values(before: &str, after: &str) -> String {
+    let values = Regex::new(r"(?m)%(\d+)\b|^(\d+):").unwrap();
+    renumber(before, after, &values)
+}
+
+/// Renames the numbered blocks of `after` to the numbers they have in `before`: `bb.5` in MIR,
+/// `^bb5` in MLIR, `bb5` in SIL and `<bb 5>` in GIMPLE.
+pub fn renumber_blocks(before: &str, after: &str) -> String {
+    let blocks = Regex::new(r"\bbb\.?(\d+)\b|<bb (\d+)>").unwrap();
+    renumber(before, after, &blocks)
+}
+
+/// Renames the metadata nodes (`!5`) and attribute groups (`#5`) of `after` to the numbers they
+/// have in `before`, along with the affine map and set aliases of MLIR (`#map5`, `#set5`).
+pub fn renumber_metadata(before: &str, after: &str) -> String {
+    let mut after = after.to_string();
+    for ids in [r"!(\d+)\b", r"#(\d+)\b", r"#map(\d+)\b", r"#set(\d+)\b"] {
+        after = renumber(before, &after, &Regex::new(ids).unwrap());
+    }
+    after
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{renumber_blocks, renumber_metadata, renumber_values};
+
+    #[test]
+    fn values_after_a_deleted_instruction_keep_their_numbers() {
+        let before = "\
+  %1 = add i32 %0, 1
+  %2 = mul i32 %1, 2
+  %3 = sub i32 %2, 3
+  ret i32 %3
+";
+        let after = "\
+  %1 = add i32 %0, 1
+  %2 = sub i32 %1, 3
+  ret i32 %2
+";
+        assert_eq!(
+            renumber_values(before, after),
+            "%1 = add i32 %0, 1\n  %3 = sub i32 %1, 3\n  ret i32 %3\n"
+        );
+    }
+
+    #[test]
+    fn new_values_are_numbered_past_the_old_ones() {
+        let before = "\
+  %0 = add i32 %a, 1
+  %1 = mul i32 %0, 2
+  ret i32 %1
+";
+        let after = "\
+  %0 = add i32 %a, 1
+  %1 = xor i32 %0, -1
+  %2 = mul i32 %0, 2
+  ret i32 %2
+";
+        assert_eq!(
+            renumber_values(before, after),
+            "%0 = add i32 %a, 1\n  %2 = xor i32 %0, -1\n  %1 = mul i32 %0, 2\n  ret i32 %1\n"
+        );
+    }
+
+    #[test]
+    fn values_used_before_their_definition_are_renamed_too() {
+        let before = "\
+  %2 = add i32 %0, 1
+  store i32 %2, ptr %p
+  br label %3
+3:
+  %4 = phi i32 [ 0, %1 ], [ %5, %3 ]
+  %5 = mul i32 %4, 2
+  br label %3
+";
+        let after = "\
+  br label %2
+2:
+  %3 = phi i32 [ 0, %1 ], [ %4, %2 ]
+  %4 = mul i32 %3, 2
+  br label %2
+";
+        assert_eq!(
+            renumber_values(before, after),
+            "br label %3\n3:\n  %4 = phi i32 [ 0, %1 ], [ %5, %3 ]\n  %5 = mul i32 %4, 2\n  br label %3\n"
+        );
+    }
+
+    #[test]
+    fn blocks_after_a_deleted_block_keep_their_numbers() {
+        let before = "\
+bb.0:
+  JMP_1 %bb.2
+bb.1:
+  $eax = MOV32ri 1
+bb.2:
+  RET 0, $eax
+";
+        let after = "\
+bb.0:
+  JMP_1 %bb.1
+bb.1:
+  RET 0, $eax
+";
+        assert_eq!(
+            renumber_blocks(before, after),
+            "bb.0:\n  JMP_1 %bb.2\nbb.2:\n  RET 0, $eax\n"
+        );
+    }
+
+    #[test]
+    fn metadata_after_a_dropped_node_keeps_its_number() {
+        let before = "\
+  ret i32 %x, !dbg !7
+!6 = !{!\"unused\"}
+!7 = !DILocation(line: 3)
+";
+        let after = "\
+  ret i32 %x, !dbg !6
+!6 = !DILocation(line: 3)
+";
+        assert_eq!(
+            renumber_metadata(before, after),
+            "ret i32 %x, !dbg !7\n!7 = !DILocation(line: 3)\n"
+        );
+    }
+}